@@ -0,0 +1,162 @@
+//! Measures the throughput and latency of concurrent mixed puts and gets.
+//!
+//! Run with `cargo bench --bench table`. Each scenario loads the table first,
+//! and then runs the same number of random operations on every thread, with
+//! one put for every nine gets.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use hdrhistogram::Histogram;
+use photondb::{std::Table, PageStoreOptions, TableOptions};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use tempfile::tempdir;
+
+const VALUE_SIZE: usize = 100;
+const READ_PERCENT: u32 = 90;
+
+struct Scenario {
+    name: &'static str,
+    num_keys: u64,
+    num_threads: usize,
+    ops_per_thread: usize,
+    write_buffer_capacity: u32,
+}
+
+const SCENARIOS: [Scenario; 2] = [
+    // The whole dataset fits in one write buffer.
+    Scenario {
+        name: "small",
+        num_keys: 10_000,
+        num_threads: 4,
+        ops_per_thread: 100_000,
+        write_buffer_capacity: 128 << 20,
+    },
+    // The dataset spans many write buffers, so that flushes and reads from
+    // page files are measured as well.
+    Scenario {
+        name: "larger_than_buffer",
+        num_keys: 200_000,
+        num_threads: 4,
+        ops_per_thread: 100_000,
+        write_buffer_capacity: 1 << 20,
+    },
+];
+
+#[derive(Clone)]
+struct Latencies {
+    get: Histogram<u64>,
+    put: Histogram<u64>,
+}
+
+impl Latencies {
+    fn new() -> Self {
+        let new_hist = || Histogram::new_with_bounds(1, 60_000_000_000, 3).unwrap();
+        Self {
+            get: new_hist(),
+            put: new_hist(),
+        }
+    }
+
+    fn add(&mut self, other: &Latencies) {
+        self.get.add(&other.get).unwrap();
+        self.put.add(&other.put).unwrap();
+    }
+}
+
+fn main() {
+    for scenario in &SCENARIOS {
+        run(scenario);
+    }
+}
+
+fn run(scenario: &Scenario) {
+    let path = tempdir().unwrap();
+    let options = TableOptions {
+        page_store: PageStoreOptions {
+            write_buffer_capacity: scenario.write_buffer_capacity,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let table = Table::open(&path, options).unwrap();
+    let value = vec![0u8; VALUE_SIZE];
+    for i in 0..scenario.num_keys {
+        table.put(&key(i), i, &value).unwrap();
+    }
+
+    let lsn = Arc::new(AtomicU64::new(scenario.num_keys));
+    let start = Instant::now();
+    let handles: Vec<_> = (0..scenario.num_threads)
+        .map(|tid| {
+            let table = table.clone();
+            let lsn = lsn.clone();
+            let num_keys = scenario.num_keys;
+            let num_ops = scenario.ops_per_thread;
+            thread::spawn(move || run_thread(table, lsn, tid as u64, num_keys, num_ops))
+        })
+        .collect();
+    let mut latencies = Latencies::new();
+    for handle in handles {
+        latencies.add(&handle.join().unwrap());
+    }
+    let elapsed = start.elapsed();
+
+    report(scenario.name, "get", &latencies.get, elapsed);
+    report(scenario.name, "put", &latencies.put, elapsed);
+    table.close().unwrap();
+}
+
+fn run_thread(
+    table: Table,
+    lsn: Arc<AtomicU64>,
+    seed: u64,
+    num_keys: u64,
+    num_ops: usize,
+) -> Latencies {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut latencies = Latencies::new();
+    let value = vec![1u8; VALUE_SIZE];
+    for _ in 0..num_ops {
+        let key = key(rng.gen_range(0..num_keys));
+        if rng.gen_range(0..100) < READ_PERCENT {
+            let start = Instant::now();
+            let got = table.get(&key, u64::MAX).unwrap();
+            latencies.get.record(elapsed_nanos(start)).unwrap();
+            assert!(got.is_some());
+        } else {
+            let lsn = lsn.fetch_add(1, Ordering::Relaxed) + 1;
+            let start = Instant::now();
+            table.put(&key, lsn, &value).unwrap();
+            latencies.put.record(elapsed_nanos(start)).unwrap();
+        }
+    }
+    latencies
+}
+
+fn key(i: u64) -> [u8; 8] {
+    i.to_be_bytes()
+}
+
+fn elapsed_nanos(start: Instant) -> u64 {
+    start.elapsed().as_nanos().max(1) as u64
+}
+
+fn report(scenario: &str, op: &str, hist: &Histogram<u64>, elapsed: Duration) {
+    let us = |q: f64| hist.value_at_quantile(q) as f64 / 1000.0;
+    println!(
+        "{scenario}/{op}: {} ops, {:.0} ops/sec, P50: {:.2} us, P99: {:.2} us, P99.9: {:.2} us, Max: {:.2} us",
+        hist.len(),
+        hist.len() as f64 / elapsed.as_secs_f64(),
+        us(0.5),
+        us(0.99),
+        us(0.999),
+        hist.max() as f64 / 1000.0,
+    );
+}
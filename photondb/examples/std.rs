@@ -16,7 +16,7 @@ fn main() -> Result<()> {
     assert_eq!(table.get(&key, 3)?, Some(val2.clone()));
     let guard = table.pin();
     // Get the value without copy.
-    assert_eq!(guard.get(&key, 3)?, Some(val2.as_slice()));
+    assert_eq!(guard.get(&key, 3)?.as_deref(), Some(val2.as_slice()));
     // Iterate the tree page by page.
     let mut pages = guard.pages();
     while let Some(page) = pages.next()? {
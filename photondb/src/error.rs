@@ -1,10 +1,16 @@
 use thiserror::Error;
 
-use crate::page_store::Error as PageError;
+use crate::page_store::{Error as PageError, SelfTestFailure};
 
 /// A list of possible errors returned by PhotonDB.
 #[derive(Error, Debug)]
 pub enum Error {
+    /// The operation conflicts with concurrent operations and can be retried.
+    #[error("Again")]
+    Again,
+    /// Some arguments are invalid.
+    #[error("InvalidArgument")]
+    InvalidArgument,
     /// Some data is corrupted.
     #[error("Corrupted")]
     Corrupted,
@@ -14,14 +20,35 @@ pub enum Error {
     /// Put data is too large.
     #[error("TooLargeSize")]
     TooLargeSize,
+    /// No more page ids can be allocated.
+    #[error("OutOfPageIds")]
+    OutOfPageIds,
+    /// No more file ids can be allocated.
+    #[error("OutOfFileIds")]
+    OutOfFileIds,
+    /// The self-test on open found some corruptions.
+    #[error("SelfTestFailed: {0}")]
+    SelfTestFailed(SelfTestFailure),
+    /// A batch contains the same key more than once.
+    #[error("DuplicateKey")]
+    DuplicateKey,
+    /// The table is opened in read-only mode and can't be modified.
+    #[error("ReadOnly")]
+    ReadOnly,
 }
 
 impl From<PageError> for Error {
     fn from(err: PageError) -> Self {
         match err {
+            PageError::Again => Self::Again,
+            PageError::InvalidArgument => Self::InvalidArgument,
             PageError::Corrupted => Self::Corrupted,
             PageError::MemoryLimit => Self::MemoryLimit,
             PageError::TooLargeSize => Self::TooLargeSize,
+            PageError::OutOfPageIds => Self::OutOfPageIds,
+            PageError::OutOfFileIds => Self::OutOfFileIds,
+            PageError::SelfTest(failure) => Self::SelfTestFailed(failure),
+            PageError::ReadOnly => Self::ReadOnly,
             e => unreachable!("unexpected error: {:?}", e),
         }
     }
@@ -20,10 +20,14 @@
 //! The [`Photon`] APIs are the default and are re-exported to the top-level
 //! module for convenience.
 //!
+//! With the `tracing` feature enabled, reads, writes, consolidations, and
+//! flushes are instrumented with [`tracing`] spans at the debug level.
+//!
 //! [`Raw`]: crate::raw
 //! [`Std`]: crate::std
 //! [`Photon`]: crate::photon
 //! [PhotonIO]: https://crates.io/crates/photonio
+//! [`tracing`]: https://crates.io/crates/tracing
 
 #![warn(missing_docs, unreachable_pub)]
 #![feature(
@@ -38,24 +42,38 @@ pub mod raw;
 pub mod std;
 
 pub mod photon;
-pub use photon::{Table, TableStats};
+pub use photon::{
+    Decodable, Encodable, Snapshot, Table, TableStats, TypedTable, UpdateIntent, WriteBatch,
+};
 
 mod error;
 pub use error::{Error, Result};
 
 mod tree;
-pub use tree::{Options as TableOptions, PageIter, ReadOptions, TreeStats, WriteOptions};
+pub use tree::{
+    ConsolidatePageInfo, DataPageLayout, Direction, EventListener, MergeOperator,
+    Options as TableOptions, PageIter, PrefixExtractor, ReadOptions, RemovePageInfo, SplitPageInfo,
+    TreeStats, WriteOptions,
+};
 
 mod page_store;
 pub use page_store::{
-    ChecksumType, Compression, FlushOptions, Options as PageStoreOptions, StoreStats,
+    ChecksumType, Compression, FlushOptions, GcPlan, Options as PageStoreOptions, PageAddr, PageId,
+    SelfTestFailure, StoreStats,
 };
 
 mod page;
+pub use page::PageKind;
+
 mod util;
 
 #[cfg(test)]
 mod tests {
+    use ::std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+    use futures::StreamExt;
     use rand::random;
     use tempfile::tempdir;
 
@@ -65,11 +83,30 @@ mod tests {
     const OPTIONS: TableOptions = TableOptions {
         page_size: 128,
         page_chain_length: 4,
+        max_chain_length: 32,
+        merge_operator: None,
+        warm_up_on_open: 0,
+        data_page_layout: DataPageLayout::Variable,
+        merge_tournament_threshold: 64,
+        negative_cache_size: 0,
+        consolidate_rate: 0,
+        reject_duplicate_keys: false,
+        prefix_extractor: None,
+        event_listener: None,
         page_store: PageStoreOptions {
             write_buffer_capacity: 1 << 20,
+            write_buffer_seal_interval: None,
+            min_write_buffer_capacity: 1 << 20,
+            fair_write_buffer_admission: false,
+            max_concurrent_writers: 0,
+            zero_on_free: false,
             max_write_buffers: 8,
+            flush_concurrency: 1,
+            min_flush_bytes: 0,
+            max_flush_delay: Duration::from_secs(1),
             use_direct_io: false,
             disable_space_reclaiming: false,
+            keep_obsolete_files: false,
             max_space_amplification_percent: 10,
             space_used_high: u64::MAX,
             file_base_size: 1 << 20,
@@ -81,7 +118,14 @@ mod tests {
             compression_on_flush: Compression::SNAPPY,
             compression_on_cold_compact: Compression::ZSTD,
             page_checksum_type: ChecksumType::CRC32,
+            verify_on_read: false,
+            self_test_on_open: false,
+            pending_frees_soft_limit: 1 << 10,
+            pending_frees_hard_limit: 1 << 16,
             avoid_flush_during_shutdown: false,
+            create_if_missing: true,
+            error_if_exists: false,
+            read_only: false,
         },
     };
 
@@ -125,53 +169,1734 @@ mod tests {
     }
 
     #[photonio::test]
-    async fn random_crud() {
+    async fn scan_with_concurrent_writes() {
         let path = tempdir().unwrap();
         let table = Table::open(&path, OPTIONS).await.unwrap();
-        const N: u64 = 1 << 12;
-        for _ in 0..N {
-            let i = random();
+        const N: u64 = 1 << 10;
+        for i in 0..N {
             must_put(&table, i, i).await;
-            must_get(&table, i, i, Some(i)).await;
         }
+
+        let guard = table.pin();
+        let mut pages = guard.pages();
+        let mut i = 0u64;
+        let mut lsn = N;
+        while let Some(page) = pages.next().await.unwrap() {
+            let start = i;
+            for (k, v) in page {
+                assert_eq!(k, &i.to_be_bytes());
+                assert_eq!(v, &i.to_be_bytes());
+                i += 1;
+            }
+            // Rewrite the scanned and the following keys to consolidate and split the
+            // pages around the cursor.
+            for j in start..(i + 16).min(N) {
+                must_put(&table, j, lsn).await;
+                lsn += 1;
+            }
+        }
+        assert_eq!(i, N);
+
         table.close().await.unwrap();
     }
 
     #[photonio::test]
-    async fn concurrent_crud() {
+    async fn scan_with_memory_budget() {
         let path = tempdir().unwrap();
         let table = Table::open(&path, OPTIONS).await.unwrap();
-        let mut tasks = Vec::new();
-        for _ in 0..4 {
-            let table = table.clone();
-            let handle = photonio::task::spawn(async move {
-                const N: u64 = 1 << 10;
-                for _ in 0..N {
-                    let i = random();
-                    must_put(&table, i, i).await;
-                    must_get(&table, i, i, Some(i)).await;
+        const N: u64 = 1 << 10;
+        for i in 0..N {
+            must_put(&table, i, i).await;
+        }
+
+        const BUDGET: usize = 1 << 10;
+        // Pages are read from write buffers first, and then from files.
+        for flush in [false, true] {
+            if flush {
+                table.flush(&FlushOptions::default()).await;
+            }
+            let mut guard = table.pin();
+            let options = ReadOptions {
+                memory_budget: BUDGET,
+                ..Default::default()
+            };
+            let mut pages = guard.pages_with_options(options);
+            let mut i = 0u64;
+            let mut peak = 0;
+            while let Some(page) = pages.next().await.unwrap() {
+                for (k, v) in page {
+                    assert_eq!(k, &i.to_be_bytes());
+                    assert_eq!(v, &i.to_be_bytes());
+                    i += 1;
                 }
-            });
-            tasks.push(handle);
+                peak = peak.max(pages.pinned_bytes());
+            }
+            assert_eq!(i, N);
+            assert!(peak > 0 && peak <= BUDGET * 2);
+
+            // The same scan pins much more memory without a budget.
+            let mut pages = guard.pages();
+            while pages.next().await.unwrap().is_some() {}
+            assert!(pages.pinned_bytes() > BUDGET * 4);
         }
-        for task in tasks {
-            task.await.unwrap();
+
+        // A single page never fits in a tiny budget.
+        let mut guard = table.pin();
+        let options = ReadOptions {
+            memory_budget: 1,
+            ..Default::default()
+        };
+        let mut pages = guard.pages_with_options(options);
+        assert!(matches!(pages.next().await, Err(Error::MemoryLimit)));
+        drop(guard);
+
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn get_across_buffer_and_file() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        let key = 1u64.to_be_bytes();
+        table.put(&key, 1, b"old").await.unwrap();
+        table.flush(&FlushOptions::default()).await;
+        // The new delta stays in the write buffer, above the flushed page.
+        table.put(&key, 2, b"new").await.unwrap();
+
+        let start = table.stats();
+        assert_eq!(table.get(&key, 2).await.unwrap(), Some(b"new".to_vec()));
+        assert_eq!(table.get(&key, 1).await.unwrap(), Some(b"old".to_vec()));
+        let stats = table.stats().sub(&start).store.writebuf;
+        assert!(stats.read_in_buf > 0);
+        assert!(stats.read_in_file > 0);
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn metadata() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        assert_eq!(table.get_metadata(b"schema").await, None);
+        table.set_metadata(b"schema", b"1").await.unwrap();
+        for i in 0..1024 {
+            must_put(&table, i, i).await;
         }
+        table.flush(&FlushOptions::default()).await;
+        table.set_metadata(b"schema", b"2").await.unwrap();
+        table.set_metadata(b"cluster", b"c1").await.unwrap();
+        assert!(matches!(
+            table.set_metadata(b"large", &[0; 64 << 10]).await,
+            Err(Error::TooLargeSize)
+        ));
+        table.close().await.unwrap();
+
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        assert_eq!(table.get_metadata(b"schema").await, Some(b"2".to_vec()));
+        assert_eq!(table.get_metadata(b"cluster").await, Some(b"c1".to_vec()));
+        assert_eq!(table.get_metadata(b"large").await, None);
         table.close().await.unwrap();
     }
 
     #[photonio::test]
-    async fn graceful_shutdown() {
+    async fn pin_range() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        const N: u64 = 1 << 10;
+        const HOT: u64 = 32;
+        for i in 0..N {
+            must_put(&table, i, i).await;
+        }
+        table.flush(&FlushOptions::default()).await;
+        let (start, end) = (0u64.to_be_bytes(), HOT.to_be_bytes());
+        table.pin_range(&start, &end).await.unwrap();
+
+        // Reading the other keys evicts pages from the small cache.
+        let stats = table.stats();
+        for i in HOT..N {
+            must_get(&table, i, i, Some(i)).await;
+        }
+        let cache = table.stats().sub(&stats).store.page_cache;
+        assert!(cache.evictions() > 0);
+
+        // The pinned pages are still in the cache.
+        let stats = table.stats();
+        for i in 0..HOT {
+            must_get(&table, i, i, Some(i)).await;
+        }
+        let cache = table.stats().sub(&stats).store.page_cache;
+        assert!(cache.lookup_hit > 0);
+        assert_eq!(cache.lookup_miss, 0);
+
+        assert!(table.unpin_range(&start, &end));
+        assert!(!table.unpin_range(&start, &end));
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn read_page_by_addr() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        let key = 1u64.to_be_bytes();
+        must_put(&table, 1, 1).await;
+
+        let mut guard = table.pin();
+        let addr: PageAddr = guard.page_addr(&key).await.unwrap();
+        let page = guard.read_page(addr).await.unwrap().to_vec();
+        assert!(page.windows(key.len()).any(|w| w == key));
+
+        // The page is still readable after it is replaced.
+        const N: u64 = 1 << 10;
+        for i in 0..N {
+            must_put(&table, i, i).await;
+        }
+        assert_ne!(guard.page_addr(&key).await.unwrap(), addr);
+        assert_eq!(guard.read_page(addr).await.unwrap(), page);
+
+        // Addresses not obtained since the table is pinned are rejected.
+        assert!(matches!(
+            guard.read_page(PageAddr::default()).await,
+            Err(Error::InvalidArgument)
+        ));
+        guard.repin();
+        assert!(matches!(
+            guard.read_page(addr).await,
+            Err(Error::InvalidArgument)
+        ));
+        drop(guard);
+
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn contains() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        const N: u64 = 1 << 10;
+        for i in 0..N {
+            must_put(&table, i, i).await;
+        }
+        for i in (0..N).step_by(2) {
+            table.delete(&i.to_be_bytes(), N + i).await.unwrap();
+        }
+        for i in 0..N * 2 {
+            let key = i.to_be_bytes();
+            for lsn in [i, N * 3] {
+                let value = table.get(&key, lsn).await.unwrap();
+                let contains = table.contains(&key, lsn).await.unwrap();
+                assert_eq!(contains, value.is_some());
+            }
+        }
+        assert!(table.contains(&1u64.to_be_bytes(), N * 3).await.unwrap());
+        assert!(!table.contains(&2u64.to_be_bytes(), N * 3).await.unwrap());
+        assert!(!table.contains(&N.to_be_bytes(), N * 3).await.unwrap());
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn delete_where() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        const N: u64 = 1 << 10;
+        for i in 0..N {
+            must_put(&table, i, i).await;
+        }
+        let is_even = |_: &[u8], v: &[u8]| v[v.len() - 1] % 2 == 0;
+
+        // Stops after the first page.
+        let first = table.delete_where(N, is_even, |_| false).await.unwrap();
+        assert!(first > 0 && first < (N / 2) as usize);
+
+        let mut last_progress = first;
+        let rest = table
+            .delete_where(N, is_even, |n| {
+                assert!(n + first >= last_progress);
+                last_progress = n + first;
+                true
+            })
+            .await
+            .unwrap();
+        assert_eq!(first + rest, (N / 2) as usize);
+        assert_eq!(last_progress, (N / 2) as usize);
+
+        for i in 0..N {
+            let expect = if i % 2 == 0 { None } else { Some(i) };
+            must_get(&table, i, N, expect).await;
+            must_get(&table, i, N - 1, Some(i)).await;
+        }
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn rename() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        const N: u64 = 1 << 10;
+        for i in (0..N).step_by(2) {
+            must_put(&table, i, i).await;
+        }
+
+        // Renames to a key in the same page and to a key far away.
+        for (from, to) in [(2, 3), (4, N - 1)] {
+            let lsn = N + from;
+            let (from_key, to_key) = (from.to_be_bytes(), to.to_be_bytes());
+            assert!(table.rename(&from_key, &to_key, lsn).await.unwrap());
+            must_get(&table, from, lsn, None).await;
+            must_get(&table, to, lsn, Some(from)).await;
+            must_get(&table, from, lsn - 1, Some(from)).await;
+            must_get(&table, to, lsn - 1, None).await;
+        }
+
+        // Absent keys are not renamed, and don't count as writes.
+        let num_writes = table.stats().tree.success.write;
+        let (from_key, to_key) = (1u64.to_be_bytes(), 5u64.to_be_bytes());
+        assert!(!table.rename(&from_key, &to_key, N * 2).await.unwrap());
+        must_get(&table, 5, N * 2, None).await;
+        assert_eq!(table.stats().tree.success.write, num_writes);
+
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn write_batch() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        const N: u64 = 1 << 10;
+        for i in 0..N {
+            must_put(&table, i, i).await;
+        }
+
+        // The last entry of a duplicate key wins.
+        let keys: Vec<_> = [1u64, 2, N - 1].iter().map(|i| i.to_be_bytes()).collect();
+        let value = 0u64.to_be_bytes();
+        let batch = [
+            (keys[2].as_slice(), Some(keys[1].as_slice())),
+            (keys[0].as_slice(), Some(value.as_slice())),
+            (keys[1].as_slice(), None),
+            (keys[0].as_slice(), None),
+            (keys[2].as_slice(), Some(value.as_slice())),
+        ];
+        table.write_batch(&batch, N).await.unwrap();
+        must_get(&table, 1, N, None).await;
+        must_get(&table, 2, N, None).await;
+        must_get(&table, N - 1, N, Some(0)).await;
+        must_get(&table, 1, N - 1, Some(1)).await;
+        table.close().await.unwrap();
+
+        let path = tempdir().unwrap();
+        let options = TableOptions {
+            reject_duplicate_keys: true,
+            ..OPTIONS
+        };
+        let table = Table::open(&path, options).await.unwrap();
+        must_put(&table, 1, 1).await;
+        assert!(matches!(
+            table.write_batch(&batch, 2).await,
+            Err(Error::DuplicateKey)
+        ));
+        must_get(&table, 1, 2, Some(1)).await;
+        table.write_batch(&batch[..3], 2).await.unwrap();
+        must_get(&table, 1, 2, Some(0)).await;
+        must_get(&table, N - 1, 2, Some(2)).await;
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn write_batch_atomic() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        const N: u64 = 1 << 10;
+        for i in 0..N {
+            must_put(&table, i, i).await;
+        }
+
+        // The batch spans many pages.
+        let mut batch = WriteBatch::new();
+        for i in (0..N).step_by(2) {
+            batch.put(&i.to_be_bytes(), &(i + 1).to_be_bytes());
+            batch.delete(&(i + 1).to_be_bytes());
+        }
+        assert_eq!(batch.len(), N as usize);
+        table.write(&batch, N).await.unwrap();
+        for i in (0..N).step_by(2) {
+            must_get(&table, i, N, Some(i + 1)).await;
+            must_get(&table, i + 1, N, None).await;
+            must_get(&table, i, N - 1, Some(i)).await;
+        }
+
+        // The entries survive reopening.
+        table.close().await.unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        for i in (0..N).step_by(2) {
+            must_get(&table, i, N, Some(i + 1)).await;
+            must_get(&table, i + 1, N, None).await;
+        }
+
+        // A batch that doesn't fit in a write buffer is rejected as a whole.
+        let value = vec![0u8; 1 << 10];
+        batch.clear();
+        for i in 0..N {
+            batch.put(&i.to_be_bytes(), &value);
+        }
+        assert!(matches!(
+            table.write(&batch, N + 1).await,
+            Err(Error::TooLargeSize)
+        ));
+        must_get(&table, 0, N + 1, Some(1)).await;
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn compare_and_swap() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        let key = 1u64.to_be_bytes();
+        let value = |i: u64| i.to_be_bytes();
+
+        // Inserts the key if it is absent.
+        assert!(table
+            .compare_and_swap(&key, 1, None, Some(&value(1)))
+            .await
+            .unwrap());
+        assert!(!table
+            .compare_and_swap(&key, 2, None, Some(&value(2)))
+            .await
+            .unwrap());
+        must_get(&table, 1, 2, Some(1)).await;
+
+        // Updates the key if the value matches.
+        assert!(!table
+            .compare_and_swap(&key, 2, Some(&value(2)), Some(&value(3)))
+            .await
+            .unwrap());
+        assert!(table
+            .compare_and_swap(&key, 2, Some(&value(1)), Some(&value(2)))
+            .await
+            .unwrap());
+        must_get(&table, 1, 2, Some(2)).await;
+        must_get(&table, 1, 1, Some(1)).await;
+
+        // Increments a counter on many pages.
+        const N: u64 = 1 << 8;
+        for lsn in 3..6 {
+            for i in 0..N {
+                let key = i.to_be_bytes();
+                let expected = table.get(&key, lsn).await.unwrap();
+                let count = expected
+                    .as_ref()
+                    .map_or(0, |v| u64::from_be_bytes(v.as_slice().try_into().unwrap()));
+                let swapped = table
+                    .compare_and_swap(&key, lsn, expected.as_deref(), Some(&value(count + 1)))
+                    .await
+                    .unwrap();
+                assert!(swapped);
+            }
+        }
+        must_get(&table, 0, 5, Some(3)).await;
+        must_get(&table, 1, 5, Some(5)).await;
+        must_get(&table, N - 1, 5, Some(3)).await;
+
+        // Deletes the key if the value matches.
+        assert!(table
+            .compare_and_swap(&key, 6, Some(&value(5)), None)
+            .await
+            .unwrap());
+        must_get(&table, 1, 6, None).await;
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn get_for_update() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        let key = 1u64.to_be_bytes();
+        let value = |i: u64| i.to_be_bytes();
+
+        // Inserts the key if it is still absent.
+        let (v, intent) = table.get_for_update(&key).await.unwrap();
+        assert_eq!(v, None);
+        assert_eq!(intent.version(), None);
+        assert!(table
+            .commit_update(&intent, 1, Some(&value(1)))
+            .await
+            .unwrap());
+        assert!(!table
+            .commit_update(&intent, 2, Some(&value(2)))
+            .await
+            .unwrap());
+        must_get(&table, 1, 2, Some(1)).await;
+
+        // Fails if the key is written after the read.
+        let (v, intent) = table.get_for_update(&key).await.unwrap();
+        assert_eq!(v, Some(value(1).to_vec()));
+        assert_eq!(intent.version(), Some(1));
+        table.put(&key, 2, &value(2)).await.unwrap();
+        assert!(!table
+            .commit_update(&intent, 3, Some(&value(3)))
+            .await
+            .unwrap());
+        must_get(&table, 1, 3, Some(2)).await;
+
+        // The LSN of the write must be greater than the version.
+        let (_, intent) = table.get_for_update(&key).await.unwrap();
+        assert_eq!(intent.version(), Some(2));
+        assert!(matches!(
+            table.commit_update(&intent, 2, Some(&value(3))).await,
+            Err(Error::InvalidArgument)
+        ));
+        assert!(table
+            .commit_update(&intent, 3, Some(&value(3)))
+            .await
+            .unwrap());
+        must_get(&table, 1, 3, Some(3)).await;
+
+        // Deletes the key if it is unchanged.
+        let (_, intent) = table.get_for_update(&key).await.unwrap();
+        assert!(table.commit_update(&intent, 4, None).await.unwrap());
+        must_get(&table, 1, 4, None).await;
+        let (v, intent) = table.get_for_update(&key).await.unwrap();
+        assert_eq!(v, None);
+        assert_eq!(intent.version(), Some(4));
+
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn bulk_loader() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        const N: u64 = 1 << 12;
+        let mut loader = table.bulk_loader(1).await.unwrap();
+        for i in 0..N {
+            let buf = i.to_be_bytes();
+            loader.add(&buf, &buf).await.unwrap();
+        }
+        // Keys must be added in order.
+        assert!(matches!(
+            loader.add(&0u64.to_be_bytes(), b"").await,
+            Err(Error::InvalidArgument)
+        ));
+        must_get(&table, 0, 1, None).await;
+        assert_eq!(loader.finish().await.unwrap(), N as usize);
+        for i in 0..N {
+            must_get(&table, i, 1, Some(i)).await;
+        }
+        must_get(&table, N, 1, None).await;
+        must_put(&table, N, 2).await;
+        must_get(&table, N, 2, Some(N)).await;
+        assert!(matches!(
+            table.bulk_loader(3).await,
+            Err(Error::InvalidArgument)
+        ));
+
+        // Loads a named tree from a scan of the table.
+        let copy = table.open_tree("copy").await.unwrap();
+        let mut loader = copy.bulk_loader(2).await.unwrap();
+        let stream = table.scan(&[], &[0xff; 9], 2).into_stream();
+        loader.extend(stream).await.unwrap();
+        assert_eq!(loader.finish().await.unwrap(), N as usize + 1);
+        for i in 0..=N {
+            must_get(&copy, i, 2, Some(i)).await;
+        }
+        drop(copy);
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn get_pinned() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        let value = vec![7u8; 4 << 10];
+        table.put(b"large", 1, &value).await.unwrap();
+        table.flush(&FlushOptions::default()).await;
+
+        let pinned = table.get_pinned(b"large", 1).await.unwrap().unwrap();
+        assert_eq!(&*pinned, value.as_slice());
+        assert!(table.get_pinned(b"large", 0).await.unwrap().is_none());
+        // Writes don't invalidate the pinned value.
+        table.put(b"large", 2, b"small").await.unwrap();
+        assert_eq!(pinned.as_ref(), value.as_slice());
+        drop(pinned);
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn typed_table() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        let typed = table.typed::<i64, String>();
+        for i in -100..100i64 {
+            typed.put(&i, 1, &i.to_string()).await.unwrap();
+        }
+        typed.delete(&0, 2).await.unwrap();
+        assert_eq!(typed.get(&-1, 2).await.unwrap(), Some("-1".to_owned()));
+        assert_eq!(typed.get(&0, 2).await.unwrap(), None);
+        assert_eq!(typed.get(&100, 2).await.unwrap(), None);
+
+        // Negative keys sort before positive ones.
+        let mut scan = typed.scan(&-3, &3, 2);
+        let mut keys = Vec::new();
+        while let Some((k, v)) = scan.next().await.unwrap() {
+            assert_eq!(v, k.to_string());
+            keys.push(k);
+        }
+        assert_eq!(keys, [-3, -2, -1, 1, 2]);
+
+        // Values of other types can't be decoded.
+        table.put(&5i64.encode(), 3, &[0xff]).await.unwrap();
+        assert!(matches!(typed.get(&5, 3).await, Err(Error::Corrupted)));
+        drop(scan);
+        drop(typed);
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn open_tree() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        let users = table.open_tree("users").await.unwrap();
+        let items = table.open_tree("items").await.unwrap();
+        const N: u64 = 1 << 8;
+        for i in 0..N {
+            must_put(&users, i, 1).await;
+        }
+        must_put(&items, N, 1).await;
+
+        // The trees don't see the entries of each other.
+        must_get(&table, 0, 1, None).await;
+        must_get(&items, 0, 1, None).await;
+        must_get(&users, N, 1, None).await;
+        must_get(&table.open_tree("users").await.unwrap(), 0, 1, Some(0)).await;
+
+        // The roots of named trees are reserved.
+        assert!(matches!(
+            table.set_metadata(b"photondb.trees", b"").await,
+            Err(Error::InvalidArgument)
+        ));
+        assert!(matches!(
+            table.rebuild_index().await,
+            Err(Error::InvalidArgument)
+        ));
+
+        drop(users);
+        drop(items);
+        table.close().await.unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        let users = table.open_tree("users").await.unwrap();
+        for i in 0..N {
+            must_get(&users, i, 1, Some(i)).await;
+        }
+        let items = table.open_tree("items").await.unwrap();
+        must_get(&items, N, 1, Some(N)).await;
+        must_get(&table, N, 1, None).await;
+        drop(users);
+        drop(items);
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn put_auto() {
         let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        let users = table.open_tree("users").await.unwrap();
+        assert_eq!(table.latest_lsn(), 0);
+        let a = table.put_auto(b"a", b"1").await.unwrap();
+        let b = users.put_auto(b"b", b"2").await.unwrap();
+        let c = table.delete_auto(b"a").await.unwrap();
+        assert!(a < b && b < c);
+        assert_eq!(table.latest_lsn(), c);
+        assert_eq!(table.get(b"a", b).await.unwrap(), Some(b"1".to_vec()));
+        assert_eq!(table.get(b"a", c).await.unwrap(), None);
+        assert!(matches!(
+            table.set_metadata(b"photondb.lsn", b"").await,
+            Err(Error::InvalidArgument)
+        ));
+
+        // The sequence continues after restarts.
+        drop(users);
+        table.close().await.unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        assert!(table.latest_lsn() >= c);
+        let d = table.put_auto(b"a", b"3").await.unwrap();
+        assert!(d > c);
+        assert_eq!(table.get(b"a", d).await.unwrap(), Some(b"3".to_vec()));
+
+        // A failed reservation assigns nothing.
         let opts = TableOptions {
             page_store: PageStoreOptions {
-                avoid_flush_during_shutdown: false,
-                ..Default::default()
+                read_only: true,
+                ..OPTIONS.page_store
             },
-            ..Default::default()
+            ..OPTIONS
         };
-        let table = Table::open(&path, opts.clone()).await.unwrap();
-        let i = random();
+        let reader = Table::open(&path, opts).await.unwrap();
+        let latest = reader.latest_lsn();
+        assert!(matches!(
+            reader.put_auto(b"a", b"4").await,
+            Err(Error::ReadOnly)
+        ));
+        assert_eq!(reader.latest_lsn(), latest);
+        reader.close().await.unwrap();
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn put_with_ttl() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        const N: u64 = 1 << 8;
+        for i in 0..N {
+            let key = i.to_be_bytes();
+            // Odd keys expire immediately.
+            let ttl = Duration::from_secs(if i % 2 == 0 { 3600 } else { 0 });
+            table.put_with_ttl(&key, 1, &key, ttl).await.unwrap();
+        }
+        // An expired value hides older versions.
+        must_put(&table, N, 1).await;
+        let key = N.to_be_bytes();
+        table
+            .put_with_ttl(&key, 2, &key, Duration::ZERO)
+            .await
+            .unwrap();
+
+        for i in 0..N {
+            must_get(&table, i, 1, (i % 2 == 0).then_some(i)).await;
+        }
+        must_get(&table, N, 1, Some(N)).await;
+        must_get(&table, N, 2, None).await;
+        assert!(table.contains(&0u64.to_be_bytes(), 1).await.unwrap());
+        assert!(!table.contains(&1u64.to_be_bytes(), 1).await.unwrap());
+
+        let mut scan = table.scan(&[], &(N + 1).to_be_bytes(), 2);
+        let mut num_keys = 0;
+        while let Some((key, _)) = scan.next().await.unwrap() {
+            assert_eq!(u64::from_be_bytes(key.try_into().unwrap()) % 2, 0);
+            num_keys += 1;
+        }
+        assert_eq!(num_keys, N / 2);
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn multi_get() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        const N: u64 = 1 << 10;
+        for i in (0..N).step_by(2) {
+            must_put(&table, i, 1).await;
+        }
+
+        // Keys are out of order, repeated, and spread over many pages.
+        let ids = [N - 2, 1, 0, N / 2, 0, 3, N, N / 2 + 2];
+        let keys: Vec<_> = ids.iter().map(|i| i.to_be_bytes()).collect();
+        let keys: Vec<&[u8]> = keys.iter().map(|k| k.as_slice()).collect();
+        let values = table.multi_get(&keys, 1).await.unwrap();
+        for (i, value) in ids.into_iter().zip(values) {
+            let expect = (i % 2 == 0 && i < N).then(|| i.to_be_bytes().to_vec());
+            assert_eq!(value, expect);
+        }
+        assert!(table.multi_get(&[], 1).await.unwrap().is_empty());
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn delete_range() {
+        let path = tempdir().unwrap();
+        let mut table = Table::open(&path, OPTIONS).await.unwrap();
+        const N: u64 = 1 << 10;
+        for i in 0..N {
+            must_put(&table, i, 1).await;
+        }
+        must_put(&table, 20, 4).await;
+
+        // Deletes the range across pages with one delta per page.
+        let start = 10u64.to_be_bytes();
+        let end = (N - 10).to_be_bytes();
+        let num_writes = table.stats().tree.success.write;
+        table.delete_range(&start, &end, 3).await.unwrap();
+        let num_deltas = table.stats().tree.success.write - num_writes;
+        assert!(num_deltas > 1 && num_deltas < (N - 20) / 2);
+        for _ in 0..2 {
+            for i in [0, 9, N - 10, N - 1] {
+                must_get(&table, i, 3, Some(i)).await;
+            }
+            for i in [10, 11, N / 2, N - 11] {
+                must_get(&table, i, 3, None).await;
+                must_get(&table, i, 1, Some(i)).await;
+            }
+            // Writes above the LSN are kept.
+            must_get(&table, 20, 4, Some(20)).await;
+            let mut scan = table.scan(&[], &N.to_be_bytes(), 4);
+            let mut expect = (0..10).chain([20]).chain(N - 10..N);
+            while let Some((k, _)) = scan.next().await.unwrap() {
+                assert_eq!(k, expect.next().unwrap().to_be_bytes());
+            }
+            assert_eq!(expect.next(), None);
+            drop(scan);
+
+            // The range deletes are recovered from the page store.
+            table.close().await.unwrap();
+            table = Table::open(&path, OPTIONS).await.unwrap();
+        }
+
+        // Deletes nothing in an empty range.
+        table.delete_range(&end, &start, 4).await.unwrap();
+        must_get(&table, N - 1, 4, Some(N - 1)).await;
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn negative_cache() {
+        let path = tempdir().unwrap();
+        let options = TableOptions {
+            negative_cache_size: 16,
+            ..OPTIONS
+        };
+        let table = Table::open(&path, options).await.unwrap();
+        const N: u64 = 1 << 10;
+        for i in 0..N {
+            must_put(&table, i, i).await;
+        }
+
+        let hits = |table: &Table| table.stats().tree.negative_cache_hits;
+        // The first read records the miss, the following ones hit the cache.
+        for _ in 0..10 {
+            must_get(&table, N, u64::MAX, None).await;
+        }
+        assert_eq!(hits(&table), 9);
+        must_get(&table, N, 0, None).await;
+        assert_eq!(hits(&table), 10);
+
+        // Writes invalidate the miss.
+        must_put(&table, N, N).await;
+        must_get(&table, N, N, Some(N)).await;
+        must_get(&table, N, N - 1, None).await;
+        assert_eq!(hits(&table), 10);
+
+        // Deleted keys still have entries, so they are not recorded.
+        table.delete(&N.to_be_bytes(), N + 1).await.unwrap();
+        for _ in 0..10 {
+            must_get(&table, N, u64::MAX, None).await;
+        }
+        assert_eq!(hits(&table), 10);
+
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn scan_since() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        const N: u64 = 1 << 10;
+        for i in 0..N {
+            must_put(&table, i, i).await;
+        }
+        // Rewrites and deletes some keys at or after the floor.
+        let mut expect = Vec::new();
+        for i in 0..N {
+            let key = i.to_be_bytes().to_vec();
+            if i % 7 == 0 {
+                table.delete(&key, N + i).await.unwrap();
+                expect.push((key, None));
+            } else if i % 3 == 0 {
+                must_put(&table, i, N + i).await;
+                expect.push((key.clone(), Some(key)));
+            }
+        }
+
+        let entries = table.scan_since(N, true).await.unwrap();
+        assert_eq!(entries, expect);
+        let entries = table.scan_since(N, false).await.unwrap();
+        expect.retain(|(_, v)| v.is_some());
+        assert_eq!(entries, expect);
+
+        // Only the newest versions are considered.
+        let entries = table.scan_since(N + N / 2, false).await.unwrap();
+        let expect: Vec<_> = expect
+            .into_iter()
+            .filter(|(k, _)| u64::from_be_bytes(k.as_slice().try_into().unwrap()) >= N / 2)
+            .collect();
+        assert_eq!(entries, expect);
+        let num_live = (0..N).filter(|i| i % 7 != 0).count();
+        assert_eq!(table.scan_since(0, false).await.unwrap().len(), num_live);
+
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn deterministic_splits() {
+        async fn page_boundaries(table: &Table) -> Vec<Vec<u8>> {
+            let guard = table.pin();
+            let mut pages = guard.pages();
+            let mut boundaries = Vec::new();
+            while let Some(mut page) = pages.next().await.unwrap() {
+                if let Some((k, _)) = page.next() {
+                    boundaries.push(k.to_vec());
+                }
+            }
+            boundaries
+        }
+
+        const N: u64 = 1 << 10;
+        let mut stats = Vec::new();
+        let mut boundaries = Vec::new();
+        for _ in 0..2 {
+            let path = tempdir().unwrap();
+            let table = Table::open(&path, OPTIONS).await.unwrap();
+            for i in 0..N {
+                let k = i * 7919 % N;
+                must_put(&table, k, i).await;
+            }
+            let tree = table.stats().tree;
+            stats.push((tree.success.split_page, tree.success.consolidate_page));
+            boundaries.push(page_boundaries(&table).await);
+            table.close().await.unwrap();
+        }
+        assert!(boundaries[0].len() > 1);
+        assert_eq!(boundaries[0], boundaries[1]);
+        assert_eq!(stats[0], stats[1]);
+    }
+
+    #[photonio::test]
+    async fn page_kind_counts() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        let counts = table.page_kind_counts().await.unwrap();
+        assert_eq!(counts.get(&PageKind::Data), Some(&1));
+        assert_eq!(counts.get(&PageKind::Split), None);
+
+        // The first split happens on the root, which doesn't leave a split page.
+        let mut i = 0;
+        while table.stats().tree.success.split_page < 2 {
+            must_put(&table, i, i).await;
+            i += 1;
+        }
+        let counts = table.page_kind_counts().await.unwrap();
+        assert!(counts[&PageKind::Data] > 1);
+        assert_eq!(counts.get(&PageKind::Split), Some(&1));
+
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn pause_maintenance() {
+        let path = tempdir().unwrap();
+        let options = TableOptions {
+            page_store: PageStoreOptions {
+                write_buffer_capacity: 16 << 10,
+                max_write_buffers: 4,
+                ..OPTIONS.page_store
+            },
+            ..OPTIONS
+        };
+        let table = Table::open(&path, options).await.unwrap();
+        table.pause_maintenance();
+
+        // Overwrite some keys to produce garbage. Writes are not stalled, since
+        // write buffers are flushed once all of them are used up.
+        const N: u64 = 1 << 12;
+        for i in 0..N {
+            must_put(&table, i % 64, i).await;
+        }
+        let stats = table.stats();
+        assert!(stats.store.jobs.flush_write_bytes > 0);
+        assert_eq!(stats.store.jobs.compact_input_bytes, 0);
+
+        table.resume_maintenance();
+        table.wait_for_reclaiming().await;
+        assert!(table.stats().store.jobs.compact_input_bytes > 0);
+        for i in N - 64..N {
+            must_get(&table, i % 64, N, Some(i)).await;
+        }
+
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn delete_obsolete_files() {
+        let path = tempdir().unwrap();
+        let options = TableOptions {
+            page_store: PageStoreOptions {
+                write_buffer_capacity: 16 << 10,
+                max_write_buffers: 4,
+                keep_obsolete_files: true,
+                ..OPTIONS.page_store
+            },
+            ..OPTIONS
+        };
+        let table = Table::open(&path, options).await.unwrap();
+        table.pause_maintenance();
+        const N: u64 = 1 << 12;
+        for i in 0..N {
+            must_put(&table, i % 64, i).await;
+        }
+
+        // The pinned guard can still read the compacted files.
+        let guard = table.pin();
+        table.resume_maintenance();
+        table.wait_for_reclaiming().await;
+        assert!(table.stats().store.jobs.compact_input_bytes > 0);
+        for _ in 0..100 {
+            photonio::task::yield_now().await;
+        }
+        assert!(table.obsolete_files().is_empty());
+
+        drop(guard);
+        let files = loop {
+            let files = table.obsolete_files();
+            if !files.is_empty() {
+                break files;
+            }
+            photonio::task::yield_now().await;
+        };
+        let file_path = |id: &u32| path.path().join(format!("map_{id}"));
+        assert!(files.iter().all(|id| file_path(id).exists()));
+        assert!(table.delete_obsolete_files().await >= files.len());
+        assert!(files.iter().all(|id| !file_path(id).exists()));
+        for i in N - 64..N {
+            must_get(&table, i % 64, N, Some(i)).await;
+        }
+
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn dump() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        const N: u64 = 1 << 10;
+        for i in 0..N {
+            let k = i * 7919 % N;
+            must_put(&table, k, i).await;
+        }
+        // Deleted entries are not dumped.
+        for i in (0..N).step_by(3) {
+            table.delete(&i.to_be_bytes(), N).await.unwrap();
+        }
+
+        let mut dump = table.dump();
+        let mut expect = (0..N).filter(|i| i % 3 != 0);
+        while let Some((k, v)) = dump.next().await.unwrap() {
+            let i = expect.next().unwrap().to_be_bytes();
+            assert_eq!(k, i);
+            assert_eq!(v, i);
+        }
+        assert_eq!(expect.next(), None);
+        drop(dump);
+
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn scan() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        const N: u64 = 1 << 10;
+        for i in 0..N {
+            let k = i * 7919 % N;
+            must_put(&table, k, i).await;
+        }
+        for i in (0..N).step_by(3) {
+            table.delete(&i.to_be_bytes(), N).await.unwrap();
+        }
+
+        let (start, end) = (100u64, 900u64);
+        let mut scan = table.scan(&start.to_be_bytes(), &end.to_be_bytes(), N);
+        let mut expect = (start..end).filter(|i| i % 3 != 0);
+        while let Some((k, v)) = scan.next().await.unwrap() {
+            let i = expect.next().unwrap().to_be_bytes();
+            assert_eq!(k, i);
+            assert_eq!(v, i);
+        }
+        assert_eq!(expect.next(), None);
+        drop(scan);
+
+        // Deletions after the read LSN are not visible.
+        let mut scan = table.scan(&3u64.to_be_bytes(), &4u64.to_be_bytes(), N - 1);
+        let k = 3u64.to_be_bytes();
+        assert_eq!(scan.next().await.unwrap(), Some((k.to_vec(), k.to_vec())));
+        assert_eq!(scan.next().await.unwrap(), None);
+        drop(scan);
+
+        // An empty range yields nothing.
+        let mut scan = table.scan(&end.to_be_bytes(), &start.to_be_bytes(), N);
+        assert_eq!(scan.next().await.unwrap(), None);
+        drop(scan);
+
+        // The keys can be scanned without values.
+        let mut scan = table
+            .scan(&start.to_be_bytes(), &end.to_be_bytes(), N)
+            .keys_only();
+        let (k, v) = scan.next().await.unwrap().unwrap();
+        assert_eq!(k, 101u64.to_be_bytes());
+        assert!(v.is_empty());
+        drop(scan);
+
+        // The entries can be consumed as a stream.
+        let stream = table
+            .scan(&start.to_be_bytes(), &end.to_be_bytes(), N)
+            .into_stream();
+        let keys: Vec<_> = stream.map(|entry| entry.unwrap().0).collect().await;
+        let expect: Vec<_> = (start..end)
+            .filter(|i| i % 3 != 0)
+            .map(|i| i.to_be_bytes().to_vec())
+            .collect();
+        assert_eq!(keys, expect);
+
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn scan_cancelled() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        const N: u64 = 1 << 10;
+        for i in 0..N {
+            must_put(&table, i, i).await;
+        }
+        // Read the pages from files, so that reading a page awaits.
+        table.flush(&FlushOptions::default()).await;
+
+        let refs = table.store().version_refs();
+        let mut scan = table.scan(&0u64.to_be_bytes(), &N.to_be_bytes(), N);
+        for _ in 0..N / 2 {
+            let mut next = Box::pin(scan.next());
+            if futures::poll!(next.as_mut()).is_pending() {
+                // Drop the future while it is reading a page.
+                break;
+            }
+        }
+        drop(scan);
+        // The guards of the cancelled scan are released.
+        assert_eq!(table.store().version_refs(), refs);
+
+        // The dropped scan doesn't block writes, flushes, or other scans.
+        must_put(&table, N, N).await;
+        table.flush(&FlushOptions::default()).await;
+        let mut scan = table.scan(&0u64.to_be_bytes(), &(N + 1).to_be_bytes(), N);
+        let mut i = 0u64;
+        while let Some((k, _)) = scan.next().await.unwrap() {
+            assert_eq!(k, i.to_be_bytes());
+            i += 1;
+        }
+        assert_eq!(i, N + 1);
+        drop(scan);
+
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn scan_with_options() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        const N: u64 = 1 << 10;
+        for i in 0..N {
+            let k = i * 7919 % N;
+            must_put(&table, k, i).await;
+        }
+        for i in (0..N).step_by(3) {
+            table.delete(&i.to_be_bytes(), N).await.unwrap();
+        }
+
+        async fn collect(table: &Table, options: ReadOptions) -> Vec<u64> {
+            let mut scan = table.scan_with_options(options);
+            let mut keys = Vec::new();
+            while let Some((k, v)) = scan.next().await.unwrap() {
+                assert_eq!(k, v);
+                keys.push(u64::from_be_bytes(k.try_into().unwrap()));
+            }
+            keys
+        }
+        let bounded = |start: u64, end: u64, limit, direction| ReadOptions {
+            lower_bound: Some(start.to_be_bytes().to_vec()),
+            upper_bound: Some(end.to_be_bytes().to_vec()),
+            limit,
+            direction,
+            ..Default::default()
+        };
+
+        let (start, end) = (100u64, 900u64);
+        let expect: Vec<_> = (start..end).filter(|i| i % 3 != 0).collect();
+        let reversed: Vec<_> = expect.iter().rev().copied().collect();
+        let keys = collect(&table, bounded(start, end, usize::MAX, Direction::Forward)).await;
+        assert_eq!(keys, expect);
+        let keys = collect(&table, bounded(start, end, usize::MAX, Direction::Backward)).await;
+        assert_eq!(keys, reversed);
+
+        // The scan stops at the limit.
+        let keys = collect(&table, bounded(start, end, 10, Direction::Forward)).await;
+        assert_eq!(keys, expect[..10]);
+        let keys = collect(&table, bounded(start, end, 10, Direction::Backward)).await;
+        assert_eq!(keys, reversed[..10]);
+        let keys = collect(&table, bounded(start, end, 0, Direction::Forward)).await;
+        assert!(keys.is_empty());
+
+        // An empty range yields nothing.
+        let keys = collect(&table, bounded(end, start, usize::MAX, Direction::Backward)).await;
+        assert!(keys.is_empty());
+
+        // Unbounded scans cover the whole table.
+        let expect: Vec<_> = (0..N).filter(|i| i % 3 != 0).rev().collect();
+        let options = ReadOptions {
+            direction: Direction::Backward,
+            ..Default::default()
+        };
+        assert_eq!(collect(&table, options).await, expect);
+
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn scan_prefix() {
+        fn extract(key: &[u8]) -> Option<&[u8]> {
+            key.get(..2)
+        }
+
+        let path = tempdir().unwrap();
+        let options = TableOptions {
+            prefix_extractor: Some(extract),
+            ..OPTIONS
+        };
+        let table = Table::open(&path, options).await.unwrap();
+        let prefixes = [0u16, 1, 7, 0xFF, 0x100, 0xFFFF];
+        let key = |p: u16, s: u16| [p.to_be_bytes(), s.to_be_bytes()].concat();
+        let mut lsn = 0;
+        // Writes the keys a few times so that the pages are consolidated.
+        for _ in 0..3 {
+            for p in prefixes {
+                for s in 0..64 {
+                    lsn += 1;
+                    table.put(&key(p, s), lsn, &key(s, p)).await.unwrap();
+                }
+            }
+        }
+        for s in (0..64).step_by(2) {
+            lsn += 1;
+            table.delete(&key(7, s), lsn).await.unwrap();
+        }
+
+        for p in prefixes {
+            let mut scan = table.scan_prefix(&p.to_be_bytes(), lsn);
+            let step = if p == 7 { 2 } else { 1 };
+            let mut expect = (0..64).filter(|s| s % step == step - 1);
+            while let Some((k, v)) = scan.next().await.unwrap() {
+                let s = expect.next().unwrap();
+                assert_eq!(k, key(p, s));
+                assert_eq!(v, key(s, p));
+            }
+            assert_eq!(expect.next(), None);
+        }
+        // Absent prefixes and partial prefixes.
+        let mut scan = table.scan_prefix(&2u16.to_be_bytes(), lsn);
+        assert_eq!(scan.next().await.unwrap(), None);
+        drop(scan);
+        let mut scan = table.scan_prefix(&[1], lsn);
+        let mut count = 0;
+        while let Some((k, _)) = scan.next().await.unwrap() {
+            assert_eq!(k[0], 1);
+            count += 1;
+        }
+        assert_eq!(count, 64);
+        drop(scan);
+
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn warm_up_on_open() {
+        let path = tempdir().unwrap();
+        let options = TableOptions {
+            page_size: 8 << 10,
+            page_chain_length: 32,
+            ..OPTIONS
+        };
+        let table = Table::open(&path, options.clone()).await.unwrap();
+        const N: u64 = 16;
+        for i in 0..N {
+            must_put(&table, i, i).await;
+        }
+        table.close().await.unwrap();
+
+        // The recovered root keeps its delta chain.
+        let table = Table::open(&path, options.clone()).await.unwrap();
+        let counts = table.page_kind_counts().await.unwrap();
+        assert_eq!(counts[&PageKind::Data] as u64, N + 1);
+        table.close().await.unwrap();
+
+        let options = TableOptions {
+            page_chain_length: 4,
+            warm_up_on_open: 1,
+            ..options
+        };
+        let table = Table::open(&path, options).await.unwrap();
+        let counts = table.page_kind_counts().await.unwrap();
+        assert_eq!(counts[&PageKind::Data], 1);
+        assert_eq!(table.stats().tree.success.consolidate_page, 1);
+        for i in 0..N {
+            must_get(&table, i, N, Some(i)).await;
+        }
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn self_test_on_open() {
+        let path = tempdir().unwrap();
+        // Keep all pages in a single delta chain so that they stay active.
+        let options = TableOptions {
+            page_size: 8 << 10,
+            page_chain_length: 32,
+            ..OPTIONS
+        };
+        let table = Table::open(&path, options.clone()).await.unwrap();
+        const N: u64 = 16;
+        for i in 0..N {
+            must_put(&table, i, i).await;
+        }
+        table.close().await.unwrap();
+
+        let options = TableOptions {
+            page_store: PageStoreOptions {
+                self_test_on_open: true,
+                ..options.page_store
+            },
+            ..options
+        };
+        let table = Table::open(&path, options.clone()).await.unwrap();
+        table.close().await.unwrap();
+
+        // Corrupt the first page of the first file.
+        let first_file = std::fs::read_dir(&path)
+            .unwrap()
+            .filter_map(|entry| {
+                let name = entry.unwrap().file_name().into_string().unwrap();
+                name.strip_prefix("map_")?.parse::<u32>().ok()
+            })
+            .min()
+            .unwrap();
+        let first_file = path.path().join(format!("map_{first_file}"));
+        let mut content = std::fs::read(&first_file).unwrap();
+        content[0] ^= 0xFF;
+        std::fs::write(&first_file, content).unwrap();
+
+        match Table::open(&path, options.clone()).await {
+            Err(Error::SelfTestFailed(failure)) => {
+                assert!(failure.corrupted_files.is_empty());
+                assert_eq!(failure.corrupted_pages.len(), 1);
+            }
+            Err(err) => panic!("unexpected error {err:?}"),
+            Ok(_) => panic!("the self-test must fail"),
+        }
+
+        // The self-test can be skipped.
+        let options = TableOptions {
+            page_store: PageStoreOptions {
+                self_test_on_open: false,
+                ..options.page_store
+            },
+            ..options
+        };
+        let table = Table::open(&path, options).await.unwrap();
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn savepoint() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        const N: u64 = 64;
+        let value = |i: u64, lsn: u64| (i * 1000 + lsn).to_be_bytes();
+        for i in 0..N {
+            table.put(&i.to_be_bytes(), 1, &value(i, 1)).await.unwrap();
+        }
+        // Writes that don't install anything are not taken into account.
+        let key = 0u64.to_be_bytes();
+        let swapped = table.compare_and_swap(&key, 9, None, Some(&key)).await;
+        assert!(!swapped.unwrap());
+        let num_versions = table.store().version_refs();
+        assert_eq!(table.create_savepoint("sp"), 1);
+        // The savepoint pins the current version.
+        assert_eq!(table.store().version_refs(), num_versions + 1);
+
+        // Overwrite the entries and advance the safe LSN beyond the savepoint, so
+        // that the pages are consolidated.
+        for lsn in 2..8 {
+            for i in 0..N {
+                table
+                    .put(&i.to_be_bytes(), lsn, &value(i, lsn))
+                    .await
+                    .unwrap();
+            }
+            table.set_safe_lsn(lsn);
+        }
+        assert!(table.stats().tree.success.consolidate_page > 0);
+
+        for i in 0..N {
+            let got = table
+                .get_at_savepoint("sp", &i.to_be_bytes())
+                .await
+                .unwrap();
+            assert_eq!(got, Some(value(i, 1).to_vec()));
+        }
+        let guard = table.pin();
+        let mut pages = guard.scan_at_savepoint("sp").unwrap();
+        let mut i = 0u64;
+        while let Some(page) = pages.next().await.unwrap() {
+            for (k, v) in page {
+                assert_eq!(k, &i.to_be_bytes());
+                assert_eq!(v, &value(i, 1));
+                i += 1;
+            }
+        }
+        assert_eq!(i, N);
+        drop(pages);
+        drop(guard);
+
+        assert!(table.release_savepoint("sp"));
+        assert!(!table.release_savepoint("sp"));
+        assert!(matches!(
+            table.get_at_savepoint("sp", &0u64.to_be_bytes()).await,
+            Err(Error::InvalidArgument)
+        ));
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn snapshot() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        const N: u64 = 64;
+        let value = |i: u64, lsn: u64| (i * 1000 + lsn).to_be_bytes();
+        for i in 0..N {
+            table.put(&i.to_be_bytes(), 1, &value(i, 1)).await.unwrap();
+        }
+        // Writes that don't install anything are not taken into account.
+        let key = 0u64.to_be_bytes();
+        let swapped = table.compare_and_swap(&key, 9, None, Some(&key)).await;
+        assert!(!swapped.unwrap());
+        let num_versions = table.store().version_refs();
+        let snapshot = table.snapshot();
+        assert_eq!(snapshot.lsn(), 1);
+        // The snapshot pins the current version.
+        assert_eq!(table.store().version_refs(), num_versions + 1);
+
+        // Overwrite and delete the entries with the safe LSN beyond the
+        // snapshot, so that the pages are consolidated.
+        for lsn in 2..8 {
+            for i in 0..N {
+                table
+                    .put(&i.to_be_bytes(), lsn, &value(i, lsn))
+                    .await
+                    .unwrap();
+            }
+            table.set_safe_lsn(lsn);
+        }
+        for i in (0..N).step_by(2) {
+            table.delete(&i.to_be_bytes(), 8).await.unwrap();
+        }
+        table.set_safe_lsn(8);
+        assert!(table.stats().tree.success.consolidate_page > 0);
+
+        for i in 0..N {
+            let got = table.get_at(&snapshot, &i.to_be_bytes()).await.unwrap();
+            assert_eq!(got, Some(value(i, 1).to_vec()));
+        }
+        let mut scan = table.scan_at(&snapshot, &0u64.to_be_bytes(), &N.to_be_bytes());
+        let mut i = 0u64;
+        while let Some((k, v)) = scan.next().await.unwrap() {
+            assert_eq!(k, i.to_be_bytes());
+            assert_eq!(v, value(i, 1));
+            i += 1;
+        }
+        assert_eq!(i, N);
+        drop(scan);
+
+        // A newer snapshot sees the latest entries.
+        let latest = table.snapshot();
+        assert_eq!(latest.lsn(), 8);
+        let got = table.get_at(&latest, &0u64.to_be_bytes()).await.unwrap();
+        assert_eq!(got, None);
+        let got = table.get_at(&latest, &1u64.to_be_bytes()).await.unwrap();
+        assert_eq!(got, Some(value(1, 7).to_vec()));
+        drop(latest);
+        drop(snapshot);
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn try_get() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+
+        // Puts until the last leaf page is split. The split is not reconciled to
+        // the parent yet, so the next read on the page will conflict. Note that
+        // the first split happens on the root, which doesn't conflict.
+        let mut i = 0;
+        let mut splits = 1;
+        while table.stats().tree.success.split_page <= splits {
+            must_put(&table, i, i).await;
+            i += 1;
+        }
+        let key = (i - 1).to_be_bytes();
+        assert!(matches!(table.try_get(&key, i).await, Err(Error::Again)));
+        let value = table.try_get(&key, i).await.unwrap();
+        assert_eq!(value, Some(key.to_vec()));
+
+        // Reads with retries mask the conflict.
+        splits = table.stats().tree.success.split_page;
+        while table.stats().tree.success.split_page <= splits {
+            must_put(&table, i, i).await;
+            i += 1;
+        }
+        let conflicts = table.stats().tree.conflict.read;
+        must_get(&table, i - 1, i, Some(i - 1)).await;
+        assert!(table.stats().tree.conflict.read > conflicts);
+
+        table.close().await.unwrap();
+    }
+
+    fn add_operator(_: &[u8], base: Option<&[u8]>, operand: &[u8]) -> Vec<u8> {
+        let base = base.map_or(0, |v| u64::from_be_bytes(v.try_into().unwrap()));
+        let operand = u64::from_be_bytes(operand.try_into().unwrap());
+        (base + operand).to_be_bytes().to_vec()
+    }
+
+    #[photonio::test]
+    async fn merge() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        let buf = 0u64.to_be_bytes();
+        assert!(matches!(
+            table.merge(&buf, 0, &buf).await,
+            Err(Error::InvalidArgument)
+        ));
+        table.close().await.unwrap();
+
+        let path = tempdir().unwrap();
+        let options = TableOptions {
+            merge_operator: Some(add_operator),
+            ..OPTIONS
+        };
+        let table = Table::open(&path, options).await.unwrap();
+        const N: u64 = 1 << 8;
+        const R: u64 = 8;
+        let mut lsn = 0;
+        for r in 1..=R {
+            for i in 0..N {
+                lsn += 1;
+                table
+                    .merge(&i.to_be_bytes(), lsn, &r.to_be_bytes())
+                    .await
+                    .unwrap();
+            }
+            // Allow consolidations to collapse the older operands.
+            if r == R / 2 {
+                table.set_safe_lsn(lsn);
+            }
+        }
+        assert!(table.stats().tree.success.consolidate_page > 0);
+
+        let sum = R * (R + 1) / 2;
+        for i in 0..N {
+            must_get(&table, i, lsn, Some(sum)).await;
+        }
+        // Reads before the last round don't see its operands.
+        must_get(&table, 0, N * (R - 1), Some(sum - R)).await;
+
+        let guard = table.pin();
+        let mut pages = guard.pages();
+        let mut i = 0u64;
+        while let Some(page) = pages.next().await.unwrap() {
+            for (k, v) in page {
+                assert_eq!(k, &i.to_be_bytes());
+                assert_eq!(v, &sum.to_be_bytes());
+                i += 1;
+            }
+        }
+        assert_eq!(i, N);
+        drop(guard);
+
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn fixed_key_layout() {
+        let path = tempdir().unwrap();
+        let options = TableOptions {
+            data_page_layout: DataPageLayout::FixedKey(8),
+            ..OPTIONS
+        };
+        let table = Table::open(&path, options.clone()).await.unwrap();
+        assert!(matches!(
+            table.put(&[0], 0, &[0]).await,
+            Err(Error::InvalidArgument)
+        ));
+        const N: u64 = 1 << 10;
+        for i in 0..N {
+            must_put(&table, i, i).await;
+        }
+        for i in (0..N).step_by(2) {
+            table.delete(&i.to_be_bytes(), N + i).await.unwrap();
+        }
+        assert!(table.stats().tree.success.split_page > 0);
+        assert!(table.stats().tree.success.consolidate_page > 0);
+        table.close().await.unwrap();
+
+        // Pages with fixed-size keys are read back from the store.
+        let table = Table::open(&path, options).await.unwrap();
+        for i in 0..N {
+            let expect = if i % 2 == 0 { None } else { Some(i) };
+            must_get(&table, i, N * 2, expect).await;
+            must_get(&table, i, i, Some(i)).await;
+        }
+        table.close().await.unwrap();
+    }
+
+    #[derive(Debug, Default)]
+    struct RecordingListener {
+        splits: Mutex<Vec<SplitPageInfo>>,
+        consolidations: Mutex<Vec<ConsolidatePageInfo>>,
+    }
+
+    impl EventListener for RecordingListener {
+        fn on_split_page(&self, info: &SplitPageInfo) {
+            self.splits.lock().unwrap().push(info.clone());
+        }
+
+        fn on_consolidate_page(&self, info: &ConsolidatePageInfo) {
+            self.consolidations.lock().unwrap().push(info.clone());
+        }
+    }
+
+    #[photonio::test]
+    async fn event_listener() {
+        let path = tempdir().unwrap();
+        let listener = Arc::new(RecordingListener::default());
+        let options = TableOptions {
+            event_listener: Some(listener.clone() as Arc<dyn EventListener>),
+            ..OPTIONS
+        };
+        let table = Table::open(&path, options).await.unwrap();
+        const N: u64 = 1 << 10;
+        for i in 0..N {
+            must_put(&table, i, i).await;
+        }
+        table.close().await.unwrap();
+
+        let splits = listener.splits.lock().unwrap();
+        assert!(!splits.is_empty());
+        // The root is split first, whose halves are moved to new pages.
+        assert_ne!(splits[0].left_id, splits[0].id);
+        for info in splits.iter() {
+            assert_ne!(info.right_id, info.id);
+            assert!(info.right_size > 0 && info.right_size < info.size);
+        }
+        let consolidations = listener.consolidations.lock().unwrap();
+        assert!(!consolidations.is_empty());
+        for info in consolidations.iter() {
+            assert!(info.chain_len > 1);
+            assert!(info.new_chain_len >= 1 && info.new_chain_len < info.chain_len);
+        }
+    }
+
+    #[photonio::test]
+    async fn random_crud() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        const N: u64 = 1 << 12;
+        for _ in 0..N {
+            let i = random();
+            must_put(&table, i, i).await;
+            must_get(&table, i, i, Some(i)).await;
+        }
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn concurrent_crud() {
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        let mut tasks = Vec::new();
+        for _ in 0..4 {
+            let table = table.clone();
+            let handle = photonio::task::spawn(async move {
+                const N: u64 = 1 << 10;
+                for _ in 0..N {
+                    let i = random();
+                    must_put(&table, i, i).await;
+                    must_get(&table, i, i, Some(i)).await;
+                }
+            });
+            tasks.push(handle);
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn load_sharded() {
+        async fn collect(table: &Table) -> Vec<(Vec<u8>, Vec<u8>)> {
+            let guard = table.pin();
+            let mut pages = guard.pages();
+            let mut entries = Vec::new();
+            while let Some(page) = pages.next().await.unwrap() {
+                entries.extend(page.map(|(k, v)| (k.to_vec(), v.to_vec())));
+            }
+            entries
+        }
+
+        const N: u64 = 1 << 12;
+        let entries: Vec<_> = (0..N)
+            .map(|i| (i.to_be_bytes().to_vec(), i.to_be_bytes().to_vec()))
+            .collect();
+
+        let serial_path = tempdir().unwrap();
+        let serial = Table::open(&serial_path, OPTIONS).await.unwrap();
+        for i in 0..N {
+            must_put(&serial, i, 1).await;
+        }
+
+        let path = tempdir().unwrap();
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        table.load_sharded(entries.clone(), 1, 4).await.unwrap();
+        for i in 0..N {
+            must_get(&table, i, 1, Some(i)).await;
+        }
+        assert_eq!(collect(&table).await, entries);
+        assert_eq!(collect(&table).await, collect(&serial).await);
+
+        // The table is not empty anymore.
+        assert!(matches!(
+            table.load_sharded(entries.clone(), 2, 4).await,
+            Err(Error::InvalidArgument)
+        ));
+
+        let unsorted_path = tempdir().unwrap();
+        let unsorted_table = Table::open(&unsorted_path, OPTIONS).await.unwrap();
+        let mut unsorted = entries;
+        unsorted.swap(0, 1);
+        assert!(matches!(
+            unsorted_table.load_sharded(unsorted, 2, 4).await,
+            Err(Error::InvalidArgument)
+        ));
+        unsorted_table.close().await.unwrap();
+
+        table.close().await.unwrap();
+        serial.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn graceful_shutdown() {
+        let path = tempdir().unwrap();
+        let opts = TableOptions {
+            page_store: PageStoreOptions {
+                avoid_flush_during_shutdown: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let table = Table::open(&path, opts.clone()).await.unwrap();
+        let i = random();
         must_put(&table, i, i).await;
         must_get(&table, i, i, Some(i)).await;
         table.close().await.unwrap();
@@ -179,4 +1904,171 @@ mod tests {
         let table = Table::open(&path, opts).await.unwrap();
         must_get(&table, i, i, Some(i)).await;
     }
+
+    #[photonio::test]
+    async fn open_options() {
+        let path = tempdir().unwrap();
+        let with = |create_if_missing, error_if_exists, read_only| TableOptions {
+            page_store: PageStoreOptions {
+                create_if_missing,
+                error_if_exists,
+                read_only,
+                ..OPTIONS.page_store
+            },
+            ..OPTIONS
+        };
+        assert!(matches!(
+            Table::open(&path, with(false, false, false)).await,
+            Err(Error::InvalidArgument)
+        ));
+        assert!(matches!(
+            Table::open(&path, with(true, false, true)).await,
+            Err(Error::InvalidArgument)
+        ));
+
+        let table = Table::open(&path, with(true, true, false)).await.unwrap();
+        const N: u64 = 1 << 8;
+        for i in 0..N {
+            must_put(&table, i, 1).await;
+        }
+        table.flush(&FlushOptions::default()).await;
+        assert!(matches!(
+            Table::open(&path, with(true, true, false)).await,
+            Err(Error::InvalidArgument)
+        ));
+
+        // A read-only table can be opened while the table is live.
+        let reader = Table::open(&path, with(false, false, true)).await.unwrap();
+        for i in 0..N {
+            must_get(&reader, i, 1, Some(i)).await;
+        }
+        must_put(&table, N, 1).await;
+        must_get(&reader, N, 1, None).await;
+        assert!(matches!(
+            reader.put(b"k", 2, b"v").await,
+            Err(Error::ReadOnly)
+        ));
+        assert!(matches!(
+            reader.set_metadata(b"k", b"v").await,
+            Err(Error::ReadOnly)
+        ));
+        reader.close().await.unwrap();
+        table.close().await.unwrap();
+    }
+
+    #[photonio::test]
+    async fn destroy() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("table");
+        let table = Table::open(&path, OPTIONS).await.unwrap();
+        const N: u64 = 1 << 8;
+        for i in 0..N {
+            must_put(&table, i, 1).await;
+        }
+        table.flush(&FlushOptions::default()).await;
+
+        // A read-only table can't be destroyed.
+        let opts = TableOptions {
+            page_store: PageStoreOptions {
+                read_only: true,
+                ..OPTIONS.page_store
+            },
+            ..OPTIONS
+        };
+        let reader = Table::open(&path, opts).await.unwrap();
+        assert!(matches!(
+            reader.destroy().await.unwrap(),
+            Err(Error::ReadOnly)
+        ));
+        must_get(&table, 1, 1, Some(1)).await;
+
+        // A table with other references can't be destroyed.
+        let other = table.open_tree("other").await.unwrap();
+        let table = table.destroy().await.unwrap_err();
+        drop(other);
+        table.destroy().await.unwrap().unwrap();
+        assert!(!path.exists());
+
+        let opts = TableOptions {
+            page_store: PageStoreOptions {
+                create_if_missing: false,
+                ..OPTIONS.page_store
+            },
+            ..OPTIONS
+        };
+        assert!(matches!(
+            Table::open(&path, opts).await,
+            Err(Error::InvalidArgument)
+        ));
+    }
+
+    #[photonio::test]
+    async fn sync() {
+        let path = tempdir().unwrap();
+        let opts = TableOptions {
+            page_store: PageStoreOptions {
+                avoid_flush_during_shutdown: true,
+                ..OPTIONS.page_store
+            },
+            ..OPTIONS
+        };
+        let table = Table::open(&path, opts.clone()).await.unwrap();
+        const N: u64 = 1 << 8;
+        for i in 0..N {
+            must_put(&table, i, 1).await;
+        }
+        table.sync().await;
+        // Nothing is left to wait for.
+        table.sync().await;
+        table.close().await.unwrap();
+
+        // The writes survive without flushing on shutdown.
+        let table = Table::open(&path, opts).await.unwrap();
+        for i in 0..N {
+            must_get(&table, i, 1, Some(i)).await;
+        }
+        table.close().await.unwrap();
+    }
+
+    #[cfg(feature = "tracing")]
+    struct SpanRecorder(::std::sync::Arc<::std::sync::Mutex<Vec<&'static str>>>);
+
+    #[cfg(feature = "tracing")]
+    impl tracing::Subscriber for SpanRecorder {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            let mut spans = self.0.lock().unwrap();
+            spans.push(span.metadata().name());
+            tracing::span::Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+        fn event(&self, _: &tracing::Event<'_>) {}
+
+        fn enter(&self, _: &tracing::span::Id) {}
+
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn tracing_spans() {
+        let path = tempdir().unwrap();
+        let table = crate::std::Table::open(&path, OPTIONS).unwrap();
+        table.put(b"key", 1, b"value").unwrap();
+
+        let spans = ::std::sync::Arc::default();
+        let subscriber = SpanRecorder(::std::sync::Arc::clone(&spans));
+        let value = tracing::subscriber::with_default(subscriber, || table.get(b"key", 1).unwrap());
+        assert_eq!(value, Some(b"value".to_vec()));
+        assert_eq!(*spans.lock().unwrap(), ["get", "get_from_leaf"]);
+
+        table.close().unwrap();
+    }
 }
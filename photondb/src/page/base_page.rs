@@ -43,6 +43,12 @@ impl PagePtr {
         self.flags().kind()
     }
 
+    /// Returns true if the page stores fixed-size keys without length
+    /// framing.
+    pub(crate) fn has_fixed_keys(&self) -> bool {
+        self.flags().has_fixed_keys()
+    }
+
     /// Returns the page epoch.
     pub(crate) fn epoch(&self) -> u64 {
         unsafe {
@@ -316,16 +322,24 @@ impl From<u8> for PageTier {
 }
 
 /// A list of possible page kinds.
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 #[repr(u8)]
-pub(crate) enum PageKind {
+pub enum PageKind {
+    /// A page that contains sorted entries.
     Data = PAGE_KIND_DATA,
+    /// A delta page that records a pending split.
     Split = PAGE_KIND_SPLIT,
+    /// A delta page that records a pending removal.
+    Remove = PAGE_KIND_REMOVE,
+    /// A delta page that deletes a range of keys.
+    DeleteRange = PAGE_KIND_DELETE_RANGE,
 }
 
 const PAGE_KIND_MASK: u8 = 0b0000_1110;
 const PAGE_KIND_DATA: u8 = 0b0000_0000;
 const PAGE_KIND_SPLIT: u8 = 0b0000_0010;
+const PAGE_KIND_REMOVE: u8 = 0b0000_0100;
+const PAGE_KIND_DELETE_RANGE: u8 = 0b0000_0110;
 
 impl PageKind {
     pub(crate) fn is_data(&self) -> bool {
@@ -335,6 +349,14 @@ impl PageKind {
     pub(crate) fn is_split(&self) -> bool {
         self == &Self::Split
     }
+
+    pub(crate) fn is_remove(&self) -> bool {
+        self == &Self::Remove
+    }
+
+    pub(crate) fn is_delete_range(&self) -> bool {
+        self == &Self::DeleteRange
+    }
 }
 
 impl From<u8> for PageKind {
@@ -342,16 +364,47 @@ impl From<u8> for PageKind {
         match value & PAGE_KIND_MASK {
             PAGE_KIND_DATA => Self::Data,
             PAGE_KIND_SPLIT => Self::Split,
+            PAGE_KIND_REMOVE => Self::Remove,
+            PAGE_KIND_DELETE_RANGE => Self::DeleteRange,
             _ => unreachable!(),
         }
     }
 }
 
+const PAGE_FIXED_KEYS: u8 = 0b0001_0000;
+
+/// Returns the page content and whether it stores fixed-size keys if `data`
+/// starts with a valid page header.
+pub(super) fn verify_header(data: &[u8]) -> Option<(&[u8], bool)> {
+    if data.len() < PAGE_HEADER_LEN || !data.as_ptr().is_aligned_to(8) {
+        return None;
+    }
+    let flags = data[PAGE_EPOCH_LEN];
+    if flags & !(PAGE_TIER_MASK | PAGE_KIND_MASK | PAGE_FIXED_KEYS) != 0 {
+        return None;
+    }
+    match flags & PAGE_KIND_MASK {
+        PAGE_KIND_DATA | PAGE_KIND_SPLIT | PAGE_KIND_REMOVE | PAGE_KIND_DELETE_RANGE => {
+            let fixed_keys = PageFlags(flags).has_fixed_keys();
+            Some((&data[PAGE_HEADER_LEN..], fixed_keys))
+        }
+        _ => None,
+    }
+}
+
 struct PageFlags(u8);
 
 impl PageFlags {
-    fn new(tier: PageTier, kind: PageKind) -> Self {
-        Self(tier as u8 | kind as u8)
+    fn new(tier: PageTier, kind: PageKind, fixed_keys: bool) -> Self {
+        let mut flags = tier as u8 | kind as u8;
+        if fixed_keys {
+            flags |= PAGE_FIXED_KEYS;
+        }
+        Self(flags)
+    }
+
+    fn has_fixed_keys(&self) -> bool {
+        self.0 & PAGE_FIXED_KEYS != 0
     }
 
     fn tier(&self) -> PageTier {
@@ -367,11 +420,21 @@ impl PageFlags {
 pub(super) struct PageBuilder {
     tier: PageTier,
     kind: PageKind,
+    fixed_keys: bool,
 }
 
 impl PageBuilder {
     pub(super) fn new(tier: PageTier, kind: PageKind) -> Self {
-        Self { tier, kind }
+        Self {
+            tier,
+            kind,
+            fixed_keys: false,
+        }
+    }
+
+    /// Marks the page to store fixed-size keys without length framing.
+    pub(super) fn set_fixed_keys(&mut self, fixed_keys: bool) {
+        self.fixed_keys = fixed_keys;
     }
 
     pub(super) fn size(&self, content_size: usize) -> usize {
@@ -379,7 +442,7 @@ impl PageBuilder {
     }
 
     pub(super) fn build(&self, page: &mut PageBuf<'_>) {
-        let flags = PageFlags::new(self.tier, self.kind);
+        let flags = PageFlags::new(self.tier, self.kind, self.fixed_keys);
         page.set_flags(flags);
         page.set_epoch(0);
         page.set_chain_len(1);
@@ -1,4 +1,7 @@
-use std::cmp::Ordering;
+use std::{
+    cmp::Ordering,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub(crate) struct Key<'a> {
@@ -53,16 +56,36 @@ impl<'a> Range<'a> {
 pub(crate) enum Value<'a> {
     Put(&'a [u8]),
     Delete,
+    /// An operand to merge into the previous value.
+    Merge(&'a [u8]),
+    /// A value that expires at the time in milliseconds since the Unix epoch.
+    Expiring(&'a [u8], u64),
 }
 
 impl<'a> Value<'a> {
     /// Returns the length of value.
     pub(crate) fn len(&self) -> usize {
         match self {
-            Value::Put(v) => v.len(),
+            Value::Put(v) | Value::Merge(v) | Value::Expiring(v, _) => v.len(),
             Value::Delete => 0,
         }
     }
+
+    /// Returns the value seen at `now`, where an expired value is a delete.
+    pub(crate) fn expire(self, now: u64) -> Self {
+        match self {
+            Value::Expiring(_, expires_at) if expires_at <= now => Value::Delete,
+            v => v,
+        }
+    }
+}
+
+/// Returns the current time in milliseconds since the Unix epoch, which is
+/// the clock of expiring values.
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
 }
 
 /// An index to a child page.
@@ -213,13 +213,22 @@ where
 }
 
 /// An iterator that merges multiple ordered iterators into one.
-#[derive(Default)]
 pub(crate) struct MergingIter<I>
 where
     I: Iterator,
     OrderedIter<I>: Iterator + Ord,
 {
-    heap: BinaryHeap<Reverse<OrderedIter<I>>>,
+    strategy: MergingStrategy<I>,
+}
+
+/// The way a [`MergingIter`] picks the smallest iterator.
+enum MergingStrategy<I>
+where
+    I: Iterator,
+    OrderedIter<I>: Iterator + Ord,
+{
+    Heap(BinaryHeap<Reverse<OrderedIter<I>>>),
+    Tournament(TournamentTree<I>),
 }
 
 impl<I> MergingIter<I>
@@ -227,23 +236,38 @@ where
     I: Iterator,
     OrderedIter<I>: Iterator + Ord,
 {
-    fn init(mut vec: Vec<Reverse<OrderedIter<I>>>) -> Self {
+    fn init(mut vec: Vec<Reverse<OrderedIter<I>>>, tournament_threshold: usize) -> Self {
         for iter in vec.iter_mut() {
             iter.0.init();
         }
-        Self { heap: vec.into() }
+        let strategy = if vec.len() > tournament_threshold {
+            let iters = vec.into_iter().map(|iter| iter.0).collect();
+            MergingStrategy::Tournament(TournamentTree::new(iters))
+        } else {
+            MergingStrategy::Heap(vec.into())
+        };
+        Self { strategy }
     }
 
     fn for_each<F>(&mut self, mut f: F)
     where
-        F: FnMut(&mut Reverse<OrderedIter<I>>),
+        F: FnMut(&mut OrderedIter<I>),
     {
-        let mut vec = mem::take(&mut self.heap).into_vec();
-        for iter in vec.iter_mut() {
-            f(iter)
+        match &mut self.strategy {
+            MergingStrategy::Heap(heap) => {
+                let mut vec = mem::take(heap).into_vec();
+                for iter in vec.iter_mut() {
+                    f(&mut iter.0)
+                }
+                *heap = BinaryHeap::from(vec);
+            }
+            MergingStrategy::Tournament(tree) => {
+                for iter in tree.iters.iter_mut() {
+                    f(iter)
+                }
+                tree.rebuild();
+            }
         }
-        let mut heap = BinaryHeap::from(vec);
-        mem::swap(&mut self.heap, &mut heap);
     }
 }
 
@@ -255,10 +279,9 @@ where
     type Item = I::Item;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(mut iter) = self.heap.peek_mut() {
-            iter.0.next()
-        } else {
-            None
+        match &mut self.strategy {
+            MergingStrategy::Heap(heap) => heap.peek_mut().and_then(|mut iter| iter.0.next()),
+            MergingStrategy::Tournament(tree) => tree.next(),
         }
     }
 }
@@ -269,7 +292,7 @@ where
     OrderedIter<I>: RewindableIterator<Item = I::Item> + Ord,
 {
     fn rewind(&mut self) {
-        self.for_each(|iter| iter.0.rewind());
+        self.for_each(|iter| iter.rewind());
     }
 }
 
@@ -282,7 +305,7 @@ where
     fn seek(&mut self, target: &T) -> bool {
         let mut found = false;
         self.for_each(|iter| {
-            if iter.0.seek(target) {
+            if iter.seek(target) {
                 found = true;
             }
         });
@@ -290,12 +313,88 @@ where
     }
 }
 
+/// A tree of losers that picks the smallest of many iterators.
+///
+/// Each internal node keeps the loser of the match between its children, so
+/// replacing the winner replays only the matches on its path to the root. This
+/// takes `log2(n)` comparisons per item, about half of what a binary heap
+/// takes, and the nodes are indexes into the iterators, which are never moved.
+struct TournamentTree<I>
+where
+    I: Iterator,
+{
+    iters: Vec<OrderedIter<I>>,
+    // `losers[0]` is the overall winner, and `losers[i]` is the loser of the
+    // match at node `i`. The leaf of iterator `i` is node `n + i`.
+    losers: Vec<usize>,
+}
+
+impl<I> TournamentTree<I>
+where
+    I: Iterator,
+    OrderedIter<I>: Iterator + Ord,
+{
+    fn new(iters: Vec<OrderedIter<I>>) -> Self {
+        let mut tree = Self {
+            losers: vec![0; iters.len()],
+            iters,
+        };
+        tree.rebuild();
+        tree
+    }
+
+    /// Replays all matches after the iterators are repositioned.
+    fn rebuild(&mut self) {
+        let n = self.iters.len();
+        if n == 0 {
+            return;
+        }
+        let mut winners = vec![0; n * 2];
+        for (i, winner) in winners[n..].iter_mut().enumerate() {
+            *winner = i;
+        }
+        for node in (1..n).rev() {
+            let (a, b) = (winners[node * 2], winners[node * 2 + 1]);
+            let (winner, loser) = if self.iters[a] <= self.iters[b] {
+                (a, b)
+            } else {
+                (b, a)
+            };
+            winners[node] = winner;
+            self.losers[node] = loser;
+        }
+        self.losers[0] = winners[1];
+    }
+
+    /// Replays the matches of the winner after it is advanced.
+    fn replay(&mut self) {
+        let n = self.iters.len();
+        let mut winner = self.losers[0];
+        let mut node = (n + winner) / 2;
+        while node > 0 {
+            if self.iters[self.losers[node]] < self.iters[winner] {
+                mem::swap(&mut winner, &mut self.losers[node]);
+            }
+            node /= 2;
+        }
+        self.losers[0] = winner;
+    }
+
+    fn next(&mut self) -> Option<<OrderedIter<I> as Iterator>::Item> {
+        let winner = *self.losers.first()?;
+        let next = self.iters[winner].next();
+        self.replay();
+        next
+    }
+}
+
 /// Builds a [`MergingIter`] from multiple iterators.
 pub(crate) struct MergingIterBuilder<I>
 where
     I: Iterator,
 {
     iters: Vec<Reverse<OrderedIter<I>>>,
+    tournament_threshold: usize,
 }
 
 impl<I, K, V> MergingIterBuilder<I>
@@ -306,16 +405,26 @@ where
     /// Creates a new [`MergingIterBuilder`].
     #[cfg(test)]
     pub(crate) fn new() -> Self {
-        Self { iters: Vec::new() }
+        Self::with_capacity(0)
     }
 
     /// Creates a new [`MergingIterBuilder`] with the given capacity.
     pub(crate) fn with_capacity(capacity: usize) -> Self {
         Self {
             iters: Vec::with_capacity(capacity),
+            tournament_threshold: usize::MAX,
         }
     }
 
+    /// Merges with a tournament tree instead of a binary heap if there are
+    /// more than `threshold` iterators.
+    ///
+    /// The merged items are the same in either way.
+    pub(crate) fn with_tournament_threshold(mut self, threshold: usize) -> Self {
+        self.tournament_threshold = threshold;
+        self
+    }
+
     /// Returns the number of iterators in the builder.
     pub(crate) fn len(&self) -> usize {
         self.iters.len()
@@ -331,7 +440,7 @@ where
     ///
     /// The returned iterator will be positioned at the first item.
     pub(crate) fn build(self) -> MergingIter<I> {
-        MergingIter::init(self.iters)
+        MergingIter::init(self.iters, self.tournament_threshold)
     }
 }
 
@@ -416,4 +525,61 @@ mod tests {
         assert_eq!(iter.next(), Some((7, "d")));
         assert_eq!(iter.next(), Some((8, "c")));
     }
+
+    #[test]
+    fn merging_iter_tournament() {
+        const N: usize = 300;
+        let input: Vec<Vec<(usize, usize)>> = (0..N)
+            .map(|i| (0..i % 7).map(|j| ((i * 31 + j * 17) % 97, i)).collect())
+            .map(|mut vec: Vec<_>| {
+                vec.sort();
+                vec
+            })
+            .collect();
+        let build = |threshold| {
+            let mut builder =
+                MergingIterBuilder::with_capacity(N).with_tournament_threshold(threshold);
+            for slice in input.iter() {
+                builder.add(SliceIter::new(slice));
+            }
+            builder.build()
+        };
+        let mut heap = build(usize::MAX);
+        let mut tournament = build(N - 1);
+        assert!(matches!(heap.strategy, MergingStrategy::Heap(_)));
+        let MergingStrategy::Tournament(tree) = &tournament.strategy else {
+            panic!("expect a tournament tree");
+        };
+        // The tree keeps one index per iterator besides the iterators.
+        assert_eq!(tree.losers.len(), N);
+
+        let expect: Vec<_> = heap.by_ref().collect();
+        assert_eq!(
+            expect.len(),
+            input.iter().map(|vec| vec.len()).sum::<usize>()
+        );
+        assert!(expect.windows(2).all(|w| w[0] <= w[1]));
+        for _ in 0..2 {
+            assert_eq!(tournament.by_ref().collect::<Vec<_>>(), expect);
+            assert_eq!(tournament.next(), None);
+            tournament.rewind();
+        }
+
+        heap.rewind();
+        for target in [(0, 0), (13, 5), (50, 0), (96, N), (97, 0)] {
+            assert_eq!(heap.seek(&target), tournament.seek(&target));
+            assert_eq!(heap.next(), tournament.next());
+            assert_eq!(heap.next(), tournament.next());
+        }
+
+        // Corner cases of tiny trees.
+        for n in 0..3 {
+            let mut builder = MergingIterBuilder::new().with_tournament_threshold(0);
+            for i in 0..n {
+                builder.add(ItemIter::new((i, i)));
+            }
+            let expect: Vec<_> = (0..n).map(|i| (i, i)).collect();
+            assert_eq!(builder.build().collect::<Vec<_>>(), expect);
+        }
+    }
 }
@@ -4,13 +4,14 @@ pub(crate) use iter::{
 };
 
 mod data;
-pub(crate) use data::{Index, Key, Range, Value};
+pub(crate) use data::{now_millis, Index, Key, Range, Value};
 
 mod codec;
 
 mod base_page;
 use base_page::PageBuilder;
-pub(crate) use base_page::{PageBuf, PageInfo, PageKind, PageRef, PageTier};
+pub use base_page::PageKind;
+pub(crate) use base_page::{PageBuf, PageInfo, PageRef, PageTier};
 
 mod sorted_page;
 pub(crate) use sorted_page::{
@@ -20,6 +21,15 @@ pub(crate) use sorted_page::{
 pub(crate) type ValuePageRef<'a> = SortedPageRef<'a, Key<'a>, Value<'a>>;
 pub(crate) type IndexPageRef<'a> = SortedPageRef<'a, &'a [u8], Index>;
 
+/// Returns true if `data` is a well-formed page.
+///
+/// This checks the page header and the item offsets, but not the items.
+pub(crate) fn verify_page(data: &[u8]) -> bool {
+    base_page::verify_header(data).map_or(false, |(content, fixed_keys)| {
+        sorted_page::verify_content(content, fixed_keys)
+    })
+}
+
 #[cfg(test)]
 pub(crate) mod tests {
     use std::{
@@ -70,7 +80,20 @@ pub(crate) mod tests {
         where
             I: RewindableIterator<Item = (K, V)>,
         {
-            let builder = SortedPageBuilder::new(PageTier::Leaf, PageKind::Data).with_iter(iter);
+            Self::build(iter, None)
+        }
+
+        pub(crate) fn from_slice_with_key_size(data: &[(K, V)], key_size: usize) -> Self {
+            Self::build(SliceIter::new(data), Some(key_size))
+        }
+
+        fn build<I>(iter: I, key_size: Option<usize>) -> Self
+        where
+            I: RewindableIterator<Item = (K, V)>,
+        {
+            let builder = SortedPageBuilder::new(PageTier::Leaf, PageKind::Data)
+                .with_key_size(key_size)
+                .with_iter(iter);
             let mut buf = alloc_page(builder.size());
             let mut page = PageBuf::new(buf.as_mut());
             builder.build(&mut page);
@@ -89,4 +112,32 @@ pub(crate) mod tests {
             self.buf.as_ref().into()
         }
     }
+
+    #[test]
+    fn verify_sorted_page() {
+        let data = [
+            (Key::new(&[1], 2), Value::Put(&[1])),
+            (Key::new(&[2], 1), Value::Delete),
+        ];
+        let page = OwnedSortedPage::from_slice(&data);
+        let buf = page.as_ref().data();
+        assert!(verify_page(buf));
+
+        let empty = OwnedSortedPage::<Key, Value>::from_slice(&[]);
+        assert!(verify_page(empty.as_ref().data()));
+
+        // Truncated header.
+        assert!(!verify_page(&buf[..8]));
+
+        // Invalid flags.
+        let mut corrupted = alloc_page(buf.len());
+        corrupted.copy_from_slice(buf);
+        corrupted[6] = 0xff;
+        assert!(!verify_page(&corrupted));
+
+        // Invalid offsets.
+        corrupted.copy_from_slice(buf);
+        corrupted[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(!verify_page(&corrupted));
+    }
 }
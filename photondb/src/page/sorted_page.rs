@@ -12,12 +12,26 @@ use super::{
     RewindableIterator, SeekableIterator, SliceIter,
 };
 
+/// Content format of pages with fixed-size keys {
+///     num_items  : 4 bytes
+///     key_stride : 4 bytes
+///     offsets    : num_items * 4 bytes
+///     keys       : num_items * key_stride bytes
+///     values     : multiple bytes
+/// }
+///
+/// The offsets point to the values, and the keys are stored without length
+/// framing, so that a key can be located with its index alone.
+const FIXED_KEY_HEADER_LEN: usize = 8;
+
 /// Builds a sorted page from an iterator.
 pub(crate) struct SortedPageBuilder<I> {
     base: PageBuilder,
     iter: Option<I>,
     num_items: usize,
     content_size: usize,
+    key_size: Option<usize>,
+    key_stride: Option<usize>,
 }
 
 impl<I, K, V> SortedPageBuilder<I>
@@ -34,17 +48,44 @@ where
             iter: None,
             num_items: 0,
             content_size: 0,
+            key_size: None,
+            key_stride: None,
         }
     }
 
+    /// Lays out the page for keys of the given size, if any.
+    ///
+    /// This must be called before the iterator is given. The page falls back
+    /// to the variable-size layout if some keys are of other sizes, or if the
+    /// layout doesn't save space.
+    pub(crate) fn with_key_size(mut self, key_size: Option<usize>) -> Self {
+        self.key_size = key_size;
+        self
+    }
+
     /// Creates a [`SortedPageBuilder`] that will build a page from the given
     /// iterator.
     pub(crate) fn with_iter(mut self, mut iter: I) -> Self {
+        let mut keys_size = 0;
+        let mut fixed_keys_size = 0;
+        let mut values_size = 0;
+        let mut key_size = self.key_size;
         for (k, v) in &mut iter {
             self.num_items += 1;
-            self.content_size += k.encode_size() + v.encode_size();
+            keys_size += k.encode_size();
+            fixed_keys_size += k.fixed_encode_size();
+            values_size += v.encode_size();
+            if key_size.map_or(false, |size| k.as_raw().len() != size) {
+                key_size = None;
+            }
+        }
+        let offsets_size = self.num_items * mem::size_of::<u32>();
+        if key_size.is_some() && keys_size - fixed_keys_size > FIXED_KEY_HEADER_LEN {
+            self.key_stride = Some(fixed_keys_size / self.num_items);
+            self.content_size = FIXED_KEY_HEADER_LEN + offsets_size + fixed_keys_size + values_size;
+        } else {
+            self.content_size = offsets_size + keys_size + values_size;
         }
-        self.content_size += self.num_items * mem::size_of::<u32>();
         // We use `u32` to store item offsets, so the content size must not exceed
         // `u32::MAX`.
         assert!(self.content_size <= u32::MAX as usize);
@@ -64,13 +105,21 @@ where
     /// Panics if the page is not large enough to hold the content.
     pub(crate) fn build(mut self, page: &mut PageBuf<'_>) {
         assert!(page.size() >= self.size());
+        self.base.set_fixed_keys(self.key_stride.is_some());
         self.base.build(page);
         if let Some(iter) = self.iter.as_mut() {
             unsafe {
-                let mut buf = SortedPageBuf::new(page.content_mut(), self.num_items);
                 iter.rewind();
-                for (k, v) in iter {
-                    buf.add(k, v);
+                if let Some(stride) = self.key_stride {
+                    let mut buf = FixedKeyPageBuf::new(page.content_mut(), self.num_items, stride);
+                    for (k, v) in iter {
+                        buf.add(k, v);
+                    }
+                } else {
+                    let mut buf = SortedPageBuf::new(page.content_mut(), self.num_items);
+                    for (k, v) in iter {
+                        buf.add(k, v);
+                    }
                 }
             }
         }
@@ -130,15 +179,62 @@ where
     }
 }
 
+struct FixedKeyPageBuf<K, V> {
+    offsets: Encoder,
+    keys: Encoder,
+    values: Encoder,
+    values_start: usize,
+    _marker: PhantomData<(K, V)>,
+}
+
+impl<K, V> FixedKeyPageBuf<K, V>
+where
+    K: SortedPageKey,
+    V: SortedPageValue,
+{
+    unsafe fn new(content: &mut [u8], num_items: usize, key_stride: usize) -> Self {
+        let (header, content) = content.split_at_mut(FIXED_KEY_HEADER_LEN);
+        let mut header = Encoder::new(header);
+        header.put_u32(num_items as u32);
+        header.put_u32(key_stride as u32);
+        let offsets_size = num_items * mem::size_of::<u32>();
+        let (offsets, content) = content.split_at_mut(offsets_size);
+        let (keys, values) = content.split_at_mut(num_items * key_stride);
+        Self {
+            offsets: Encoder::new(offsets),
+            keys: Encoder::new(keys),
+            values: Encoder::new(values),
+            values_start: FIXED_KEY_HEADER_LEN + offsets_size + num_items * key_stride,
+            _marker: PhantomData,
+        }
+    }
+
+    unsafe fn add(&mut self, key: K, value: V) {
+        let offset = self.values_start + self.values.offset();
+        self.offsets.put_u32(offset as u32);
+        key.encode_fixed_to(&mut self.keys);
+        value.encode_to(&mut self.values);
+    }
+}
+
 /// An immutable reference to a sorted page.
 #[derive(Clone)]
 pub(crate) struct SortedPageRef<'a, K, V> {
     page: PageRef<'a>,
     content: &'a [u8],
+    // The item offsets. Items only hold the values if the keys are fixed-size.
     offsets: &'a [u32],
+    fixed_keys: Option<FixedKeys<'a>>,
     _marker: PhantomData<(K, V)>,
 }
 
+/// The keys of a page with fixed-size keys.
+#[derive(Clone)]
+struct FixedKeys<'a> {
+    data: &'a [u8],
+    stride: usize,
+}
+
 impl<'a, K, V> SortedPageRef<'a, K, V>
 where
     K: SortedPageKey,
@@ -146,6 +242,9 @@ where
 {
     pub(crate) fn new(page: PageRef<'a>) -> Self {
         let content = page.content();
+        if page.has_fixed_keys() {
+            return Self::with_fixed_keys(page, content);
+        }
         let offsets = unsafe {
             let ptr = content.as_ptr() as *const u32;
             let len = if content.is_empty() {
@@ -160,6 +259,27 @@ where
             page,
             content,
             offsets,
+            fixed_keys: None,
+            _marker: PhantomData,
+        }
+    }
+
+    fn with_fixed_keys(page: PageRef<'a>, content: &'a [u8]) -> Self {
+        let (offsets, fixed_keys) = unsafe {
+            let mut dec = Decoder::new(content);
+            let len = dec.get_u32() as usize;
+            let stride = dec.get_u32() as usize;
+            let ptr = content.as_ptr().add(FIXED_KEY_HEADER_LEN) as *const u32;
+            let offsets = slice::from_raw_parts(ptr, len);
+            let keys_start = FIXED_KEY_HEADER_LEN + len * mem::size_of::<u32>();
+            let data = &content[keys_start..keys_start + len * stride];
+            (offsets, FixedKeys { data, stride })
+        };
+        Self {
+            page,
+            content,
+            offsets,
+            fixed_keys: Some(fixed_keys),
             _marker: PhantomData,
         }
     }
@@ -174,7 +294,10 @@ where
         if let Some(item) = self.item(index) {
             let mut dec = Decoder::new(item);
             unsafe {
-                let k = K::decode_from(&mut dec);
+                let k = match self.fixed_keys {
+                    Some(_) => self.key(index),
+                    None => K::decode_from(&mut dec),
+                };
                 let v = V::decode_from(&mut dec);
                 Some((k, v))
             }
@@ -199,11 +322,7 @@ where
         let mut right = self.len();
         while left < right {
             let mid = (left + right) / 2;
-            let key = unsafe {
-                let item = self.item(mid).unwrap();
-                let mut dec = Decoder::new(item);
-                K::decode_from(&mut dec)
-            };
+            let key = unsafe { self.key(mid) };
             match key.borrow().cmp(target) {
                 Ordering::Less => left = mid + 1,
                 Ordering::Greater => right = mid,
@@ -215,6 +334,10 @@ where
 
     /// Finds a separator to split the page into two halves.
     ///
+    /// The page is split around the item where the accumulated item size
+    /// reaches half of the page content, so pages with skewed item sizes are
+    /// divided evenly by bytes rather than by item count.
+    ///
     /// If a split separator is found, returns [`Option::Some`] with the split
     /// separator, an iterator over items before the separator, and another
     /// iterator over items at or after the separator.
@@ -227,7 +350,7 @@ where
         SortedPageRangeIter<'a, K, V>,
     )> {
         let len = self.len();
-        if let Some((mid, _)) = self.get(len / 2) {
+        if let Some((mid, _)) = self.get(self.split_index()) {
             let sep = mid.as_split_separator();
             let index = match self.rank(&sep) {
                 Ok(i) => i,
@@ -242,6 +365,63 @@ where
         None
     }
 
+    /// Returns the index of the item that divides the page content into two
+    /// halves of roughly equal size.
+    fn split_index(&self) -> usize {
+        let len = self.len();
+        let half = self.items_size(len) / 2;
+        // The first index whose item starts after the half point.
+        let mut left = 0;
+        let mut right = len;
+        while left < right {
+            let mid = (left + right) / 2;
+            if self.items_size(mid) <= half {
+                left = mid + 1;
+            } else {
+                right = mid;
+            }
+        }
+        if left == 0 {
+            return 0;
+        }
+        // Pick the item boundary closest to the half point. Ties go to the
+        // lower index, which matches the count-based split for equal sizes.
+        let (lower, upper) = (left - 1, left);
+        if upper < len && self.items_size(upper) - half < half - self.items_size(lower) {
+            upper
+        } else {
+            lower
+        }
+    }
+
+    /// Returns the size of the items before the given index.
+    fn items_size(&self, index: usize) -> usize {
+        let start = self.item_offset(0).unwrap_or(self.content.len());
+        let end = self.item_offset(index).unwrap_or(self.content.len());
+        let stride = self.fixed_keys.as_ref().map_or(0, |keys| keys.stride);
+        end - start + index * stride
+    }
+
+    /// Returns the key at the given index.
+    ///
+    /// # Safety
+    ///
+    /// The index must be less than the number of items.
+    unsafe fn key(&self, index: usize) -> K {
+        match &self.fixed_keys {
+            Some(keys) => {
+                let start = index * keys.stride;
+                let mut dec = Decoder::new(&keys.data[start..start + keys.stride]);
+                K::decode_fixed_from(&mut dec)
+            }
+            None => {
+                let item = self.item(index).unwrap();
+                let mut dec = Decoder::new(item);
+                K::decode_from(&mut dec)
+            }
+        }
+    }
+
     fn item(&self, index: usize) -> Option<&[u8]> {
         if let Some(offset) = self.item_offset(index) {
             let next_offset = self.item_offset(index + 1).unwrap_or(self.content.len());
@@ -275,6 +455,58 @@ where
     }
 }
 
+/// Returns true if the item offsets in the page content are valid.
+pub(super) fn verify_content(content: &[u8], fixed_keys: bool) -> bool {
+    const OFFSET_LEN: usize = mem::size_of::<u32>();
+    let read_offset = |i: usize| {
+        let buf = content[i * OFFSET_LEN..(i + 1) * OFFSET_LEN]
+            .try_into()
+            .unwrap();
+        u32::from_le_bytes(buf) as usize
+    };
+    let verify_offsets = |mut last_offset: usize, range: Range<usize>| {
+        range.map(read_offset).all(|offset| {
+            let valid = offset >= last_offset && offset <= content.len();
+            last_offset = offset;
+            valid
+        })
+    };
+
+    if fixed_keys {
+        if content.len() < FIXED_KEY_HEADER_LEN {
+            return false;
+        }
+        // The offsets follow the header and point to the values after the keys.
+        let len = read_offset(0);
+        let stride = read_offset(1);
+        // The header is untrusted, so the arithmetic must not overflow.
+        let values_start = (OFFSET_LEN + stride)
+            .checked_mul(len)
+            .and_then(|size| size.checked_add(FIXED_KEY_HEADER_LEN));
+        let Some(values_start) = values_start.filter(|start| *start <= content.len()) else {
+            return false;
+        };
+        if len == 0 {
+            return false;
+        }
+        let first = FIXED_KEY_HEADER_LEN / OFFSET_LEN;
+        return verify_offsets(values_start, first..first + len);
+    }
+
+    if content.is_empty() {
+        return true;
+    }
+    if content.len() < OFFSET_LEN {
+        return false;
+    }
+    // The first item starts right after the offsets.
+    let offsets_size = read_offset(0);
+    if offsets_size == 0 || offsets_size % OFFSET_LEN != 0 || offsets_size > content.len() {
+        return false;
+    }
+    verify_offsets(offsets_size, 1..offsets_size / OFFSET_LEN)
+}
+
 /// An iterator over the items in a sorted page.
 #[derive(Clone)]
 pub(crate) struct SortedPageIter<'a, K, V> {
@@ -415,12 +647,35 @@ pub(crate) trait SortedPageKey: Codec + Clone + Ord {
 
     /// Returns a key that can be used as a split separator.
     fn as_split_separator(&self) -> Self;
+
+    /// Returns the size of the key encoded without length framing.
+    fn fixed_encode_size(&self) -> usize;
+
+    /// Encodes the key without length framing.
+    unsafe fn encode_fixed_to(&self, enc: &mut Encoder);
+
+    /// Decodes a key encoded without length framing, which spans the rest of
+    /// the decoder.
+    unsafe fn decode_fixed_from(dec: &mut Decoder) -> Self;
+
+    /// Returns the LSN of the key, or `None` if the key has no LSN.
+    fn lsn(&self) -> Option<u64>;
+
+    /// Returns a key with the same raw part at the LSN.
+    fn with_lsn(&self, lsn: u64) -> Self;
 }
 
 /// Required methods for values in a sorted page.
-pub(crate) trait SortedPageValue: Codec + Clone {}
+pub(crate) trait SortedPageValue: Codec + Clone {
+    /// Returns a tombstone value, or `None` if the value can't be deleted.
+    fn tombstone() -> Option<Self> {
+        None
+    }
+}
+
+impl SortedPageValue for &[u8] {}
 
-impl<T> SortedPageValue for T where T: Codec + Clone {}
+impl SortedPageValue for Index {}
 
 impl Codec for &[u8] {
     fn encode_size(&self) -> usize {
@@ -446,6 +701,26 @@ impl SortedPageKey for &[u8] {
     fn as_split_separator(&self) -> Self {
         self
     }
+
+    fn fixed_encode_size(&self) -> usize {
+        self.len()
+    }
+
+    unsafe fn encode_fixed_to(&self, enc: &mut Encoder) {
+        enc.put_slice(self);
+    }
+
+    unsafe fn decode_fixed_from(dec: &mut Decoder) -> Self {
+        dec.get_slice(dec.remaining())
+    }
+
+    fn lsn(&self) -> Option<u64> {
+        None
+    }
+
+    fn with_lsn(&self, _: u64) -> Self {
+        self
+    }
 }
 
 impl Codec for Key<'_> {
@@ -474,17 +749,43 @@ impl SortedPageKey for Key<'_> {
         // Avoid splitting on the same raw key.
         Key::new(self.raw, u64::MAX)
     }
+
+    fn fixed_encode_size(&self) -> usize {
+        self.raw.len() + mem::size_of::<u64>()
+    }
+
+    unsafe fn encode_fixed_to(&self, enc: &mut Encoder) {
+        enc.put_slice(self.raw);
+        enc.put_u64(self.lsn);
+    }
+
+    unsafe fn decode_fixed_from(dec: &mut Decoder) -> Self {
+        let raw = dec.get_slice(dec.remaining() - mem::size_of::<u64>());
+        let lsn = dec.get_u64();
+        Self::new(raw, lsn)
+    }
+
+    fn lsn(&self) -> Option<u64> {
+        Some(self.lsn)
+    }
+
+    fn with_lsn(&self, lsn: u64) -> Self {
+        Key::new(self.raw, lsn)
+    }
 }
 
 /// These values are persisted to disk, don't change them.
 const VALUE_KIND_PUT: u8 = 0;
 const VALUE_KIND_DELETE: u8 = 1;
+const VALUE_KIND_MERGE: u8 = 2;
+const VALUE_KIND_EXPIRING: u8 = 3;
 
 impl Codec for Value<'_> {
     fn encode_size(&self) -> usize {
         1 + match self {
-            Self::Put(v) => v.len(),
+            Self::Put(v) | Self::Merge(v) => v.len(),
             Self::Delete => 0,
+            Self::Expiring(v, _) => mem::size_of::<u64>() + v.len(),
         }
     }
 
@@ -495,6 +796,15 @@ impl Codec for Value<'_> {
                 enc.put_slice(v);
             }
             Value::Delete => enc.put_u8(VALUE_KIND_DELETE),
+            Value::Merge(v) => {
+                enc.put_u8(VALUE_KIND_MERGE);
+                enc.put_slice(v);
+            }
+            Value::Expiring(v, expires_at) => {
+                enc.put_u8(VALUE_KIND_EXPIRING);
+                enc.put_u64(*expires_at);
+                enc.put_slice(v);
+            }
         }
     }
 
@@ -503,11 +813,22 @@ impl Codec for Value<'_> {
         match kind {
             VALUE_KIND_PUT => Self::Put(dec.get_slice(dec.remaining())),
             VALUE_KIND_DELETE => Self::Delete,
+            VALUE_KIND_MERGE => Self::Merge(dec.get_slice(dec.remaining())),
+            VALUE_KIND_EXPIRING => {
+                let expires_at = dec.get_u64();
+                Self::Expiring(dec.get_slice(dec.remaining()), expires_at)
+            }
             _ => unreachable!(),
         }
     }
 }
 
+impl SortedPageValue for Value<'_> {
+    fn tombstone() -> Option<Self> {
+        Some(Self::Delete)
+    }
+}
+
 impl Codec for Index {
     fn encode_size(&self) -> usize {
         mem::size_of::<u64>() * 2
@@ -584,6 +905,84 @@ mod tests {
         }
     }
 
+    #[test]
+    fn sorted_page_split_by_size() {
+        let keys: Vec<[u8; 1]> = (0..16u8).map(|i| [i]).collect();
+        let small = [0u8; 8];
+        let large = [0u8; 256];
+        let data: Vec<(Key, &[u8])> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, k)| {
+                let v = if i < 12 { &small[..] } else { &large[..] };
+                (Key::new(k, 0), v)
+            })
+            .collect();
+        let owned_page = OwnedSortedPage::from_slice(&data);
+
+        let page = owned_page.as_ref();
+        let (_, left_iter, right_iter) = page.into_split_iter().unwrap();
+        let size = |(k, v): (Key, &[u8])| k.encode_size() + v.encode_size();
+        let left: Vec<usize> = left_iter.map(size).collect();
+        let right: Vec<usize> = right_iter.map(size).collect();
+        assert_eq!(left.len() + right.len(), data.len());
+        // A count-based split would put most large items on the right.
+        assert!(left.len() > data.len() / 2);
+        let left_size: usize = left.iter().sum();
+        let right_size: usize = right.iter().sum();
+        let max_size = size(data[data.len() - 1]);
+        assert!(left_size.abs_diff(right_size) <= max_size);
+    }
+
+    #[test]
+    fn sorted_page_fixed_keys() {
+        let keys: Vec<[u8; 8]> = (0..64u64).map(|i| i.to_be_bytes()).collect();
+        let data: Vec<(Key, Value)> = keys
+            .iter()
+            .map(|k| (Key::new(k, 1), Value::Put(k)))
+            .collect();
+        let variable_page = OwnedSortedPage::from_slice(&data);
+        let fixed_page = OwnedSortedPage::from_slice_with_key_size(&data, 8);
+        let variable = variable_page.as_ref();
+        let fixed = fixed_page.as_ref();
+        assert!(!variable.has_fixed_keys());
+        assert!(fixed.has_fixed_keys());
+        assert!(crate::page::verify_page(fixed.data()));
+        // The fixed layout saves the offset of each key.
+        assert_eq!(
+            variable.size() - fixed.size(),
+            data.len() * mem::size_of::<u32>() - FIXED_KEY_HEADER_LEN
+        );
+
+        assert_eq!(fixed.len(), data.len());
+        for (i, item) in data.iter().enumerate() {
+            assert_eq!(fixed.get(i), Some(*item));
+            assert_eq!(fixed.rank(&item.0), Ok(i));
+            assert_eq!(fixed.rank(&Key::new(item.0.raw, 0)), Err(i + 1));
+        }
+        assert_eq!(fixed.get(data.len()), None);
+
+        let mut iter = fixed_page.as_iter();
+        assert!(!iter.seek(&Key::new(&keys[10], 2)));
+        assert_eq!(iter.next(), Some(data[10]));
+        let (split_key, left_iter, right_iter) = fixed.into_split_iter().unwrap();
+        let split_data = variable.clone().into_split_iter().unwrap();
+        assert_eq!(split_key, split_data.0);
+        assert!(left_iter.eq(split_data.1));
+        assert!(right_iter.eq(split_data.2));
+
+        // Keys of other sizes fall back to the variable layout.
+        let page = OwnedSortedPage::from_slice_with_key_size(&data, 4);
+        assert!(!page.as_ref().has_fixed_keys());
+        assert_eq!(page.as_ref().size(), variable.size());
+
+        // A corrupted header is rejected without overflows.
+        let mut content = [0u8; FIXED_KEY_HEADER_LEN];
+        content[..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        content[4..].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(!verify_content(&content, true));
+    }
+
     #[test]
     fn sorted_page_split_none() {
         {
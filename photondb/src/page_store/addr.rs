@@ -0,0 +1,101 @@
+use std::fmt;
+
+/// The address of a page.
+///
+/// An address consists of the id of the write buffer or page group that
+/// contains the page and the offset of the page in it. Addresses change every
+/// time a page is updated, so they must not be used as page ids.
+///
+/// The public API and the page table use this type, while the tree, the
+/// versions and the records in write buffers keep raw `u64` addresses and
+/// convert them at those boundaries.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PageAddr(u64);
+
+impl PageAddr {
+    pub(crate) const fn new(file_id: u32, offset: u32) -> Self {
+        Self(((file_id as u64) << 32) | offset as u64)
+    }
+
+    pub(crate) const fn from_raw(raw: u64) -> Self {
+        Self(raw)
+    }
+
+    /// Returns the id of the write buffer or page group that contains the
+    /// page.
+    pub const fn file_id(self) -> u32 {
+        (self.0 >> 32) as u32
+    }
+
+    /// Returns the offset of the page in its write buffer or page group.
+    pub const fn offset(self) -> u32 {
+        self.0 as u32
+    }
+}
+
+impl From<PageAddr> for u64 {
+    fn from(addr: PageAddr) -> Self {
+        addr.0
+    }
+}
+
+impl fmt::Display for PageAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file_id(), self.offset())
+    }
+}
+
+/// The id of a page.
+///
+/// A page keeps its id until it is removed, while its address changes every
+/// time it is updated.
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PageId(u64);
+
+impl PageId {
+    pub(crate) const fn new(id: u64) -> Self {
+        Self(id)
+    }
+}
+
+impl From<PageId> for u64 {
+    fn from(id: PageId) -> Self {
+        id.0
+    }
+}
+
+impl fmt::Display for PageId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_addr_parts() {
+        let addr = PageAddr::new(3, 64);
+        assert_eq!(addr.file_id(), 3);
+        assert_eq!(addr.offset(), 64);
+        assert_eq!(u64::from(addr), (3 << 32) | 64);
+        assert_eq!(PageAddr::from_raw(u64::from(addr)), addr);
+        assert_eq!(addr.to_string(), "3:64");
+
+        let addr = PageAddr::new(u32::MAX, u32::MAX);
+        assert_eq!(addr.file_id(), u32::MAX);
+        assert_eq!(addr.offset(), u32::MAX);
+        assert_eq!(u64::from(addr), u64::MAX);
+    }
+
+    #[test]
+    fn page_id_roundtrip() {
+        let id = PageId::new(42);
+        assert_eq!(u64::from(id), 42);
+        assert_eq!(id.to_string(), "42");
+        assert!(PageId::new(1) < PageId::new(2));
+    }
+}
@@ -1,33 +1,106 @@
 use std::{
     sync::{
-        atomic::{AtomicPtr, Ordering},
-        Arc,
+        atomic::{AtomicPtr, AtomicU32, Ordering},
+        Arc, Mutex,
     },
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use crossbeam_epoch::Guard;
-use log::{debug, info};
+use log::{debug, error, info, warn};
 
 use super::{
+    pending_frees::{self, PendingFrees},
     stats::{AtomicBufferSetStats, BufferSetStats},
     write_buffer::ReleaseState,
-    FlushOptions, Result, WriteBuffer,
+    Error, FlushOptions, Result, WriteBuffer,
 };
 use crate::util::notify::Notify;
 
+/// The largest id of write buffers.
+///
+/// The end of [`BufferSetVersion::buffers_range`] is one past the last buffer,
+/// so it must not overflow. The ids are never wrapped, since addresses with a
+/// reused file id would resolve to another page.
+pub(crate) const MAX_BUFFER_ID: u32 = u32::MAX - 1;
+
+/// Indicates that no successor install is orphaned.
+const NO_ORPHANED_SUCCESSOR: u32 = u32::MAX;
+
+/// A slot of the writers limited by
+/// [`BufferSet::with_max_concurrent_writers`], which is released once the
+/// permit is dropped.
+pub(crate) struct WriterPermit<'a> {
+    buffer_set: &'a BufferSet,
+}
+
+impl Drop for WriterPermit<'_> {
+    fn drop(&mut self) {
+        if let Some(permits) = &self.buffer_set.writer_permits {
+            permits.release();
+        }
+    }
+}
+
 pub(crate) struct BufferSet {
     buffer_capacity: u32,
     max_sealed_buffers: usize,
+    /// Chooses the capacity of the next buffer if it is adaptive.
+    sizer: Option<BufferSizer>,
+    /// Whether the buffers admit contended writers in arrival order.
+    fair_admission: bool,
+    /// Whether the memory of the buffers is zeroed before it is freed.
+    zero_on_free: bool,
+    /// Whether the buffers reject all writes.
+    read_only: bool,
+    /// Limits the writers across all buffers, if any.
+    writer_permits: Option<buffer_permits::WriteBufferPermits>,
+    /// Counts the deferred frees of former versions of the page store.
+    pending_frees: PendingFrees,
+    /// The number of pending frees beyond which new transactions wait.
+    max_pending_frees: Option<u64>,
 
     current: AtomicPtr<BufferSetVersion>,
+    /// The sealed buffer whose successor is not installed, because the
+    /// routine that sealed it was cancelled.
+    orphaned_successor: AtomicU32,
 
     flush_notify: Notify,
+    /// One past the largest id of the buffers that manual flushes wait for.
+    awaited_flush: AtomicU32,
     write_buffer_permits: buffer_permits::WriteBufferPermits,
 
     stats: AtomicBufferSetStats,
 }
 
+/// Hands the install of the successor of a sealed buffer over to the next
+/// routine that switches the buffer, if the current one is cancelled.
+struct SuccessorGuard<'a> {
+    buffer_set: &'a BufferSet,
+    group_id: u32,
+    notify_flush: bool,
+    installed: bool,
+}
+
+impl Drop for SuccessorGuard<'_> {
+    fn drop(&mut self) {
+        if self.installed {
+            return;
+        }
+        warn!(
+            "Installing the successor of buffer {} is cancelled, hand it over",
+            self.group_id
+        );
+        self.buffer_set
+            .orphaned_successor
+            .store(self.group_id, Ordering::Release);
+        // The sealed buffer can still be flushed.
+        if self.notify_flush {
+            self.buffer_set.notify_flush_job();
+        }
+    }
+}
+
 pub(crate) struct BufferSetVersion {
     /// The range of the buffers referenced by the version, include
     /// `current_buffer`.
@@ -50,12 +123,17 @@ pub(crate) struct BufferRef<'a> {
 }
 
 impl BufferSet {
+    /// Creates a [`BufferSet`], whose buffers admit the contended writers in
+    /// arrival order if `fair_admission` is true, see
+    /// [`WriteBuffer::with_fair_admission`].
     pub(crate) fn new(
         next_group_id: u32,
         buffer_capacity: u32,
         max_sealed_buffers: usize,
+        fair_admission: bool,
     ) -> BufferSet {
-        let buf = WriteBuffer::with_capacity(next_group_id, buffer_capacity);
+        let buf = WriteBuffer::with_capacity(next_group_id, buffer_capacity)
+            .with_fair_admission(fair_admission);
         let version = Box::new(BufferSetVersion {
             buffers_range: next_group_id..(next_group_id + 1),
             sealed_buffers: Vec::default(),
@@ -69,16 +147,139 @@ impl BufferSet {
         BufferSet {
             buffer_capacity,
             max_sealed_buffers,
+            sizer: None,
+            fair_admission,
+            zero_on_free: false,
+            read_only: false,
+            writer_permits: None,
+            pending_frees: PendingFrees::default(),
+            max_pending_frees: None,
             current: AtomicPtr::new(raw),
+            orphaned_successor: AtomicU32::new(NO_ORPHANED_SUCCESSOR),
             flush_notify: Notify::new(),
+            awaited_flush: AtomicU32::new(0),
             write_buffer_permits,
             stats: AtomicBufferSetStats::default(),
         }
     }
 
+    /// Limits the number of writers holding a write guard of any buffer at
+    /// once, see [`BufferSet::acquire_writer`].
+    pub(crate) fn with_max_concurrent_writers(mut self, max_writers: usize) -> Self {
+        self.writer_permits = Some(buffer_permits::WriteBufferPermits::new(max_writers));
+        self
+    }
+
+    /// Waits until a writer can acquire a write guard.
+    ///
+    /// The slot is held until the returned permit is dropped, which should
+    /// outlive the write guard.
+    pub(crate) async fn acquire_writer(&self) -> WriterPermit<'_> {
+        if let Some(permits) = &self.writer_permits {
+            permits.acquire().await;
+        }
+        WriterPermit { buffer_set: self }
+    }
+
+    /// Adapts the capacity of the following buffers to the recent write rate,
+    /// so that they are sealed roughly every `seal_interval`.
+    ///
+    /// The capacity is bounded by `min_capacity` and the initial capacity. A
+    /// buffer is still large enough for the records rejected by the former one,
+    /// so records are checked against the initial capacity.
+    pub(crate) fn with_seal_interval(mut self, seal_interval: Duration, min_capacity: u32) -> Self {
+        let min_capacity = min_capacity.next_power_of_two().min(self.buffer_capacity);
+        self.sizer = Some(BufferSizer::new(
+            seal_interval,
+            min_capacity,
+            self.buffer_capacity,
+            Instant::now(),
+        ));
+        self
+    }
+
+    /// Zeroes the memory of the buffers before it is freed, see
+    /// [`WriteBuffer::with_zero_on_free`].
+    pub(crate) fn with_zero_on_free(mut self) -> Self {
+        self.zero_on_free = true;
+        self.renew_current_buffer();
+        self
+    }
+
+    /// Rejects all writes to the buffers with [`Error::ReadOnly`], so that no
+    /// buffer is ever sealed or installed.
+    pub(crate) fn with_read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    /// Returns true if the buffers reject all writes.
+    #[inline]
+    pub(crate) fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Creates a buffer with the options of the set.
+    fn new_buffer(&self, group_id: u32, buffer_capacity: u32) -> WriteBuffer {
+        WriteBuffer::with_capacity(group_id, buffer_capacity)
+            .with_max_capacity(self.buffer_capacity)
+            .with_fair_admission(self.fair_admission)
+            .with_zero_on_free(self.zero_on_free)
+    }
+
+    /// Replaces the initial buffer with one that follows the options of the
+    /// set.
+    fn renew_current_buffer(&mut self) {
+        let group_id = self.current().current_buffer.group_id();
+        let buf = self.new_buffer(group_id, self.buffer_capacity);
+        let version = Box::new(BufferSetVersion {
+            buffers_range: group_id..(group_id + 1),
+            sealed_buffers: Vec::default(),
+            current_buffer: Arc::new(buf),
+        });
+        let prev = std::mem::replace(self.current.get_mut(), Box::leak(version));
+        // Safety: the set isn't shared yet, and the version is leaked from a box.
+        drop(unsafe { Box::from_raw(prev) });
+    }
+
     #[inline]
     pub(crate) fn stats(&self) -> BufferSetStats {
-        self.stats.snapshot()
+        let mut stats = self.stats.snapshot();
+        stats.next_buffer_id = self.current().next_buffer_id();
+        stats.sealed_buffers = self.sealed_buffers().len() as u32;
+        stats
+    }
+
+    /// Returns the deferred frees of former [`BufferSetVersion`]s and
+    /// versions of the page store.
+    #[inline]
+    pub(crate) fn pending_frees(&self) -> &PendingFrees {
+        &self.pending_frees
+    }
+
+    /// Makes new transactions wait once there are more than
+    /// `max_pending_frees` pending frees, see
+    /// [`BufferSet::wait_pending_frees`].
+    pub(crate) fn with_max_pending_frees(mut self, max_pending_frees: u64) -> Self {
+        self.max_pending_frees = Some(max_pending_frees);
+        self
+    }
+
+    /// Waits until the pending frees of former versions are drained to the
+    /// limit, if any.
+    ///
+    /// The wait gives up once the frees stop making progress, since they can't
+    /// run while some thread stays pinned.
+    pub(crate) async fn wait_pending_frees(&self) {
+        if let Some(limit) = self.max_pending_frees {
+            self.pending_frees.drain(limit).await;
+        }
+    }
+
+    /// Tries to run the pending frees of former [`BufferSetVersion`]s.
+    #[inline]
+    pub(crate) fn collect_pending_frees() {
+        buffer_set_guard::collect()
     }
 
     /// Obtains a reference of current [`BufferSetVersion`].
@@ -104,16 +305,38 @@ impl BufferSet {
         })
     }
 
+    /// Returns a snapshot of the sealed [`WriteBuffer`]s that are not released
+    /// yet, from the oldest to the newest.
+    ///
+    /// The last writer buffer is included only if it is sealed.
+    pub(crate) fn sealed_buffers(&self) -> Vec<Arc<WriteBuffer>> {
+        let current = self.current();
+        let mut buffers = current.sealed_buffers.clone();
+        if current.current_buffer.is_sealed() {
+            buffers.push(current.current_buffer.clone());
+        }
+        buffers
+    }
+
     /// Install new [`BufferSetVersion`] by replacing `current_buffer` to new
     /// [`WriteBuffer`].
     ///
     /// There are no concurrent requests here, because only the routine that
     /// seals the previous [`WriteBuffer`] can install the new [`WriteBuffer`].
     ///
+    /// Returns [`Error::Again`] without any changes if a buffer with the same
+    /// file ID has been installed, so that a duplicate install is harmless.
+    ///
+    /// Returns [`Error::OutOfFileIds`] if the file ID is larger than
+    /// [`MAX_BUFFER_ID`].
+    ///
     /// # Panic
     ///
-    /// Panic if file IDs are not consecutive.
-    pub(crate) fn install(&self, write_buffer: Arc<WriteBuffer>) {
+    /// Panic if there is a gap between the file IDs.
+    pub(crate) fn install(&self, write_buffer: Arc<WriteBuffer>) -> Result<()> {
+        if write_buffer.group_id() > MAX_BUFFER_ID {
+            return Err(Error::OutOfFileIds);
+        }
         let mut guard = buffer_set_guard::pin();
 
         // Safety: guard by `buffer_set_guard::pin`.
@@ -121,6 +344,12 @@ impl BufferSet {
         loop {
             let next_group_id = current.buffers_range.end;
             let group_id = write_buffer.group_id();
+            if group_id < next_group_id {
+                warn!(
+                    "the buffer {group_id} to be installed has been installed, expect {next_group_id}."
+                );
+                return Err(Error::Again);
+            }
             if group_id != next_group_id {
                 panic!("the buffer {group_id} to be installed is not a successor of the previous buffers, expect {next_group_id}.");
             }
@@ -138,7 +367,7 @@ impl BufferSet {
             );
 
             match self.switch_version(guard, current, new) {
-                Ok(_) => break,
+                Ok(_) => return Ok(()),
                 Err(v) => (guard, current) = v,
             };
         }
@@ -151,8 +380,9 @@ impl BufferSet {
         // Safety: guarded by `buffer_set_guard::pin`.
         let mut current = unsafe { self.current_without_guard() };
         loop {
-            // Because versions are released one by one, should not be a gap in theory here.
-            if current.min_buffer_id() + 1 != first_buffer_id {
+            // Because versions are released one by one, should not be a gap in theory here,
+            // but a version might flush several buffers at once.
+            if current.min_buffer_id() >= first_buffer_id {
                 panic!(
                     "Release buffers not in order, current min is {}, but release until {}",
                     current.min_buffer_id(),
@@ -213,6 +443,13 @@ impl BufferSet {
         self.flush_notify.notify_one();
     }
 
+    /// Returns true if a manual flush waits for the buffer of `group_id` or a
+    /// later one, which can't be flushed before the buffer.
+    #[inline]
+    pub(crate) fn is_flush_awaited(&self, group_id: u32) -> bool {
+        self.awaited_flush.load(Ordering::Acquire) > group_id
+    }
+
     /// Obtain current [`BufferSetVersion`].
     ///
     /// # Safety
@@ -246,7 +483,7 @@ impl BufferSet {
             Ordering::Acquire,
         ) {
             Ok(_) => {
-                guard.defer(move || {
+                pending_frees::defer_free(&guard, self.pending_frees.buffer_sets(), move || {
                     // Safety: the backing memory is obtained from [`Box::into_raw`] and there no
                     // any references to the memory, which guarrantted by epoch based reclamation.
                     drop(unsafe { Box::from_raw(current as *mut BufferSetVersion) });
@@ -321,17 +558,67 @@ impl BufferSet {
     }
 
     /// Seal the corresponding write buffer and switch active buffer to new one.
+    ///
+    /// This is cancel safe. If the routine that sealed the buffer is cancelled
+    /// before the successor is installed, the next routine that switches the
+    /// same buffer takes over the install, otherwise the writers of the sealed
+    /// buffer would retry forever.
     pub(crate) async fn switch_buffer(&self, group_id: u32) {
-        let Some(release_state) = self.seal_buffer(group_id) else { return };
+        let notify_flush = match self.seal_buffer(group_id) {
+            Some(release_state) => matches!(release_state, ReleaseState::Flush),
+            None if self.take_orphaned_successor(group_id) => false,
+            None => return,
+        };
+        let mut guard = SuccessorGuard {
+            buffer_set: self,
+            group_id,
+            notify_flush,
+            installed: false,
+        };
         self.install_successor(group_id).await;
-        if matches!(release_state, ReleaseState::Flush) {
+        guard.installed = true;
+        if notify_flush {
             self.notify_flush_job();
         }
     }
 
+    /// Claims the install of the successor of `group_id` if it is orphaned.
+    fn take_orphaned_successor(&self, group_id: u32) -> bool {
+        self.orphaned_successor
+            .compare_exchange(
+                group_id,
+                NO_ORPHANED_SUCCESSOR,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_ok()
+    }
+
     /// Install the corresponding successor of `group_id`.
+    ///
+    /// Nothing is installed if the buffer ids are exhausted, in which case the
+    /// writers of the sealed buffer fail with [`Error::OutOfFileIds`].
     async fn install_successor(&self, group_id: u32) {
+        if group_id >= MAX_BUFFER_ID {
+            error!("Write buffer ids are exhausted, no buffer can be installed after {group_id}");
+            return;
+        }
+        // Measure the write rate before stalling, which is not part of it.
+        let buffer_capacity = match &self.sizer {
+            Some(sizer) => {
+                let buffer = self.get(group_id);
+                let written = buffer.as_ref().map_or(0, |buffer| buffer.allocated_size());
+                let capacity = sizer.next_capacity(written, Instant::now());
+                // Make room for the records that are too large for the sealed buffer.
+                let required = buffer.map_or(0, |buffer| buffer.required_capacity());
+                capacity.max(required)
+            }
+            None => self.buffer_capacity,
+        };
         if self.write_buffer_permits.try_acquire().is_none() {
+            // Make sure that the flush job sees the used up buffers, even if it is
+            // paused.
+            self.notify_flush_job();
             info!(
                 "Stalling writes because we have {} sealed write buffers (wait for flush)",
                 self.max_sealed_buffers
@@ -344,8 +631,10 @@ impl BufferSet {
                 .add(start_at.elapsed().as_millis() as u64);
         }
 
-        let write_buffer = WriteBuffer::with_capacity(group_id + 1, self.buffer_capacity);
-        self.install(Arc::new(write_buffer));
+        let write_buffer = self.new_buffer(group_id + 1, buffer_capacity);
+        // The successor is only installed by the routine that sealed the buffer, so
+        // a duplicate install can be ignored.
+        let _ = self.install(Arc::new(write_buffer));
     }
 
     /// Seal the corresponding buffer.
@@ -360,17 +649,31 @@ impl BufferSet {
 
     /// Seal the current write buffer and switch to new one, so the sealed
     /// buffer will be flushed by flusher.
+    ///
+    /// If the current write buffer is empty, there is nothing to seal, but
+    /// the sealed buffers are still waited for if required.
     pub(crate) async fn flush_active_buffer(&self, opts: &FlushOptions) {
-        let buffer = {
+        let (buffer, is_sealed) = {
             let current = self.current();
-            if current.current_buffer.is_empty() {
+            if !current.current_buffer.is_empty() {
+                (current.current_buffer.clone(), false)
+            } else if let Some(buffer) = current.sealed_buffers.last().filter(|_| opts.wait) {
+                (buffer.clone(), true)
+            } else {
                 return;
             }
-            current.current_buffer.clone()
         };
 
         let group_id = buffer.group_id();
-        if opts.allow_write_stall {
+        if opts.wait {
+            // Mark the buffer before it is sealed, so that the flush job never
+            // holds it back.
+            self.awaited_flush.fetch_max(group_id + 1, Ordering::AcqRel);
+        }
+        if is_sealed {
+            // The flush job may be holding back the buffer already.
+            self.notify_flush_job();
+        } else if opts.allow_write_stall {
             self.switch_buffer(group_id).await;
         } else {
             self.switch_buffer_without_stalling(group_id).await;
@@ -449,6 +752,66 @@ impl<'a> std::ops::Deref for BufferRef<'a> {
     }
 }
 
+/// Chooses the capacity of write buffers from the recent write rate.
+struct BufferSizer {
+    seal_interval: Duration,
+    min_capacity: u32,
+    max_capacity: u32,
+    state: Mutex<BufferSizerState>,
+}
+
+struct BufferSizerState {
+    /// The time that the last buffer was switched.
+    last_switch: Instant,
+    /// The smoothed write rate in bytes per second, or zero if unknown.
+    write_rate: f64,
+}
+
+impl BufferSizer {
+    fn new(seal_interval: Duration, min_capacity: u32, max_capacity: u32, now: Instant) -> Self {
+        debug_assert!(min_capacity.is_power_of_two() && max_capacity.is_power_of_two());
+        debug_assert!(min_capacity <= max_capacity);
+        BufferSizer {
+            seal_interval,
+            min_capacity,
+            max_capacity,
+            state: Mutex::new(BufferSizerState {
+                last_switch: now,
+                write_rate: 0.0,
+            }),
+        }
+    }
+
+    /// Returns the capacity of the next buffer, given the bytes written to the
+    /// buffer switched at `now`.
+    fn next_capacity(&self, written: usize, now: Instant) -> u32 {
+        let mut state = self.state.lock().expect("Poisoned");
+        let elapsed = now.saturating_duration_since(state.last_switch);
+        state.last_switch = now;
+        if !elapsed.is_zero() {
+            let rate = written as f64 / elapsed.as_secs_f64();
+            // Smooth the rate so that a single burst doesn't swing the capacity.
+            state.write_rate = if state.write_rate > 0.0 {
+                (state.write_rate + rate) / 2.0
+            } else {
+                rate
+            };
+        }
+        if state.write_rate <= 0.0 {
+            return self.max_capacity;
+        }
+        let target = (state.write_rate * self.seal_interval.as_secs_f64()) as u64;
+        let capacity = target
+            .clamp(self.min_capacity as u64, self.max_capacity as u64)
+            .next_power_of_two() as u32;
+        debug!(
+            "Choose write buffer capacity {capacity} for write rate {:.0} bytes/s",
+            state.write_rate
+        );
+        capacity
+    }
+}
+
 mod buffer_permits {
     use std::sync::atomic::{AtomicUsize, Ordering};
 
@@ -611,6 +974,11 @@ mod buffer_set_guard {
         with_handle(|handle| handle.is_pinned())
     }
 
+    /// Tries to advance the epoch and runs the deferred frees that are ready.
+    pub(super) fn collect() {
+        pin().flush();
+    }
+
     #[inline]
     fn with_handle<F, R>(mut f: F) -> R
     where
@@ -624,7 +992,7 @@ mod buffer_set_guard {
 
 #[cfg(test)]
 mod tests {
-    use std::sync::atomic::AtomicU32;
+    use std::sync::atomic::{AtomicU32, AtomicUsize};
 
     use futures::{channel::mpsc, SinkExt, StreamExt};
 
@@ -637,22 +1005,226 @@ mod tests {
         }
     }
 
+    #[test]
+    fn buffer_set_guard_pending_frees() {
+        const N: u64 = 1000;
+        let pending_frees = PendingFrees::default();
+        let guard = buffer_set_guard::pin();
+        for _ in 0..N {
+            pending_frees::defer_free(&guard, pending_frees.buffer_sets(), || {});
+        }
+        // The deferred frees can't run while the thread is pinned.
+        assert_eq!(pending_frees.num_buffer_sets(), N);
+        drop(guard);
+
+        // Advance the epoch to run the deferred frees.
+        for _ in 0..10000 {
+            if pending_frees.num_buffer_sets() < N {
+                break;
+            }
+            buffer_set_guard::pin().flush();
+        }
+        assert!(pending_frees.num_buffer_sets() < N);
+    }
+
+    #[photonio::test]
+    async fn drain_pending_frees_after_stuck_epoch() {
+        use std::sync::mpsc;
+
+        const N: u64 = 10000;
+        const LIMIT: u64 = N / 2;
+        let (pinned_tx, pinned_rx) = mpsc::channel();
+        let (unpin_tx, unpin_rx) = mpsc::channel::<()>();
+        let stuck = std::thread::spawn(move || {
+            let _guard = buffer_set_guard::pin();
+            pinned_tx.send(()).unwrap();
+            let _ = unpin_rx.recv();
+        });
+        pinned_rx.recv().unwrap();
+
+        let pending_frees = PendingFrees::default();
+        let guard = buffer_set_guard::pin();
+        for _ in 0..N {
+            pending_frees::defer_free(&guard, pending_frees.buffer_sets(), || {});
+        }
+        drop(guard);
+        // The frees can't run while the epoch is stuck.
+        assert!(!pending_frees.drain(LIMIT).await);
+        assert_eq!(pending_frees.num_buffer_sets(), N);
+
+        drop(unpin_tx);
+        stuck.join().unwrap();
+        // Other tests may pin the collector at the same time, retry for a while.
+        for _ in 0..1000 {
+            if pending_frees.drain(LIMIT).await {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert!(pending_frees.num_buffer_sets() <= LIMIT);
+    }
+
+    #[photonio::test]
+    async fn pending_frees_per_buffer_set() {
+        let buffer_set = BufferSet::new(1, 1 << 10, 8, false);
+        let other = BufferSet::new(1, 1 << 10, 8, false);
+        let guard = buffer_set_guard::pin();
+        buffer_set
+            .install(Arc::new(WriteBuffer::with_capacity(2, 1 << 10)))
+            .unwrap();
+        // The former version is counted by its own set only.
+        assert_eq!(buffer_set.pending_frees().num_buffer_sets(), 1);
+        assert_eq!(other.pending_frees().num_buffer_sets(), 0);
+        drop(guard);
+
+        let pending_frees = buffer_set.pending_frees();
+        for _ in 0..1000 {
+            if pending_frees.drain(0).await {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(pending_frees.num_buffer_sets(), 0);
+    }
+
+    #[test]
+    fn buffer_sizer_adapts_to_write_rate() {
+        let start = Instant::now();
+        let sizer = BufferSizer::new(Duration::from_secs(2), 1 << 10, 1 << 20, start);
+
+        // Fills each buffer at the given rate and returns the last capacity.
+        let mut now = start;
+        let mut capacity = 1 << 20;
+        let mut drive = |rate: u64, steps: usize| {
+            for _ in 0..steps {
+                now += Duration::from_secs_f64(capacity as f64 / rate as f64);
+                capacity = sizer.next_capacity(capacity as usize, now);
+            }
+            capacity
+        };
+
+        // 64KB/s, the buffer is sealed every 2s.
+        assert_eq!(drive(64 << 10, 1), 128 << 10);
+        assert_eq!(drive(64 << 10, 4), 128 << 10);
+        // 1MB/s, which is bounded by the maximum capacity.
+        assert_eq!(drive(1 << 20, 1), 1 << 20);
+        assert_eq!(drive(1 << 20, 4), 1 << 20);
+        // 4KB/s, the capacity shrinks gradually.
+        let mut last = 1 << 20;
+        for _ in 0..32 {
+            let capacity = drive(4 << 10, 1);
+            assert!(capacity <= last);
+            last = capacity;
+        }
+        assert_eq!(last, 8 << 10);
+        // 256B/s, which is bounded by the minimum capacity.
+        assert_eq!(drive(256, 16), 1 << 10);
+    }
+
     #[test]
     fn buffer_set_construct_and_drop() {
-        drop(BufferSet::new(1, 1 << 10, 8));
+        drop(BufferSet::new(1, 1 << 10, 8, false));
     }
 
     #[test]
     fn buffer_set_write_buffer_install() {
-        let buffer_set = BufferSet::new(1, 1 << 10, 8);
+        let buffer_set = BufferSet::new(1, 1 << 10, 8, false);
         let file_id = buffer_set.current().next_buffer_id();
         let buf = WriteBuffer::with_capacity(file_id, buffer_set.buffer_capacity);
-        buffer_set.install(Arc::new(buf));
+        buffer_set.install(Arc::new(buf)).unwrap();
+    }
+
+    #[test]
+    fn buffer_set_write_buffer_duplicate_install() {
+        let buffer_set = BufferSet::new(1, 1 << 10, 8, false);
+        let buf = WriteBuffer::with_capacity(2, buffer_set.buffer_capacity);
+        buffer_set.install(Arc::new(buf)).unwrap();
+
+        // Installing an existing file id again changes nothing.
+        for file_id in [1, 2] {
+            let buf = WriteBuffer::with_capacity(file_id, buffer_set.buffer_capacity);
+            assert!(matches!(
+                buffer_set.install(Arc::new(buf)),
+                Err(Error::Again)
+            ));
+        }
+        let current = buffer_set.current();
+        assert_eq!(current.buffers_range, 1..3);
+        assert_eq!(current.last_writer_buffer().group_id(), 2);
+    }
+
+    #[photonio::test]
+    async fn buffer_set_write_buffer_install_out_of_ids() {
+        let buffer_set = BufferSet::new(MAX_BUFFER_ID - 1, 1 << 10, 8, false);
+        let buf = WriteBuffer::with_capacity(MAX_BUFFER_ID, buffer_set.buffer_capacity);
+        buffer_set.install(Arc::new(buf)).unwrap();
+        assert_eq!(buffer_set.stats().next_buffer_id, u32::MAX);
+
+        // The ids never wrap around.
+        let buf = WriteBuffer::with_capacity(u32::MAX, buffer_set.buffer_capacity);
+        assert!(matches!(
+            buffer_set.install(Arc::new(buf)),
+            Err(Error::OutOfFileIds)
+        ));
+        buffer_set.switch_buffer(MAX_BUFFER_ID).await;
+        let current = buffer_set.current();
+        assert_eq!(current.buffers_range, (MAX_BUFFER_ID - 1)..u32::MAX);
+        assert_eq!(current.last_writer_buffer().group_id(), MAX_BUFFER_ID);
+        assert!(current.last_writer_buffer().is_sealed());
+    }
+
+    #[photonio::test]
+    async fn buffer_set_switch_buffer_cancelled() {
+        // No permits are left, so the successor waits for a flush.
+        let buffer_set = BufferSet::new(1, 1 << 10, 1, false);
+        {
+            let mut switch = Box::pin(buffer_set.switch_buffer(1));
+            assert!(futures::poll!(switch.as_mut()).is_pending());
+        }
+        let current = buffer_set.current();
+        assert!(current.last_writer_buffer().is_sealed());
+        assert_eq!(current.buffers_range, 1..2);
+        drop(current);
+
+        // The next switch of the same buffer takes over the install.
+        buffer_set.write_buffer_permits.release();
+        buffer_set.switch_buffer(1).await;
+        let current = buffer_set.current();
+        assert_eq!(current.buffers_range, 1..3);
+        assert_eq!(current.last_writer_buffer().group_id(), 2);
+        assert!(!current.last_writer_buffer().is_sealed());
+        drop(current);
+        // The install is taken over only once.
+        buffer_set.switch_buffer(1).await;
+        assert_eq!(buffer_set.current().buffers_range, 1..3);
+
+        // The notification of the sealed buffer is not lost.
+        buffer_set.wait_flushable().await;
+    }
+
+    #[photonio::test]
+    async fn buffer_set_wait_flushable_cancelled() {
+        let buffer_set = BufferSet::new(1, 1 << 10, 8, false);
+        {
+            let mut wait = Box::pin(buffer_set.wait_flushable());
+            assert!(futures::poll!(wait.as_mut()).is_pending());
+        }
+        // A notification after the waiter is dropped is kept for the next one.
+        buffer_set.notify_flush_job();
+        buffer_set.wait_flushable().await;
+    }
+
+    #[test]
+    #[should_panic]
+    fn buffer_set_write_buffer_gapped_install() {
+        let buffer_set = BufferSet::new(1, 1 << 10, 8, false);
+        let buf = WriteBuffer::with_capacity(3, buffer_set.buffer_capacity);
+        let _ = buffer_set.install(Arc::new(buf));
     }
 
     #[test]
     fn buffer_set_write_buffer_install_and_release() {
-        let buffer_set = BufferSet::new(1, 1 << 10, 8);
+        let buffer_set = BufferSet::new(1, 1 << 10, 8, false);
         let file_id = buffer_set.current().last_writer_buffer().group_id();
 
         // 1. seal current.
@@ -663,7 +1235,7 @@ mod tests {
         // 2. install
         {
             let buf = WriteBuffer::with_capacity(file_id + 1, buffer_set.buffer_capacity);
-            buffer_set.install(Arc::new(buf));
+            buffer_set.install(Arc::new(buf)).unwrap();
         }
 
         // 3. seal current again.
@@ -678,9 +1250,41 @@ mod tests {
         assert!(buffer_set.current().get(file_id + 1).is_some());
     }
 
+    #[test]
+    fn buffer_set_sealed_buffers() {
+        let buffer_set = BufferSet::new(1, 1 << 10, 8, false);
+        assert!(buffer_set.sealed_buffers().is_empty());
+
+        for file_id in 1..4 {
+            buffer_set.current().last_writer_buffer().seal().unwrap();
+            let buffers = buffer_set.sealed_buffers();
+            assert_eq!(buffers.last().unwrap().group_id(), file_id);
+
+            let buf = WriteBuffer::with_capacity(file_id + 1, buffer_set.buffer_capacity);
+            buffer_set.install(Arc::new(buf)).unwrap();
+        }
+
+        // The active buffer is excluded.
+        let file_ids: Vec<_> = buffer_set
+            .sealed_buffers()
+            .iter()
+            .map(|buf| buf.group_id())
+            .collect();
+        assert_eq!(file_ids, vec![1, 2, 3]);
+        assert_eq!(buffer_set.stats().sealed_buffers, 3);
+
+        buffer_set.release_until(2);
+        let file_ids: Vec<_> = buffer_set
+            .sealed_buffers()
+            .iter()
+            .map(|buf| buf.group_id())
+            .collect();
+        assert_eq!(file_ids, vec![2, 3]);
+    }
+
     #[photonio::test]
     async fn buffer_set_concurrent_update() {
-        let buffer_set = Arc::new(BufferSet::new(1, 32, 8));
+        let buffer_set = Arc::new(BufferSet::new(1, 32, 8, false));
         let file_id = buffer_set.current().last_writer_buffer().group_id();
         let first_active_buffer_id = Arc::new(AtomicU32::new(file_id));
         let cloned_first_active_buffer_id = first_active_buffer_id.clone();
@@ -701,7 +1305,7 @@ mod tests {
                     photonio::task::yield_now().await;
                 }
                 let buf = WriteBuffer::with_capacity(file_id, 32);
-                cloned_buffer_set.install(Arc::new(buf));
+                cloned_buffer_set.install(Arc::new(buf)).unwrap();
                 cloned_first_active_buffer_id.store(file_id, Ordering::Release);
                 photonio::task::yield_now().await;
             }
@@ -730,7 +1334,7 @@ mod tests {
 
     #[photonio::test]
     async fn buffer_set_write_buffer_flush_wait_and_notify() {
-        let buffer_set = Arc::new(BufferSet::new(1, 1 << 10, 8));
+        let buffer_set = Arc::new(BufferSet::new(1, 1 << 10, 8, false));
         let cloned_buffer_set = buffer_set.clone();
         let handle = photonio::task::spawn(async move {
             cloned_buffer_set.wait_flushable().await;
@@ -741,7 +1345,7 @@ mod tests {
 
         let file_id = buffer_set.current().next_buffer_id();
         let buf = WriteBuffer::with_capacity(file_id, buffer_set.buffer_capacity);
-        buffer_set.install(Arc::new(buf));
+        buffer_set.install(Arc::new(buf)).unwrap();
 
         buffer_set.notify_flush_job();
         handle.await.unwrap();
@@ -749,7 +1353,7 @@ mod tests {
 
     #[test]
     fn buffer_set_write_buffer_switch_release() {
-        let buffer_set = BufferSet::new(1, 1 << 10, 8);
+        let buffer_set = BufferSet::new(1, 1 << 10, 8, false);
         let (file_id, buf) = {
             let current = buffer_set.current();
             let buf = current.last_writer_buffer();
@@ -761,10 +1365,12 @@ mod tests {
         buf.seal().unwrap();
 
         // Install new buf.
-        buffer_set.install(Arc::new(WriteBuffer::with_capacity(
-            file_id + 1,
-            buffer_set.buffer_capacity,
-        )));
+        buffer_set
+            .install(Arc::new(WriteBuffer::with_capacity(
+                file_id + 1,
+                buffer_set.buffer_capacity,
+            )))
+            .unwrap();
         buffer_set.release_until(file_id + 1);
 
         // Advance epoch and reclaim [`BufferSetVersion`].
@@ -785,7 +1391,7 @@ mod tests {
 
     #[photonio::test]
     async fn buffer_set_flush_active_buffer() {
-        let buffer_set = BufferSet::new(1, 1 << 10, 8);
+        let buffer_set = BufferSet::new(1, 1 << 10, 8, false);
 
         let opts = FlushOptions {
             wait: false,
@@ -801,9 +1407,33 @@ mod tests {
         buffer_set.flush_active_buffer(&opts).await;
     }
 
+    #[photonio::test]
+    async fn buffer_set_switch_to_required_capacity() {
+        // The buffers shrink to the min capacity at any write rate.
+        let buffer_set = BufferSet::new(1, 4 << 10, 8, false)
+            .with_seal_interval(Duration::from_nanos(1), 1 << 10);
+        let alloc = |page_size| {
+            let current = buffer_set.current();
+            let buf = current.last_writer_buffer();
+            let result = unsafe { buf.alloc_page(1, page_size, false) }.map(|_| ());
+            (buf.group_id(), result)
+        };
+        let (group_id, result) = alloc(32);
+        result.unwrap();
+        buffer_set.switch_buffer(group_id).await;
+
+        // The page is too large for the current buffer, but not for the set.
+        let (group_id, result) = alloc(2 << 10);
+        assert!(matches!(result, Err(Error::Again)));
+        buffer_set.switch_buffer(group_id).await;
+        // The next buffer is large enough for it.
+        let (_, result) = alloc(2 << 10);
+        result.unwrap();
+    }
+
     #[photonio::test]
     async fn buffer_set_flush_active_buffer_and_wait() {
-        let buffer_set = BufferSet::new(1, 1 << 10, 8);
+        let buffer_set = BufferSet::new(1, 1 << 10, 8, false);
 
         {
             let current = buffer_set.current();
@@ -820,6 +1450,64 @@ mod tests {
         buffer_set.flush_active_buffer(&opts).await;
     }
 
+    #[photonio::test]
+    async fn buffer_set_flush_empty_active_buffer_and_wait() {
+        let buffer_set = BufferSet::new(1, 1 << 10, 8, false);
+
+        let buf = {
+            let current = buffer_set.current();
+            let buf = current.current_buffer.clone();
+            unsafe { buf.alloc_page(1, 32, false).unwrap() };
+            buf
+        };
+        let opts = FlushOptions {
+            wait: false,
+            allow_write_stall: false,
+        };
+        buffer_set.flush_active_buffer(&opts).await;
+        assert!(!buffer_set.is_flush_awaited(buf.group_id()));
+
+        // The sealed buffer is waited for even if the active one is empty.
+        buf.on_flushed();
+        let opts = FlushOptions {
+            wait: true,
+            allow_write_stall: false,
+        };
+        buffer_set.flush_active_buffer(&opts).await;
+        assert!(buffer_set.is_flush_awaited(buf.group_id()));
+    }
+
+    #[photonio::test]
+    async fn max_concurrent_writers() {
+        let buffer_set =
+            Arc::new(BufferSet::new(1, 1 << 10, 8, false).with_max_concurrent_writers(2));
+        let first = buffer_set.acquire_writer().await;
+        let second = buffer_set.acquire_writer().await;
+
+        // The writers beyond the limit wait for a slot.
+        let admitted = Arc::new(AtomicUsize::new(0));
+        let mut tasks = Vec::new();
+        for _ in 0..2 {
+            let buffer_set = buffer_set.clone();
+            let admitted = admitted.clone();
+            tasks.push(photonio::task::spawn(async move {
+                let _permit = buffer_set.acquire_writer().await;
+                admitted.fetch_add(1, Ordering::AcqRel);
+            }));
+        }
+        for _ in 0..8 {
+            photonio::task::yield_now().await;
+        }
+        assert_eq!(admitted.load(Ordering::Acquire), 0);
+
+        drop(first);
+        drop(second);
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(admitted.load(Ordering::Acquire), 2);
+    }
+
     #[photonio::test]
     async fn write_buffer_permits_basic() {
         let write_permits = Arc::new(buffer_permits::WriteBufferPermits::new(2));
@@ -914,7 +914,11 @@ impl<T: Clone> ClockCacheShard<T> {
     }
 
     fn shard_stats(&self) -> CacheStats {
-        self.table.stats.snapshot()
+        let mut stats = self.table.stats.snapshot();
+        stats.usage = (self.table.usage.load(Ordering::Relaxed)
+            - self.table.detached_usage.load(Ordering::Relaxed)) as u64;
+        stats.capacity = self.capacity as u64;
+        stats
     }
 
     fn shard_advice(&self, predicted_load_factors: &mut Vec<f64>, min_recommendation: &mut usize) {
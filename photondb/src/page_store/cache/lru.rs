@@ -223,8 +223,11 @@ impl<T: Clone> Cache<T> for LRUCache<T> {
 
     fn stats(self: &std::sync::Arc<Self>) -> crate::page_store::stats::CacheStats {
         let mut summary = CacheStats::default();
-        for s in &self.stats {
-            let shard_stats = s.snapshot();
+        for (s, shard) in self.stats.iter().zip(&self.shards) {
+            let mut shard_stats = s.snapshot();
+            let shard = shard.lock();
+            shard_stats.usage = shard.usage.load(Ordering::Relaxed) as u64;
+            shard_stats.capacity = shard.capacity as u64;
             summary = summary.add(&shard_stats);
         }
         summary
@@ -383,6 +383,8 @@ impl AtomicCacheStats {
             active_evict: self.active_evict.get(),
             passive_evict: self.passive_evict.get(),
             recommendation: vec![],
+            usage: 0,
+            capacity: 0,
         }
     }
 }
@@ -482,6 +484,27 @@ mod tests {
         assert!(h.is_none());
     }
 
+    #[test]
+    fn test_lru_stats_usage() {
+        use super::lru::*;
+
+        let c = Arc::new(LRUCache::new(10, -1, 0.0, 0.0));
+        for n in 1..=100 {
+            let h = c
+                .insert(n, Some(vec![n]), 3, CacheOption::default())
+                .unwrap()
+                .unwrap();
+            drop(h);
+            let stats = c.stats();
+            assert_eq!(stats.capacity, 10);
+            assert!(stats.usage <= stats.capacity);
+        }
+        let stats = c.stats();
+        assert_eq!(stats.insert, 100);
+        assert_eq!(stats.usage, 9);
+        assert_eq!(stats.evictions(), 97);
+    }
+
     #[test]
     fn test_lru_pri_op() {
         use super::lru::*;
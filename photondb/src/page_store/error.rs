@@ -1,9 +1,13 @@
 use thiserror::Error;
 
+use super::SelfTestFailure;
+
 #[derive(Error, Debug)]
 pub(crate) enum Error {
     #[error("Again")]
     Again,
+    #[error("Contended")]
+    Contended,
     #[error("Corrupted")]
     Corrupted,
     #[error("Invalid argument")]
@@ -12,6 +16,14 @@ pub(crate) enum Error {
     MemoryLimit,
     #[error("TooLarge put size")]
     TooLargeSize,
+    #[error("Page ids are exhausted")]
+    OutOfPageIds,
+    #[error("File ids are exhausted")]
+    OutOfFileIds,
+    #[error("Self-test failed: {0}")]
+    SelfTest(SelfTestFailure),
+    #[error("Read only")]
+    ReadOnly,
     #[error("IO {0}")]
     Io(#[from] std::io::Error),
 }
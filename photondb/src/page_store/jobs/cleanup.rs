@@ -1,6 +1,6 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
-use log::info;
+use log::{info, warn};
 
 use crate::{
     env::Env,
@@ -11,13 +11,23 @@ use crate::{
 pub(crate) struct CleanupCtx<E: Env> {
     shutdown: Shutdown,
     page_files: Arc<PageFiles<E>>,
+    pending_frees_limit: u64,
+    // If set, obsolete files are kept here instead of being removed.
+    kept_files: Option<Arc<Mutex<Vec<u32>>>>,
 }
 
 impl<E: Env> CleanupCtx<E> {
-    pub(crate) fn new(shutdown: Shutdown, page_files: Arc<PageFiles<E>>) -> Self {
+    pub(crate) fn new(
+        shutdown: Shutdown,
+        page_files: Arc<PageFiles<E>>,
+        pending_frees_limit: u64,
+        kept_files: Option<Arc<Mutex<Vec<u32>>>>,
+    ) -> Self {
         CleanupCtx {
             shutdown,
             page_files,
+            pending_frees_limit,
+            kept_files,
         }
     }
 
@@ -41,6 +51,13 @@ impl<E: Env> CleanupCtx<E> {
             // safety.
             version.release_previous_buffers();
 
+            // Each new version defers the free of the former one, run them if they
+            // are piling up.
+            let pending_frees = version.buffer_set.pending_frees();
+            if !pending_frees.drain(self.pending_frees_limit).await {
+                warn!("The pending frees of former versions can't be drained");
+            }
+
             // Now it is safety to cleanup the version.
             self.clean_obsoleted_files(&version, files).await;
         }
@@ -57,9 +74,16 @@ impl<E: Env> CleanupCtx<E> {
             }
         }
 
-        if !obsoleted_files.is_empty() {
+        if obsoleted_files.is_empty() {
+            return;
+        }
+        self.page_files.evict_cached_pages(&obsoleted_files);
+        if let Some(kept_files) = &self.kept_files {
+            info!("Keep obsoleted files {obsoleted_files:?}");
+            let mut kept_files = kept_files.lock().expect("Poisoned");
+            kept_files.extend(obsoleted_files);
+        } else {
             info!("Clean obsoleted files {obsoleted_files:?}");
-            self.page_files.evict_cached_pages(&obsoleted_files);
             self.page_files.remove_files(obsoleted_files).await;
         }
     }
@@ -1,17 +1,28 @@
-use std::{sync::Arc, time::Instant};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
+use futures::{
+    future::{self, Either},
+    stream::{FuturesOrdered, StreamExt},
+};
 use log::info;
 use rustc_hash::{FxHashMap, FxHashSet};
 
 use crate::{
     env::Env,
     page_store::{stats::AtomicJobStats, *},
-    util::shutdown::{with_shutdown, Shutdown},
+    util::{
+        pause::Pause,
+        shutdown::{with_shutdown, Shutdown},
+    },
 };
 
 pub(crate) struct FlushCtx<E: Env> {
     options: Options,
     shutdown: Shutdown,
+    pause: Pause,
     job_stats: Arc<AtomicJobStats>,
     version_owner: Arc<VersionOwner>,
     page_files: Arc<PageFiles<E>>,
@@ -32,6 +43,7 @@ impl<E: Env> FlushCtx<E> {
     pub(crate) fn new(
         options: Options,
         shutdown: Shutdown,
+        pause: Pause,
         job_stats: Arc<AtomicJobStats>,
         version_owner: Arc<VersionOwner>,
         page_files: Arc<PageFiles<E>>,
@@ -40,6 +52,7 @@ impl<E: Env> FlushCtx<E> {
         FlushCtx {
             options,
             shutdown,
+            pause,
             job_stats,
             version_owner,
             page_files,
@@ -47,36 +60,134 @@ impl<E: Env> FlushCtx<E> {
         }
     }
 
-    pub(crate) async fn run(mut self) {
-        'OUTER: loop {
+    /// Flushes sealed [`WriteBuffer`]s until shutdown.
+    ///
+    /// At most `flush_concurrency` page files are built concurrently. Each of
+    /// them claims the next flushable [`WriteBuffer`]s, and the files are
+    /// installed in the order they were claimed, so that versions always
+    /// advance over consecutive write buffers.
+    ///
+    /// No more write buffers are claimed while the job is paused, unless all
+    /// write buffers are used up, which would stall writes forever.
+    pub(crate) async fn run(self) {
+        let mut shutdown = self.shutdown.clone();
+        let concurrency = self.options.flush_concurrency.max(1);
+        let mut inflights = FuturesOrdered::new();
+        let mut next_group_id = self.version_owner.current().min_write_buffer().group_id();
+        // The group id of the first write buffer held back, and when it is.
+        let mut deferred = None;
+        loop {
             let version = self.version_owner.current();
-            let write_buffer = version.min_write_buffer();
+            while inflights.len() < concurrency {
+                if self.pause.is_paused()
+                    && version.buffers_range().len() < self.options.max_write_buffers
+                {
+                    break;
+                }
+                let write_buffer = match version.buffer_set.current().get(next_group_id) {
+                    Some(buffer) if buffer.is_flushable() => buffer.clone(),
+                    _ => break,
+                };
+                let write_buffers =
+                    collect_flushable_buffers(&version, write_buffer, &self.options);
+                if self.should_defer_flush(&version, &write_buffers, &mut deferred) {
+                    break;
+                }
+                next_group_id += write_buffers.len() as u32;
+                // File ids are allocated in the claiming order.
+                let file_id = self.alloc_file_id(next_group_id - 1).await;
+                let ctx = &self;
+                inflights.push_back(async move {
+                    let start_at = Instant::now();
+                    let output = match file_id {
+                        Ok(file_id) => ctx.build_page_file_with_id(&write_buffers, file_id).await,
+                        Err(err) => Err(err),
+                    };
+                    (write_buffers, start_at, output)
+                });
+            }
 
-            // If the [`WriteBuffer`] can be flushed, then it should continue because
-            // [`Notify`] is single permits. But this may also lead to [`WriteBuffer`]
-            // flushed but notified is not consumed, so loop detection is required.
-            while !write_buffer.is_flushable() {
-                if with_shutdown(&mut self.shutdown, version.buffer_set.wait_flushable())
+            let flushed = if inflights.is_empty() {
+                // If the [`WriteBuffer`] can be flushed, then it should be claimed above
+                // because [`Notify`] is single permits. But this may also lead to
+                // [`WriteBuffer`] flushed but notified is not consumed, so loop detection is
+                // required.
+                if with_shutdown(&mut shutdown, version.buffer_set.wait_flushable())
                     .await
                     .is_none()
                 {
-                    break 'OUTER;
+                    break;
                 }
-            }
+                continue;
+            } else if inflights.len() < concurrency {
+                // Claims more write buffers once they are sealed.
+                let wait_flushable = Box::pin(version.buffer_set.wait_flushable());
+                match future::select(inflights.next(), wait_flushable).await {
+                    Either::Left((flushed, _)) => flushed,
+                    Either::Right(_) => continue,
+                }
+            } else {
+                inflights.next().await
+            };
 
-            match self.flush(write_buffer.as_ref()).await {
-                Ok(()) => {}
-                Err(err) => {
-                    todo!("flush write buffer: {err:?}");
+            let (write_buffers, start_at, output) = flushed.expect("inflights is not empty");
+            let result = match output {
+                Ok(output) => {
+                    self.install_page_file(&write_buffers, output, start_at, true)
+                        .await
                 }
+                Err(err) => Err(err),
+            };
+            if let Err(err) = result {
+                todo!("flush write buffer: {err:?}");
             }
         }
+        drop(inflights);
 
         if !self.options.avoid_flush_during_shutdown {
             self.flush_during_shutdown().await;
         }
     }
 
+    /// Returns true if the write buffers are too small to be flushed yet.
+    ///
+    /// They are held back until more write buffers are sealed to reach
+    /// `min_flush_bytes`, unless a manual flush waits for them or all write
+    /// buffers are used up, which would stall writes. They are not held back
+    /// for more than `max_flush_delay` either, which is tracked in `deferred`.
+    fn should_defer_flush(
+        &self,
+        version: &Version,
+        write_buffers: &[Arc<WriteBuffer>],
+        deferred: &mut Option<(u32, Instant)>,
+    ) -> bool {
+        let min_size = self
+            .options
+            .min_flush_bytes
+            .min(self.options.file_base_size);
+        let size = write_buffers
+            .iter()
+            .map(|b| b.allocated_size())
+            .sum::<usize>();
+        let group_id = write_buffers[0].group_id();
+        if size >= min_size
+            || version.buffer_set.is_flush_awaited(group_id)
+            || version.buffers_range().len() >= self.options.max_write_buffers
+        {
+            return false;
+        }
+
+        let max_delay = self.options.max_flush_delay;
+        match *deferred {
+            Some((id, since)) if id == group_id => since.elapsed() < max_delay,
+            _ => {
+                *deferred = Some((group_id, Instant::now()));
+                notify_flush_job_after(version, max_delay);
+                true
+            }
+        }
+    }
+
     /// Flush write buffers when user try to shutdown a page store.
     ///
     /// Note: it assumes that there no any inflights writers during shutdown.
@@ -99,43 +210,59 @@ impl<E: Env> FlushCtx<E> {
                 let _ = buffer.seal();
             }
             assert!(buffer.is_flushable());
-            self.flush_impl(&buffer, false)
+            self.flush_impl(&[buffer], false)
                 .await
                 .expect("TODO: flush write buffer");
         }
     }
 
-    #[inline]
-    async fn flush(&self, write_buffer: &WriteBuffer) -> Result<()> {
-        self.flush_impl(write_buffer, true).await
-    }
-
-    async fn flush_impl(&self, write_buffer: &WriteBuffer, wait: bool) -> Result<()> {
+    async fn flush_impl(&self, write_buffers: &[Arc<WriteBuffer>], wait: bool) -> Result<()> {
         let start_at = Instant::now();
-        let group_id = write_buffer.group_id();
-        let (dealloc_pages, page_group, file_info) = self.build_page_file(write_buffer).await?;
+        let output = self.build_page_file(write_buffers).await?;
+        self.install_page_file(write_buffers, output, start_at, wait)
+            .await
+    }
 
+    async fn install_page_file(
+        &self,
+        write_buffers: &[Arc<WriteBuffer>],
+        (dealloc_pages, page_groups, file_info): (Vec<u64>, FxHashMap<u32, PageGroup>, FileInfo),
+        start_at: Instant,
+        wait: bool,
+    ) -> Result<()> {
+        let group_ids = write_buffers
+            .iter()
+            .map(|b| b.group_id())
+            .collect::<Vec<_>>();
         let file_id = file_info.meta().file_id;
         let file_size = file_info.meta().file_size;
         info!(
-            "Flush page group {group_id} output file {file_id} with {file_size} bytes, \
+            "Flush page groups {group_ids:?} output file {file_id} with {file_size} bytes, \
                 {} active pages, {} dealloc pages, lasted {} microseconds",
-            page_group.num_active_pages(),
+            page_groups
+                .values()
+                .map(|g| g.num_active_pages())
+                .sum::<usize>(),
             dealloc_pages.len(),
             start_at.elapsed().as_micros()
         );
 
-        self.save_and_install_version(page_group, file_info, dealloc_pages, wait)
+        self.save_and_install_version(&group_ids, page_groups, file_info, dealloc_pages, wait)
             .await?;
+        self.job_stats
+            .record_flush(file_size as u64, start_at.elapsed());
 
-        write_buffer.on_flushed();
+        for write_buffer in write_buffers {
+            write_buffer.on_flushed();
+        }
 
         Ok(())
     }
 
     async fn save_and_install_version(
         &self,
-        page_group: PageGroup,
+        group_ids: &[u32],
+        new_page_groups: FxHashMap<u32, PageGroup>,
         file_info: FileInfo,
         dealloc_pages: Vec<u64>,
         wait_new_buffer: bool,
@@ -148,10 +275,11 @@ impl<E: Env> FlushCtx<E> {
             self.apply_dealloc_pages(&version, now, dealloc_pages.to_owned());
         let obsoleted_files = drain_obsoleted_files(&mut page_groups, &mut file_infos);
 
-        let group_id = page_group.meta().group_id;
         let file_id = file_info.meta().file_id;
-        if !page_group.is_empty() {
-            page_groups.insert(group_id, page_group);
+        for (group_id, page_group) in new_page_groups {
+            if !page_group.is_empty() {
+                page_groups.insert(group_id, page_group);
+            }
         }
         file_infos.insert(file_id, file_info);
 
@@ -163,7 +291,9 @@ impl<E: Env> FlushCtx<E> {
         // Release buffer permit and ensure the new buffer is installed, before install
         // new version.
         if wait_new_buffer {
-            version.buffer_set.release_permit_and_wait(group_id).await;
+            for &group_id in group_ids {
+                version.buffer_set.release_permit_and_wait(group_id).await;
+            }
         }
 
         let delta = DeltaVersion {
@@ -173,28 +303,60 @@ impl<E: Env> FlushCtx<E> {
             obsoleted_files,
         };
         // Safety: the mutable reference of [`Manifest`] is hold.
-        unsafe { self.version_owner.install(delta) };
+        unsafe {
+            self.version_owner
+                .install_flushed(delta, group_ids.len() as u32)
+        };
         Ok(())
     }
 
-    /// Flush [`WriteBuffer`] to files and returns dealloc pages.
+    /// Allocates a file id for the [`WriteBuffer`]s up to `last_group_id`.
+    async fn alloc_file_id(&self, last_group_id: u32) -> Result<u32> {
+        let mut lock = self.manifest.lock().await;
+        // The next buffer id is recovered from the max file id, so the file id must not
+        // be less than the ids of the page groups in it.
+        let mut file_id = lock.next_file_id()?;
+        while file_id < last_group_id {
+            file_id = lock.next_file_id()?;
+        }
+        Ok(file_id)
+    }
+
+    /// Flush [`WriteBuffer`]s to one file and returns dealloc pages.
+    ///
+    /// Each [`WriteBuffer`] is flushed to a page group with the same id.
     async fn build_page_file(
         &self,
-        write_buffer: &WriteBuffer,
-    ) -> Result<(Vec<u64>, PageGroup, FileInfo)> {
-        assert!(write_buffer.is_flushable());
+        write_buffers: &[Arc<WriteBuffer>],
+    ) -> Result<(Vec<u64>, FxHashMap<u32, PageGroup>, FileInfo)> {
+        let last_group_id = write_buffers[write_buffers.len() - 1].group_id();
+        let file_id = self.alloc_file_id(last_group_id).await?;
+        self.build_page_file_with_id(write_buffers, file_id).await
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(group_id = write_buffers[0].group_id(), num_buffers = write_buffers.len())
+        )
+    )]
+    async fn build_page_file_with_id(
+        &self,
+        write_buffers: &[Arc<WriteBuffer>],
+        file_id: u32,
+    ) -> Result<(Vec<u64>, FxHashMap<u32, PageGroup>, FileInfo)> {
+        assert!(write_buffers.iter().all(|b| b.is_flushable()));
 
         let mut flush_stats = FlushPageStats::default();
         let (dealloc_pages, skip_pages) =
-            collect_dealloc_pages_and_stats(write_buffer, &mut flush_stats);
+            collect_dealloc_pages_and_stats(write_buffers, &mut flush_stats);
 
-        let group_id = write_buffer.group_id();
-        info!("Flush write buffer {group_id} to file, {flush_stats}");
+        let first_group_id = write_buffers[0].group_id();
+        let last_group_id = write_buffers[write_buffers.len() - 1].group_id();
+        info!("Flush write buffers {first_group_id}..={last_group_id} to file, {flush_stats}");
 
-        let file_id = {
-            let mut lock = self.manifest.lock().await;
-            lock.next_file_id()
-        };
         let mut builder = self
             .page_files
             .new_file_builder(
@@ -203,35 +365,37 @@ impl<E: Env> FlushCtx<E> {
                 self.options.page_checksum_type,
             )
             .await?;
-        let mut group_builder = builder.add_page_group(group_id);
         let mut write_bytes = 0;
         let mut discard_bytes = 0;
-        for (page_addr, header, record_ref) in write_buffer.iter() {
-            if let RecordRef::Page(page) = record_ref {
-                if header.is_tombstone() || skip_pages.contains(&(page_addr as u32)) {
-                    discard_bytes += header.page_size();
-                    continue;
+        for (write_buffer, dealloc_pages) in write_buffers.iter().zip(&dealloc_pages) {
+            let mut group_builder = builder.add_page_group(write_buffer.group_id());
+            let write_buffer = write_buffer.try_into_sealed().expect("must be flushable");
+            for (page_addr, header, record_ref) in write_buffer.iter() {
+                if let RecordRef::Page(page) = record_ref {
+                    if header.is_tombstone() || skip_pages.contains(&page_addr) {
+                        discard_bytes += header.page_size();
+                        continue;
+                    }
+                    let content = page.data();
+                    group_builder
+                        .add_page(header.page_id(), page_addr, page.info(), content)
+                        .await?;
+                    write_bytes += content.len();
+                    let _ = self.page_files.populate_cache(page_addr, content);
                 }
-                let content = page.data();
-                group_builder
-                    .add_page(header.page_id(), page_addr, page.info(), content)
-                    .await?;
-                write_bytes += content.len();
-                let _ = self.page_files.populate_cache(page_addr, content);
             }
+            group_builder.add_dealloc_pages(dealloc_pages);
+            builder = group_builder.finish().await?;
         }
-        group_builder.add_dealloc_pages(&dealloc_pages);
-        builder = group_builder.finish().await?;
         let (page_groups, file_info) = builder.finish(file_id).await?;
-        let page_group = page_groups
-            .get(&group_id)
-            .expect("FileBuilder ensure the existence of page group")
-            .clone();
+        debug_assert!(write_buffers
+            .iter()
+            .all(|b| page_groups.contains_key(&b.group_id())));
 
         self.job_stats.flush_write_bytes.add(write_bytes as u64);
         self.job_stats.flush_discard_bytes.add(discard_bytes as u64);
 
-        Ok((dealloc_pages, page_group, file_info))
+        Ok((dealloc_pages.concat(), page_groups, file_info))
     }
 
     fn apply_dealloc_pages(
@@ -311,44 +475,86 @@ fn drain_obsoleted_files(
     obsoleted_files
 }
 
+// Notifies the flush job once the delay passes. The job has no timer of its
+// own, so a thread sleeps until then.
+fn notify_flush_job_after(version: &Version, delay: Duration) {
+    let buffer_set = version.buffer_set.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(delay);
+        buffer_set.notify_flush_job();
+    });
+}
+
+/// Collects the [`WriteBuffer`]s that can be flushed into one file.
+///
+/// Small sealed [`WriteBuffer`]s, which come from bursts of manual flushes,
+/// are coalesced until the total size exceeds the `file_base_size`, to reduce
+/// the number of files and syncs.
+fn collect_flushable_buffers(
+    version: &Version,
+    write_buffer: Arc<WriteBuffer>,
+    options: &Options,
+) -> Vec<Arc<WriteBuffer>> {
+    let mut total_size = write_buffer.allocated_size();
+    let mut group_id = write_buffer.group_id();
+    let mut write_buffers = vec![write_buffer];
+    let current = version.buffer_set.current();
+    while let Some(buffer) = current.get(group_id + 1) {
+        total_size += buffer.allocated_size();
+        if !buffer.is_flushable() || total_size > options.file_base_size {
+            break;
+        }
+        write_buffers.push(buffer.clone());
+        group_id += 1;
+    }
+    write_buffers
+}
+
+/// Collects the pages deallocated by each [`WriteBuffer`] and the pages to
+/// skip.
+///
+/// Pages in the flushing [`WriteBuffer`]s are skipped if they are
+/// deallocated, other pages are returned for each [`WriteBuffer`].
 fn collect_dealloc_pages_and_stats(
-    write_buffer: &WriteBuffer,
+    write_buffers: &[Arc<WriteBuffer>],
     flush_stats: &mut FlushPageStats,
-) -> (Vec<u64>, FxHashSet<u32>) {
-    let file_id = write_buffer.group_id();
-    let mut dealloc_pages = Vec::new();
+) -> (Vec<Vec<u64>>, FxHashSet<u64>) {
+    let flushing_groups = write_buffers
+        .iter()
+        .map(|b| b.group_id())
+        .collect::<FxHashSet<_>>();
+    let mut dealloc_pages = Vec::with_capacity(write_buffers.len());
     let mut skip_pages = FxHashSet::default();
-    for (_, header, record_ref) in write_buffer.iter() {
-        flush_stats.num_records += 1;
-        if header.is_tombstone() {
-            flush_stats.num_tombstone_records += 1;
-        }
+    for write_buffer in write_buffers {
+        let mut pages = Vec::new();
+        let write_buffer = write_buffer.try_into_sealed().expect("must be flushable");
+        for (_, header, record_ref) in write_buffer.iter() {
+            flush_stats.num_records += 1;
+            if header.is_tombstone() {
+                flush_stats.num_tombstone_records += 1;
+            }
 
-        flush_stats.data_size += header.page_size();
-        if let RecordRef::DeallocPages(pages) = record_ref {
-            dealloc_pages.extend(
-                pages
-                    .as_slice()
-                    .iter()
-                    .filter(|&&v| (v >> 32) as u32 != file_id),
-            );
-            skip_pages.extend(
-                pages
-                    .as_slice()
-                    .iter()
-                    .filter(|&&v| (v >> 32) as u32 == file_id)
-                    .map(|&v| v as u32),
-            );
+            flush_stats.data_size += header.page_size();
+            if let RecordRef::DeallocPages(record) = record_ref {
+                for &addr in record.as_slice() {
+                    if flushing_groups.contains(&((addr >> 32) as u32)) {
+                        skip_pages.insert(addr);
+                    } else {
+                        pages.push(addr);
+                    }
+                }
+            }
         }
+        flush_stats.num_dealloc_pages += pages.len();
+        dealloc_pages.push(pages);
     }
 
     flush_stats.num_skip_pages = skip_pages.len();
-    flush_stats.num_dealloc_pages = dealloc_pages.len();
 
     (dealloc_pages, skip_pages)
 }
 
-pub(super) fn version_snapshot(version: &Version) -> VersionEdit {
+pub(crate) fn version_snapshot(version: &Version) -> VersionEdit {
     let new_files: Vec<NewFile> = version
         .file_infos()
         .values()
@@ -364,6 +570,7 @@ pub(super) fn version_snapshot(version: &Version) -> VersionEdit {
     };
     VersionEdit {
         file_stream: Some(stream),
+        metadata: Vec::new(),
     }
 }
 
@@ -376,21 +583,26 @@ fn make_flush_version_edit(file_id: u32, obsoleted_files: &FxHashSet<u32>) -> Ve
     };
     VersionEdit {
         file_stream: Some(stream),
+        metadata: Vec::new(),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{path::Path, sync::Arc};
+    use std::{
+        path::Path,
+        sync::Arc,
+        time::{Duration, Instant},
+    };
 
-    use super::FlushCtx;
+    use super::{collect_flushable_buffers, FlushCtx};
     use crate::{
         env::Photon,
         page_store::{
             version::{DeltaVersion, Version, VersionOwner},
             Manifest, PageFiles, WriteBuffer,
         },
-        util::shutdown::ShutdownNotifier,
+        util::{pause::Pause, shutdown::ShutdownNotifier},
         PageStoreOptions,
     };
 
@@ -407,6 +619,7 @@ mod tests {
         FlushCtx {
             options: opt.to_owned(),
             shutdown,
+            pause: Pause::default(),
             job_stats: Arc::default(),
             version_owner,
             page_files: Arc::new(PageFiles::new(Photon, base, &opt).await.unwrap()),
@@ -425,7 +638,9 @@ mod tests {
             let (addr, _, _) = wb.alloc_page(1, 123, false).unwrap();
             wb.dealloc_pages(&[addr], false).unwrap();
             wb.seal().unwrap();
-            let (deleted_pages, file_info, _) = ctx.build_page_file(&wb).await.unwrap();
+            let (deleted_pages, page_groups, _) =
+                ctx.build_page_file(&[Arc::new(wb)]).await.unwrap();
+            let file_info = &page_groups[&1];
             assert!(deleted_pages.is_empty());
             assert!(!file_info.is_page_active(addr));
             assert!(file_info.get_page_handle(addr).is_none())
@@ -441,10 +656,219 @@ mod tests {
             let (addr, header, _) = wb.alloc_page(1, 123, false).unwrap();
             header.set_tombstone();
             wb.seal().unwrap();
-            let (deleted_pages, file_info, _) = ctx.build_page_file(&wb).await.unwrap();
+            let (deleted_pages, page_groups, _) =
+                ctx.build_page_file(&[Arc::new(wb)]).await.unwrap();
+            let file_info = &page_groups[&1];
             assert!(deleted_pages.is_empty());
             assert!(!file_info.is_page_active(addr));
             assert!(file_info.get_page_handle(addr).is_none());
         }
     }
+
+    #[photonio::test]
+    async fn flush_write_buffers_into_one_file() {
+        let base = tempdir::TempDir::new("flush_write_buffers_into_one_file").unwrap();
+        let ctx = new_flush_ctx(base.path()).await;
+        let mut write_buffers = Vec::new();
+        let mut page_addrs = Vec::new();
+        for group_id in 1..=3 {
+            let wb = WriteBuffer::with_capacity(group_id, 1 << 16);
+            unsafe {
+                let (addr, _, _) = wb.alloc_page(group_id as u64, 123, false).unwrap();
+                page_addrs.push(addr);
+                if group_id == 3 {
+                    // Deallocate a page flushed in the same file.
+                    wb.dealloc_pages(&[page_addrs[0]], false).unwrap();
+                }
+            }
+            wb.seal().unwrap();
+            write_buffers.push(Arc::new(wb));
+        }
+
+        let (deleted_pages, page_groups, file_info) =
+            ctx.build_page_file(&write_buffers).await.unwrap();
+        assert!(deleted_pages.is_empty());
+        assert_eq!(page_groups.len(), 3);
+        let file_id = file_info.meta().file_id;
+        assert!(file_id >= 3);
+        for (i, addr) in page_addrs.into_iter().enumerate() {
+            let page_group = &page_groups[&(i as u32 + 1)];
+            assert_eq!(page_group.meta().file_id, file_id);
+            if i == 0 {
+                assert!(page_group.get_page_handle(addr).is_none());
+            } else {
+                assert!(page_group.is_page_active(addr));
+                assert!(page_group.get_page_handle(addr).is_some());
+            }
+        }
+    }
+
+    #[photonio::test]
+    async fn flush_write_buffers_concurrently() {
+        let base = tempdir::TempDir::new("flush_write_buffers_concurrently").unwrap();
+        let mut ctx = new_flush_ctx(base.path()).await;
+        let notifier = ShutdownNotifier::default();
+        ctx.shutdown = notifier.subscribe();
+        ctx.options.flush_concurrency = 4;
+        // Flush each write buffer into its own file.
+        ctx.options.file_base_size = 1;
+
+        let version_owner = ctx.version_owner.clone();
+        let version = version_owner.current();
+        for group_id in 1..=6 {
+            {
+                let current = version.buffer_set.current();
+                let buf = current.last_writer_buffer();
+                unsafe { buf.alloc_page(group_id as u64, 64, false) }.unwrap();
+                buf.seal().unwrap();
+            }
+            let buf = WriteBuffer::with_capacity(group_id + 1, 1 << 16);
+            version.buffer_set.install(Arc::new(buf)).unwrap();
+        }
+        drop(version);
+
+        let handle = photonio::task::spawn(ctx.run());
+        while version_owner.current().buffers_range().start <= 6 {
+            photonio::task::yield_now().await;
+        }
+
+        let version = version_owner.current();
+        assert_eq!(version.buffers_range(), 7..8);
+        assert_eq!(version.file_infos().len(), 6);
+        let mut last_file_id = 0;
+        for group_id in 1..=6 {
+            let file_id = version.page_groups()[&group_id].meta().file_id;
+            assert!(version.file_infos().contains_key(&file_id));
+            assert!(file_id > last_file_id);
+            last_file_id = file_id;
+        }
+        drop(version);
+
+        notifier.terminate();
+        handle.await.unwrap_or_default();
+    }
+
+    #[photonio::test]
+    async fn defer_flushing_small_buffers() {
+        let base = tempdir::TempDir::new("defer_flushing_small_buffers").unwrap();
+        let mut ctx = new_flush_ctx(base.path()).await;
+        let notifier = ShutdownNotifier::default();
+        ctx.shutdown = notifier.subscribe();
+
+        let version_owner = ctx.version_owner.clone();
+        let seal_and_switch = |group_id: u32| {
+            let version = version_owner.current();
+            {
+                let current = version.buffer_set.current();
+                let buf = current.last_writer_buffer();
+                unsafe { buf.alloc_page(group_id as u64, 64, false) }.unwrap();
+                buf.seal().unwrap();
+            }
+            let buf = WriteBuffer::with_capacity(group_id + 1, 1 << 16);
+            version.buffer_set.install(Arc::new(buf)).unwrap();
+            version.buffer_set.notify_flush_job();
+        };
+        seal_and_switch(1);
+        // Three buffers are needed to reach the minimum.
+        let buffer_size = version_owner.current().min_write_buffer().allocated_size();
+        ctx.options.min_flush_bytes = buffer_size * 3;
+        seal_and_switch(2);
+
+        let handle = photonio::task::spawn(ctx.run());
+        for _ in 0..100 {
+            photonio::task::yield_now().await;
+        }
+        let version = version_owner.current();
+        assert_eq!(version.buffers_range(), 1..4);
+        assert!(version.file_infos().is_empty());
+        drop(version);
+
+        seal_and_switch(3);
+        while version_owner.current().buffers_range().start <= 3 {
+            photonio::task::yield_now().await;
+        }
+        let version = version_owner.current();
+        assert_eq!(version.buffers_range(), 4..5);
+        assert_eq!(version.file_infos().len(), 1);
+        let file_id = version.page_groups()[&1].meta().file_id;
+        for group_id in 2..=3 {
+            assert_eq!(version.page_groups()[&group_id].meta().file_id, file_id);
+        }
+        drop(version);
+
+        notifier.terminate();
+        handle.await.unwrap_or_default();
+    }
+
+    #[photonio::test]
+    async fn flush_small_buffers_after_max_delay() {
+        let base = tempdir::TempDir::new("flush_small_buffers_after_max_delay").unwrap();
+        let mut ctx = new_flush_ctx(base.path()).await;
+        let notifier = ShutdownNotifier::default();
+        ctx.shutdown = notifier.subscribe();
+
+        let version_owner = ctx.version_owner.clone();
+        let version = version_owner.current();
+        {
+            let current = version.buffer_set.current();
+            let buf = current.last_writer_buffer();
+            unsafe { buf.alloc_page(1, 64, false) }.unwrap();
+            buf.seal().unwrap();
+        }
+        let buf = WriteBuffer::with_capacity(2, 1 << 16);
+        version.buffer_set.install(Arc::new(buf)).unwrap();
+        version.buffer_set.notify_flush_job();
+        // The buffer never reaches the minimum, but it is flushed once it is
+        // held back for the delay.
+        let buffer_size = version.min_write_buffer().allocated_size();
+        ctx.options.min_flush_bytes = buffer_size * 2;
+        ctx.options.max_flush_delay = Duration::from_millis(50);
+        drop(version);
+
+        let start_at = Instant::now();
+        let handle = photonio::task::spawn(ctx.run());
+        while version_owner.current().buffers_range().start <= 1 {
+            photonio::task::yield_now().await;
+        }
+        assert!(start_at.elapsed() >= Duration::from_millis(50));
+        let version = version_owner.current();
+        assert_eq!(version.buffers_range(), 2..3);
+        assert_eq!(version.file_infos().len(), 1);
+        drop(version);
+
+        notifier.terminate();
+        handle.await.unwrap_or_default();
+    }
+
+    #[test]
+    fn collect_small_flushable_buffers() {
+        let version = Version::new(1 << 10, 1, 8, DeltaVersion::default());
+        for group_id in 1..=4 {
+            {
+                let current = version.buffer_set.current();
+                let buf = current.last_writer_buffer();
+                assert_eq!(buf.group_id(), group_id);
+                unsafe { buf.alloc_page(group_id as u64, 64, false) }.unwrap();
+                if group_id == 4 {
+                    break;
+                }
+                buf.seal().unwrap();
+            }
+            let buf = WriteBuffer::with_capacity(group_id + 1, 1 << 10);
+            version.buffer_set.install(Arc::new(buf)).unwrap();
+        }
+
+        // The active buffer is not flushed.
+        let opt = PageStoreOptions::default();
+        let write_buffers = collect_flushable_buffers(&version, version.min_write_buffer(), &opt);
+        let group_ids: Vec<_> = write_buffers.iter().map(|b| b.group_id()).collect();
+        assert_eq!(group_ids, vec![1, 2, 3]);
+
+        let opt = PageStoreOptions {
+            file_base_size: 128,
+            ..Default::default()
+        };
+        let write_buffers = collect_flushable_buffers(&version, version.min_write_buffer(), &opt);
+        assert_eq!(write_buffers.len(), 1);
+    }
 }
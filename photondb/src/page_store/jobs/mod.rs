@@ -4,5 +4,6 @@ pub(crate) mod cleanup;
 pub(crate) mod flush;
 pub(crate) mod reclaim;
 
-use flush::version_snapshot;
-pub(crate) use reclaim::wait_for_reclaiming;
+pub(crate) use flush::version_snapshot;
+pub use reclaim::GcPlan;
+pub(crate) use reclaim::{plan_reclaiming, wait_for_reclaiming};
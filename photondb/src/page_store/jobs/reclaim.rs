@@ -7,14 +7,17 @@ use crate::{
     env::Env,
     page::PageRef,
     page_store::{
-        page_file::{FileBuilder, FileMetaHolder, FileReader, PageGroupBuilder},
+        page_file::{FileBuilder, FileFormat, FileMetaHolder, FileReader, PageGroupBuilder},
         stats::AtomicJobStats,
         strategy::ReclaimPickStrategy,
         version::{DeltaVersion, VersionOwner, VersionUpdateReason},
         FileInfo, Manifest, NewFile, Options, PageFiles, PageGroup, Result, StrategyBuilder,
         StreamEdit, Version, VersionEdit,
     },
-    util::shutdown::{with_shutdown, Shutdown},
+    util::{
+        pause::Pause,
+        shutdown::{with_shutdown, Shutdown},
+    },
 };
 
 pub(crate) struct ReclaimCtx<E>
@@ -23,6 +26,7 @@ where
 {
     options: Options,
     shutdown: Shutdown,
+    pause: Pause,
 
     strategy_builder: Box<dyn StrategyBuilder>,
 
@@ -35,6 +39,18 @@ where
     job_stats: Arc<AtomicJobStats>,
 }
 
+/// The files that space reclaiming would compact under a version.
+#[non_exhaustive]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GcPlan {
+    /// The ids of the files to compact, in the order they would be picked.
+    pub files: Vec<u32>,
+    /// The size of the active pages that would be rewritten.
+    pub rewrite_bytes: u64,
+    /// The estimated space that would be reclaimed.
+    pub reclaim_bytes: u64,
+}
+
 #[derive(Debug)]
 struct ReclaimJobBuilder {
     target_file_base: usize,
@@ -85,6 +101,7 @@ where
     pub(crate) fn new(
         options: Options,
         shutdown: Shutdown,
+        pause: Pause,
         strategy_builder: Box<dyn StrategyBuilder>,
         page_files: Arc<PageFiles<E>>,
         version_owner: Arc<VersionOwner>,
@@ -94,6 +111,7 @@ where
         ReclaimCtx {
             options,
             shutdown,
+            pause,
             strategy_builder,
             page_files,
             version_owner,
@@ -105,6 +123,12 @@ where
 
     pub(crate) async fn run(mut self, mut version: Arc<Version>) {
         loop {
+            if with_shutdown(&mut self.shutdown, self.pause.wait_resumed())
+                .await
+                .is_none()
+            {
+                break;
+            }
             if !self.options.disable_space_reclaiming {
                 self.reclaim(&version).await;
                 version.reclaimed();
@@ -122,6 +146,13 @@ where
         let mut progress = ReclaimProgress::new(&self.options, version, &cleaned_files);
         progress.trace_log();
         if !progress.is_reclaimable() {
+            // There is no space to reclaim, take the chance to upgrade files.
+            if let Err(err) = self
+                .upgrade_outdated_files(&mut progress, version, &cleaned_files)
+                .await
+            {
+                error!("Upgrade files: {err:?}");
+            }
             return;
         }
 
@@ -155,6 +186,7 @@ where
             }
 
             if self.shutdown.is_terminated()
+                || self.pause.is_paused()
                 || version.has_next_version()
                 || !progress.is_reclaimable()
             {
@@ -164,6 +196,46 @@ where
         Ok(())
     }
 
+    /// Rewrites a batch of files written in outdated formats into a file in
+    /// the current format.
+    ///
+    /// The batch is about [`Options::file_base_size`], the remaining files
+    /// are upgraded on later versions.
+    async fn upgrade_outdated_files(
+        &mut self,
+        progress: &mut ReclaimProgress,
+        version: &Arc<Version>,
+        cleaned_files: &FxHashSet<u32>,
+    ) -> Result<()> {
+        let file_infos = version.file_infos();
+        let mut outdated_files = file_infos
+            .iter()
+            .filter(|(id, info)| {
+                info.meta().format < FileFormat::CURRENT && !cleaned_files.contains(id)
+            })
+            .map(|(&id, _)| id)
+            .collect::<Vec<_>>();
+        if outdated_files.is_empty() {
+            return Ok(());
+        }
+        outdated_files.sort_unstable();
+
+        let mut victims = FxHashSet::default();
+        let mut victims_size = 0;
+        for id in outdated_files {
+            victims.insert(id);
+            victims_size += compute_effective_size(&file_infos[&id], version.page_groups());
+            if victims_size >= self.options.file_base_size as u64 {
+                break;
+            }
+        }
+        info!(
+            "Upgrade files {victims:?} to format {:?}",
+            FileFormat::CURRENT
+        );
+        self.reclaim_files(progress, version, victims).await
+    }
+
     async fn reclaim_files(
         &mut self,
         progress: &mut ReclaimProgress,
@@ -172,12 +244,12 @@ where
     ) -> Result<()> {
         let file_id = {
             let mut lock = self.manifest.lock().await;
-            lock.next_file_id()
+            lock.next_file_id()?
         };
 
         let file_infos = version.file_infos();
         let page_groups = version.page_groups();
-        let (page_groups, file_info) = self
+        let (page_groups, file_info, victims) = self
             .compact_files(progress, file_id, file_infos, page_groups, &victims)
             .await?;
 
@@ -223,6 +295,10 @@ where
 
     /// Compact a set of files into a new file, and release mark the compacted
     /// files as obsoleted to reclaim space.
+    ///
+    /// The files are compacted in the order of ids. If the job is shut down or
+    /// paused in between, the remaining files are left to later compactions.
+    /// Returns the ids of the compacted files.
     async fn compact_files(
         &mut self,
         progress: &mut ReclaimProgress,
@@ -230,7 +306,7 @@ where
         file_infos: &FxHashMap<u32, FileInfo>,
         page_groups: &FxHashMap<u32, PageGroup>,
         victims: &FxHashSet<u32>,
-    ) -> Result<(FxHashMap<u32, PageGroup>, FileInfo)> {
+    ) -> Result<(FxHashMap<u32, PageGroup>, FileInfo, FxHashSet<u32>)> {
         let start_at = Instant::now();
         let mut builder = self
             .page_files
@@ -244,7 +320,11 @@ where
         victims.sort_unstable();
         let mut stats = CompactStats::default();
         let mut up2_sum = 0;
+        let mut compacted = 0;
         for &id in &victims {
+            if compacted > 0 && (self.shutdown.is_terminated() || self.pause.is_paused()) {
+                break;
+            }
             let info = file_infos.get(&id).expect("Victim must exists");
             up2_sum += info.up2();
             builder = self
@@ -252,12 +332,14 @@ where
                 .await?;
             self.cleaned_files.insert(id);
             progress.track_file(info, page_groups);
+            compacted += 1;
         }
+        victims.truncate(compacted);
 
         // When we include the page in a new segment that contains re-written pages from
         // other segments, the value for up2 for the new segment is the average up2 for
         // all pages written to it.
-        let up2 = up2_sum / (victims.len() as u32);
+        let up2 = up2_sum / (compacted as u32);
         let (page_groups, file_info) = builder.finish(up2).await?;
 
         let elapsed = start_at.elapsed().as_micros();
@@ -280,7 +362,7 @@ where
                     latest {elapsed} microseconds"
         );
 
-        Ok((page_groups, file_info))
+        Ok((page_groups, file_info, victims.into_iter().collect()))
     }
 
     async fn compact_file<'a>(
@@ -418,17 +500,7 @@ impl ReclaimProgress {
 
     fn track_file(&mut self, file: &FileInfo, page_files: &FxHashMap<u32, PageGroup>) {
         self.used_space = self.used_space.saturating_sub(file.meta().file_size as u64);
-        let effective_size = file
-            .meta()
-            .page_groups
-            .keys()
-            .map(|id| {
-                page_files
-                    .get(id)
-                    .map(PageGroup::effective_size)
-                    .unwrap_or_default()
-            })
-            .sum::<usize>() as u64;
+        let effective_size = compute_effective_size(file, page_files);
         self.base_size = self.base_size.saturating_sub(effective_size);
         self.additional_size = self.used_space.saturating_sub(self.base_size);
     }
@@ -516,6 +588,67 @@ pub(crate) async fn wait_for_reclaiming(options: &Options, mut version: Arc<Vers
     }
 }
 
+/// Plans the files to reclaim under the version, in the same way as the
+/// reclaim job, but without any I/O.
+///
+/// The plan stops once the space would be reclaimable no more. The job may
+/// pick different files if the version changes before it runs.
+pub(crate) fn plan_reclaiming(
+    options: &Options,
+    strategy_builder: &dyn StrategyBuilder,
+    version: &Version,
+    now: u32,
+) -> GcPlan {
+    let mut plan = GcPlan::default();
+    if options.disable_space_reclaiming {
+        return plan;
+    }
+
+    let mut progress = ReclaimProgress::new(options, version, &FxHashSet::default());
+    if !progress.is_reclaimable() {
+        return plan;
+    }
+
+    let page_groups = version.page_groups();
+    let file_infos = version.file_infos();
+    let mut strategy = strategy_builder.build(now);
+    for file in file_infos.values() {
+        strategy.collect_file(page_groups, file);
+    }
+    let mut builder = ReclaimJobBuilder::new(options.file_base_size);
+    while let Some((file, active_size)) = strategy.apply() {
+        if let Some(ReclaimJob::Compact(victims)) = builder.add(file, active_size) {
+            let mut victims = victims.into_iter().collect::<Vec<_>>();
+            victims.sort_unstable();
+            for id in victims {
+                let info = file_infos.get(&id).expect("Victim must exists");
+                let effective_size = compute_effective_size(info, page_groups);
+                plan.files.push(id);
+                plan.rewrite_bytes += effective_size;
+                plan.reclaim_bytes += (info.meta().file_size as u64).saturating_sub(effective_size);
+                progress.track_file(info, page_groups);
+            }
+        }
+        if !progress.is_reclaimable() {
+            break;
+        }
+    }
+    plan
+}
+
+fn compute_effective_size(file: &FileInfo, page_groups: &FxHashMap<u32, PageGroup>) -> u64 {
+    file.meta()
+        .page_groups
+        .keys()
+        .map(|id| {
+            page_groups
+                .get(id)
+                .map(PageGroup::effective_size)
+                .unwrap_or_default()
+        })
+        .sum::<usize>() as u64
+}
+
 fn compute_base_size(
     page_files: &FxHashMap<u32, PageGroup>,
     cleaned_files: &FxHashSet<u32>,
@@ -552,6 +685,7 @@ fn make_compact_version_edit(
             new_files,
             deleted_files,
         }),
+        metadata: Vec::new(),
     }
 }
 
@@ -593,6 +727,7 @@ mod tests {
         ReclaimCtx {
             options,
             shutdown,
+            pause: Pause::default(),
             strategy_builder,
             page_files,
             manifest,
@@ -610,11 +745,21 @@ mod tests {
         page_files: &PageFiles<Photon>,
         file_id: u32,
         pages: FxHashMap<u32, Vec<(u64, u64)>>,
+    ) -> (FxHashMap<u32, PageGroup>, FileInfo) {
+        build_file_with_format(page_files, file_id, pages, FileFormat::CURRENT).await
+    }
+
+    async fn build_file_with_format(
+        page_files: &PageFiles<Photon>,
+        file_id: u32,
+        pages: FxHashMap<u32, Vec<(u64, u64)>>,
+        format: FileFormat,
     ) -> (FxHashMap<u32, PageGroup>, FileInfo) {
         let mut builder = page_files
             .new_file_builder(file_id, Compression::ZSTD, ChecksumType::CRC32)
             .await
-            .unwrap();
+            .unwrap()
+            .with_format(format);
         for (id, pages) in pages {
             let mut file_builder = builder.add_page_group(id);
             for (page_id, page_addr) in pages {
@@ -656,10 +801,11 @@ mod tests {
         let victims = HashSet::from_iter(vec![m1, m2].into_iter());
         let version = ctx.version_owner.current();
         let mut progress = ReclaimProgress::new(&ctx.options, &version, &HashSet::default());
-        let (virtual_infos, m3_info) = ctx
+        let (virtual_infos, m3_info, compacted) = ctx
             .compact_files(&mut progress, m3, &map_files, &page_files, &victims)
             .await
             .unwrap();
+        assert_eq!(compacted, victims);
 
         assert!(virtual_infos.contains_key(&f1));
         assert!(virtual_infos.contains_key(&f2));
@@ -686,6 +832,161 @@ mod tests {
         assert!(base_size < used_size);
     }
 
+    #[photonio::test]
+    async fn plan_files_reclaiming() {
+        let root = TempDir::new("plan_files_reclaiming").unwrap();
+        let root = root.into_path();
+
+        let ctx = build_reclaim_ctx(&root).await;
+
+        let (f1, f2, f3, f4) = (1, 2, 3, 4);
+        let (m1, m2, m3) = (1, 2, 3);
+        let mut pages = FxHashMap::default();
+        pages.insert(f1, vec![(1, pa(f1, 16)), (2, pa(f1, 32)), (3, pa(f1, 64))]);
+        pages.insert(f2, vec![(4, pa(f2, 16)), (5, pa(f2, 32)), (6, pa(f2, 64))]);
+        let (virtual_infos, m1_info) = build_file(&ctx.page_files, m1, pages).await;
+        let mut page_groups = virtual_infos;
+
+        let mut pages = FxHashMap::default();
+        pages.insert(f3, vec![(7, pa(f3, 16)), (8, pa(f3, 32)), (9, pa(f3, 64))]);
+        pages.insert(f4, vec![(1, pa(f4, 16)), (2, pa(f4, 32)), (3, pa(f4, 64))]);
+        let (virtual_infos, m2_info) = build_file(&ctx.page_files, m2, pages).await;
+        page_groups.extend(virtual_infos.into_iter());
+
+        // Most pages of `m1` are deallocated, while `m2` has only one garbage page.
+        let garbage = [(f1, 16), (f1, 32), (f2, 16), (f2, 32), (f2, 64), (f3, 16)];
+        for (group_id, offset) in garbage {
+            let addr = pa(group_id, offset);
+            let group = page_groups.get_mut(&group_id).unwrap();
+            assert!(group.deactivate_page(addr));
+        }
+        let m1_size = m1_info.meta().file_size as u64;
+        let m1_active_size = [f1, f2]
+            .iter()
+            .map(|id| page_groups[id].effective_size())
+            .sum::<usize>() as u64;
+        assert!(m1_active_size > 0);
+
+        let mut file_infos = FxHashMap::default();
+        file_infos.insert(m1, m1_info);
+        file_infos.insert(m2, m2_info);
+        let delta = DeltaVersion {
+            reason: VersionUpdateReason::Flush,
+            page_groups,
+            file_infos,
+            ..Default::default()
+        };
+        // No concurrent operations.
+        unsafe { ctx.version_owner.install(delta) };
+        let version = ctx.version_owner.current();
+
+        let options = Options {
+            file_base_size: 1,
+            max_space_amplification_percent: 10,
+            ..ctx.options.clone()
+        };
+        let plan = plan_reclaiming(&options, &MinDeclineRateStrategyBuilder, &version, m3);
+        assert_eq!(plan.files, vec![m1]);
+        assert_eq!(plan.rewrite_bytes, m1_active_size);
+        assert_eq!(plan.reclaim_bytes, m1_size - m1_active_size);
+
+        // Planning has no side effects.
+        let version = ctx.version_owner.current();
+        assert_eq!(version.file_infos().len(), 2);
+        assert_eq!(
+            plan_reclaiming(&options, &MinDeclineRateStrategyBuilder, &version, m3),
+            plan
+        );
+
+        let options = Options {
+            disable_space_reclaiming: true,
+            ..options
+        };
+        let plan = plan_reclaiming(&options, &MinDeclineRateStrategyBuilder, &version, m3);
+        assert!(plan.files.is_empty());
+    }
+
+    #[photonio::test]
+    async fn files_upgrading() {
+        let root = TempDir::new("files_upgrading").unwrap();
+        let root = root.into_path();
+
+        let mut ctx = build_reclaim_ctx(&root).await;
+
+        let (f1, f2) = (1, 2);
+        let (m1, m2, m3) = (1, 2, 3);
+        {
+            let mut lock = ctx.manifest.lock().await;
+            lock.reset_next_file_id(m3);
+        }
+        let mut pages = FxHashMap::default();
+        pages.insert(f1, vec![(1, pa(f1, 16)), (2, pa(f1, 32))]);
+        let (mut page_groups, m1_info) = build_file(&ctx.page_files, m1, pages).await;
+
+        let mut pages = FxHashMap::default();
+        pages.insert(f2, vec![(3, pa(f2, 16)), (4, pa(f2, 32))]);
+        let legacy = FileFormat::Legacy;
+        let (virtual_infos, m2_info) =
+            build_file_with_format(&ctx.page_files, m2, pages, legacy).await;
+        page_groups.extend(virtual_infos.into_iter());
+
+        // Files in both formats are decoded.
+        for (file_id, group_id, format) in [(m1, f1, FileFormat::V1), (m2, f2, legacy)] {
+            let holder = ctx.page_files.read_file_meta(file_id).await.unwrap();
+            assert_eq!(holder.file_meta.format, format);
+            let handle = page_groups[&group_id]
+                .get_page_handle(pa(group_id, 32))
+                .unwrap();
+            let page = ctx
+                .page_files
+                .read_file_page(file_id, &holder.file_meta, handle)
+                .await
+                .unwrap();
+            assert_eq!(page, vec![0; 32]);
+        }
+
+        let mut file_infos = FxHashMap::default();
+        file_infos.insert(m1, m1_info);
+        file_infos.insert(m2, m2_info);
+        let delta = DeltaVersion {
+            reason: VersionUpdateReason::Flush,
+            page_groups,
+            file_infos,
+            ..Default::default()
+        };
+        // No concurrent operations.
+        unsafe { ctx.version_owner.install(delta) };
+        let version = ctx.version_owner.current();
+        let mut progress = ReclaimProgress::new(&ctx.options, &version, &HashSet::default());
+        ctx.upgrade_outdated_files(&mut progress, &version, &HashSet::default())
+            .await
+            .unwrap();
+
+        // Only the legacy file is rewritten.
+        let version = ctx.version_owner.current();
+        let file_infos = version.file_infos();
+        assert!(file_infos.contains_key(&m1));
+        assert!(!file_infos.contains_key(&m2));
+        let m3_meta = file_infos[&m3].meta();
+        assert_eq!(m3_meta.format, FileFormat::CURRENT);
+        let handle = version.page_groups()[&f2]
+            .get_page_handle(pa(f2, 32))
+            .unwrap();
+        let page = ctx
+            .page_files
+            .read_file_page(m3, m3_meta, handle)
+            .await
+            .unwrap();
+        assert_eq!(page, vec![0; 32]);
+
+        // Nothing is left to upgrade.
+        let mut progress = ReclaimProgress::new(&ctx.options, &version, &HashSet::default());
+        ctx.upgrade_outdated_files(&mut progress, &version, &HashSet::default())
+            .await
+            .unwrap();
+        assert!(!version.has_next_version());
+    }
+
     #[photonio::test]
     async fn files_reclaiming() {
         let root = TempDir::new("map_files_reclaiming").unwrap();
@@ -753,4 +1054,86 @@ mod tests {
         assert!(!map_files.contains_key(&m2));
         assert!(map_files.contains_key(&m3));
     }
+
+    #[photonio::test]
+    async fn files_reclaiming_resume() {
+        let root = TempDir::new("files_reclaiming_resume").unwrap();
+        let root = root.into_path();
+
+        let mut ctx = build_reclaim_ctx(&root).await;
+
+        let (f1, f2) = (1, 2);
+        let (m1, m2, m3, m4) = (1, 2, 3, 4);
+        {
+            let mut lock = ctx.manifest.lock().await;
+            lock.reset_next_file_id(m3);
+        }
+        let mut pages = FxHashMap::default();
+        pages.insert(f1, vec![(1, pa(f1, 16)), (2, pa(f1, 32))]);
+        let (mut page_groups, m1_info) = build_file(&ctx.page_files, m1, pages).await;
+
+        let mut pages = FxHashMap::default();
+        pages.insert(f2, vec![(3, pa(f2, 16)), (4, pa(f2, 32))]);
+        let (virtual_infos, m2_info) = build_file(&ctx.page_files, m2, pages).await;
+        page_groups.extend(virtual_infos.into_iter());
+        let f2_size = page_groups[&f2].meta().total_page_size() as u64;
+
+        let mut file_infos = FxHashMap::default();
+        file_infos.insert(m1, m1_info);
+        file_infos.insert(m2, m2_info);
+        let delta = DeltaVersion {
+            reason: VersionUpdateReason::Flush,
+            page_groups,
+            file_infos,
+            ..Default::default()
+        };
+        // No concurrent operations.
+        unsafe { ctx.version_owner.install(delta) };
+
+        // The job is paused in the middle, so only the first file is compacted.
+        ctx.pause.pause();
+        let version = ctx.version_owner.current();
+        let mut progress = ReclaimProgress::new(&ctx.options, &version, &HashSet::default());
+        let victims = HashSet::from_iter([m1, m2]);
+        ctx.reclaim_files(&mut progress, &version, victims)
+            .await
+            .unwrap();
+        let version = ctx.version_owner.current();
+        let file_infos = version.file_infos();
+        assert!(!file_infos.contains_key(&m1));
+        assert!(file_infos.contains_key(&m2));
+        assert!(file_infos.contains_key(&m3));
+
+        // Restart from the manifest, which only keeps the file not compacted
+        // yet, and compact it without compacting the first file again.
+        let mut manifest = Manifest::open(Photon, &root).await.unwrap();
+        let mut live_files = HashSet::default();
+        for edit in manifest.list_versions().await.unwrap() {
+            if let Some(stream) = edit.file_stream {
+                live_files.extend(stream.new_files.iter().map(|file| file.id));
+                for id in &stream.deleted_files {
+                    live_files.remove(id);
+                }
+            }
+        }
+        assert_eq!(live_files, HashSet::from_iter([m2, m3]));
+        manifest.reset_next_file_id(m4);
+        ctx.manifest = Arc::new(futures::lock::Mutex::new(manifest));
+        ctx.pause.resume();
+        let input_bytes = ctx.job_stats.compact_input_bytes.get();
+        let mut progress = ReclaimProgress::new(&ctx.options, &version, &HashSet::default());
+        live_files.remove(&m3);
+        ctx.reclaim_files(&mut progress, &version, live_files)
+            .await
+            .unwrap();
+        assert_eq!(
+            ctx.job_stats.compact_input_bytes.get() - input_bytes,
+            f2_size
+        );
+        let version = ctx.version_owner.current();
+        let file_infos = version.file_infos();
+        assert!(!file_infos.contains_key(&m2));
+        assert!(file_infos.contains_key(&m3));
+        assert!(file_infos.contains_key(&m4));
+    }
 }
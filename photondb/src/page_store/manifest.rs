@@ -1,14 +1,18 @@
-use std::{io::ErrorKind, path::PathBuf};
+use std::{collections::BTreeMap, io::ErrorKind, path::PathBuf};
 
 use prost::Message;
 
-use super::{meta::VersionEdit, Error};
+use super::{
+    meta::{MetadataEntry, VersionEdit},
+    Error,
+};
 use crate::{env::*, page_store::Result};
 
 const CURRENT_FILE_NAME: &str = "CURRENT";
 const MANIFEST_FILE_NAME: &str = "MANIFEST";
 const TEMPFILE_SUFFIX: &str = "tmpdb";
 const MAX_MANIFEST_SIZE: u64 = 128 << 20; // 128 MiB
+const MAX_METADATA_SIZE: usize = 64 << 10; // 64 KiB
 
 pub(crate) struct Manifest<E: Env> {
     env: E,
@@ -21,6 +25,11 @@ pub(crate) struct Manifest<E: Env> {
 
     current_file_num: Option<u32>,
     current_writer: Option<ManifestWriter<E::SequentialWriter>>,
+
+    // The user metadata, which is written into the snapshot of each rolled
+    // manifest file.
+    metadata: BTreeMap<Vec<u8>, Vec<u8>>,
+    metadata_size: usize,
 }
 
 struct ManifestWriter<W> {
@@ -32,9 +41,35 @@ impl<E: Env> Manifest<E> {
     // Open manifest in specified folder.
     // it will reopen manifest by find CURRENT and do some cleanup.
     pub(crate) async fn open(env: E, base: impl Into<PathBuf>) -> Result<Self> {
-        let base = base.into();
+        let mut manifest = Self::new(env, base.into());
+        manifest.create_base_dir_if_not_exist().await?;
+        manifest.current_file_num = manifest.load_current().await?;
+        manifest.open_base_dir().await?;
+        manifest.cleanup_obsolete_files().await?;
 
-        let mut manifest = Self {
+        Ok(manifest)
+    }
+
+    /// Opens the manifest in the folder without modifying anything, so that
+    /// versions can be listed from a folder owned by others.
+    pub(crate) async fn open_read_only(env: E, base: impl Into<PathBuf>) -> Result<Self> {
+        let mut manifest = Self::new(env, base.into());
+        manifest.current_file_num = manifest.load_current().await?;
+        manifest.open_base_dir().await?;
+        Ok(manifest)
+    }
+
+    /// Returns true if a manifest exists in the folder.
+    pub(crate) async fn exists(env: &E, base: impl Into<PathBuf>) -> Result<bool> {
+        match env.metadata(base.into().join(CURRENT_FILE_NAME)).await {
+            Ok(_) => Ok(true),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn new(env: E, base: PathBuf) -> Self {
+        Self {
             env,
             base,
             base_dir: None,
@@ -42,13 +77,28 @@ impl<E: Env> Manifest<E> {
             next_file_id: 0,
             current_file_num: Default::default(),
             current_writer: None,
-        };
-        manifest.create_base_dir_if_not_exist().await?;
-        manifest.current_file_num = manifest.load_current().await?;
-        manifest.open_base_dir().await?;
-        manifest.cleanup_obsolete_files().await?;
+            metadata: BTreeMap::new(),
+            metadata_size: 0,
+        }
+    }
 
-        Ok(manifest)
+    /// Removes the CURRENT file, then all manifest files and temporary files.
+    ///
+    /// The CURRENT file is removed first, so that the folder is treated as
+    /// empty even if the removal is interrupted.
+    pub(crate) async fn destroy(&mut self) -> Result<()> {
+        self.current_writer = None;
+        self.current_file_num = None;
+        let current_path = self.base.join(CURRENT_FILE_NAME);
+        match self.env.remove_file(&current_path).await {
+            Ok(_) => {}
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+        if let Some(base_dir) = self.base_dir.as_ref() {
+            base_dir.sync_all().await?;
+        }
+        self.cleanup_obsolete_files().await
     }
 
     async fn create_base_dir_if_not_exist(&self) -> Result<()> {
@@ -74,10 +124,13 @@ impl<E: Env> Manifest<E> {
         self.next_file_id = next_id;
     }
 
-    pub(crate) fn next_file_id(&mut self) -> u32 {
+    /// Allocates the next file id.
+    ///
+    /// Returns [`Error::OutOfFileIds`] if the file ids are exhausted.
+    pub(crate) fn next_file_id(&mut self) -> Result<u32> {
         let id = self.next_file_id;
-        self.next_file_id += 1;
-        id
+        self.next_file_id = id.checked_add(1).ok_or(Error::OutOfFileIds)?;
+        Ok(id)
     }
 
     pub(crate) fn now(&self) -> u32 {
@@ -116,7 +169,15 @@ impl<E: Env> Manifest<E> {
         let mut current = current.unwrap();
         let written = if rolled_path.is_some() {
             // TODO: remove new created file when write fail.
-            let base_snapshot = version_snapshot();
+            let mut base_snapshot = version_snapshot();
+            base_snapshot.metadata = self
+                .metadata
+                .iter()
+                .map(|(key, value)| MetadataEntry {
+                    key: key.clone(),
+                    value: value.clone(),
+                })
+                .collect();
             let base_written = VersionEditEncoder(base_snapshot)
                 .encode(&mut current.current_writer)
                 .await?;
@@ -160,6 +221,57 @@ impl<E: Env> Manifest<E> {
         Ok(())
     }
 
+    /// Returns the value of the user metadata of the key.
+    pub(crate) fn metadata(&self, key: &[u8]) -> Option<&[u8]> {
+        self.metadata.get(key).map(Vec::as_slice)
+    }
+
+    /// Sets the user metadata of the key, and records it as a version edit.
+    ///
+    /// Returns [`Error::TooLargeSize`] if the total size of the metadata
+    /// exceeds the limit.
+    pub(crate) async fn set_metadata(
+        &mut self,
+        key: &[u8],
+        value: &[u8],
+        version_snapshot: impl FnOnce() -> VersionEdit,
+    ) -> Result<()> {
+        let old_size = self.metadata.get(key).map_or(0, |v| key.len() + v.len());
+        let new_size = self.metadata_size - old_size + key.len() + value.len();
+        if new_size > MAX_METADATA_SIZE {
+            return Err(Error::TooLargeSize);
+        }
+
+        let entry = MetadataEntry {
+            key: key.to_vec(),
+            value: value.to_vec(),
+        };
+        let edit = VersionEdit {
+            file_stream: None,
+            metadata: vec![entry.clone()],
+        };
+        self.record_version_edit(edit, version_snapshot).await?;
+        self.apply_metadata(&[entry]);
+        Ok(())
+    }
+
+    /// Restores the user metadata from the version edits listed on open.
+    pub(super) fn recover_metadata(&mut self, edits: &[VersionEdit]) {
+        for edit in edits {
+            self.apply_metadata(&edit.metadata);
+        }
+    }
+
+    fn apply_metadata(&mut self, entries: &[MetadataEntry]) {
+        for entry in entries {
+            let size = entry.key.len() + entry.value.len();
+            if let Some(old) = self.metadata.insert(entry.key.clone(), entry.value.clone()) {
+                self.metadata_size -= entry.key.len() + old.len();
+            }
+            self.metadata_size += size;
+        }
+    }
+
     // List current versions.
     // the caller can recovery Versions by apply each version_edits.
     pub(crate) async fn list_versions(&self) -> Result<Vec<VersionEdit>> {
@@ -350,6 +462,17 @@ mod tests {
         ids.into_iter().map(Into::into).collect()
     }
 
+    #[photonio::test]
+    fn test_next_file_id_exhausted() {
+        let base = tempdir::TempDir::new("next_file_id_exhausted").unwrap();
+        let mut manifest = Manifest::open(crate::env::Photon, base.as_ref())
+            .await
+            .unwrap();
+        manifest.reset_next_file_id(u32::MAX - 1);
+        assert_eq!(manifest.next_file_id().unwrap(), u32::MAX - 1);
+        assert!(matches!(manifest.next_file_id(), Err(Error::OutOfFileIds)));
+    }
+
     #[photonio::test]
     fn test_cleanup_when_restart() {
         let env = crate::env::Photon;
@@ -368,6 +491,7 @@ mod tests {
                             new_files: new_files(vec![2, 3]),
                             deleted_files: vec![1],
                         }),
+                        metadata: Vec::new(),
                     },
                     version_snapshot,
                 )
@@ -381,6 +505,7 @@ mod tests {
                             new_files: new_files(vec![2, 3]),
                             deleted_files: vec![1],
                         }),
+                        metadata: Vec::new(),
                     },
                     version_snapshot,
                 )
@@ -393,6 +518,7 @@ mod tests {
                             new_files: new_files(vec![2, 3]),
                             deleted_files: vec![1],
                         }),
+                        metadata: Vec::new(),
                     },
                     version_snapshot,
                 )
@@ -434,6 +560,7 @@ mod tests {
                 new_files: vec![],
                 deleted_files: vec![],
             }),
+            metadata: Vec::new(),
         }));
 
         let ve_snapshot = || {
@@ -466,6 +593,7 @@ mod tests {
                     new_files: new_files(vec![0]),
                     deleted_files: vec![],
                 }),
+                metadata: Vec::new(),
             };
             manifest
                 .record_version_edit(ve.to_owned(), ve_snapshot)
@@ -481,6 +609,7 @@ mod tests {
                         new_files: new_files(vec![i]),
                         deleted_files: vec![r],
                     }),
+                    metadata: Vec::new(),
                 };
                 manifest
                     .record_version_edit(ve.to_owned(), ve_snapshot)
@@ -498,6 +627,7 @@ mod tests {
 
             let mut recover_ver = VersionEdit {
                 file_stream: Some(StreamEdit::default()),
+                metadata: Vec::new(),
             };
             for ve in versions {
                 let recover_ver = recover_ver.file_stream.as_mut().unwrap();
@@ -518,6 +648,7 @@ mod tests {
                     new_files: new_files(vec![1]),
                     deleted_files: vec![],
                 }),
+                metadata: Vec::new(),
             };
             manifest2
                 .record_version_edit(ve.to_owned(), ve_snapshot)
@@ -544,6 +675,7 @@ mod tests {
                             new_files: new_files(vec![2, 3]),
                             deleted_files: vec![1],
                         }),
+                        metadata: Vec::new(),
                     },
                     version_snapshot,
                 )
@@ -556,6 +688,7 @@ mod tests {
                             new_files: new_files(vec![4]),
                             deleted_files: vec![],
                         }),
+                        metadata: Vec::new(),
                     },
                     version_snapshot,
                 )
@@ -568,6 +701,7 @@ mod tests {
                             new_files: new_files(vec![5]),
                             deleted_files: vec![],
                         }),
+                        metadata: Vec::new(),
                     },
                     version_snapshot,
                 )
@@ -581,4 +715,48 @@ mod tests {
             assert_eq!(versions.len(), 4);
         }
     }
+
+    #[photonio::test]
+    fn metadata_survives_rolling() {
+        let env = crate::env::Photon;
+        let base = tempdir::TempDir::new("metadata_survives_rolling").unwrap();
+        let version_snapshot = VersionEdit::default;
+        {
+            let mut manifest = Manifest::open(env.to_owned(), base.as_ref()).await.unwrap();
+            manifest
+                .set_metadata(b"a", b"1", version_snapshot)
+                .await
+                .unwrap();
+            manifest
+                .set_metadata(b"b", b"2", version_snapshot)
+                .await
+                .unwrap();
+            manifest
+                .set_metadata(b"a", b"3", version_snapshot)
+                .await
+                .unwrap();
+            assert!(matches!(
+                manifest
+                    .set_metadata(b"c", &[0; MAX_METADATA_SIZE], version_snapshot)
+                    .await,
+                Err(Error::TooLargeSize)
+            ));
+
+            // The metadata is carried over to the new manifest file.
+            manifest.max_file_size = 1;
+            manifest
+                .record_version_edit(VersionEdit::default(), version_snapshot)
+                .await
+                .unwrap();
+        }
+
+        let mut manifest = Manifest::open(env, base.as_ref()).await.unwrap();
+        let versions = manifest.list_versions().await.unwrap();
+        assert_eq!(versions.len(), 2);
+        manifest.recover_metadata(&versions);
+        assert_eq!(manifest.metadata(b"a"), Some(b"3".as_slice()));
+        assert_eq!(manifest.metadata(b"b"), Some(b"2".as_slice()));
+        assert_eq!(manifest.metadata(b"c"), None);
+        assert_eq!(manifest.metadata_size, 4);
+    }
 }
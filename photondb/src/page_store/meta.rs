@@ -22,12 +22,25 @@ pub(crate) struct StreamEdit {
     pub deleted_files: Vec<u32>,
 }
 
+/// A user metadata entry.
+#[allow(unreachable_pub)]
+#[derive(Clone, PartialEq, Eq, Message)]
+pub(crate) struct MetadataEntry {
+    #[prost(bytes = "vec", tag = "1")]
+    pub key: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub value: Vec<u8>,
+}
+
 #[allow(unreachable_pub)]
 #[derive(Clone, PartialEq, Message)]
 pub(crate) struct VersionEdit {
     /// A set of map files.
     #[prost(message, tag = "1")]
     pub file_stream: Option<StreamEdit>,
+    /// The user metadata entries set in this edit.
+    #[prost(message, repeated, tag = "2")]
+    pub metadata: Vec<MetadataEntry>,
 }
 
 mod convert {
@@ -67,6 +80,10 @@ mod tests {
                 new_files,
                 deleted_files: vec![1, 2, 3],
             }),
+            metadata: vec![MetadataEntry {
+                key: b"k".to_vec(),
+                value: b"v".to_vec(),
+            }],
         };
 
         let payload = edit.encode_to_vec();
@@ -1,13 +1,21 @@
-use std::{fmt, mem, path::Path, sync::Arc};
+use std::{fmt, mem, path::Path, sync::Arc, time::Duration};
 
-use crate::{env::Env, util::shutdown::ShutdownNotifier};
+use log::info;
+
+use crate::{
+    env::Env,
+    util::{pause::Pause, shutdown::ShutdownNotifier},
+};
 
 mod error;
 pub(crate) use error::{Error, Result};
 
+mod addr;
+pub use addr::{PageAddr, PageId};
+
 mod page_txn;
 use futures::lock::Mutex;
-pub(crate) use page_txn::{CacheOption, Guard};
+pub(crate) use page_txn::{CacheEntryGuard, CacheOption, Guard, PinMark};
 
 mod page_table;
 use page_table::PageTable;
@@ -20,12 +28,16 @@ mod version;
 use version::{DeltaVersion, Version, VersionOwner, VersionUpdateReason};
 
 mod jobs;
+pub use jobs::GcPlan;
 use jobs::{cleanup::CleanupCtx, flush::FlushCtx, reclaim::ReclaimCtx};
 
 mod write_buffer;
 pub(crate) use write_buffer::{RecordRef, WriteBuffer};
 
 mod buffer_set;
+use buffer_set::BufferSet;
+
+mod pending_frees;
 
 mod manifest;
 pub(crate) use manifest::Manifest;
@@ -34,6 +46,7 @@ mod page_file;
 pub(crate) use page_file::{FileInfo, PageFiles, PageGroup};
 
 mod recover;
+pub use recover::SelfTestFailure;
 mod strategy;
 pub(crate) use strategy::{MinDeclineRateStrategyBuilder, StrategyBuilder};
 
@@ -46,8 +59,8 @@ pub use page_file::{ChecksumType, Compression};
 pub use stats::StoreStats;
 
 use self::{
-    jobs::wait_for_reclaiming,
-    stats::{AtomicJobStats, AtomicWritebufStats},
+    jobs::{plan_reclaiming, version_snapshot, wait_for_reclaiming},
+    stats::{AtomicJobStats, AtomicWritebufStats, EpochStats},
 };
 
 /// Options to configure a page store.
@@ -56,9 +69,63 @@ use self::{
 pub struct Options {
     /// The capacity of the write buffer. It should be power of two.
     ///
+    /// If [`Options::write_buffer_seal_interval`] is set, this is the maximum
+    /// capacity of the write buffer.
+    ///
     /// Default: 128MB
     pub write_buffer_capacity: u32,
 
+    /// The target interval between two write buffers are sealed.
+    ///
+    /// If set, the capacity of the next write buffer is chosen from the recent
+    /// write rate, so that buffers are sealed and flushed at a stable pace as
+    /// the load changes. The capacity is rounded up to a power of two between
+    /// [`Options::min_write_buffer_capacity`] and
+    /// [`Options::write_buffer_capacity`].
+    ///
+    /// Default: None
+    pub write_buffer_seal_interval: Option<Duration>,
+
+    /// The minimum capacity of the write buffer if
+    /// [`Options::write_buffer_seal_interval`] is set.
+    ///
+    /// Pages larger than the capacity make the next buffer larger, so this
+    /// should be much larger than the page size.
+    ///
+    /// Default: 8MB
+    pub min_write_buffer_capacity: u32,
+
+    /// Whether to admit the writers of a contended write buffer in arrival
+    /// order.
+    ///
+    /// This bounds the wait of each writer under heavy contention, at the
+    /// cost of some throughput.
+    ///
+    /// Default: false
+    pub fair_write_buffer_admission: bool,
+
+    /// The maximum number of transactions writing to write buffers at once.
+    ///
+    /// A transaction takes a slot when it allocates its first page and holds
+    /// it until it is committed or dropped. Transactions beyond the limit wait
+    /// for a slot, which bounds the contention on the buffers under extreme
+    /// concurrency.
+    ///
+    /// If this is zero, writers are not limited.
+    ///
+    /// Default: 0
+    pub max_concurrent_writers: usize,
+
+    /// Whether to zero the memory of write buffers before it is freed.
+    ///
+    /// The pages of a write buffer, including the deallocated ones, live in
+    /// its memory until the buffer is released, so this keeps the contents of
+    /// the pages from lingering in freed memory, at the cost of touching each
+    /// buffer once more.
+    ///
+    /// Default: false
+    pub zero_on_free: bool,
+
     /// The maxmum number of write buffers.
     ///
     /// If there exists too many write buffers, writing will be stalled until at
@@ -67,6 +134,37 @@ pub struct Options {
     /// Default: 8
     pub max_write_buffers: usize,
 
+    /// The maximum number of page files that are flushed concurrently.
+    ///
+    /// Each flush claims distinct sealed write buffers and writes them into its
+    /// own file, but the flushed files are always installed in the order of
+    /// the write buffers.
+    ///
+    /// Default: 1
+    pub flush_concurrency: usize,
+
+    /// The minimum total size of the sealed write buffers flushed into one
+    /// file.
+    ///
+    /// Smaller sealed write buffers, which come from manual flushes that don't
+    /// wait, are held back until more write buffers are sealed, so that they
+    /// are coalesced into one file instead of many tiny files. They are
+    /// flushed anyway once a manual flush waits for them, all write buffers
+    /// are used up, they are held back for [`Options::max_flush_delay`], or
+    /// the store is closed. The size is capped at [`Options::file_base_size`].
+    ///
+    /// Default: 0
+    pub min_flush_bytes: usize,
+
+    /// The maximum time sealed write buffers are held back by
+    /// [`Options::min_flush_bytes`].
+    ///
+    /// This bounds how long the pages of a small write buffer stay in memory
+    /// if no more write buffers are sealed after it.
+    ///
+    /// Default: 1s
+    pub max_flush_delay: Duration,
+
     /// If true, use O_DIRECT to read/write page files.
     ///
     /// Default: false
@@ -77,6 +175,21 @@ pub struct Options {
     /// Default: false
     pub disable_space_reclaiming: bool,
 
+    /// If true, obsolete files are kept until they are removed manually.
+    ///
+    /// A file is obsolete once it is not referenced by the current version
+    /// nor any version pinned by readers. Obsolete files are removed in the
+    /// background by default. If they are kept, they can be listed with
+    /// [`Table::obsolete_files`] and removed with
+    /// [`Table::delete_obsolete_files`]. Kept files are also removed when the
+    /// store is opened again.
+    ///
+    /// [`Table::obsolete_files`]: crate::raw::Table::obsolete_files
+    /// [`Table::delete_obsolete_files`]: crate::raw::Table::delete_obsolete_files
+    ///
+    /// Default: false
+    pub keep_obsolete_files: bool,
+
     /// The max percentage of the space amplification.
     ///
     /// The space amplification is defined as the amount (in percentage) of
@@ -91,7 +204,8 @@ pub struct Options {
     /// Default: u64::MAX
     pub space_used_high: u64,
 
-    /// Target file size for compaction.
+    /// Target file size for compaction. Small sealed write buffers are also
+    /// coalesced into one file up to this size when flushing.
     ///
     /// Default: 64MB
     pub file_base_size: usize,
@@ -153,21 +267,106 @@ pub struct Options {
     /// Default: NONE.
     pub page_checksum_type: ChecksumType,
 
+    /// Verifies the structure of every page read from files or the page cache,
+    /// in addition to the checksum, and returns [`Error::Corrupted`] on
+    /// failure.
+    ///
+    /// This is intended for debugging corruptions, since it slows down reads.
+    ///
+    /// Default: false
+    ///
+    /// [`Error::Corrupted`]: crate::Error::Corrupted
+    pub verify_on_read: bool,
+
+    /// Checks the integrity of the page files before the page store is opened,
+    /// and fails the open with [`Error::SelfTestFailed`] if anything is wrong.
+    ///
+    /// The self-test decodes the footers of all files, verifies every active
+    /// page, and checks that the page table maps each of them to an active
+    /// page. It reads all the files, so it slows down the open a lot.
+    ///
+    /// Default: false
+    ///
+    /// [`Error::SelfTestFailed`]: crate::Error::SelfTestFailed
+    pub self_test_on_open: bool,
+
+    /// The number of pending frees of former versions beyond which the
+    /// background jobs force the epochs to advance to run them.
+    ///
+    /// Former versions are freed once no thread can access them, so they pile
+    /// up if the epochs don't advance in time.
+    ///
+    /// Default: 1024
+    pub pending_frees_soft_limit: u64,
+
+    /// The number of pending frees of former versions beyond which new write
+    /// transactions wait for them to run.
+    ///
+    /// The wait gives up once the frees stop making progress, since they can't
+    /// run while some thread stays pinned.
+    ///
+    /// Default: 65536
+    pub pending_frees_hard_limit: u64,
+
     /// PhotonDB will flush all write buffers on DB close, if there are
     /// unpersisted data. The flush can be skip to speed up DB close, but
     /// unpersisted data WILL BE LOST.
     ///
     /// Default: false
     pub avoid_flush_during_shutdown: bool,
+
+    /// Creates the page store if it doesn't exist in the path.
+    ///
+    /// If false, opening a missing page store fails with
+    /// [`Error::InvalidArgument`].
+    ///
+    /// Default: true
+    ///
+    /// [`Error::InvalidArgument`]: crate::Error::InvalidArgument
+    pub create_if_missing: bool,
+
+    /// Fails with [`Error::InvalidArgument`] if the page store already exists
+    /// in the path.
+    ///
+    /// Default: false
+    ///
+    /// [`Error::InvalidArgument`]: crate::Error::InvalidArgument
+    pub error_if_exists: bool,
+
+    /// Opens the page store in read-only mode.
+    ///
+    /// The page table is recovered from the files as usual, but nothing is
+    /// written to the path: no write buffer is ever flushed, no background
+    /// job is spawned, and no file is removed. Any modification fails with
+    /// [`Error::ReadOnly`]. This is intended for backup and inspection tools,
+    /// which may run against the path of a live page store. They see the
+    /// state as of the open, as long as the files are not removed by the
+    /// owner of the page store.
+    ///
+    /// The page store must exist if this is true.
+    ///
+    /// Default: false
+    ///
+    /// [`Error::ReadOnly`]: crate::Error::ReadOnly
+    pub read_only: bool,
 }
 
 impl Default for Options {
     fn default() -> Self {
         Self {
             write_buffer_capacity: 128 << 20,
+            write_buffer_seal_interval: None,
+            min_write_buffer_capacity: 8 << 20,
+            fair_write_buffer_admission: false,
+            max_concurrent_writers: 0,
+            zero_on_free: false,
             max_write_buffers: 8,
+            flush_concurrency: 1,
+            min_flush_bytes: 0,
+            max_flush_delay: Duration::from_secs(1),
             use_direct_io: false,
             disable_space_reclaiming: false,
+            keep_obsolete_files: false,
             max_space_amplification_percent: 100,
             space_used_high: u64::MAX,
             file_base_size: 64 << 20,
@@ -179,7 +378,14 @@ impl Default for Options {
             compression_on_flush: Compression::SNAPPY,
             compression_on_cold_compact: Compression::ZSTD,
             page_checksum_type: ChecksumType::NONE,
+            verify_on_read: false,
+            self_test_on_open: false,
+            pending_frees_soft_limit: 1 << 10,
+            pending_frees_hard_limit: 1 << 16,
             avoid_flush_during_shutdown: false,
+            create_if_missing: true,
+            error_if_exists: false,
+            read_only: false,
         }
     }
 }
@@ -208,6 +414,14 @@ impl Default for FlushOptions {
     }
 }
 
+/// A reference to a version of a page store taken by
+/// [`PageStore::pin_version`].
+///
+/// The pages and files of the version are not released until it is dropped.
+pub(crate) struct VersionPin {
+    _version: Arc<Version>,
+}
+
 pub(crate) struct PageStore<E: Env> {
     options: Options,
     #[allow(unused)]
@@ -217,12 +431,15 @@ pub(crate) struct PageStore<E: Env> {
     version_owner: Arc<VersionOwner>,
     page_files: Arc<PageFiles<E>>,
     manifest: Arc<Mutex<Manifest<E>>>,
+    // The obsolete files kept for manual removal.
+    obsolete_files: Arc<std::sync::Mutex<Vec<u32>>>,
 
     job_stats: Arc<AtomicJobStats>,
     writebuf_stats: Arc<AtomicWritebufStats>,
 
     jobs: Vec<E::JoinHandle<()>>,
     shutdown: ShutdownNotifier,
+    pause: Pause,
 }
 
 impl<E: Env> PageStore<E> {
@@ -233,12 +450,26 @@ impl<E: Env> PageStore<E> {
         let (next_page_file_id, manifest, table, page_files, delta) =
             Self::recover(env.to_owned(), path, &options).await?;
 
-        let version = Version::new(
-            options.write_buffer_capacity,
+        let mut buffer_set = BufferSet::new(
             next_page_file_id,
+            options.write_buffer_capacity,
             options.max_write_buffers,
-            delta,
+            options.fair_write_buffer_admission,
         );
+        if let Some(interval) = options.write_buffer_seal_interval {
+            buffer_set = buffer_set.with_seal_interval(interval, options.min_write_buffer_capacity);
+        }
+        buffer_set = buffer_set.with_max_pending_frees(options.pending_frees_hard_limit);
+        if options.max_concurrent_writers > 0 {
+            buffer_set = buffer_set.with_max_concurrent_writers(options.max_concurrent_writers);
+        }
+        if options.zero_on_free {
+            buffer_set = buffer_set.with_zero_on_free();
+        }
+        if options.read_only {
+            buffer_set = buffer_set.with_read_only();
+        }
+        let version = Version::with_buffer_set(next_page_file_id, Arc::new(buffer_set), delta);
 
         let version_owner = Arc::new(VersionOwner::new(version));
         let manifest = Arc::new(futures::lock::Mutex::new(manifest));
@@ -254,20 +485,50 @@ impl<E: Env> PageStore<E> {
             version_owner,
             page_files,
             manifest,
+            obsolete_files: Arc::default(),
             job_stats,
             writebuf_stats,
             jobs: Vec::new(),
             shutdown,
+            pause: Pause::default(),
         };
 
         // Spawn background jobs.
-        store.spawn_flush_job();
-        store.spawn_cleanup_job();
-        store.spawn_reclaim_job();
+        if !store.options.read_only {
+            store.spawn_flush_job();
+            store.spawn_cleanup_job();
+            store.spawn_reclaim_job();
+        }
 
         Ok(store)
     }
 
+    /// Returns the value of the user metadata of the key.
+    pub(crate) async fn get_metadata(&self, key: &[u8]) -> Option<Vec<u8>> {
+        let manifest = self.manifest.lock().await;
+        manifest.metadata(key).map(<[u8]>::to_vec)
+    }
+
+    /// Sets the user metadata of the key in the manifest.
+    ///
+    /// The metadata is written as a version edit, so it is persisted
+    /// atomically with respect to the installation of versions.
+    pub(crate) async fn set_metadata(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        if self.options.read_only {
+            return Err(Error::ReadOnly);
+        }
+        let mut manifest = self.manifest.lock().await;
+        let version = self.version();
+        manifest
+            .set_metadata(key, value, || version_snapshot(&version))
+            .await
+    }
+
+    #[inline]
+    pub(crate) fn env(&self) -> &E {
+        &self.env
+    }
+
     #[inline]
     pub(crate) fn guard(&self) -> Guard<E> {
         Guard::new(
@@ -278,17 +539,40 @@ impl<E: Env> PageStore<E> {
         )
     }
 
+    /// Pins the current version, see [`VersionPin`].
+    pub(crate) fn pin_version(&self) -> VersionPin {
+        VersionPin {
+            _version: self.version(),
+        }
+    }
+
+    /// Returns the number of references to the current version, which
+    /// include the guards of live transactions.
+    #[cfg(test)]
+    pub(crate) fn version_refs(&self) -> usize {
+        Arc::strong_count(&self.version()) - 1
+    }
+
     pub(crate) fn stats(&self) -> StoreStats {
         let (page_cache, file_reader_cache) = self.page_files.stats();
         let writebuf = self.writebuf_stats.snapshot();
         let jobs = self.job_stats.snapshot();
-        let buffer_set = self.version().buffer_set.stats();
+        let version = self.version();
+        let buffer_set = version.buffer_set.stats();
+        let pending_frees = version.buffer_set.pending_frees();
+        let epoch = EpochStats {
+            pending_buffer_set_frees: pending_frees.num_buffer_sets(),
+            pending_version_frees: pending_frees.num_versions(),
+            pending_page_id_frees: pending_frees.num_page_ids(),
+        };
         StoreStats {
             page_cache,
             file_reader_cache,
             writebuf,
             jobs,
             buffer_set,
+            epoch,
+            page_table: self.table.stats(),
         }
     }
 
@@ -300,18 +584,84 @@ impl<E: Env> PageStore<E> {
         }
     }
 
+    /// Closes the page store and removes its files.
+    ///
+    /// The background jobs are stopped before any file is removed. The caller
+    /// must ensure that there is no other reference to the page store.
+    pub(crate) async fn destroy(mut self) -> Result<()> {
+        if self.options.read_only {
+            return Err(Error::ReadOnly);
+        }
+        self.shutdown.terminate();
+        let jobs = mem::take(&mut self.jobs);
+        for job in jobs {
+            job.await;
+        }
+        info!("Destroy page store");
+        self.manifest.lock().await.destroy().await?;
+        self.page_files.destroy().await
+    }
+
     /// Flush the active write buffer if it is not empty.
     #[inline]
     pub(crate) async fn flush(&self, opts: &FlushOptions) {
         self.version().buffer_set.flush_active_buffer(opts).await
     }
 
+    /// Returns the ids of the obsolete files kept for manual removal.
+    pub(crate) fn obsolete_files(&self) -> Vec<u32> {
+        let mut files = self.obsolete_files.lock().expect("Poisoned").clone();
+        files.sort_unstable();
+        files
+    }
+
+    /// Removes the obsolete files kept for manual removal. Returns the number
+    /// of removed files.
+    pub(crate) async fn delete_obsolete_files(&self) -> usize {
+        let files = mem::take(&mut *self.obsolete_files.lock().expect("Poisoned"));
+        let num_files = files.len();
+        if num_files > 0 {
+            info!("Remove obsolete files {files:?}");
+            self.page_files.remove_files(files).await;
+        }
+        num_files
+    }
+
+    /// Pauses flushing and reclaiming until
+    /// [`PageStore::resume_maintenance`] is called.
+    ///
+    /// Write buffers are still flushed once all of them are used up, so writes
+    /// are never stalled forever.
+    pub(crate) fn pause_maintenance(&self) {
+        self.pause.pause();
+    }
+
+    /// Resumes flushing and reclaiming paused by
+    /// [`PageStore::pause_maintenance`].
+    pub(crate) fn resume_maintenance(&self) {
+        self.pause.resume();
+        // Wake up the flush job to claim write buffers sealed during the pause.
+        self.version().buffer_set.notify_flush_job();
+    }
+
     /// Wait all pending reclaiming to finish.
     #[inline]
     pub(crate) async fn wait_for_reclaiming(&self) {
         wait_for_reclaiming(&self.options, self.version()).await;
     }
 
+    /// Returns the files that space reclaiming would compact now, without
+    /// compacting them.
+    pub(crate) async fn plan_reclaiming(&self) -> GcPlan {
+        let now = self.manifest.lock().await.now();
+        plan_reclaiming(
+            &self.options,
+            &MinDeclineRateStrategyBuilder,
+            &self.version(),
+            now,
+        )
+    }
+
     #[inline]
     fn version(&self) -> Arc<Version> {
         self.version_owner.current()
@@ -321,6 +671,7 @@ impl<E: Env> PageStore<E> {
         let job = FlushCtx::new(
             self.options.clone(),
             self.shutdown.subscribe(),
+            self.pause.clone(),
             self.job_stats.clone(),
             self.version_owner.clone(),
             self.page_files.clone(),
@@ -331,7 +682,16 @@ impl<E: Env> PageStore<E> {
     }
 
     fn spawn_cleanup_job(&mut self) {
-        let job = CleanupCtx::new(self.shutdown.subscribe(), self.page_files.clone());
+        let kept_files = self
+            .options
+            .keep_obsolete_files
+            .then(|| self.obsolete_files.clone());
+        let job = CleanupCtx::new(
+            self.shutdown.subscribe(),
+            self.page_files.clone(),
+            self.options.pending_frees_soft_limit,
+            kept_files,
+        );
         let handle = self.env.spawn_background(job.run(self.version()));
         self.jobs.push(handle);
     }
@@ -341,6 +701,7 @@ impl<E: Env> PageStore<E> {
         let job = ReclaimCtx::new(
             self.options.clone(),
             self.shutdown.subscribe(),
+            self.pause.clone(),
             strategy_builder,
             self.page_files.clone(),
             self.version_owner.clone(),
@@ -10,7 +10,7 @@ use super::{
     compression::Compression,
     constant::*,
     file_builder::CommonFileBuilder,
-    types::{split_page_addr, FileMeta},
+    types::{split_page_addr, FileFormat, FileMeta},
     BlockHandle, BufferedWriter, ChecksumType, FileInfo, PageGroup,
 };
 use crate::{
@@ -44,6 +44,7 @@ pub(crate) struct FileBuilder<'a, E: Env> {
     file_offset: usize,
     compression: Compression,
     checksum: ChecksumType,
+    format: FileFormat,
 }
 
 /// A builder for page group.
@@ -98,9 +99,17 @@ impl<'a, E: Env> FileBuilder<'a, E> {
             block_size,
             compression,
             checksum,
+            format: FileFormat::CURRENT,
         }
     }
 
+    /// Writes the file in an older format, to test reading and upgrading it.
+    #[cfg(test)]
+    pub(crate) fn with_format(mut self, format: FileFormat) -> Self {
+        self.format = format;
+        self
+    }
+
     pub(crate) fn add_page_group(self, group_id: u32) -> PageGroupBuilder<'a, E> {
         let compression = self.compression;
         let checksum_type = self.checksum;
@@ -128,7 +137,7 @@ impl<'a, E: Env> FileBuilder<'a, E> {
             .iter()
             .map(|(&id, info)| (id, info.meta().clone()))
             .collect::<FxHashMap<_, _>>();
-        let file_meta = Arc::new(FileMeta::new(
+        let file_meta = FileMeta::new(
             self.file_id,
             file_size,
             DEFAULT_BLOCK_SIZE,
@@ -136,8 +145,9 @@ impl<'a, E: Env> FileBuilder<'a, E> {
             self.compression,
             self.get_referenced_groups(),
             page_groups,
-        ));
-        let file_info = FileInfo::new(up2, up2, file_meta);
+        )
+        .with_format(self.format);
+        let file_info = FileInfo::new(up2, up2, Arc::new(file_meta));
         Ok((self.page_groups, file_info))
     }
 
@@ -145,7 +155,7 @@ impl<'a, E: Env> FileBuilder<'a, E> {
         let page_index_handle = self.finish_page_index_block().await?;
         let dealloc_pages_handle = self.finish_dealloc_pages_block().await?;
         let footer = Footer {
-            magic: FILE_MAGIC,
+            magic: self.format.magic(),
             page_index_handle,
             dealloc_pages_handle,
             compression: self.compression,
@@ -8,7 +8,7 @@ pub(crate) use file_reader::FileReader;
 
 mod types;
 pub(crate) use facade::PageFiles;
-pub(crate) use types::{FileInfo, PageGroup, PageGroupMeta};
+pub(crate) use types::{FileFormat, FileInfo, PageGroup, PageGroupMeta};
 
 mod map_file_builder;
 pub(crate) use map_file_builder::{FileBuilder, PageGroupBuilder};
@@ -45,7 +45,7 @@ pub(crate) mod facade {
     };
     use crate::{
         env::{Env, PositionalReader, SequentialWriter},
-        page::PageRef,
+        page::{verify_page, PageRef},
         page_store::{
             page_txn::{CacheOption, CachePriority},
             stats::CacheStats,
@@ -65,6 +65,7 @@ pub(crate) mod facade {
 
         use_direct: bool,
         prepopulate_cache_on_flush: bool,
+        verify_on_read: bool,
 
         reader_cache: cache::FileReaderCache<E>,
         page_cache: Arc<LRUCache<Vec<u8>>>,
@@ -84,12 +85,14 @@ pub(crate) mod facade {
             let page_cache = Arc::new(LRUCache::new(options.cache_capacity, -1, 0.5, 0.0));
             let use_direct = options.use_direct_io;
             let prepopulate_cache_on_flush = options.prepopulate_cache_on_flush;
+            let verify_on_read = options.verify_on_read;
             Ok(Self {
                 env,
                 base,
                 base_dir,
                 use_direct,
                 prepopulate_cache_on_flush,
+                verify_on_read,
                 reader_cache,
                 page_cache,
             })
@@ -126,10 +129,16 @@ pub(crate) mod facade {
             mut hint: CacheOption,
         ) -> Result<(CacheEntry<Vec<u8>, LRUCache<Vec<u8>>>, /* hit */ bool)> {
             if let Some(cache_entry) = self.page_cache.lookup(addr) {
+                if self.verify_on_read && !verify_page(cache_entry.value()) {
+                    return Err(Error::Corrupted);
+                }
                 return Ok((cache_entry, true));
             }
 
             let buf = self.read_file_page(file_id, file_meta, handle).await?;
+            if self.verify_on_read && !verify_page(&buf) {
+                return Err(Error::Corrupted);
+            }
 
             let is_inner = {
                 let page =
@@ -280,6 +289,17 @@ pub(crate) mod facade {
             }
         }
 
+        /// Removes all page files, then the base folder if nothing else is
+        /// left in it.
+        pub(crate) async fn destroy(&self) -> Result<()> {
+            let files = self.list_files()?;
+            self.remove_files(files).await;
+            if self.env.read_dir(&self.base)?.next().is_none() {
+                self.env.remove_dir_all(&self.base).await?;
+            }
+            Ok(())
+        }
+
         pub(crate) fn list_files(&self) -> Result<Vec<u32>> {
             let prefix = format!("{}_", FILE_PREFIX).into_bytes();
             self.list_files_with_prefix(&prefix)
@@ -398,6 +418,57 @@ pub(crate) mod facade {
             }
         }
 
+        #[photonio::test]
+        fn test_read_page_with_verification() {
+            let env = crate::env::Photon;
+            let base = TempDir::new("test_verify_on_read").unwrap();
+            let files = PageFiles::new(env, base.path(), &test_option())
+                .await
+                .unwrap();
+            let file_id = 1;
+            let mut corrupted = [0u8; 20];
+            corrupted[16..].copy_from_slice(&1000u32.to_le_bytes());
+            let (group, info) = {
+                let b = files
+                    .new_file_builder(file_id, Compression::NONE, ChecksumType::NONE)
+                    .await
+                    .unwrap();
+                let mut b = b.add_page_group(123);
+                b.add_page(1, page_addr(1, 1), empty_page_info(), &corrupted)
+                    .await
+                    .unwrap();
+                b.add_page(2, page_addr(1, 2), empty_page_info(), &[0u8; 16])
+                    .await
+                    .unwrap();
+                let builder = b.finish().await.unwrap();
+                let (groups, info) = builder.finish(1).await.unwrap();
+                (groups.get(&123).unwrap().clone(), info)
+            };
+
+            // Structural errors are not detected by default.
+            let addr = page_addr(1, 1);
+            let handle = group.get_page_handle(addr).unwrap();
+            let res = files
+                .read_page(file_id, info.meta(), addr, handle, CacheOption::default())
+                .await;
+            assert!(res.is_ok());
+
+            let mut opt = test_option();
+            opt.verify_on_read = true;
+            let files = PageFiles::new(env, base.path(), &opt).await.unwrap();
+            let res = files
+                .read_page(file_id, info.meta(), addr, handle, CacheOption::default())
+                .await;
+            assert!(matches!(res, Err(Error::Corrupted)));
+
+            let addr = page_addr(1, 2);
+            let handle = group.get_page_handle(addr).unwrap();
+            let res = files
+                .read_page(file_id, info.meta(), addr, handle, CacheOption::default())
+                .await;
+            assert!(res.is_ok());
+        }
+
         #[photonio::test]
         fn test_simple_write_reader() {
             let env = crate::env::Photon;
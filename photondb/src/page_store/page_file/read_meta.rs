@@ -6,7 +6,7 @@ use super::{
     file_builder::IndexBlock,
     file_reader::FileReader,
     map_file_builder::{Footer, PageIndex},
-    types::FileMeta,
+    types::{FileFormat, FileMeta},
     PageGroupMeta,
 };
 use crate::{
@@ -32,6 +32,7 @@ impl FileMetaHolder {
         reader: Arc<FileReader<R>>,
     ) -> Result<Self> {
         let footer = Self::read_footer(&reader).await?;
+        let format = FileFormat::from_magic(footer.magic).ok_or(Error::Corrupted)?;
         let page_indexes = Self::read_page_indexes(&reader, &footer).await?;
         let mut file_meta_map = FxHashMap::default();
         let mut page_tables = FxHashMap::default();
@@ -54,7 +55,7 @@ impl FileMetaHolder {
                 referenced_groups.insert((page_addr >> 32) as u32);
             }
         }
-        let file_meta = Arc::new(FileMeta::new(
+        let file_meta = FileMeta::new(
             file_id,
             reader.file_size,
             reader.align_size,
@@ -62,10 +63,11 @@ impl FileMetaHolder {
             footer.compression,
             referenced_groups,
             file_meta_map.clone(),
-        ));
+        )
+        .with_format(format);
         Ok(FileMetaHolder {
             page_groups: file_meta_map,
-            file_meta,
+            file_meta: Arc::new(file_meta),
             page_tables,
             dealloc_pages,
         })
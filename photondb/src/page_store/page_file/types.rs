@@ -2,9 +2,24 @@ use std::{collections::BTreeMap, sync::Arc};
 
 use rustc_hash::{FxHashMap, FxHashSet};
 
-use super::{compression::Compression, BlockHandle, ChecksumType};
+use super::{compression::Compression, constant::FILE_MAGIC, BlockHandle, ChecksumType};
 use crate::{page::PageInfo, util::bitmap::FixedBitmap};
 
+/// The format of a page file, which tells readers how to decode it.
+///
+/// The format is stored in the high 32 bits of the magic number in the
+/// footer. Files written before the format was recorded carry the bare magic
+/// number, and are read as [`FileFormat::Legacy`]. Files in older formats are
+/// rewritten in the current format by space reclaiming.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum FileFormat {
+    /// Files written before the format was recorded.
+    Legacy = 0,
+    /// The first recorded format. It has the same layout as
+    /// [`FileFormat::Legacy`], so both are decoded in the same way.
+    V1 = 1,
+}
+
 #[derive(Debug, Copy, Clone)]
 pub(crate) struct PageHandle {
     pub(crate) offset: u32,
@@ -75,6 +90,9 @@ pub(crate) struct FileMeta {
     pub(crate) checksum_type: ChecksumType,
     pub(crate) compression: Compression,
     pub(crate) page_groups: FxHashMap<u32, Arc<PageGroupMeta>>,
+
+    /// The format that the file is written in.
+    pub(crate) format: FileFormat,
 }
 
 impl PageGroup {
@@ -318,7 +336,38 @@ impl FileMeta {
             compression,
             referenced_groups,
             page_groups,
+            format: FileFormat::CURRENT,
+        }
+    }
+
+    pub(crate) fn with_format(mut self, format: FileFormat) -> Self {
+        self.format = format;
+        self
+    }
+}
+
+impl FileFormat {
+    /// The format of new files.
+    pub(crate) const CURRENT: FileFormat = FileFormat::V1;
+
+    /// Returns the format recorded in the magic number.
+    ///
+    /// Returns `None` if the magic number is invalid, or the format is newer
+    /// than the ones known by this build.
+    pub(crate) fn from_magic(magic: u64) -> Option<FileFormat> {
+        if magic & u32::MAX as u64 != FILE_MAGIC {
+            return None;
         }
+        match magic >> 32 {
+            0 => Some(FileFormat::Legacy),
+            1 => Some(FileFormat::V1),
+            _ => None,
+        }
+    }
+
+    /// Returns the magic number that records the format.
+    pub(crate) fn magic(self) -> u64 {
+        ((self as u64) << 32) | FILE_MAGIC
     }
 }
 
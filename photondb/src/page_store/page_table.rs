@@ -7,6 +7,11 @@ use std::{
     },
 };
 
+use super::{
+    stats::{AtomicPageTableStats, PageTableStats},
+    PageAddr, PageId,
+};
+
 pub(crate) const NAN_ID: u64 = 0;
 pub(crate) const MIN_ID: u64 = 1;
 pub(crate) const MAX_ID: u64 = L2_FANOUT - 1;
@@ -19,12 +24,13 @@ pub(crate) struct PageTableBuilder {
 }
 
 impl PageTableBuilder {
-    pub(crate) fn get(&mut self, id: u64) -> u64 {
-        self.inner.index(id).load(Ordering::Relaxed)
+    pub(crate) fn get(&mut self, id: PageId) -> PageAddr {
+        PageAddr::from_raw(self.inner.index(id.into()).load(Ordering::Relaxed))
     }
 
-    pub(crate) fn set(&mut self, id: u64, addr: u64) {
-        self.inner.index(id).store(addr, Ordering::Relaxed);
+    pub(crate) fn set(&mut self, id: PageId, addr: PageAddr) {
+        let id = u64::from(id);
+        self.inner.index(id).store(addr.into(), Ordering::Relaxed);
         self.max_id = self.max_id.max(id);
     }
 
@@ -48,6 +54,9 @@ impl PageTableBuilder {
 }
 
 /// A table that maps page ids to page addresses.
+///
+/// The entries of free ids link to the next free id, so only the allocated
+/// ids map to [`PageAddr`]s.
 #[derive(Clone, Default)]
 pub(crate) struct PageTable {
     inner: Arc<Inner>,
@@ -55,27 +64,74 @@ pub(crate) struct PageTable {
 
 impl PageTable {
     /// Returns the address of the page with the given id.
-    pub(crate) fn get(&self, id: u64) -> u64 {
-        self.inner.index(id).load(Ordering::Acquire)
+    pub(crate) fn get(&self, id: PageId) -> PageAddr {
+        PageAddr::from_raw(self.inner.index(id.into()).load(Ordering::Acquire))
     }
 
     /// Updates the address of the page with the given id.
-    pub(crate) fn set(&self, id: u64, new: u64) {
-        self.inner.index(id).store(new, Ordering::Release)
+    pub(crate) fn set(&self, id: PageId, new: PageAddr) {
+        self.inner
+            .index(id.into())
+            .store(new.into(), Ordering::Release)
     }
 
-    pub(crate) fn cas(&self, id: u64, old: u64, new: u64) -> Result<u64, u64> {
-        self.inner
-            .index(id)
-            .compare_exchange(old, new, Ordering::AcqRel, Ordering::Acquire)
+    /// Returns the address of the page with the given id.
+    ///
+    /// Returns [`None`] if the id has never been allocated, which happens if
+    /// the id comes from corrupted data.
+    pub(crate) fn try_get(&self, id: PageId) -> Option<PageAddr> {
+        if self.inner.contains(id.into()) {
+            Some(self.get(id))
+        } else {
+            None
+        }
+    }
+
+    /// Updates the address of the page with the given id.
+    ///
+    /// Returns an error if the id has never been allocated. Unlike
+    /// [`PageTable::set`], this never allocates memory for such ids.
+    pub(crate) fn try_set(&self, id: PageId, new: PageAddr) -> Result<(), ()> {
+        if self.inner.contains(id.into()) {
+            self.set(id, new);
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+
+    pub(crate) fn cas(
+        &self,
+        id: PageId,
+        old: PageAddr,
+        new: PageAddr,
+    ) -> Result<PageAddr, PageAddr> {
+        let entry = self.inner.index(id.into());
+        let result = entry
+            .compare_exchange(old.into(), new.into(), Ordering::AcqRel, Ordering::Acquire)
+            .map(PageAddr::from_raw)
+            .map_err(PageAddr::from_raw);
+        #[cfg(feature = "contention-stats")]
+        if result.is_err() {
+            self.inner.stats.update_conflicts.inc();
+        }
+        result
     }
 
-    pub(crate) unsafe fn alloc(&self) -> Option<u64> {
-        self.inner.alloc()
+    /// Allocates an unused page id.
+    ///
+    /// Returns [`None`] if all page ids are exhausted.
+    pub(crate) unsafe fn alloc(&self) -> Option<PageId> {
+        self.inner.alloc().map(PageId::new)
     }
 
-    pub(crate) unsafe fn dealloc(&self, id: u64) {
-        self.inner.dealloc(id)
+    pub(crate) unsafe fn dealloc(&self, id: PageId) {
+        self.inner.dealloc(id.into())
+    }
+
+    /// Returns the statistics of the table.
+    pub(crate) fn stats(&self) -> PageTableStats {
+        self.inner.stats.snapshot()
     }
 }
 
@@ -91,6 +147,11 @@ struct Inner {
     // The head of the free list.
     // The list uses epoch-based reclamation to prevent the ABA problem.
     free: AtomicU64,
+    // The upper bound (exclusive) of the allocatable ids.
+    capacity: u64,
+    // Counts the contentions on the table if the `contention-stats` feature is
+    // enabled.
+    stats: AtomicPageTableStats,
 }
 
 impl Default for Inner {
@@ -101,11 +162,18 @@ impl Default for Inner {
             l2: Box::default(),
             next: AtomicU64::new(MIN_ID),
             free: AtomicU64::new(NAN_ID),
+            capacity: MAX_ID,
+            stats: AtomicPageTableStats::default(),
         }
     }
 }
 
 impl Inner {
+    // Returns true if the id has been allocated before.
+    fn contains(&self, id: u64) -> bool {
+        id < self.next.load(Ordering::Acquire).min(self.capacity)
+    }
+
     fn index(&self, index: u64) -> &AtomicU64 {
         if index < L0_FANOUT {
             self.l0.index(index)
@@ -127,16 +195,20 @@ impl Inner {
                 .compare_exchange(id, next, Ordering::AcqRel, Ordering::Acquire)
             {
                 Ok(_) => break,
-                Err(actual) => id = actual,
+                Err(actual) => {
+                    #[cfg(feature = "contention-stats")]
+                    self.stats.alloc_retries.inc();
+                    id = actual;
+                }
             }
         }
         if id == NAN_ID {
             id = self.next.load(Ordering::Relaxed);
-            if id < MAX_ID {
+            if id < self.capacity {
                 id = self.next.fetch_add(1, Ordering::Relaxed);
             }
         }
-        if id < MAX_ID {
+        if id < self.capacity {
             Some(id)
         } else {
             None
@@ -152,7 +224,11 @@ impl Inner {
                 .compare_exchange(next, id, Ordering::AcqRel, Ordering::Acquire)
             {
                 Ok(_) => break,
-                Err(actual) => next = actual,
+                Err(actual) => {
+                    #[cfg(feature = "contention-stats")]
+                    self.stats.dealloc_retries.inc();
+                    next = actual;
+                }
             }
         }
     }
@@ -238,20 +314,57 @@ const L2_FANOUT: u64 = L1_FANOUT * FANOUT as u64;
 define_level!(L1, L0<FANOUT>, L0_FANOUT);
 define_level!(L2, L1<FANOUT>, L1_FANOUT);
 
+#[cfg(test)]
+impl PageTable {
+    /// Creates a table that can allocate at most `capacity - MIN_ID` ids.
+    pub(crate) fn with_capacity(capacity: u64) -> Self {
+        assert!(capacity <= MAX_ID);
+        let inner = Inner {
+            capacity,
+            ..Default::default()
+        };
+        Self {
+            inner: Arc::new(inner),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn id(raw: u64) -> PageId {
+        PageId::new(raw)
+    }
+
+    fn addr(raw: u64) -> PageAddr {
+        PageAddr::from_raw(raw)
+    }
+
     #[test]
     fn alloc() {
         let table = PageTable::default();
         unsafe {
-            assert_eq!(table.alloc(), Some(1));
-            assert_eq!(table.alloc(), Some(2));
-            table.dealloc(1);
-            table.dealloc(2);
-            assert_eq!(table.alloc(), Some(2));
-            assert_eq!(table.alloc(), Some(1));
+            assert_eq!(table.alloc(), Some(id(1)));
+            assert_eq!(table.alloc(), Some(id(2)));
+            table.dealloc(id(1));
+            table.dealloc(id(2));
+            assert_eq!(table.alloc(), Some(id(2)));
+            assert_eq!(table.alloc(), Some(id(1)));
+        }
+    }
+
+    #[test]
+    fn alloc_exhausted() {
+        let table = PageTable::with_capacity(3);
+        unsafe {
+            assert_eq!(table.alloc(), Some(id(1)));
+            assert_eq!(table.alloc(), Some(id(2)));
+            assert_eq!(table.alloc(), None);
+            assert_eq!(table.alloc(), None);
+            table.dealloc(id(1));
+            assert_eq!(table.alloc(), Some(id(1)));
+            assert_eq!(table.alloc(), None);
         }
     }
 
@@ -266,27 +379,96 @@ mod tests {
             L1_FANOUT,
             L2_FANOUT - 1,
         ] {
-            table.set(i, i);
-            assert_eq!(table.get(i), i);
+            table.set(id(i), addr(i));
+            assert_eq!(table.get(id(i)), addr(i));
         }
     }
 
+    #[test]
+    fn index_out_of_range() {
+        let table = PageTable::default();
+        let first = unsafe { table.alloc() }.unwrap();
+        assert_eq!(table.try_set(first, addr(1)), Ok(()));
+        assert_eq!(table.try_get(first), Some(addr(1)));
+        for i in [
+            u64::from(first) + 1,
+            L1_FANOUT,
+            L2_FANOUT - 1,
+            L2_FANOUT,
+            u64::MAX,
+        ] {
+            assert_eq!(table.try_get(id(i)), None);
+            assert_eq!(table.try_set(id(i), addr(1)), Err(()));
+        }
+        // No child blocks should be allocated for the out-of-range ids.
+        fn is_null<T>(p: &AtomicPtr<T>) -> bool {
+            p.load(Ordering::Relaxed).is_null()
+        }
+        assert!(table.inner.l1.0.iter().all(is_null));
+        assert!(table.inner.l2.0.iter().all(is_null));
+
+        let table = PageTable::with_capacity(3);
+        unsafe {
+            assert_eq!(table.alloc(), Some(id(1)));
+            assert_eq!(table.alloc(), Some(id(2)));
+            assert_eq!(table.alloc(), None);
+        }
+        assert_eq!(table.try_get(id(2)), Some(PageAddr::default()));
+        assert_eq!(table.try_get(id(3)), None);
+        assert_eq!(table.try_set(id(3), addr(1)), Err(()));
+    }
+
     #[test]
     fn recover() {
         let mut builder = PageTableBuilder::default();
-        builder.set(1, 1);
-        builder.set(3, 3);
-        builder.set(5, 5);
+        builder.set(id(1), addr(1));
+        builder.set(id(3), addr(3));
+        builder.set(id(5), addr(5));
         let table = builder.build();
-        assert_eq!(table.get(1), 1);
-        assert_eq!(table.get(3), 3);
-        assert_eq!(table.get(5), 5);
+        assert_eq!(table.get(id(1)), addr(1));
+        assert_eq!(table.get(id(3)), addr(3));
+        assert_eq!(table.get(id(5)), addr(5));
         unsafe {
-            assert_eq!(table.alloc(), Some(2));
-            assert_eq!(table.alloc(), Some(4));
-            assert_eq!(table.alloc(), Some(6));
-            table.dealloc(2);
-            assert_eq!(table.alloc(), Some(2));
+            assert_eq!(table.alloc(), Some(id(2)));
+            assert_eq!(table.alloc(), Some(id(4)));
+            assert_eq!(table.alloc(), Some(id(6)));
+            table.dealloc(id(2));
+            assert_eq!(table.alloc(), Some(id(2)));
         }
     }
+
+    #[cfg(feature = "contention-stats")]
+    #[test]
+    fn contention_stats() {
+        use std::sync::atomic::AtomicBool;
+
+        let table = PageTable::default();
+        let id = unsafe { table.alloc() }.unwrap();
+        table.set(id, addr(1));
+        assert!(table.cas(id, addr(2), addr(3)).is_err());
+        assert_eq!(table.stats().update_conflicts, 1);
+
+        // Allocate and deallocate ids concurrently until some CASs fail.
+        let stop = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    while !stop.load(Ordering::Relaxed) {
+                        unsafe {
+                            let id = table.alloc().unwrap();
+                            table.dealloc(id);
+                        }
+                    }
+                });
+            }
+            loop {
+                let stats = table.stats();
+                if stats.alloc_retries > 0 && stats.dealloc_retries > 0 {
+                    break;
+                }
+                std::thread::yield_now();
+            }
+            stop.store(true, Ordering::Relaxed);
+        });
+    }
 }
@@ -1,16 +1,23 @@
 use std::{
     collections::HashMap,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
 };
 
 use bitflags::bitflags;
+use log::warn;
 
 use super::{
+    buffer_set::{WriterPermit, MAX_BUFFER_ID},
     cache::CacheToken,
+    pending_frees,
     stats::AtomicWritebufStats,
-    version::Version,
+    version::{Version, VersionOwner},
     write_buffer::{RecordHeader, ReleaseState},
-    CacheEntry, Error, LRUCache, PageFiles, PageTable, Result, WriteBuffer, NAN_ID,
+    CacheEntry, Error, LRUCache, PageAddr, PageFiles, PageId, PageTable, Result, WriteBuffer,
+    NAN_ID,
 };
 use crate::{
     env::Env,
@@ -84,7 +91,10 @@ pub(crate) enum CachePriority {
     Bottom,
 }
 
-type CacheEntryGuard = CacheEntry<Vec<u8>, LRUCache<Vec<u8>>>;
+// The number of attempts to reclaim page ids before giving up on insertion.
+const MAX_PAGE_ID_RECLAIM_ATTEMPTS: usize = 16;
+
+pub(crate) type CacheEntryGuard = CacheEntry<Vec<u8>, LRUCache<Vec<u8>>>;
 
 pub(crate) struct Guard<E: Env>
 where
@@ -94,9 +104,25 @@ where
     page_table: PageTable,
     page_files: Arc<PageFiles<E>>,
     cache_guards: Mutex<Vec<CacheEntryGuard>>,
+    // The size of pages read by this guard, see `Guard::pinned_bytes`.
+    pinned_bytes: AtomicUsize,
     writebuf_stats: Arc<AtomicWritebufStats>,
 }
 
+/// A position in the pages read by a [`Guard`], see [`Guard::unpin_since`].
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct PinMark {
+    num_cache_guards: usize,
+    pinned_bytes: usize,
+}
+
+impl PinMark {
+    /// Returns the size of pages read by the guard before the mark.
+    pub(crate) fn pinned_bytes(&self) -> usize {
+        self.pinned_bytes
+    }
+}
+
 impl<E: Env> Guard<E> {
     pub(crate) fn new(
         version: Arc<Version>,
@@ -109,16 +135,21 @@ impl<E: Env> Guard<E> {
             page_table,
             page_files,
             cache_guards: Mutex::default(),
+            pinned_bytes: AtomicUsize::new(0),
             writebuf_stats,
         }
     }
 
     pub(crate) async fn begin(&self) -> PageTxn<E> {
-        let buffer_id = self.version.buffer_set.acquire_active_buffer_id().await;
+        let buffer_set = &self.version.buffer_set;
+        // Apply backpressure if the former versions are piling up.
+        buffer_set.wait_pending_frees().await;
+        let buffer_id = buffer_set.acquire_active_buffer_id().await;
         PageTxn {
             guard: self,
             buffer_id,
             hold_write_guard: false,
+            writer_permit: None,
             records: HashMap::default(),
             page_ids: Vec::default(),
         }
@@ -135,28 +166,81 @@ impl<E: Env> Guard<E> {
     /// [`MAX_ID`]: super::MAX_ID
     #[inline]
     pub(crate) fn page_addr(&self, id: u64) -> u64 {
-        self.page_table.get(id)
+        self.page_table.get(PageId::new(id)).into()
     }
 
+    /// Returns the address of the corresponding page.
+    ///
+    /// Returns [`None`] if the page id has never been allocated.
+    #[inline]
+    pub(crate) fn try_page_addr(&self, id: u64) -> Option<u64> {
+        self.page_table.try_get(PageId::new(id)).map(u64::from)
+    }
+
+    /// Deallocates the page id once no one is able to access it.
+    ///
+    /// The id must be unreachable for new guards. It is released along with
+    /// the latest version, after all the guards that may have observed it.
+    pub(crate) fn dealloc_page_id(&self, id: u64) {
+        let version = self
+            .version
+            .refresh()
+            .unwrap_or_else(|| self.version.clone());
+        let page_table = self.page_table.clone();
+        let pending = version.buffer_set.pending_frees().page_ids();
+        version.defer_until_released(pending_frees::counted(pending, move || {
+            // Safety: the id is not accessible anymore.
+            unsafe { page_table.dealloc(PageId::new(id)) };
+        }));
+    }
+
+    /// Returns the info of the page at the address.
+    ///
+    /// Returns [`Error::Corrupted`] if there is no page at the address, which
+    /// happens if the address comes from corrupted data.
     pub(crate) fn read_page_info(&self, addr: u64) -> Result<PageInfo> {
         let logical_id = (addr >> 32) as u32;
         if let Some(buf) = self.version.get(logical_id) {
             // Safety: all mutable references are released.
-            let page = unsafe { buf.page(addr) };
+            let page = unsafe { buf.page(PageAddr::from_raw(addr)) };
             return Ok(page.info());
         }
 
         let Some(file_info) = self.version.page_groups().get(&logical_id) else {
-            panic!("File {logical_id} (addr {addr}) is not exists");
+            warn!("File {logical_id} (addr {addr}) is not exists");
+            return Err(Error::Corrupted);
         };
 
         let Some(page_info) = file_info.get_page_info(addr) else {
-            panic!("The addr {addr} is not belongs to the target file");
+            warn!("The addr {addr} is not belongs to the target file");
+            return Err(Error::Corrupted);
         };
 
         Ok(page_info)
     }
 
+    /// Returns the size of the page at the address as it is stored, which is
+    /// the size in the file if the page is flushed, or the size in the write
+    /// buffer otherwise.
+    pub(crate) fn page_stored_size(&self, addr: u64) -> Result<usize> {
+        let logical_id = (addr >> 32) as u32;
+        if let Some(buf) = self.version.get(logical_id) {
+            // Safety: all mutable references are released.
+            let page = unsafe { buf.page(PageAddr::from_raw(addr)) };
+            return Ok(page.size());
+        }
+
+        let Some(page_group) = self.version.page_groups().get(&logical_id) else {
+            warn!("File {logical_id} (addr {addr}) is not exists");
+            return Err(Error::Corrupted);
+        };
+        let Some(handle) = page_group.get_page_handle(addr) else {
+            warn!("The addr {addr} is not belongs to the target file");
+            return Err(Error::Corrupted);
+        };
+        Ok(handle.size as usize)
+    }
+
     pub(crate) async fn read_page(
         &self,
         addr: u64,
@@ -164,14 +248,22 @@ impl<E: Env> Guard<E> {
     ) -> Result<(PageRef, Option<CacheToken>)> {
         let logical_id = (addr >> 32) as u32;
         if let Some(buf) = self.version.get(logical_id) {
+            let page_addr = PageAddr::from_raw(addr);
+            if !buf.contains_page(page_addr) {
+                warn!("The addr {page_addr} is out of the write buffer {logical_id}");
+                return Err(Error::InvalidArgument);
+            }
             self.writebuf_stats.read_in_buf.inc();
             // Safety: all mutable references are released.
-            return Ok((unsafe { buf.page(addr) }, None));
+            let page = unsafe { buf.page(page_addr) };
+            self.pinned_bytes.fetch_add(page.size(), Ordering::Relaxed);
+            return Ok((page, None));
         }
         self.writebuf_stats.read_in_file.inc();
 
         let Some(page_group) = self.version.page_groups().get(&logical_id) else {
-            panic!("File {logical_id} (addr {addr}) is not exists");
+            warn!("File {logical_id} (addr {addr}) is not exists");
+            return Err(Error::Corrupted);
         };
 
         let physical_id = page_group.meta().file_id;
@@ -179,7 +271,8 @@ impl<E: Env> Guard<E> {
             panic!("Target file {physical_id} is missing");
         };
         let Some(handle) = page_group.get_page_handle(addr) else {
-            panic!("The addr {addr} is not belongs to the target file {physical_id:?}");
+            warn!("The addr {addr} is not belongs to the target file {physical_id:?}");
+            return Err(Error::Corrupted);
         };
 
         let (entry, hit) = self
@@ -192,6 +285,7 @@ impl<E: Env> Guard<E> {
 
         let last_guard = owned_pages.last().unwrap();
         let page = last_guard.value();
+        self.pinned_bytes.fetch_add(page.len(), Ordering::Relaxed);
         if !hit {
             self.writebuf_stats.read_file_bytes.add(page.len() as u64);
         }
@@ -208,6 +302,47 @@ impl<E: Env> Guard<E> {
 
         Ok((page, Some(cache_token)))
     }
+
+    /// Returns the size of pages read by this guard, either from write buffers
+    /// or from files.
+    ///
+    /// A page is counted every time it is read.
+    #[inline]
+    pub(crate) fn pinned_bytes(&self) -> usize {
+        self.pinned_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Returns the current position in the pages read by this guard.
+    pub(crate) fn pin_mark(&self) -> PinMark {
+        let owned_pages = self.cache_guards.lock().expect("Poisoned");
+        PinMark {
+            num_cache_guards: owned_pages.len(),
+            pinned_bytes: self.pinned_bytes(),
+        }
+    }
+
+    /// Releases the pages read since the mark, so that the pages from files
+    /// can be evicted.
+    ///
+    /// # Safety
+    ///
+    /// Users must ensure that no references to the pages read since the mark
+    /// are alive.
+    pub(crate) unsafe fn unpin_since(&self, mark: PinMark) {
+        let mut owned_pages = self.cache_guards.lock().expect("Poisoned");
+        owned_pages.truncate(mark.num_cache_guards);
+        self.pinned_bytes
+            .store(mark.pinned_bytes, Ordering::Relaxed);
+    }
+
+    /// Consumes the guard and returns the cache entries of the pages it read
+    /// from files.
+    ///
+    /// The pages are not evicted from the cache until the entries are
+    /// dropped.
+    pub(crate) fn into_cache_entries(self) -> Vec<CacheEntryGuard> {
+        self.cache_guards.into_inner().expect("Poisoned")
+    }
 }
 
 /// A transaction to manipulate pages in a page store.
@@ -231,8 +366,11 @@ where
     // WriteBuffer. "hold_write_guard" is set to true when the first time we allocate a page
     // buffer from WriteBuffer.
     hold_write_guard: bool,
+    // The slot of the writers across all buffers, which is held with the write
+    // guard.
+    writer_permit: Option<WriterPermit<'a>>,
     records: HashMap<u64 /* page addr */, &'a mut RecordHeader>,
-    page_ids: Vec<u64>,
+    page_ids: Vec<PageId>,
 }
 
 impl<'a, E: Env> PageTxn<'a, E> {
@@ -249,27 +387,59 @@ impl<'a, E: Env> PageTxn<'a, E> {
         Ok((addr, buf))
     }
 
+    /// Allocates page buffers with the given sizes from one write buffer at
+    /// once.
+    ///
+    /// Returns the addresses and buffers of the allocated pages in order.
+    ///
+    /// If the transaction aborts, all pages allocated by this transaction will
+    /// be deallocated.
+    pub(crate) async fn alloc_pages(&mut self, sizes: &[usize]) -> Result<Vec<(u64, PageBuf<'a>)>> {
+        let new_page_list: Vec<_> = sizes.iter().map(|size| (NAN_ID, *size as u32)).collect();
+        let records = self
+            .with_write_guard(|buf, is_first_op| unsafe {
+                // Safety: [`guard`] guarantees the lifetime of the page reference.
+                buf.alloc_pages(&new_page_list, is_first_op)
+            })
+            .await?;
+        let pages = records
+            .into_iter()
+            .map(|(addr, header, buf)| {
+                self.records.insert(addr, header);
+                (addr, buf)
+            })
+            .collect();
+        Ok(pages)
+    }
+
     /// Inserts a new page into the store. Insertion happens when page splits or
     /// tree initializes. It returns the id of the inserted page.
     ///
     /// If the transaction aborts, the inserted page will be deleted.
     ///
+    /// If page ids are exhausted, the ids whose deallocation is deferred until
+    /// former versions are released are reclaimed, and [`Error::OutOfPageIds`]
+    /// is returned if no id is available after a bounded number of attempts.
+    ///
     /// # Panics
     ///
     /// Panics if `addr` is not allocated by this transaction.
-    pub(crate) fn insert_page(&mut self, addr: u64) -> u64 {
-        let header = self.records.get_mut(&addr).expect("no such pages");
+    pub(crate) async fn insert_page(&mut self, addr: u64) -> Result<u64> {
+        let header = self.records.get(&addr).expect("no such pages");
         if header.is_tombstone() {
             panic!("insert page with tombstone");
         }
 
-        // TODO: safety conditions
-        let page_id = unsafe { self.guard.page_table.alloc() }.expect("page id is exhausted");
-        self.guard.page_table.set(page_id, addr);
+        let page_id = match self.alloc_page_id() {
+            Some(id) => id,
+            None => self.reclaim_page_id().await?,
+        };
+        self.guard.page_table.set(page_id, PageAddr::from_raw(addr));
 
-        header.set_page_id(page_id);
+        let header = self.records.get_mut(&addr).expect("no such pages");
+        header.set_page_id(page_id.into());
         self.page_ids.push(page_id);
-        page_id
+        Ok(page_id.into())
     }
 
     /// Updates the page address to `new_addr` if its current value is the same
@@ -293,8 +463,9 @@ impl<'a, E: Env> PageTxn<'a, E> {
             return Err(None);
         }
 
-        if let Err(addr) = self.guard.page_table.cas(id, old_addr, new_addr) {
-            return Err(Some((self, addr)));
+        let (old, new) = (PageAddr::from_raw(old_addr), PageAddr::from_raw(new_addr));
+        if let Err(addr) = self.guard.page_table.cas(PageId::new(id), old, new) {
+            return Err(Some((self, addr.into())));
         }
 
         let record_header = self
@@ -307,6 +478,37 @@ impl<'a, E: Env> PageTxn<'a, E> {
         Ok(())
     }
 
+    /// Updates the page address to `new_addr` if its current value is the same
+    /// as `old_addr`, without committing the transaction.
+    ///
+    /// This is used to install several pages in one transaction. The page at
+    /// `new_addr` is kept even if the transaction aborts later. On failure, if
+    /// `new_addr` is not large than `old_addr`, `Err(None)` is returned,
+    /// otherwise returns the current address of the page.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new_addr` is not allocated by this transaction.
+    pub(crate) fn try_update_page(
+        &mut self,
+        id: u64,
+        old_addr: u64,
+        new_addr: u64,
+    ) -> Result<(), Option<u64>> {
+        if new_addr <= old_addr {
+            return Err(None);
+        }
+
+        let (old, new) = (PageAddr::from_raw(old_addr), PageAddr::from_raw(new_addr));
+        if let Err(addr) = self.guard.page_table.cas(PageId::new(id), old, new) {
+            return Err(Some(addr.into()));
+        }
+
+        let record_header = self.records.remove(&new_addr).expect("No such page exists");
+        record_header.set_page_id(id);
+        Ok(())
+    }
+
     /// This function is similar to [`Self::update_page`], except that it also
     /// deallocates some pages on success.
     ///
@@ -335,6 +537,37 @@ impl<'a, E: Env> PageTxn<'a, E> {
         Ok(())
     }
 
+    /// Deallocates the pages and commits the transaction.
+    ///
+    /// This releases pages that are never reachable from any page id, such as
+    /// the pages of an aborted bulk load. The deallocated pages will still be
+    /// valid until no one is able to access them.
+    pub(crate) async fn dealloc_pages(mut self, page_addrs: &[u64]) -> Result<()> {
+        self.dealloc_pages_impl(page_addrs).await?;
+        self.commit();
+        Ok(())
+    }
+
+    #[inline]
+    fn alloc_page_id(&self) -> Option<PageId> {
+        // TODO: safety conditions
+        unsafe { self.guard.page_table.alloc() }
+    }
+
+    // Advances the epoch to release former versions, which runs the page id
+    // deallocations deferred to them, until an id becomes available.
+    async fn reclaim_page_id(&self) -> Result<PageId> {
+        for _ in 0..MAX_PAGE_ID_RECLAIM_ATTEMPTS {
+            VersionOwner::collect_pending_frees();
+            if let Some(id) = self.alloc_page_id() {
+                return Ok(id);
+            }
+            // Let the tasks pinning the epoch make progress.
+            photonio::task::yield_now().await;
+        }
+        Err(Error::OutOfPageIds)
+    }
+
     #[inline]
     async fn alloc_page_impl(
         &mut self,
@@ -359,29 +592,54 @@ impl<'a, E: Env> PageTxn<'a, E> {
     #[inline]
     async fn with_write_guard<F, O>(&mut self, f: F) -> Result<O>
     where
-        F: FnOnce(&WriteBuffer, bool) -> Result<O>,
+        F: Fn(&WriteBuffer, bool) -> Result<O>,
     {
         let is_first_op = !self.hold_write_guard;
+        let guard = self.guard;
+        let buffer_set = &guard.version.buffer_set;
+        if buffer_set.is_read_only() {
+            return Err(Error::ReadOnly);
+        }
+        // The permit is released once dropped, if the write fails or the
+        // transaction is cancelled before it holds the write guard.
+        let permit = if is_first_op {
+            Some(buffer_set.acquire_writer().await)
+        } else {
+            None
+        };
         let result = {
             let buffer = self
                 .guard
                 .version
                 .get(self.buffer_id)
-                .expect("The target buffer must exists");
-            f(&buffer, is_first_op)
+                .expect("The target buffer must exists")
+                .clone();
+            // Queue up behind the writers waiting for the buffer, if any.
+            let mut turn = buffer.admit(false).await;
+            let mut result = f(&buffer, is_first_op);
+            if matches!(result, Err(Error::Contended)) {
+                // The fast path is contended, retry with a turn.
+                turn = buffer.admit(true).await;
+                result = f(&buffer, is_first_op);
+            }
+            drop(turn);
+            result
         };
         match result {
             Ok(val) => {
+                if is_first_op {
+                    self.writer_permit = permit;
+                }
                 self.hold_write_guard = true;
                 Ok(val)
             }
             Err(Error::TooLargeSize) => Err(Error::TooLargeSize),
             Err(Error::Again) => {
-                self.guard
-                    .version
-                    .buffer_set
-                    .switch_buffer(self.buffer_id)
-                    .await;
+                buffer_set.switch_buffer(self.buffer_id).await;
+                // No buffer can be installed after the last one.
+                if self.buffer_id >= MAX_BUFFER_ID {
+                    return Err(Error::OutOfFileIds);
+                }
                 Err(Error::Again)
             }
             _ => unreachable!(),
@@ -411,6 +669,7 @@ impl<'a, E: Env> PageTxn<'a, E> {
         if matches!(release_state, ReleaseState::Flush) {
             self.guard.version.buffer_set.notify_flush_job();
         }
+        self.writer_permit = None;
     }
 }
 
@@ -436,8 +695,9 @@ mod tests {
     use super::*;
     use crate::{
         page_store::{
+            buffer_set::BufferSet,
             page_table::PageTable,
-            version::{DeltaVersion, Version},
+            version::{DeltaVersion, Version, VersionUpdateReason},
         },
         PageStoreOptions,
     };
@@ -460,13 +720,71 @@ mod tests {
         let guard = Guard::new(version.clone(), page_table, files, Default::default());
         let mut page_txn = guard.begin().await;
         let (addr, _) = page_txn.alloc_page(123).await.unwrap();
-        let id = page_txn.insert_page(addr);
+        let id = page_txn.insert_page(addr).await.unwrap();
         let (new, _) = page_txn.alloc_page(123).await.unwrap();
         assert!(page_txn.update_page(id, addr, new).is_ok());
 
         assert_current_buffer_is_flushable(version);
     }
 
+    #[photonio::test]
+    async fn page_txn_cancelled_releases_writer_permit() {
+        let env = crate::env::Photon;
+        let base = tempdir::TempDir::new("test_page_txn_cancelled_writer").unwrap();
+        let files = Arc::new(
+            PageFiles::new(env, base.path(), &test_option())
+                .await
+                .unwrap(),
+        );
+        let buffer_set = BufferSet::new(1, 512, 8, true).with_max_concurrent_writers(1);
+        let version = Arc::new(Version::with_buffer_set(
+            1,
+            Arc::new(buffer_set),
+            DeltaVersion::default(),
+        ));
+        let page_table = PageTable::default();
+        let guard = Guard::new(version.clone(), page_table, files, Default::default());
+
+        // Hold a turn of the buffer, so that the writer waits to be admitted
+        // after acquiring its permit, and cancel it there.
+        let buffer = version.get(1).unwrap().clone();
+        let turn = buffer.admit(true).await;
+        let mut page_txn = guard.begin().await;
+        {
+            let mut alloc = Box::pin(page_txn.alloc_page(123));
+            assert!(futures::poll!(alloc.as_mut()).is_pending());
+        }
+        drop(page_txn);
+        drop(turn);
+
+        // The permit of the cancelled writer is released.
+        let mut page_txn = guard.begin().await;
+        let mut alloc = Box::pin(page_txn.alloc_page(123));
+        assert!(futures::poll!(alloc.as_mut()).is_ready());
+    }
+
+    #[photonio::test]
+    async fn guard_read_unknown_page() {
+        let env = crate::env::Photon;
+        let base = tempdir::TempDir::new("test_guard_read_unknown_page").unwrap();
+        let files = Arc::new(
+            PageFiles::new(env, base.path(), &test_option())
+                .await
+                .unwrap(),
+        );
+        let version = new_version(512);
+        let page_table = PageTable::default();
+        let guard = Guard::new(version, page_table, files, Default::default());
+
+        // The address belongs to neither a write buffer nor a file.
+        let addr = (100 << 32) | 64;
+        assert!(matches!(guard.read_page_info(addr), Err(Error::Corrupted)));
+        assert!(matches!(
+            guard.read_page(addr, CacheOption::default()).await,
+            Err(Error::Corrupted)
+        ));
+    }
+
     #[photonio::test]
     async fn page_txn_failed_update_page() {
         let env = crate::env::Photon;
@@ -484,7 +802,7 @@ mod tests {
         // insert old page.
         let mut page_txn = guard.begin().await;
         let (addr, _) = page_txn.alloc_page(123).await.unwrap();
-        let id = page_txn.insert_page(addr);
+        let id = page_txn.insert_page(addr).await.unwrap();
         let (new, _) = page_txn.alloc_page(123).await.unwrap();
         assert!(page_txn.update_page(id, addr, new).is_ok());
 
@@ -528,7 +846,7 @@ mod tests {
         let guard = Guard::new(version.clone(), page_table, files, Default::default());
         let mut page_txn = guard.begin().await;
         let (addr, _) = page_txn.alloc_page(123).await.unwrap();
-        let id = page_txn.insert_page(addr);
+        let id = page_txn.insert_page(addr).await.unwrap();
         let (new, _) = page_txn.alloc_page(123).await.unwrap();
         assert!(page_txn
             .replace_page(id, addr, new, &[1, 2, 3])
@@ -604,10 +922,70 @@ mod tests {
         );
         let mut page_txn = guard.begin().await;
         let (addr, _) = page_txn.alloc_page(123).await.unwrap();
-        let id = page_txn.insert_page(addr);
+        let id = page_txn.insert_page(addr).await.unwrap();
+        page_txn.commit();
+
+        assert_eq!(u64::from(page_table.get(PageId::new(id))), addr);
+        assert_current_buffer_is_flushable(version);
+    }
+
+    #[photonio::test]
+    async fn page_txn_insert_page_exhausted() {
+        let env = crate::env::Photon;
+        let base = tempdir::TempDir::new("test_page_insert_page_exhausted").unwrap();
+        let files = Arc::new(
+            PageFiles::new(env, base.path(), &test_option())
+                .await
+                .unwrap(),
+        );
+
+        let owner = VersionOwner::new(Version::new(1 << 10, 1, 8, DeltaVersion::default()));
+        let page_table = PageTable::with_capacity(3);
+        let guard = Guard::new(
+            owner.current(),
+            page_table.clone(),
+            files.clone(),
+            Default::default(),
+        );
+
+        // Exhaust all page ids.
+        let mut page_txn = guard.begin().await;
+        let (addr, _) = page_txn.alloc_page(16).await.unwrap();
+        let id = page_txn.insert_page(addr).await.unwrap();
+        let (addr, _) = page_txn.alloc_page(16).await.unwrap();
+        page_txn.insert_page(addr).await.unwrap();
+        page_txn.commit();
+
+        let mut page_txn = guard.begin().await;
+        let (addr, _) = page_txn.alloc_page(16).await.unwrap();
+        assert!(matches!(
+            page_txn.insert_page(addr).await,
+            Err(Error::OutOfPageIds)
+        ));
+        drop(page_txn);
+
+        // The id is released along with the current version, which is only
+        // freed by advancing the epoch once a new version is installed.
+        guard.dealloc_page_id(id);
+        let buffer_set = owner.current().buffer_set.clone();
+        assert_eq!(buffer_set.pending_frees().num_page_ids(), 1);
+        drop(guard);
+        // Safety: no concurrent operations.
+        unsafe {
+            owner.install(DeltaVersion {
+                reason: VersionUpdateReason::Compact,
+                ..Default::default()
+            })
+        };
+
+        let version = owner.current();
+        let guard = Guard::new(version.clone(), page_table, files, Default::default());
+        let mut page_txn = guard.begin().await;
+        let (addr, _) = page_txn.alloc_page(16).await.unwrap();
+        assert_eq!(page_txn.insert_page(addr).await.unwrap(), id);
+        assert_eq!(buffer_set.pending_frees().num_page_ids(), 0);
         page_txn.commit();
 
-        assert_eq!(page_table.get(id), addr);
         assert_current_buffer_is_flushable(version);
     }
 
@@ -0,0 +1,123 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use crossbeam_epoch::Guard;
+
+use super::{buffer_set::BufferSet, version::VersionOwner};
+
+/// Counts the frees deferred by a page store.
+///
+/// The frees of former versions run once the epochs advance, and the frees of
+/// page ids run once the versions that may observe them are released.
+#[derive(Default)]
+pub(crate) struct PendingFrees {
+    buffer_sets: Arc<AtomicU64>,
+    versions: Arc<AtomicU64>,
+    page_ids: Arc<AtomicU64>,
+}
+
+impl PendingFrees {
+    /// Returns the counter of the deferred frees of former buffer set
+    /// versions.
+    #[inline]
+    pub(super) fn buffer_sets(&self) -> &Arc<AtomicU64> {
+        &self.buffer_sets
+    }
+
+    /// Returns the counter of the deferred frees of former versions.
+    #[inline]
+    pub(super) fn versions(&self) -> &Arc<AtomicU64> {
+        &self.versions
+    }
+
+    /// Returns the counter of the deferred frees of page ids.
+    #[inline]
+    pub(super) fn page_ids(&self) -> &Arc<AtomicU64> {
+        &self.page_ids
+    }
+
+    /// Returns the number of deferred frees of former buffer set versions that
+    /// have not run yet.
+    #[inline]
+    pub(crate) fn num_buffer_sets(&self) -> u64 {
+        self.buffer_sets.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of deferred frees of former versions that have not
+    /// run yet.
+    #[inline]
+    pub(crate) fn num_versions(&self) -> u64 {
+        self.versions.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of deferred frees of page ids that have not run
+    /// yet.
+    #[inline]
+    pub(crate) fn num_page_ids(&self) -> u64 {
+        self.page_ids.load(Ordering::Relaxed)
+    }
+
+    // The frees of page ids wait for guards rather than epochs, so they are
+    // not drained.
+    #[inline]
+    fn total(&self) -> u64 {
+        self.num_buffer_sets() + self.num_versions()
+    }
+
+    /// Advances the epochs until there are at most `limit` pending frees.
+    ///
+    /// Returns false if the frees stop making progress, which happens if some
+    /// thread stays pinned.
+    pub(crate) async fn drain(&self, limit: u64) -> bool {
+        // The frees run two epochs after they are deferred, so it takes a few
+        // collections to make progress.
+        const MAX_STALLS: usize = 4;
+
+        let mut last = self.total();
+        let mut stalls = 0;
+        while last > limit {
+            BufferSet::collect_pending_frees();
+            VersionOwner::collect_pending_frees();
+            let current = self.total();
+            if current < last {
+                stalls = 0;
+            } else {
+                stalls += 1;
+                if stalls >= MAX_STALLS {
+                    return false;
+                }
+                // Let the tasks pinning the epochs on this thread move on.
+                photonio::task::yield_now().await;
+            }
+            last = current;
+        }
+        true
+    }
+}
+
+/// Defers `f` until all threads pinned in the collector of `guard` are
+/// unpinned.
+///
+/// The deferred function is counted in `pending` until it runs.
+pub(super) fn defer_free<F>(guard: &Guard, pending: &Arc<AtomicU64>, f: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    guard.defer(counted(pending, f));
+}
+
+/// Returns a function that runs `f`, which is counted in `pending` from now
+/// until it runs.
+pub(super) fn counted<F>(pending: &Arc<AtomicU64>, f: F) -> impl FnOnce() + Send + 'static
+where
+    F: FnOnce() + Send + 'static,
+{
+    let pending = pending.clone();
+    pending.fetch_add(1, Ordering::Relaxed);
+    move || {
+        f();
+        pending.fetch_sub(1, Ordering::Relaxed);
+    }
+}
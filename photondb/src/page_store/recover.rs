@@ -1,17 +1,35 @@
 use std::{
     collections::{HashMap, HashSet},
+    fmt,
     path::Path,
 };
 
-use log::debug;
+use log::{debug, warn};
 use rustc_hash::FxHashMap;
 
 use super::{
     page_table::{PageTable, PageTableBuilder},
     version::DeltaVersion,
-    FileInfo, NewFile, PageFiles, PageGroup, PageStore, Result, VersionEdit,
+    Error, FileInfo, NewFile, PageAddr, PageFiles, PageGroup, PageId, PageStore, Result,
+    VersionEdit,
 };
-use crate::{env::Env, page_store::Manifest};
+use crate::{env::Env, page::verify_page, page_store::Manifest};
+
+/// The corruptions found by the self-test on open.
+///
+/// See [`PageStoreOptions::self_test_on_open`](crate::PageStoreOptions::self_test_on_open).
+#[non_exhaustive]
+#[derive(Debug, Default)]
+pub struct SelfTestFailure {
+    /// The ids of the files whose footers or metadata can't be decoded.
+    pub corrupted_files: Vec<u32>,
+    /// The addresses of the active pages that are malformed or mismatch their
+    /// checksums.
+    pub corrupted_pages: Vec<u64>,
+    /// The addresses of the active pages whose page ids are not mapped to any
+    /// active page.
+    pub orphan_pages: Vec<u64>,
+}
 
 struct FileInfoBuilder<'a, E: Env> {
     facade: &'a PageFiles<E>,
@@ -46,18 +64,39 @@ impl<E: Env> PageStore<E> {
         PageFiles<E>,
         DeltaVersion,
     )> {
-        let mut manifest = Manifest::open(env.to_owned(), path.as_ref()).await?;
+        let exists = Manifest::exists(&env, path.as_ref()).await?;
+        if exists && options.error_if_exists {
+            return Err(Error::InvalidArgument);
+        }
+        if !exists && (options.read_only || !options.create_if_missing) {
+            return Err(Error::InvalidArgument);
+        }
+        let mut manifest = if options.read_only {
+            Manifest::open_read_only(env.to_owned(), path.as_ref()).await?
+        } else {
+            Manifest::open(env.to_owned(), path.as_ref()).await?
+        };
         let versions = manifest.list_versions().await?;
+        manifest.recover_metadata(&versions);
         let summary = Self::apply_version_edits(versions);
         debug!("Recover with file summary {summary:?}");
 
         let page_files = PageFiles::new(env, path.as_ref(), options).await?;
+        if options.self_test_on_open {
+            Self::check_file_footers(&page_files, &summary.active_files).await?;
+        }
 
         let mut builder = FileInfoBuilder::new(&page_files);
         Self::recover_page_groups(&mut builder, &summary.active_files).await?;
+        builder.maintain_active_pages();
+        if options.self_test_on_open {
+            builder.check_active_pages().await?;
+        }
         let (page_groups, file_infos, page_table) = builder.build();
 
-        Self::delete_unreferenced_page_files(&page_files, &summary).await?;
+        if !options.read_only {
+            Self::delete_unreferenced_page_files(&page_files, &summary).await?;
+        }
 
         let next_file_id = summary.next_file_id();
         manifest.reset_next_file_id(summary.next_file_id());
@@ -103,6 +142,24 @@ impl<E: Env> PageStore<E> {
         Ok(())
     }
 
+    /// Checks that the footers and metadata of all active files can be
+    /// decoded.
+    async fn check_file_footers(
+        page_files: &PageFiles<E>,
+        active_files: &HashMap<u32, NewFile>,
+    ) -> Result<()> {
+        let mut failure = SelfTestFailure::default();
+        for &file_id in active_files.keys() {
+            match page_files.read_file_meta(file_id).await {
+                Ok(_) => {}
+                Err(Error::Corrupted) => failure.corrupted_files.push(file_id),
+                Err(err) => return Err(err),
+            }
+        }
+        failure.corrupted_files.sort_unstable();
+        failure.into_result()
+    }
+
     async fn delete_unreferenced_page_files(
         page_files: &PageFiles<E>,
         summary: &FilesSummary,
@@ -170,6 +227,7 @@ impl<'a, E: Env> FileInfoBuilder<'a, E> {
         // 3. recover page table.
         for (_, page_table) in meta_reader.page_tables {
             for (page_addr, page_id) in page_table {
+                let (page_id, page_addr) = (PageId::new(page_id), PageAddr::from_raw(page_addr));
                 if self.page_table_builder.get(page_id) < page_addr {
                     self.page_table_builder.set(page_id, page_addr);
                 }
@@ -179,7 +237,58 @@ impl<'a, E: Env> FileInfoBuilder<'a, E> {
         Ok(())
     }
 
+    /// Checks that all active pages are well-formed, and that the page table
+    /// maps the id of each active page to an active page.
+    ///
+    /// This must be called after [`FileInfoBuilder::maintain_active_pages`].
+    async fn check_active_pages(&mut self) -> Result<()> {
+        let mut failure = SelfTestFailure::default();
+        let mut file_ids = self.file_infos.keys().cloned().collect::<Vec<_>>();
+        file_ids.sort_unstable();
+        for file_id in file_ids {
+            let file_meta = self.file_infos[&file_id].meta().clone();
+            let meta_reader = self.facade.read_file_meta(file_id).await?;
+            for (group_id, page_table) in &meta_reader.page_tables {
+                let Some(page_group) = self.page_groups.get(group_id) else {
+                    continue;
+                };
+                if page_group.meta().file_id != file_id {
+                    continue;
+                }
+                for page_addr in page_group.iter() {
+                    let handle = page_group.get_page_handle(page_addr).expect("Must exists");
+                    match self
+                        .facade
+                        .read_file_page(file_id, &file_meta, handle)
+                        .await
+                    {
+                        Ok(page) if verify_page(&page) => {}
+                        Ok(_) | Err(Error::Corrupted) => failure.corrupted_pages.push(page_addr),
+                        Err(err) => return Err(err),
+                    }
+                    let page_id = *page_table.get(&page_addr).expect("Must exists");
+                    let head_addr = self.page_table_builder.get(PageId::new(page_id)).into();
+                    if !self.is_page_active(head_addr) {
+                        failure.orphan_pages.push(page_addr);
+                    }
+                }
+            }
+        }
+        failure.corrupted_pages.sort_unstable();
+        failure.orphan_pages.sort_unstable();
+        failure.into_result()
+    }
+
+    fn is_page_active(&self, page_addr: u64) -> bool {
+        let group_id = (page_addr >> 32) as u32;
+        self.page_groups
+            .get(&group_id)
+            .map_or(false, |g| g.get_page_handle(page_addr).is_some())
+    }
+
     /// Build page groups, file infos, orphan page files, and page table.
+    ///
+    /// This must be called after [`FileInfoBuilder::maintain_active_pages`].
     fn build(
         mut self,
     ) -> (
@@ -187,7 +296,6 @@ impl<'a, E: Env> FileInfoBuilder<'a, E> {
         FxHashMap<u32, FileInfo>,
         PageTable,
     ) {
-        self.maintain_active_pages();
         self.page_groups.retain(|_, g| !g.is_empty());
         let page_table = self.page_table_builder.build();
         (self.page_groups, self.file_infos, page_table)
@@ -213,6 +321,29 @@ impl<'a, E: Env> FileInfoBuilder<'a, E> {
     }
 }
 
+impl SelfTestFailure {
+    fn into_result(self) -> Result<()> {
+        if self.corrupted_files.is_empty()
+            && self.corrupted_pages.is_empty()
+            && self.orphan_pages.is_empty()
+        {
+            return Ok(());
+        }
+        warn!("Self-test failed: {self}");
+        Err(Error::SelfTest(self))
+    }
+}
+
+impl fmt::Display for SelfTestFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "corrupted files {:?}, corrupted pages {:?}, orphan pages {:?}",
+            self.corrupted_files, self.corrupted_pages, self.orphan_pages
+        )
+    }
+}
+
 impl FilesSummary {
     fn next_file_id(&self) -> u32 {
         let val = std::cmp::max(
@@ -1,4 +1,4 @@
-use std::fmt::Display;
+use std::{collections::VecDeque, fmt::Display, sync::Mutex, time::Duration};
 
 use crate::util::atomic::Counter;
 
@@ -15,6 +15,10 @@ pub struct StoreStats {
     pub jobs: JobStats,
     /// Statistics of buffer set.
     pub buffer_set: BufferSetStats,
+    /// Statistics of epoch-based reclamation.
+    pub epoch: EpochStats,
+    /// Statistics of page table.
+    pub page_table: PageTableStats,
 }
 
 impl StoreStats {
@@ -26,6 +30,8 @@ impl StoreStats {
             writebuf: self.writebuf.sub(&o.writebuf),
             jobs: self.jobs.sub(&o.jobs),
             buffer_set: self.buffer_set.sub(&o.buffer_set),
+            epoch: self.epoch,
+            page_table: self.page_table.sub(&o.page_table),
         }
     }
 }
@@ -35,7 +41,7 @@ impl Display for StoreStats {
         self.writebuf.fmt(f)?;
         writeln!(
             f,
-            "PageCacheStats: lookup_hit: {}, lookup_miss: {}, hit_rate: {:.2}%, insert: {}, active_evict: {}, passive_evict: {}, recommendation: {:?}",
+            "PageCacheStats: lookup_hit: {}, lookup_miss: {}, hit_rate: {:.2}%, insert: {}, active_evict: {}, passive_evict: {}, usage: {}, capacity: {}, recommendation: {:?}",
             self.page_cache.lookup_hit,
             self.page_cache.lookup_miss,
             (self.page_cache.lookup_hit as f64) * 100.
@@ -43,11 +49,13 @@ impl Display for StoreStats {
             self.page_cache.insert,
             self.page_cache.active_evict,
             self.page_cache.passive_evict,
+            self.page_cache.usage,
+            self.page_cache.capacity,
             self.page_cache.recommendation,
         )?;
         writeln!(
             f,
-            "FileReaderCacheStats: lookup_hit: {}, lookup_miss: {}, hit_rate: {}%, insert: {}, active_evict: {}, passive_evict: {}, recommendation: {:?}",
+            "FileReaderCacheStats: lookup_hit: {}, lookup_miss: {}, hit_rate: {}%, insert: {}, active_evict: {}, passive_evict: {}, usage: {}, capacity: {}, recommendation: {:?}",
             self.file_reader_cache.lookup_hit,
             self.file_reader_cache.lookup_miss,
             (self.file_reader_cache.lookup_hit as f64) * 100.
@@ -55,9 +63,13 @@ impl Display for StoreStats {
             self.file_reader_cache.insert,
             self.file_reader_cache.active_evict,
             self.file_reader_cache.passive_evict,
+            self.file_reader_cache.usage,
+            self.file_reader_cache.capacity,
             self.file_reader_cache.recommendation,
         )?;
         self.buffer_set.fmt(f)?;
+        self.epoch.fmt(f)?;
+        self.page_table.fmt(f)?;
         self.jobs.fmt(f)
     }
 }
@@ -71,6 +83,11 @@ pub struct CacheStats {
     pub active_evict: u64,
     pub passive_evict: u64,
     pub recommendation: Vec<String>,
+    /// The total charge of the entries in the cache, which is the size of
+    /// pages for the page cache.
+    pub usage: u64,
+    /// The capacity of the cache, in the same unit as `usage`.
+    pub capacity: u64,
 }
 
 impl CacheStats {
@@ -82,9 +99,16 @@ impl CacheStats {
             active_evict: self.active_evict.wrapping_sub(o.active_evict),
             passive_evict: self.passive_evict.wrapping_sub(o.passive_evict),
             recommendation: self.recommendation.to_owned(),
+            usage: self.usage,
+            capacity: self.capacity,
         }
     }
 
+    /// Returns the number of entries evicted from the cache.
+    pub fn evictions(&self) -> u64 {
+        self.active_evict.wrapping_add(self.passive_evict)
+    }
+
     pub(crate) fn add(&self, o: &CacheStats) -> CacheStats {
         CacheStats {
             lookup_hit: self.lookup_hit.wrapping_add(o.lookup_hit),
@@ -93,6 +117,8 @@ impl CacheStats {
             active_evict: self.active_evict.wrapping_add(o.active_evict),
             passive_evict: self.passive_evict.wrapping_add(o.passive_evict),
             recommendation: [self.recommendation.to_owned(), o.recommendation.to_owned()].concat(),
+            usage: self.usage.wrapping_add(o.usage),
+            capacity: self.capacity.wrapping_add(o.capacity),
         }
     }
 }
@@ -163,6 +189,16 @@ pub struct JobStats {
     pub compact_input_bytes: u64,
     /// The total bytes read during compaction.
     pub read_file_bytes: u64,
+    /// The total number of flushed files.
+    pub flush_files: u64,
+    /// The total bytes of flushed files.
+    pub flush_file_bytes: u64,
+    /// The total duration of flushes in microseconds.
+    pub flush_duration_micros: u64,
+    /// The average duration of recent flushes in microseconds.
+    pub recent_flush_duration_micros: u64,
+    /// The throughput of recent flushes in bytes per second.
+    pub recent_flush_throughput: u64,
 }
 
 #[derive(Default, Debug)]
@@ -172,6 +208,41 @@ pub(crate) struct AtomicJobStats {
     pub(super) compact_write_bytes: Counter,
     pub(super) compact_input_bytes: Counter,
     pub(super) read_file_bytes: Counter,
+    pub(super) flush_files: Counter,
+    pub(super) flush_file_bytes: Counter,
+    pub(super) flush_duration_micros: Counter,
+    recent_flushes: Mutex<RecentFlushes>,
+}
+
+/// A window of the most recent flushes.
+#[derive(Default, Debug)]
+struct RecentFlushes {
+    // The file bytes and the duration of each flush, from the oldest.
+    flushes: VecDeque<(u64, Duration)>,
+}
+
+impl RecentFlushes {
+    const WINDOW: usize = 16;
+
+    fn push(&mut self, file_bytes: u64, duration: Duration) {
+        if self.flushes.len() == Self::WINDOW {
+            self.flushes.pop_front();
+        }
+        self.flushes.push_back((file_bytes, duration));
+    }
+
+    /// Returns the average duration in microseconds and the throughput in
+    /// bytes per second.
+    fn average(&self) -> (u64, u64) {
+        let bytes = self.flushes.iter().map(|(bytes, _)| bytes).sum::<u64>();
+        let duration = self.flushes.iter().map(|(_, d)| d).sum::<Duration>();
+        if self.flushes.is_empty() || duration.is_zero() {
+            return (0, 0);
+        }
+        let avg_duration = duration.as_micros() as u64 / self.flushes.len() as u64;
+        let throughput = (bytes as f64 / duration.as_secs_f64()) as u64;
+        (avg_duration, throughput)
+    }
 }
 
 impl JobStats {
@@ -182,6 +253,14 @@ impl JobStats {
             compact_write_bytes: self.compact_write_bytes.wrapping_sub(o.compact_write_bytes),
             compact_input_bytes: self.compact_input_bytes.wrapping_sub(o.compact_input_bytes),
             read_file_bytes: self.read_file_bytes.wrapping_sub(o.read_file_bytes),
+            flush_files: self.flush_files.wrapping_sub(o.flush_files),
+            flush_file_bytes: self.flush_file_bytes.wrapping_sub(o.flush_file_bytes),
+            flush_duration_micros: self
+                .flush_duration_micros
+                .wrapping_sub(o.flush_duration_micros),
+            // The recent values are not accumulated.
+            recent_flush_duration_micros: self.recent_flush_duration_micros,
+            recent_flush_throughput: self.recent_flush_throughput,
         }
     }
 }
@@ -202,27 +281,56 @@ impl Display for JobStats {
             compact_input_bytes: {}, \
             compact_write_bytes: {}, \
             read_file_bytes: {}, \
-            write_amp: {:.2}",
+            write_amp: {:.2}, \
+            flush_files: {}, \
+            flush_file_bytes: {}, \
+            flush_duration_micros: {}, \
+            recent_flush_duration_micros: {}, \
+            recent_flush_throughput: {}",
             self.flush_write_bytes,
             self.flush_discard_bytes,
             self.compact_input_bytes,
             self.compact_write_bytes,
             self.read_file_bytes,
-            write_amp
+            write_amp,
+            self.flush_files,
+            self.flush_file_bytes,
+            self.flush_duration_micros,
+            self.recent_flush_duration_micros,
+            self.recent_flush_throughput,
         )
     }
 }
 
 impl AtomicJobStats {
     pub(crate) fn snapshot(&self) -> JobStats {
+        let (recent_flush_duration_micros, recent_flush_throughput) =
+            self.recent_flushes.lock().expect("Poisoned").average();
         JobStats {
             flush_write_bytes: self.flush_write_bytes.get(),
             flush_discard_bytes: self.flush_discard_bytes.get(),
             compact_write_bytes: self.compact_write_bytes.get(),
             compact_input_bytes: self.compact_input_bytes.get(),
             read_file_bytes: self.read_file_bytes.get(),
+            flush_files: self.flush_files.get(),
+            flush_file_bytes: self.flush_file_bytes.get(),
+            flush_duration_micros: self.flush_duration_micros.get(),
+            recent_flush_duration_micros,
+            recent_flush_throughput,
         }
     }
+
+    /// Records a flush that outputs a file with `file_bytes` bytes in
+    /// `duration`.
+    pub(crate) fn record_flush(&self, file_bytes: u64, duration: Duration) {
+        self.flush_files.inc();
+        self.flush_file_bytes.add(file_bytes);
+        self.flush_duration_micros.add(duration.as_micros() as u64);
+        self.recent_flushes
+            .lock()
+            .expect("Poisoned")
+            .push(file_bytes, duration);
+    }
 }
 
 #[derive(Debug, Copy, Clone, Default)]
@@ -231,6 +339,12 @@ pub struct BufferSetStats {
     pub stall_writes: u64,
     /// The total interval of stalling writes.
     pub stall_intervals_ms: u64,
+    /// The id of the next write buffer, which is the watermark of the file
+    /// ids in use.
+    pub next_buffer_id: u32,
+    /// The number of sealed write buffers that are not released yet, which
+    /// is the backlog of the flush job.
+    pub sealed_buffers: u32,
 }
 
 impl BufferSetStats {
@@ -238,6 +352,8 @@ impl BufferSetStats {
         BufferSetStats {
             stall_writes: self.stall_writes.wrapping_sub(o.stall_writes),
             stall_intervals_ms: self.stall_intervals_ms.wrapping_sub(o.stall_intervals_ms),
+            next_buffer_id: self.next_buffer_id,
+            sealed_buffers: self.sealed_buffers,
         }
     }
 }
@@ -246,8 +362,33 @@ impl Display for BufferSetStats {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(
             f,
-            "BufferSet: stall_writes: {} stall_intervals_ms: {}",
-            self.stall_writes, self.stall_intervals_ms,
+            "BufferSet: stall_writes: {} stall_intervals_ms: {} next_buffer_id: {} sealed_buffers: {}",
+            self.stall_writes, self.stall_intervals_ms, self.next_buffer_id, self.sealed_buffers,
+        )
+    }
+}
+
+/// Statistics of epoch-based reclamation.
+///
+/// The values are gauges, which keep growing if epochs don't advance.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct EpochStats {
+    /// The number of deferred buffer set versions that are not freed yet.
+    pub pending_buffer_set_frees: u64,
+    /// The number of deferred versions that are not freed yet.
+    pub pending_version_frees: u64,
+    /// The number of deallocated page ids that are not freed yet, which wait
+    /// for the versions that may observe them to be released.
+    pub pending_page_id_frees: u64,
+}
+
+impl Display for EpochStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "EpochStats: pending_buffer_set_frees: {} pending_version_frees: {} \
+             pending_page_id_frees: {}",
+            self.pending_buffer_set_frees, self.pending_version_frees, self.pending_page_id_frees,
         )
     }
 }
@@ -263,6 +404,94 @@ impl AtomicBufferSetStats {
         BufferSetStats {
             stall_writes: self.stall_writes.get(),
             stall_intervals_ms: self.stall_intervals_ms.get(),
+            next_buffer_id: 0,
+            sealed_buffers: 0,
+        }
+    }
+}
+
+/// Statistics of page table.
+///
+/// The counters are only maintained with the `contention-stats` feature
+/// enabled, otherwise they are always zero.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct PageTableStats {
+    /// The total number of CAS retries to allocate page ids.
+    pub alloc_retries: u64,
+    /// The total number of CAS retries to deallocate page ids.
+    pub dealloc_retries: u64,
+    /// The total number of failed CASs to update page addresses.
+    pub update_conflicts: u64,
+}
+
+impl PageTableStats {
+    pub(crate) fn sub(&self, o: &Self) -> Self {
+        PageTableStats {
+            alloc_retries: self.alloc_retries.wrapping_sub(o.alloc_retries),
+            dealloc_retries: self.dealloc_retries.wrapping_sub(o.dealloc_retries),
+            update_conflicts: self.update_conflicts.wrapping_sub(o.update_conflicts),
+        }
+    }
+}
+
+impl Display for PageTableStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "PageTableStats: alloc_retries: {} dealloc_retries: {} update_conflicts: {}",
+            self.alloc_retries, self.dealloc_retries, self.update_conflicts,
+        )
+    }
+}
+
+#[derive(Default, Debug)]
+pub(crate) struct AtomicPageTableStats {
+    pub(crate) alloc_retries: Counter,
+    pub(crate) dealloc_retries: Counter,
+    pub(crate) update_conflicts: Counter,
+}
+
+impl AtomicPageTableStats {
+    pub(crate) fn snapshot(&self) -> PageTableStats {
+        PageTableStats {
+            alloc_retries: self.alloc_retries.get(),
+            dealloc_retries: self.dealloc_retries.get(),
+            update_conflicts: self.update_conflicts.get(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_flush() {
+        let stats = AtomicJobStats::default();
+        let jobs = stats.snapshot();
+        assert_eq!(jobs.recent_flush_duration_micros, 0);
+        assert_eq!(jobs.recent_flush_throughput, 0);
+
+        stats.record_flush(1 << 20, Duration::from_millis(100));
+        stats.record_flush(3 << 20, Duration::from_millis(300));
+        let jobs = stats.snapshot();
+        assert_eq!(jobs.flush_files, 2);
+        assert_eq!(jobs.flush_file_bytes, 4 << 20);
+        assert_eq!(jobs.flush_duration_micros, 400_000);
+        assert_eq!(jobs.recent_flush_duration_micros, 200_000);
+        assert_eq!(jobs.recent_flush_throughput, 10 << 20);
+
+        // Only the recent flushes are averaged.
+        for _ in 0..RecentFlushes::WINDOW {
+            stats.record_flush(1 << 20, Duration::from_millis(500));
         }
+        let jobs = stats.snapshot();
+        assert_eq!(jobs.flush_files, 2 + RecentFlushes::WINDOW as u64);
+        assert_eq!(jobs.recent_flush_duration_micros, 500_000);
+        assert_eq!(jobs.recent_flush_throughput, 2 << 20);
+        // The recent values are kept as they are.
+        let diff = jobs.sub(&stats.snapshot());
+        assert_eq!(diff.flush_files, 0);
+        assert_eq!(diff.recent_flush_duration_micros, 500_000);
     }
 }
@@ -8,7 +8,7 @@ use futures::channel::oneshot;
 use log::debug;
 use rustc_hash::{FxHashMap, FxHashSet};
 
-use super::{buffer_set::*, FileInfo, PageGroup, WriteBuffer};
+use super::{buffer_set::*, pending_frees, FileInfo, PageGroup, WriteBuffer};
 use crate::util::latch::Latch;
 
 pub(crate) struct VersionOwner {
@@ -28,12 +28,18 @@ pub(crate) struct Version {
     /// Records the ID of the file that can be deleted.
     obsoleted_files: FxHashSet<u32>,
 
+    // Each version only links to its successor, so a former version is released
+    // along with its links once all readers drop it, and the chain never grows
+    // beyond the oldest version in use.
     next_version: AtomicPtr<Arc<Version>>,
     new_version_latch: Latch,
     reclaiming_latch: Latch,
 
     _cleanup_guard: oneshot::Sender<()>,
     cleanup_handle: Mutex<Option<oneshot::Receiver<()>>>,
+
+    // Functions to run once the version is released.
+    deferred: Mutex<Vec<Box<dyn FnOnce() + Send>>>,
 }
 
 #[derive(Default)]
@@ -59,7 +65,17 @@ impl VersionOwner {
         }
     }
 
+    /// Tries to run the pending frees of former [`Version`]s.
+    #[inline]
+    pub(crate) fn collect_pending_frees() {
+        version_guard::collect()
+    }
+
     /// Obtains a reference of current [`Version`].
+    ///
+    /// The version is always loaded from the owner instead of a per-thread
+    /// cache, so a task that migrates between threads never sees a version
+    /// older than the one it has observed.
     #[inline]
     pub(crate) fn current(&self) -> Arc<Version> {
         let _guard = version_guard::pin();
@@ -86,6 +102,18 @@ impl VersionOwner {
     ///
     /// The installer should hold the mutable reference of [`Manifest`].
     pub(crate) unsafe fn install(&self, delta: DeltaVersion) {
+        let num_flushed = u32::from(matches!(delta.reason, VersionUpdateReason::Flush));
+        // Safety: guaranteed by the caller.
+        unsafe { self.install_flushed(delta, num_flushed) };
+    }
+
+    /// Try install new version into version chains, with `num_flushed` write
+    /// buffers persisted.
+    ///
+    /// # Safety
+    ///
+    /// The installer should hold the mutable reference of [`Manifest`].
+    pub(crate) unsafe fn install_flushed(&self, delta: DeltaVersion, num_flushed: u32) {
         let guard = version_guard::pin();
 
         // Safety: guard by `buffer_set_guard::pin`.
@@ -93,9 +121,9 @@ impl VersionOwner {
         let mut first_buffer_id = current.first_buffer_id;
 
         // Advance to next buffer if current has been persisted.
-        if matches!(delta.reason, VersionUpdateReason::Flush) {
-            debug!("Install new version with file {first_buffer_id}");
-            first_buffer_id = current.first_buffer_id + 1;
+        if num_flushed > 0 {
+            debug!("Install new version with {num_flushed} files flushed from {first_buffer_id}");
+            first_buffer_id = current.first_buffer_id + num_flushed;
         }
 
         let buffer_set = current.buffer_set.clone();
@@ -133,8 +161,9 @@ impl VersionOwner {
             .expect("There has already exists a version");
         former.new_version_latch.count_down();
 
+        let pending = former.buffer_set.pending_frees().versions();
         let raw_former = raw_former as usize;
-        guard.defer(move || {
+        pending_frees::defer_free(&guard, pending, move || {
             // Safety:
             // 1. Obtained from `Box::new`, so it is aligned and not null.
             // 2. There is not mutable references pointer to it.
@@ -159,6 +188,7 @@ impl Drop for VersionOwner {
 }
 
 impl Version {
+    #[allow(dead_code)]
     pub(crate) fn new(
         buffer_capacity: u32,
         next_group_id: u32,
@@ -169,6 +199,7 @@ impl Version {
             next_group_id,
             buffer_capacity,
             max_sealed_buffers,
+            false,
         ));
         Self::with_buffer_set(next_group_id, buffer_set, delta)
     }
@@ -192,6 +223,7 @@ impl Version {
             reclaiming_latch: Latch::new(1),
             _cleanup_guard: sender,
             cleanup_handle: Mutex::new(Some(receiver)),
+            deferred: Mutex::default(),
         }
     }
 
@@ -216,6 +248,17 @@ impl Version {
         handle.await.unwrap_or_default();
     }
 
+    /// Defers `f` until the [`Version`] is released.
+    ///
+    /// A former version links to its successors, so `f` also runs after all
+    /// former versions are released.
+    pub(crate) fn defer_until_released<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.deferred.lock().expect("Poisoned").push(Box::new(f));
+    }
+
     /// Fetch the files which obsoleted but referenced by former [`Version`]s.
     #[inline]
     pub(crate) fn obsoleted_files(&self) -> Vec<u32> {
@@ -310,6 +353,10 @@ impl Version {
 
 impl Drop for Version {
     fn drop(&mut self) {
+        for f in std::mem::take(self.deferred.get_mut().expect("Poisoned")) {
+            f();
+        }
+
         let raw = self.next_version.load(Ordering::SeqCst);
         if !raw.is_null() {
             unsafe {
@@ -365,6 +412,11 @@ mod version_guard {
         with_handle(|handle| handle.is_pinned())
     }
 
+    /// Tries to advance the epoch and runs the deferred frees that are ready.
+    pub(super) fn collect() {
+        pin().flush();
+    }
+
     #[inline]
     fn with_handle<F, R>(mut f: F) -> R
     where
@@ -402,7 +454,7 @@ mod tests {
                 buf.seal().unwrap();
             }
             let buf = Arc::new(WriteBuffer::with_capacity(buffer_id + i, 1 << 10));
-            version.buffer_set.install(buf);
+            version.buffer_set.install(buf).unwrap();
 
             let delta = DeltaVersion {
                 reason: VersionUpdateReason::Flush,
@@ -413,13 +465,64 @@ mod tests {
         }
     }
 
+    #[test]
+    fn version_chain_pruned_without_readers() {
+        let version = Version::new(1 << 10, 1, 8, DeltaVersion::default());
+        let owner = VersionOwner::new(version);
+        let first = Arc::downgrade(&owner.current());
+        let second = {
+            let delta = DeltaVersion {
+                reason: VersionUpdateReason::Compact,
+                ..Default::default()
+            };
+            // Safety: no concurrent operations
+            unsafe { owner.install(delta) };
+            Arc::downgrade(&owner.current())
+        };
+
+        // The former versions are released once the epoch advances.
+        let mut installed = 0;
+        while first.upgrade().is_some() || second.upgrade().is_some() {
+            assert!(installed < 1 << 10, "former versions are never released");
+            let delta = DeltaVersion {
+                reason: VersionUpdateReason::Compact,
+                ..Default::default()
+            };
+            // Safety: no concurrent operations
+            unsafe { owner.install(delta) };
+            installed += 1;
+        }
+        assert!(!owner.current().has_next_version());
+    }
+
+    #[test]
+    fn version_current_after_migration() {
+        let version = Version::new(1 << 10, 1, 8, DeltaVersion::default());
+        let owner = VersionOwner::new(version);
+        std::thread::scope(|s| {
+            let former = s.spawn(|| owner.current()).join().unwrap();
+            let delta = DeltaVersion {
+                reason: VersionUpdateReason::Compact,
+                ..Default::default()
+            };
+            // Safety: no concurrent operations
+            unsafe { owner.install(delta) };
+
+            // The task continues on another thread.
+            let current = s.spawn(|| owner.current()).join().unwrap();
+            assert!(!Arc::ptr_eq(&former, &current));
+            assert!(Arc::ptr_eq(&current, &owner.current()));
+            assert!(Arc::ptr_eq(&former.try_next().unwrap(), &current));
+        });
+    }
+
     #[test]
     fn version_access_newly_buffers() {
         let version = Version::new(1 << 10, 1, 8, DeltaVersion::default());
         let buffer_id = version.first_buffer_id;
         for i in 1..100 {
             let buf = Arc::new(WriteBuffer::with_capacity(buffer_id + i, 1 << 10));
-            version.buffer_set.install(buf);
+            version.buffer_set.install(buf).unwrap();
         }
 
         // All buffers are accessable from this version.
@@ -443,7 +546,7 @@ mod tests {
         // install new buffer.
         {
             let buf = Arc::new(WriteBuffer::with_capacity(buffer_id + 1, 32));
-            version.buffer_set.install(buf);
+            version.buffer_set.install(buf).unwrap();
         }
         drop(version);
 
@@ -1,7 +1,10 @@
 use std::{
     mem::MaybeUninit,
-    ptr::NonNull,
-    sync::atomic::{AtomicU64, Ordering},
+    ptr::{self, NonNull},
+    sync::{
+        atomic::{compiler_fence, AtomicU32, AtomicU64, Ordering},
+        Mutex,
+    },
 };
 
 use bitflags::bitflags;
@@ -10,11 +13,21 @@ use log::info;
 use super::Result;
 use crate::{
     page::{PageBuf, PageRef},
-    page_store::Error,
-    util::latch::Latch,
+    page_store::{Error, PageAddr},
+    util::{latch::Latch, notify::Notify},
 };
 
-pub(crate) struct WriteBuffer
+/// The alignment of records in a [`WriteBuffer`].
+pub(crate) const DEFAULT_RECORD_ALIGN: usize = core::mem::size_of::<usize>();
+
+/// An [`AlignedWriteBuffer`] with the default record alignment.
+pub(crate) type WriteBuffer = AlignedWriteBuffer<DEFAULT_RECORD_ALIGN>;
+
+/// A buffer of records, each of which starts at a multiple of `ALIGN` bytes.
+///
+/// `ALIGN` must be a power of two and no less than the alignment of
+/// [`RecordHeader`], which is checked at compile time.
+pub(crate) struct AlignedWriteBuffer<const ALIGN: usize>
 where
     Self: Send,
 {
@@ -23,13 +36,33 @@ where
     buf: NonNull<u8>,
     buf_size: usize,
 
+    /// Releases the memory of `buf` when the buffer is dropped.
+    dealloc: Option<DeallocFn>,
+
     // The state of current buffer, see [`BufferState`] for details.
     buffer_state: AtomicU64,
 
     /// A latch for flushed notify.
     flush_latch: Latch,
+
+    /// Admits contended writers in arrival order if it is enabled.
+    admission: Option<Admission>,
+
+    /// Whether to zero the memory of `buf` before it is released.
+    zero_on_free: bool,
+
+    /// The size of the largest record accepted, see
+    /// [`WriteBuffer::with_max_capacity`].
+    max_record_size: usize,
+    /// The capacity required by the records rejected for being larger than
+    /// this buffer.
+    required_capacity: AtomicU32,
 }
 
+/// A function that releases the memory backing a [`WriteBuffer`], which is
+/// called with the start and the size of the memory.
+pub(crate) type DeallocFn = Box<dyn FnOnce(NonNull<u8>, usize) + Send>;
+
 #[derive(Default, Debug, Clone)]
 struct BufferState {
     sealed: bool,
@@ -37,8 +70,8 @@ struct BufferState {
     /// The number of txn in progress.
     num_writer: u32,
 
-    /// The size of the allocated buffers for a [`WriteBuffer`], aligned by 8
-    /// bytes.
+    /// The size of the allocated buffers for a [`WriteBuffer`], aligned by the
+    /// record alignment.
     allocated: u32,
 }
 
@@ -55,9 +88,19 @@ pub(crate) struct RecordHeader {
     page_size: u32,
 }
 
-pub(crate) struct RecordIterator<'a> {
-    write_buffer: &'a WriteBuffer,
+/// A reference to a [`WriteBuffer`] that is sealed and has no writers.
+///
+/// The records of a [`WriteBuffer`] can only be read through this reference,
+/// so that they are never read while some writers are still in progress.
+#[derive(Clone, Copy)]
+pub(crate) struct SealedWriteBuffer<'a, const ALIGN: usize = DEFAULT_RECORD_ALIGN> {
+    write_buffer: &'a AlignedWriteBuffer<ALIGN>,
+}
+
+pub(crate) struct RecordIterator<'a, const ALIGN: usize = DEFAULT_RECORD_ALIGN> {
+    write_buffer: &'a AlignedWriteBuffer<ALIGN>,
     offset: u32,
+    end: u32,
 }
 
 pub(crate) enum RecordRef<'a> {
@@ -71,6 +114,12 @@ pub(crate) struct DeallocPagesRecordRef<'a> {
 }
 
 /// [`ReleaseState`] indicates that caller whether to notify flush job.
+///
+/// The sealed flag and the number of writers are updated by the same CAS, so
+/// [`ReleaseState::Flush`] is returned exactly once for each [`WriteBuffer`],
+/// by [`WriteBuffer::seal`] if there are no writers, or by the
+/// [`WriteBuffer::release_writer`] that releases the last writer after the
+/// buffer is sealed.
 #[derive(Debug)]
 pub(crate) enum ReleaseState {
     /// The [`WriteBuffer`] might be active or still exists pending writer.
@@ -79,12 +128,124 @@ pub(crate) enum ReleaseState {
     Flush,
 }
 
-impl WriteBuffer {
+/// A ticket queue that admits the contended writers of a [`WriteBuffer`] in
+/// arrival order.
+#[derive(Default)]
+struct Admission {
+    next_ticket: AtomicU32,
+    now_serving: AtomicU32,
+    // The tickets of the writers cancelled before their turns, which are
+    // skipped once the queue reaches them. `now_serving` is only advanced
+    // with the lock held.
+    abandoned: Mutex<Vec<u32>>,
+    notify: Notify,
+}
+
+/// The turn of a writer admitted by a [`WriteBuffer`], which is finished once
+/// it is dropped.
+pub(crate) struct AdmissionTurn<'a> {
+    admission: &'a Admission,
+    ticket: u32,
+}
+
+impl Admission {
+    /// Returns true if no writer is waiting for or holding a turn.
+    #[inline]
+    fn is_idle(&self) -> bool {
+        self.now_serving.load(Ordering::Acquire) == self.next_ticket.load(Ordering::Acquire)
+    }
+
+    /// Waits until all writers arrived earlier have finished their turns.
+    async fn enter(&self) -> AdmissionTurn<'_> {
+        let ticket = self.next_ticket.fetch_add(1, Ordering::AcqRel);
+        let turn = AdmissionTurn {
+            admission: self,
+            ticket,
+        };
+        loop {
+            // Register the waiter before checking the turn, so that an advance
+            // in between is not missed.
+            let notified = self.notify.notified();
+            if turn.is_served() {
+                return turn;
+            }
+            notified.await;
+        }
+    }
+}
+
+impl AdmissionTurn<'_> {
+    #[inline]
+    fn is_served(&self) -> bool {
+        self.admission.now_serving.load(Ordering::Acquire) == self.ticket
+    }
+}
+
+impl Drop for AdmissionTurn<'_> {
+    fn drop(&mut self) {
+        let admission = self.admission;
+        let mut abandoned = admission.abandoned.lock().expect("Poisoned");
+        if !self.is_served() {
+            // The writer is cancelled before its turn.
+            abandoned.push(self.ticket);
+            return;
+        }
+
+        let mut next = self.ticket.wrapping_add(1);
+        while let Some(index) = abandoned.iter().position(|ticket| *ticket == next) {
+            abandoned.swap_remove(index);
+            next = next.wrapping_add(1);
+        }
+        admission.now_serving.store(next, Ordering::Release);
+        drop(abandoned);
+        admission.notify.notify_waiters();
+    }
+}
+
+impl<const ALIGN: usize> AlignedWriteBuffer<ALIGN> {
+    const VALID_ALIGN: () = assert!(
+        ALIGN.is_power_of_two() && ALIGN >= core::mem::align_of::<RecordHeader>(),
+        "The record alignment must be a power of two no less than the header alignment"
+    );
+
+    /// Creates a [`WriteBuffer`] with memory from the global allocator.
     pub(crate) fn with_capacity(group_id: u32, size: u32) -> Self {
-        use std::alloc::{alloc, Layout};
+        use std::alloc::{alloc, dealloc, Layout};
+
+        let alloc_fn = |buf_size: usize| -> (NonNull<u8>, DeallocFn) {
+            let layout = Layout::from_size_align(buf_size, ALIGN).expect("Invalid layout");
+            let buf = unsafe {
+                // Safety: it is guaranteed that layout has non-zero size.
+                NonNull::new(alloc(layout)).expect("The memory is exhausted")
+            };
+            let dealloc_fn: DeallocFn = Box::new(move |buf, _| unsafe {
+                // Safety: this memory is allocated above with the same layout.
+                dealloc(buf.as_ptr(), layout);
+            });
+            (buf, dealloc_fn)
+        };
+        // Safety: the memory is allocated with the required size and alignment.
+        unsafe { Self::with_allocator(group_id, size, alloc_fn) }
+    }
+
+    /// Creates a [`WriteBuffer`] with memory from the `alloc_fn`.
+    ///
+    /// The `alloc_fn` is called with the size of the buffer, and returns the
+    /// memory and a function to release it when the buffer is dropped. This
+    /// allows the buffer to be backed by memory like huge pages or arenas.
+    ///
+    /// # Safety
+    ///
+    /// The returned memory must be valid for reads and writes of the given
+    /// size, and aligned to `ALIGN`, until the returned function is called.
+    pub(crate) unsafe fn with_allocator<F>(group_id: u32, size: u32, alloc_fn: F) -> Self
+    where
+        F: FnOnce(usize) -> (NonNull<u8>, DeallocFn),
+    {
+        let () = Self::VALID_ALIGN;
 
         let buf_size = size as usize;
-        if buf_size <= core::mem::size_of::<usize>() {
+        if buf_size <= ALIGN.max(core::mem::size_of::<RecordHeader>()) {
             panic!("The capacity of WriteBuffer is too small");
         }
 
@@ -92,22 +253,76 @@ impl WriteBuffer {
             panic!("The capacity of WriteBuffer is not pow of two");
         }
 
-        let layout = Layout::from_size_align(buf_size, core::mem::size_of::<usize>())
-            .expect("Invalid layout");
-        let buf = unsafe {
-            // Safety: it is guaranteed that layout has non-zero size.
-            NonNull::new(alloc(layout)).expect("The memory is exhausted")
-        };
+        let (buf, dealloc) = alloc_fn(buf_size);
+        assert!(
+            buf.as_ptr().is_aligned_to(ALIGN),
+            "The memory of WriteBuffer is not aligned"
+        );
         let default_state = BufferState::default();
-        WriteBuffer {
+        AlignedWriteBuffer {
             group_id,
             buf,
             buf_size,
+            dealloc: Some(dealloc),
             buffer_state: AtomicU64::new(default_state.apply()),
             flush_latch: Latch::new(1),
+            admission: None,
+            zero_on_free: false,
+            max_record_size: buf_size - core::mem::size_of::<RecordHeader>(),
+            required_capacity: AtomicU32::new(0),
         }
     }
 
+    /// Admits writers in arrival order once their allocations keep failing
+    /// under contention, so that no writer is starved by faster ones, see
+    /// [`WriteBuffer::admit`].
+    pub(crate) fn with_fair_admission(mut self, enable: bool) -> Self {
+        self.admission = enable.then(Admission::default);
+        self
+    }
+
+    /// Waits for the turn of a writer if the buffer admits writers in arrival
+    /// order, and some writers are queued already or the writer is
+    /// `contended`.
+    ///
+    /// Without a turn, allocations return [`Error::Contended`] once they keep
+    /// failing under contention, and the writer should retry them with a turn
+    /// obtained by `admit(true)`. The turn is finished once it is dropped.
+    pub(crate) async fn admit(&self, contended: bool) -> Option<AdmissionTurn<'_>> {
+        let admission = self.admission.as_ref()?;
+        if !contended && admission.is_idle() {
+            return None;
+        }
+        Some(admission.enter().await)
+    }
+
+    /// Zeroes the memory of the buffer before it is released, so that the
+    /// pages in it don't linger in freed memory.
+    pub(crate) fn with_zero_on_free(mut self, enable: bool) -> Self {
+        self.zero_on_free = enable;
+        self
+    }
+
+    /// Accepts the records that fit in a buffer with `max_capacity`, which is
+    /// the largest capacity of the buffers in the same set.
+    ///
+    /// A record that fits in such a buffer but not in this one is rejected with
+    /// [`Error::Again`] rather than [`Error::TooLargeSize`], so that it is
+    /// written to the next buffer, which should be created with at least
+    /// [`WriteBuffer::required_capacity`].
+    pub(crate) fn with_max_capacity(mut self, max_capacity: u32) -> Self {
+        let max_capacity = (max_capacity as usize).max(self.buf_size);
+        self.max_record_size = max_capacity - core::mem::size_of::<RecordHeader>();
+        self
+    }
+
+    /// Returns the capacity required by the records rejected for being larger
+    /// than this buffer, or zero if there are none.
+    #[inline]
+    pub(crate) fn required_capacity(&self) -> u32 {
+        self.required_capacity.load(Ordering::Relaxed)
+    }
+
     #[inline]
     pub(crate) fn group_id(&self) -> u32 {
         self.group_id
@@ -128,6 +343,26 @@ impl WriteBuffer {
         self.buffer_state().allocated == 0
     }
 
+    /// Returns the number of bytes allocated from the buffer.
+    #[inline]
+    pub(crate) fn allocated_size(&self) -> usize {
+        self.buffer_state().allocated as usize
+    }
+
+    /// Returns true if the address may refer to a page allocated from the
+    /// buffer, so that it can be passed to [`WriteBuffer::page`] without
+    /// panicking on its bounds or alignment.
+    pub(crate) fn contains_page(&self, page_addr: PageAddr) -> bool {
+        const HEADER_LEN: u32 = core::mem::size_of::<RecordHeader>() as u32;
+        if page_addr.file_id() != self.group_id {
+            return false;
+        }
+        let Some(offset) = page_addr.offset().checked_sub(HEADER_LEN) else {
+            return false;
+        };
+        offset as usize % ALIGN == 0 && page_addr.offset() <= self.buffer_state().allocated
+    }
+
     #[inline]
     pub(crate) async fn wait_flushed(&self) {
         self.flush_latch.wait().await;
@@ -150,14 +385,13 @@ impl WriteBuffer {
         Vec<(u64, &mut RecordHeader, PageBuf)>,
         Option<&mut RecordHeader>,
     )> {
-        const ALIGN: u32 = core::mem::size_of::<usize>() as u32;
         let dealloc_pages_size = (dealloc_pages.len() * core::mem::size_of::<u64>()) as u32;
         let need = new_page_list
             .iter()
-            .map(|(_, v)| record_size(*v))
+            .map(|(_, v)| record_size::<ALIGN>(*v))
             .sum::<u32>()
-            + record_size(dealloc_pages_size);
-        debug_assert_eq!(need % ALIGN, 0);
+            + record_size::<ALIGN>(dealloc_pages_size);
+        debug_assert_eq!(need as usize % ALIGN, 0);
 
         let mut offset = self.alloc_size(need, true)?;
         let mut records = Vec::with_capacity(new_page_list.len());
@@ -166,7 +400,7 @@ impl WriteBuffer {
             // Safety: here is the only one reference to the record.
             let (page_addr, header, page_buf) =
                 unsafe { self.new_page_at(offset, page_id, page_size) };
-            offset += header.record_size();
+            offset += header.record_size::<ALIGN>();
             records.push((page_addr, header, page_buf));
         }
 
@@ -195,12 +429,48 @@ impl WriteBuffer {
         page_size: u32,
         acquire_writer: bool,
     ) -> Result<(u64, &'a mut RecordHeader, PageBuf<'a>)> {
-        let acquire_size = record_size(page_size);
+        self.check_record_size(page_size as usize)?;
+        let acquire_size = record_size::<ALIGN>(page_size);
         let offset = self.alloc_size(acquire_size, acquire_writer)?;
         // Safety: here is the only one reference to the record.
         Ok(unsafe { self.new_page_at(offset, page_id, page_size) })
     }
 
+    /// Allocates pages with one reservation, so that either all of them are
+    /// allocated from the buffer or none of them is.
+    ///
+    /// Returns [`Error::TooLargeSize`] if the pages can't fit in the buffer
+    /// together.
+    ///
+    /// # Safety
+    ///
+    /// The user needs to ensure that the [`WriteBuffer`] is valid for as long
+    /// as the page reference is still held.
+    #[allow(clippy::type_complexity)]
+    pub(crate) unsafe fn alloc_pages<'a>(
+        &self,
+        new_page_list: &[(u64 /* page id */, u32 /* page size */)],
+        acquire_writer: bool,
+    ) -> Result<Vec<(u64, &'a mut RecordHeader, PageBuf<'a>)>> {
+        let need = new_page_list
+            .iter()
+            .map(|(_, page_size)| record_size::<ALIGN>(*page_size) as usize)
+            .sum::<usize>();
+        // The pages are reserved as if they were one record.
+        self.check_record_size(need.saturating_sub(core::mem::size_of::<RecordHeader>()))?;
+
+        let mut offset = self.alloc_size(need as u32, acquire_writer)?;
+        let mut records = Vec::with_capacity(new_page_list.len());
+        for (page_id, page_size) in new_page_list {
+            // Safety: here is the only one reference to the record.
+            let (page_addr, header, page_buf) =
+                unsafe { self.new_page_at(offset, *page_id, *page_size) };
+            offset += header.record_size::<ALIGN>();
+            records.push((page_addr, header, page_buf));
+        }
+        Ok(records)
+    }
+
     /// Deallocate pages.
     ///
     /// # Safety
@@ -212,8 +482,9 @@ impl WriteBuffer {
         page_addrs: &[u64],
         acquire_writer: bool,
     ) -> Result<&'a mut RecordHeader> {
-        let dealloc_pages_size = (page_addrs.len() * core::mem::size_of::<u64>()) as u32;
-        let acquire_size = record_size(dealloc_pages_size);
+        let dealloc_pages_size = page_addrs.len() * core::mem::size_of::<u64>();
+        self.check_record_size(dealloc_pages_size)?;
+        let acquire_size = record_size::<ALIGN>(dealloc_pages_size as u32);
         let offset = self.alloc_size(acquire_size, acquire_writer)?;
         // Safety: here is the only one reference to the record.
         let (header, body) = unsafe { self.new_dealloc_pages_record_at(offset, page_addrs.len()) };
@@ -293,16 +564,15 @@ impl WriteBuffer {
         }
     }
 
-    /// Return an iterator to iterate records in the buffer.
-    ///
-    /// # Panic
+    /// Returns a [`SealedWriteBuffer`] to read the records in the buffer.
     ///
-    /// This function will panic if the the [`WriteBuffer`] is not flushable, to
-    /// ensure that pointer aliasing rules are not violated.
-    pub(crate) fn iter(&self) -> RecordIterator {
-        RecordIterator {
-            write_buffer: self,
-            offset: 0,
+    /// Returns `None` if the buffer is not flushable, which means that it is
+    /// not sealed or some writers are still in progress.
+    pub(crate) fn try_into_sealed(&self) -> Option<SealedWriteBuffer<'_, ALIGN>> {
+        if self.is_flushable() {
+            Some(SealedWriteBuffer { write_buffer: self })
+        } else {
+            None
         }
     }
 
@@ -311,32 +581,29 @@ impl WriteBuffer {
     /// # Panic
     ///
     /// Panic if the `page_addr` is not belongs to the [`WriteBuffer`].
-    /// Panic if the `page_addr` is not aligned with
-    /// `core::mem::size_of::<usize>()`.
+    /// Panic if the record of `page_addr` is not aligned with `ALIGN`.
     /// Panic if the `page_addr` is not a valid page.
     ///
     /// # Safety
     ///
     /// Users need to ensure that the accessed page has no mutable references,
     /// so as not to violate the rules of pointer aliasing.
-    pub(crate) unsafe fn page<'a>(&self, page_addr: u64) -> PageRef<'a> {
-        const ALIGN: u32 = core::mem::size_of::<usize>() as u32;
-
-        let file_id = (page_addr >> 32) as u32;
-        let offset = (page_addr & ((1 << 32) - 1)) as u32;
+    pub(crate) unsafe fn page<'a>(&self, page_addr: PageAddr) -> PageRef<'a> {
+        let file_id = page_addr.file_id();
+        let offset = page_addr.offset();
 
         if file_id != self.group_id {
             panic!("The specified addr is not belongs to the buffer");
         }
 
-        if offset % ALIGN != 0 {
-            panic!("The specified addr is not satisfied the align requirement");
-        }
-
         let offset = offset
             .checked_sub(core::mem::size_of::<RecordHeader>() as u32)
             .expect("The specified addr is not a valid page");
 
+        if offset as usize % ALIGN != 0 {
+            panic!("The specified addr is not satisfied the align requirement");
+        }
+
         let header = self.record(offset);
         if let Some(RecordRef::Page(page_ref)) = header.record_ref() {
             return page_ref;
@@ -365,13 +632,13 @@ impl WriteBuffer {
     ///
     /// # Panic
     ///
-    /// Panic if the offset is not aligned with `core::mem::size_of::<usize>()`.
+    /// Panic if the offset is not aligned with `ALIGN`.
     /// Panic if the offset exceeds the size of buffer.
     #[inline]
     fn record_uninit(&self, offset: u32) -> &MaybeUninit<RecordHeader> {
         let offset = offset as usize;
-        if offset % core::mem::size_of::<usize>() != 0 {
-            panic!("The specified offset is not aligned with pointer size");
+        if offset % ALIGN != 0 {
+            panic!("The specified offset is not aligned with the record alignment");
         }
 
         assert!(offset + core::mem::size_of::<RecordHeader>() < self.buf_size);
@@ -397,8 +664,8 @@ impl WriteBuffer {
     #[inline]
     unsafe fn record_uninit_mut<'a>(&self, offset: u32) -> &'a mut MaybeUninit<RecordHeader> {
         let offset = offset as usize;
-        if offset % core::mem::size_of::<usize>() != 0 {
-            panic!("The specified offset is not aligned with pointer size");
+        if offset % ALIGN != 0 {
+            panic!("The specified offset is not aligned with the record alignment");
         }
 
         assert!(offset + core::mem::size_of::<RecordHeader>() < self.buf_size);
@@ -415,6 +682,27 @@ impl WriteBuffer {
         }
     }
 
+    /// Returns [`Error::TooLargeSize`] if a record with `data_size` bytes can
+    /// never fit in the buffers of the set, or [`Error::Again`] if it only
+    /// fits in a larger buffer than this one.
+    ///
+    /// This is checked before the buffer state, so that callers don't switch to
+    /// the next buffer just to fail again.
+    fn check_record_size(&self, data_size: usize) -> Result<()> {
+        // The buffer sizes are multiples of `ALIGN`, so the padding of a record
+        // that fits in a buffer never exceeds it.
+        if data_size > self.max_record_size {
+            return Err(Error::TooLargeSize);
+        }
+        let record_size = record_size::<ALIGN>(data_size as u32);
+        if record_size as usize > self.buf_size {
+            self.required_capacity
+                .fetch_max(record_size.next_power_of_two(), Ordering::Relaxed);
+            return Err(Error::Again);
+        }
+        Ok(())
+    }
+
     #[inline]
     fn buffer_state(&self) -> BufferState {
         BufferState::load(self.buffer_state.load(Ordering::Acquire))
@@ -422,18 +710,44 @@ impl WriteBuffer {
 
     /// Allocate memory and install writer. Returns the address of the first
     /// byte.
+    ///
+    /// Returns [`Error::Contended`] if the buffer admits writers in arrival
+    /// order, no writer is queued, and the allocation keeps failing.
     fn alloc_size(&self, need: u32, acquire_writer: bool) -> Result<u32> {
+        const FAST_ATTEMPTS: usize = 4;
+
+        // Once some writers are queued, new writers wait behind them, so only
+        // the writer holding the turn and the ones arrived before the queue
+        // contend for the buffer.
+        let attempts = match &self.admission {
+            Some(admission) if admission.is_idle() => FAST_ATTEMPTS,
+            _ => usize::MAX,
+        };
+        self.try_alloc_size(need, acquire_writer, attempts)
+            .unwrap_or(Err(Error::Contended))
+    }
+
+    /// Returns `None` if the state is changed by others in all `attempts`.
+    fn try_alloc_size(
+        &self,
+        need: u32,
+        acquire_writer: bool,
+        attempts: usize,
+    ) -> Option<Result<u32>> {
         let mut current = self.buffer_state.load(Ordering::Acquire);
-        loop {
+        for _ in 0..attempts {
             let mut state = BufferState::load(current);
             if state.sealed {
-                return Err(Error::Again);
+                return Some(Err(Error::Again));
             }
 
             if acquire_writer {
                 state.inc_writer();
             }
-            let offset = state.alloc_size(need, self.buf_size as u32)?;
+            let offset = match state.alloc_size(need, self.buf_size as u32, ALIGN as u32) {
+                Ok(offset) => offset,
+                Err(err) => return Some(Err(err)),
+            };
             let new = state.apply();
             match self.buffer_state.compare_exchange(
                 current,
@@ -442,13 +756,14 @@ impl WriteBuffer {
                 Ordering::Acquire,
             ) {
                 Ok(_) => {
-                    return Ok(offset);
+                    return Some(Ok(offset));
                 }
                 Err(e) => {
                     current = e;
                 }
             }
         }
+        None
     }
 
     /// New page at the corresponding offset.
@@ -474,7 +789,7 @@ impl WriteBuffer {
 
         // Compute page addr.
         let page_offset = offset + core::mem::size_of::<RecordHeader>() as u32;
-        let page_addr = ((self.group_id as u64) << 32) | (page_offset as u64);
+        let page_addr = PageAddr::new(self.group_id, page_offset).into();
 
         // Construct `PageBuf`.
         let buf = unsafe {
@@ -516,32 +831,56 @@ impl WriteBuffer {
     }
 }
 
-impl std::fmt::Debug for WriteBuffer {
+impl<'a, const ALIGN: usize> SealedWriteBuffer<'a, ALIGN> {
+    /// Return an iterator to iterate records in the buffer.
+    pub(crate) fn iter(&self) -> RecordIterator<'a, ALIGN> {
+        let buffer_state = self.write_buffer.buffer_state();
+        // Guaranteed by [`SealedWriteBuffer`].
+        debug_assert!(buffer_state.is_flushable());
+        RecordIterator {
+            write_buffer: self.write_buffer,
+            offset: 0,
+            end: buffer_state.allocated,
+        }
+    }
+}
+
+impl<'a, const ALIGN: usize> std::ops::Deref for SealedWriteBuffer<'a, ALIGN> {
+    type Target = AlignedWriteBuffer<ALIGN>;
+
+    fn deref(&self) -> &Self::Target {
+        self.write_buffer
+    }
+}
+
+impl<const ALIGN: usize> std::fmt::Debug for AlignedWriteBuffer<ALIGN> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let buffer_state = BufferState::load(self.buffer_state.load(Ordering::Relaxed));
         f.debug_struct("WriteBuffer")
             .field("file_id", &self.group_id)
             .field("buf_size", &self.buf_size)
+            .field("align", &ALIGN)
             .field("buffer_state", &buffer_state)
             .finish()
     }
 }
 
-impl Drop for WriteBuffer {
+impl<const ALIGN: usize> Drop for AlignedWriteBuffer<ALIGN> {
     fn drop(&mut self) {
-        use std::alloc::{dealloc, Layout};
-
         let state = BufferState::load(self.buffer_state.load(Ordering::SeqCst));
         if state.has_writer() {
             panic!("Try drop a write buffer that is still in use");
         }
 
-        let layout = Layout::from_size_align(self.buf_size, core::mem::size_of::<usize>())
-            .expect("Invalid layout");
-        unsafe {
-            // Safety: this memory is allocated in [`WriteBuffer::with_capacity`] and has
-            // the same layout.
-            dealloc(self.buf.as_ptr(), layout);
+        if self.zero_on_free {
+            // Safety: the memory is valid for writes of `buf_size` bytes, and
+            // there are no references to it since there are no writers.
+            unsafe { ptr::write_bytes(self.buf.as_ptr(), 0, self.buf_size) };
+            // Keeps the writes from being elided before the memory is freed.
+            compiler_fence(Ordering::SeqCst);
+        }
+        if let Some(dealloc) = self.dealloc.take() {
+            dealloc(self.buf, self.buf_size);
         }
     }
 }
@@ -550,13 +889,13 @@ impl Drop for WriteBuffer {
 ///
 /// [`WriteBuffer`] is [`Send`] since all accesses to the inner buf are
 /// guaranteed that the aliases do not overlap.
-unsafe impl Send for WriteBuffer {}
+unsafe impl<const ALIGN: usize> Send for AlignedWriteBuffer<ALIGN> {}
 
 /// # Safety
 ///
 /// [`WriteBuffer`] is [`Send`] since all accesses to the inner buf are
 /// guaranteed that the aliases do not overlap.
-unsafe impl Sync for WriteBuffer {}
+unsafe impl<const ALIGN: usize> Sync for AlignedWriteBuffer<ALIGN> {}
 
 impl BufferState {
     #[inline]
@@ -603,10 +942,9 @@ impl BufferState {
     }
 
     #[inline]
-    fn alloc_size(&mut self, required: u32, buf_size: u32) -> Result<u32> {
-        const ALIGN: u32 = core::mem::size_of::<usize>() as u32;
-        debug_assert_eq!(self.allocated % ALIGN, 0);
-        let required = next_multiple_of_u32(required, ALIGN);
+    fn alloc_size(&mut self, required: u32, buf_size: u32, align: u32) -> Result<u32> {
+        debug_assert_eq!(self.allocated % align, 0);
+        let required = next_multiple_of_u32(required, align);
         if required > buf_size {
             return Err(Error::TooLargeSize);
         }
@@ -635,10 +973,10 @@ impl RecordHeader {
     ///
     /// This value is not simply equal to `page_size +
     /// size_of::<RecordHeader>()`, because size of records need to be
-    /// aligned by 8 bytes.
+    /// aligned by `ALIGN` bytes.
     #[inline]
-    fn record_size(&self) -> u32 {
-        record_size(self.page_size)
+    fn record_size<const ALIGN: usize>(&self) -> u32 {
+        record_size::<ALIGN>(self.page_size)
     }
 
     #[inline]
@@ -697,27 +1035,24 @@ impl RecordHeader {
     }
 }
 
-impl<'a> Iterator for RecordIterator<'a> {
+impl<'a, const ALIGN: usize> Iterator for RecordIterator<'a, ALIGN> {
     type Item = (u64 /* page_addr */, &'a RecordHeader, RecordRef<'a>);
 
     fn next(&mut self) -> Option<Self::Item> {
-        let buffer_state =
-            BufferState::load(self.write_buffer.buffer_state.load(Ordering::Acquire));
-        assert!(buffer_state.is_flushable());
-
         loop {
-            if self.offset >= buffer_state.allocated {
+            if self.offset >= self.end {
                 return None;
             }
 
             let record_offset = self.offset;
-            // Safety: the request [`RecordHeader`] has been initialized (checked in above).
+            // Safety: the records before `end` have been initialized, which is
+            // guaranteed when the iterator is created.
             let record_header = unsafe { self.write_buffer.record(record_offset) };
 
-            self.offset += record_header.record_size();
+            self.offset += record_header.record_size::<ALIGN>();
             let page_offset = record_offset + core::mem::size_of::<RecordHeader>() as u32;
             if let Some(record_ref) = record_header.record_ref() {
-                let page_addr = ((self.write_buffer.group_id as u64) << 32) | (page_offset as u64);
+                let page_addr = PageAddr::new(self.write_buffer.group_id, page_offset).into();
                 return Some((page_addr, record_header, record_ref));
             }
         }
@@ -754,11 +1089,11 @@ fn next_multiple_of_u32(val: u32, multiple: u32) -> u32 {
 /// [`RecordHeader`].
 ///
 /// This value is not simply equal to `page_size + size_of::<RecordHeader>()`,
-/// because size of records need to be aligned by 8 bytes.
+/// because size of records need to be aligned by `ALIGN` bytes.
 #[inline]
-fn record_size(x: u32) -> u32 {
-    const ALIGN: u32 = core::mem::size_of::<usize>() as u32;
-    core::mem::size_of::<RecordHeader>() as u32 + next_multiple_of_u32(x, ALIGN)
+fn record_size<const ALIGN: usize>(x: u32) -> u32 {
+    let size = core::mem::size_of::<RecordHeader>() as u32 + x;
+    next_multiple_of_u32(size, ALIGN as u32)
 }
 
 bitflags! {
@@ -787,7 +1122,7 @@ mod tests {
 
         state.set_sealed();
         state.inc_writer();
-        state.alloc_size(3, 1024).unwrap();
+        state.alloc_size(3, 1024, 8).unwrap();
         let raw = state.apply();
 
         let state = BufferState::load(raw);
@@ -833,7 +1168,7 @@ mod tests {
                 page_size,
             };
             assert_eq!(
-                header.record_size(),
+                header.record_size::<8>(),
                 core::mem::size_of::<RecordHeader>() as u32 + record_size
             );
         }
@@ -876,6 +1211,60 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn write_buffer_release_writer_before_seal() {
+        let buf = WriteBuffer::with_capacity(1, 1024);
+        buf.batch(&[], &[1]).unwrap();
+        assert!(matches!(
+            unsafe { buf.release_writer() },
+            ReleaseState::None
+        ));
+        assert!(matches!(buf.seal(), Ok(ReleaseState::Flush)));
+    }
+
+    #[test]
+    fn write_buffer_seal_before_release_writer() {
+        let buf = WriteBuffer::with_capacity(1, 1024);
+        buf.batch(&[], &[1]).unwrap();
+        buf.batch(&[], &[2]).unwrap();
+        assert!(matches!(buf.seal(), Ok(ReleaseState::None)));
+        assert!(matches!(
+            unsafe { buf.release_writer() },
+            ReleaseState::None
+        ));
+        assert!(matches!(
+            unsafe { buf.release_writer() },
+            ReleaseState::Flush
+        ));
+    }
+
+    #[test]
+    fn write_buffer_seal_race_with_release_writer() {
+        use std::sync::atomic::AtomicUsize;
+
+        const WRITERS: u64 = 4;
+        for _ in 0..1000 {
+            let buf = WriteBuffer::with_capacity(1, 1024);
+            for i in 0..WRITERS {
+                buf.batch(&[], &[i]).unwrap();
+            }
+            let flushes = AtomicUsize::new(0);
+            let count = |state: ReleaseState| {
+                if matches!(state, ReleaseState::Flush) {
+                    flushes.fetch_add(1, Ordering::Relaxed);
+                }
+            };
+            std::thread::scope(|s| {
+                for _ in 0..WRITERS {
+                    s.spawn(|| count(unsafe { buf.release_writer() }));
+                }
+                s.spawn(|| count(buf.seal().unwrap()));
+            });
+            assert_eq!(flushes.load(Ordering::Relaxed), 1);
+            assert!(buf.is_flushable());
+        }
+    }
+
     #[test]
     #[should_panic]
     fn write_buffer_empty_writer_release_seal() {
@@ -910,7 +1299,7 @@ mod tests {
 
         let expect_dealloc_pages = vec![11, 12, 13, 14, 15];
         let mut active_pages: HashSet<u64> = vec![1, 3, 5, 7, 9].into_iter().collect();
-        for (_, header, record_ref) in buf.iter() {
+        for (_, header, record_ref) in buf.try_into_sealed().unwrap().iter() {
             match record_ref {
                 RecordRef::Page(_page) => {
                     let page_id = header.page_id();
@@ -925,6 +1314,80 @@ mod tests {
         assert!(active_pages.is_empty());
     }
 
+    #[test]
+    fn write_buffer_with_allocator() {
+        use std::{
+            alloc::{alloc, dealloc, Layout},
+            sync::{atomic::AtomicBool, Arc},
+        };
+
+        let released = Arc::new(AtomicBool::new(false));
+        let alloc_fn = |buf_size: usize| -> (NonNull<u8>, DeallocFn) {
+            let layout = Layout::from_size_align(buf_size, 8).unwrap();
+            let buf = NonNull::new(unsafe { alloc(layout) }).unwrap();
+            let released = released.clone();
+            let dealloc_fn: DeallocFn = Box::new(move |buf, size| {
+                assert_eq!(size, buf_size);
+                unsafe { dealloc(buf.as_ptr(), layout) };
+                released.store(true, Ordering::SeqCst);
+            });
+            (buf, dealloc_fn)
+        };
+        let buf = unsafe { WriteBuffer::with_allocator(1, 1 << 10, alloc_fn) };
+        unsafe { buf.alloc_page(1, 123, false) }.unwrap();
+        buf.seal().unwrap();
+        assert!(!released.load(Ordering::SeqCst));
+        drop(buf);
+        assert!(released.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn write_buffer_zero_on_free() {
+        use std::{
+            alloc::{alloc, dealloc, Layout},
+            slice,
+            sync::{atomic::AtomicBool, Arc},
+        };
+
+        let released = Arc::new(AtomicBool::new(false));
+        let alloc_fn = |buf_size: usize| -> (NonNull<u8>, DeallocFn) {
+            let layout = Layout::from_size_align(buf_size, 8).unwrap();
+            let buf = NonNull::new(unsafe { alloc(layout) }).unwrap();
+            let released = released.clone();
+            let dealloc_fn: DeallocFn = Box::new(move |buf, size| {
+                let bytes = unsafe { slice::from_raw_parts(buf.as_ptr(), size) };
+                assert!(bytes.iter().all(|&b| b == 0));
+                unsafe { dealloc(buf.as_ptr(), layout) };
+                released.store(true, Ordering::SeqCst);
+            });
+            (buf, dealloc_fn)
+        };
+        let buf =
+            unsafe { WriteBuffer::with_allocator(1, 1 << 10, alloc_fn) }.with_zero_on_free(true);
+        let (_, _, mut page) = unsafe { buf.alloc_page(1, 123, false) }.unwrap();
+        page.set_epoch(u64::MAX);
+        page.set_chain_next(u64::MAX);
+        buf.seal().unwrap();
+        drop(buf);
+        assert!(released.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn write_buffer_try_into_sealed() {
+        let buf = WriteBuffer::with_capacity(1, 1 << 10);
+        unsafe { buf.alloc_page(1, 123, true) }.unwrap();
+        assert!(buf.try_into_sealed().is_none());
+
+        // The records can't be read until the writer is released.
+        buf.seal().unwrap();
+        assert!(buf.try_into_sealed().is_none());
+        unsafe { buf.release_writer() };
+
+        let sealed = buf.try_into_sealed().unwrap();
+        assert_eq!(sealed.group_id(), 1);
+        assert_eq!(sealed.iter().count(), 1);
+    }
+
     #[test]
     fn write_buffer_iterate_with_correct_offset() {
         let mut page_addrs = Vec::new();
@@ -937,10 +1400,27 @@ mod tests {
 
         buf.seal().unwrap();
 
-        let new_page_addrs = buf.iter().map(|(addr, _, _)| addr).collect::<Vec<_>>();
+        let new_page_addrs = buf
+            .try_into_sealed()
+            .unwrap()
+            .iter()
+            .map(|(addr, _, _)| addr)
+            .collect::<Vec<_>>();
         assert_eq!(page_addrs, new_page_addrs);
     }
 
+    #[test]
+    fn write_buffer_contains_page() {
+        let buf = WriteBuffer::with_capacity(1, 1 << 16);
+        let (addr, _, _) = unsafe { buf.alloc_page(0, 64, false).unwrap() };
+        let addr = PageAddr::from_raw(addr);
+        assert!(buf.contains_page(addr));
+        assert!(!buf.contains_page(PageAddr::new(2, addr.offset())));
+        assert!(!buf.contains_page(PageAddr::new(1, 0)));
+        assert!(!buf.contains_page(PageAddr::new(1, addr.offset() + 1)));
+        assert!(!buf.contains_page(PageAddr::new(1, 1 << 12)));
+    }
+
     #[test]
     fn write_buffer_pages_alloc() {
         let buf = WriteBuffer::with_capacity(1, 1 << 20);
@@ -990,5 +1470,190 @@ mod tests {
             unsafe { buf.alloc_page(1, 4 << 10, true) },
             Err(Error::TooLargeSize)
         ));
+        // The size must not overflow.
+        assert!(matches!(
+            unsafe { buf.alloc_page(1, u32::MAX, true) },
+            Err(Error::TooLargeSize)
+        ));
+        // A record that fills the whole buffer is allowed.
+        let max_page_size = (2 << 10) - core::mem::size_of::<RecordHeader>() as u32;
+        assert!(matches!(
+            unsafe { buf.alloc_page(1, max_page_size + 1, true) },
+            Err(Error::TooLargeSize)
+        ));
+        unsafe { buf.alloc_page(1, max_page_size, true) }.unwrap();
+        unsafe { buf.release_writer() };
+
+        // The size is checked even if the buffer is sealed.
+        buf.seal().unwrap();
+        assert!(matches!(
+            unsafe { buf.alloc_page(1, 4 << 10, true) },
+            Err(Error::TooLargeSize)
+        ));
+        assert!(matches!(
+            unsafe { buf.alloc_page(1, 8, true) },
+            Err(Error::Again)
+        ));
+    }
+
+    #[test]
+    fn write_buffer_alloc_larger_than_buffer() {
+        let buf = WriteBuffer::with_capacity(1, 1 << 10).with_max_capacity(4 << 10);
+        assert_eq!(buf.required_capacity(), 0);
+
+        // A record that only fits in a larger buffer goes to the next buffer.
+        assert!(matches!(
+            unsafe { buf.alloc_page(1, 1 << 10, true) },
+            Err(Error::Again)
+        ));
+        assert_eq!(buf.required_capacity(), 2 << 10);
+        let max_page_size = (4 << 10) - core::mem::size_of::<RecordHeader>() as u32;
+        assert!(matches!(
+            unsafe { buf.alloc_page(1, max_page_size, true) },
+            Err(Error::Again)
+        ));
+        assert_eq!(buf.required_capacity(), 4 << 10);
+
+        // A record larger than the max capacity never fits.
+        assert!(matches!(
+            unsafe { buf.alloc_page(1, max_page_size + 1, true) },
+            Err(Error::TooLargeSize)
+        ));
+
+        // Smaller records still fit in this buffer.
+        unsafe { buf.alloc_page(1, 8, true) }.unwrap();
+        unsafe { buf.release_writer() };
+    }
+
+    #[test]
+    fn write_buffer_fair_admission() {
+        const WRITERS: usize = 8;
+        const ALLOCS: usize = 1000;
+        let buf =
+            WriteBuffer::with_capacity(1, (WRITERS * ALLOCS * 8) as u32).with_fair_admission(true);
+        let mut offsets = std::thread::scope(|s| {
+            let handles = (0..WRITERS)
+                .map(|_| {
+                    s.spawn(|| {
+                        (0..ALLOCS)
+                            .map(|_| admitted_alloc_size(&buf, 8))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        // All allocations are disjoint and fill the buffer.
+        offsets.sort_unstable();
+        let expect = (0..(WRITERS * ALLOCS) as u32)
+            .map(|i| i * 8)
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, expect);
+        assert!(matches!(buf.alloc_size(8, false), Err(Error::Again)));
+
+        // All queued writers have finished their turns.
+        let admission = buf.admission.as_ref().unwrap();
+        assert_eq!(
+            admission.next_ticket.load(Ordering::Relaxed),
+            admission.now_serving.load(Ordering::Relaxed)
+        );
+    }
+
+    #[photonio::test]
+    async fn write_buffer_admission_order() {
+        use futures::{poll, task::Poll};
+
+        let buf = WriteBuffer::with_capacity(1, 1 << 10).with_fair_admission(true);
+        assert!(buf.admit(false).await.is_none());
+
+        let first = buf.admit(true).await.unwrap();
+        let mut second = Box::pin(buf.admit(false));
+        let mut third = Box::pin(buf.admit(false));
+        let mut fourth = Box::pin(buf.admit(true));
+        assert!(poll!(second.as_mut()).is_pending());
+        assert!(poll!(third.as_mut()).is_pending());
+        assert!(poll!(fourth.as_mut()).is_pending());
+        // The queued writers are not overtaken by the fast path.
+        assert!(matches!(buf.alloc_size(8, false), Ok(0)));
+        assert!(poll!(Box::pin(buf.admit(false))).is_pending());
+
+        // The cancelled writer is skipped.
+        drop(third);
+        drop(first);
+        assert!(poll!(fourth.as_mut()).is_pending());
+        let Poll::Ready(Some(second)) = poll!(second.as_mut()) else {
+            panic!("the second writer is not admitted");
+        };
+        assert!(poll!(fourth.as_mut()).is_pending());
+        drop(second);
+        let Poll::Ready(Some(fourth)) = poll!(fourth.as_mut()) else {
+            panic!("the fourth writer is not admitted");
+        };
+        drop(fourth);
+        assert!(buf.admit(false).await.is_none());
+    }
+
+    // Allocates like a page transaction, which retries with a turn once the
+    // fast path is contended.
+    fn admitted_alloc_size(buf: &WriteBuffer, need: u32) -> u32 {
+        let _turn = futures::executor::block_on(buf.admit(false));
+        match buf.alloc_size(need, false) {
+            Err(Error::Contended) => {
+                let _turn = futures::executor::block_on(buf.admit(true));
+                buf.alloc_size(need, false).unwrap()
+            }
+            result => result.unwrap(),
+        }
+    }
+
+    fn check_aligned_write_buffer_layout<const ALIGN: usize>() {
+        let buf = AlignedWriteBuffer::<ALIGN>::with_capacity(1, 1 << 12);
+        let mut page_addrs = Vec::new();
+        for page_size in [16, 23, 64, 100] {
+            let (addr, _, page_buf) = unsafe { buf.alloc_page(0, page_size, false).unwrap() };
+            let record_offset = (addr as u32) as usize - core::mem::size_of::<RecordHeader>();
+            assert_eq!(record_offset % ALIGN, 0);
+            assert_eq!(
+                page_buf.data().as_ptr() as usize % core::mem::align_of::<u64>(),
+                0
+            );
+            page_addrs.push(addr);
+        }
+        unsafe { buf.dealloc_pages(&[1, 2, 3], false).unwrap() };
+        assert_eq!(buf.allocated_size() % ALIGN, 0);
+
+        buf.seal().unwrap();
+        let sealed = buf.try_into_sealed().unwrap();
+        let mut records = sealed.iter();
+        for (page_addr, page_size) in page_addrs.iter().zip([16, 23, 64, 100]) {
+            let (addr, header, _) = records.next().unwrap();
+            assert_eq!(addr, *page_addr);
+            assert_eq!(header.page_size(), page_size);
+            assert_eq!(
+                unsafe { buf.page(PageAddr::from_raw(addr)) }.size(),
+                page_size
+            );
+        }
+        assert!(matches!(
+            records.next(),
+            Some((_, _, RecordRef::DeallocPages(_)))
+        ));
+        assert!(records.next().is_none());
+    }
+
+    #[test]
+    fn aligned_write_buffer_layout() {
+        check_aligned_write_buffer_layout::<DEFAULT_RECORD_ALIGN>();
+        check_aligned_write_buffer_layout::<64>();
+
+        // A wider alignment pads each record to a multiple of it.
+        let header_size = core::mem::size_of::<RecordHeader>() as u32;
+        assert_eq!(record_size::<8>(1), header_size + 8);
+        assert_eq!(record_size::<64>(1), 64);
+        assert_eq!(record_size::<64>(64 - header_size + 1), 128);
     }
 }
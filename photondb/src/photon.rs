@@ -5,7 +5,7 @@
 
 use std::{ops::Deref, path::Path};
 
-pub use crate::raw::TableStats;
+pub use crate::raw::{Decodable, Encodable, Snapshot, TableStats, UpdateIntent, WriteBatch};
 use crate::{env::Photon, raw, Result, TableOptions};
 
 /// A reference to a latch-free, log-structured table that stores sorted
@@ -32,6 +32,33 @@ impl Table {
     pub async fn close(self) -> Result<(), Self> {
         self.0.close().await.map_err(Self)
     }
+
+    /// Closes the table and removes all its files if this is the only
+    /// reference to it.
+    ///
+    /// This is the same as [`raw::Table::destroy`] with the [`Photon`]
+    /// environment.
+    pub async fn destroy(self) -> Result<Result<()>, Self> {
+        self.0.destroy().await.map_err(Self)
+    }
+
+    /// Opens the named tree in the table, creating it if it doesn't exist.
+    ///
+    /// This is the same as [`raw::Table::open_tree`] with the [`Photon`]
+    /// environment.
+    pub async fn open_tree(&self, name: &str) -> Result<Self> {
+        let table = self.0.open_tree(name).await?;
+        Ok(Self(table))
+    }
+
+    /// Returns a [`TypedTable`] over this table.
+    pub fn typed<K, V>(&self) -> TypedTable<K, V>
+    where
+        K: Encodable + Decodable,
+        V: Encodable + Decodable,
+    {
+        TypedTable::new(self.0.clone())
+    }
 }
 
 impl Deref for Table {
@@ -47,3 +74,21 @@ pub type Guard<'a> = raw::Guard<'a, Photon>;
 
 /// An iterator over pages in a table.
 pub type Pages<'a, 't> = raw::Pages<'a, 't, Photon>;
+
+/// An iterator over all key-value entries in a table.
+pub type Dump<'a> = raw::Dump<'a, Photon>;
+
+/// An iterator over the key-value entries in a range of a table.
+pub type Scan<'a> = raw::Scan<'a, Photon>;
+
+/// A value that pins the pages it is read from.
+pub type PinnedValue<'a> = raw::PinnedValue<'a, Photon>;
+
+/// A loader that fills an empty table with entries in key order.
+pub type BulkLoader<'a> = raw::BulkLoader<'a, Photon>;
+
+/// A table with keys and values of the types.
+pub type TypedTable<K, V> = raw::TypedTable<K, V, Photon>;
+
+/// An iterator over the decoded entries in a range of a typed table.
+pub type TypedScan<'a, K, V> = raw::TypedScan<'a, K, V, Photon>;
@@ -0,0 +1,63 @@
+use futures::{pin_mut, Stream, StreamExt};
+
+use super::Table;
+use crate::{env::Env, tree::BulkLoad, Result};
+
+/// A loader that fills an empty table with entries in key order.
+///
+/// This is created by [`Table::bulk_loader`]. Instead of writing the entries
+/// one by one through delta pages, the loader packs them into leaf pages
+/// directly, and builds the index pages on top of them when it is finished.
+/// The entries are invisible until [`BulkLoader::finish`] returns. If the
+/// loader is dropped before that, the pages built so far are released by the
+/// next loader of the table.
+pub struct BulkLoader<'a, E: Env> {
+    table: &'a Table<E>,
+    load: BulkLoad,
+}
+
+impl<'a, E: Env> BulkLoader<'a, E> {
+    pub(super) fn new(table: &'a Table<E>, load: BulkLoad) -> Self {
+        Self { table, load }
+    }
+
+    /// Adds an entry to the table.
+    ///
+    /// Returns [`Error::InvalidArgument`] if the key is not larger than the
+    /// keys added before, or it can't be written with the options.
+    ///
+    /// [`Error::InvalidArgument`]: crate::Error::InvalidArgument
+    pub async fn add(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        let txn = self.table.begin();
+        self.load.add(&txn, key, value).await
+    }
+
+    /// Adds all entries of the stream to the table.
+    ///
+    /// The stream can be created by [`Scan::into_stream`] of another table,
+    /// for example.
+    ///
+    /// [`Scan::into_stream`]: super::Scan::into_stream
+    pub async fn extend<S>(&mut self, stream: S) -> Result<()>
+    where
+        S: Stream<Item = Result<(Vec<u8>, Vec<u8>)>>,
+    {
+        pin_mut!(stream);
+        while let Some((key, value)) = stream.next().await.transpose()? {
+            self.add(&key, &value).await?;
+        }
+        Ok(())
+    }
+
+    /// Builds the index of the added entries and makes them visible.
+    ///
+    /// Returns the number of loaded entries, or [`Error::Again`] if the table
+    /// is written by others during the load, in which case the pages of the
+    /// load are released.
+    ///
+    /// [`Error::Again`]: crate::Error::Again
+    pub async fn finish(self) -> Result<usize> {
+        let txn = self.table.begin();
+        self.load.finish(&txn).await
+    }
+}
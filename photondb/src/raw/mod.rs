@@ -1,7 +1,15 @@
 //! Raw PhotonDB APIs that can can run with different environments.
 
 mod table;
-pub use table::{Guard, Pages, Table, TableStats};
+pub use table::{
+    Dump, Guard, Pages, PinnedValue, Scan, Snapshot, Table, TableStats, UpdateIntent, WriteBatch,
+};
+
+mod typed;
+pub use typed::{Decodable, Encodable, TypedScan, TypedTable};
+
+mod bulk_load;
+pub use bulk_load::BulkLoader;
 
 #[cfg(test)]
 mod tree_test {
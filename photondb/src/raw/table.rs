@@ -1,11 +1,26 @@
-use std::{path::Path, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    ops::Deref,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use futures::{lock::Mutex, stream, Stream};
 
+use super::BulkLoader;
 use crate::{
     env::Env,
-    page::{Key, Value},
-    page_store::{FlushOptions, PageStore, StoreStats},
+    page::{now_millis, Key, PageKind, Value},
+    page_store::{
+        FlushOptions, GcPlan, PageAddr, PageId, PageStore, PinMark, StoreStats, VersionPin,
+    },
     tree::*,
-    Result,
+    Error, Result,
 };
 
 /// A reference to a latch-free, log-structured table that stores sorted
@@ -16,21 +31,88 @@ use crate::{
 pub struct Table<E: Env> {
     tree: Arc<Tree>,
     store: Arc<PageStore<E>>,
+    // The named trees opened in the store, shared by all references.
+    trees: Arc<Mutex<HashMap<String, Arc<Tree>>>>,
+    // The LSNs assigned by the table, shared by all trees.
+    lsns: Arc<LsnSequence>,
 }
 
+/// The metadata key of the root ids of named trees, which is reserved.
+const TREES_METADATA_KEY: &[u8] = b"photondb.trees";
+/// The metadata key of the largest reserved LSN, which is reserved.
+const LSN_METADATA_KEY: &[u8] = b"photondb.lsn";
+/// The number of LSNs reserved in the metadata at a time.
+const LSN_RESERVE_SIZE: u64 = 1 << 16;
+
 impl<E: Env> Table<E> {
     /// Opens a table in the path with the given options.
     pub async fn open<P: AsRef<Path>>(env: E, path: P, options: Options) -> Result<Self> {
         let tree = Arc::new(Tree::new(options.clone()));
         let store = PageStore::open(env, path, options.page_store).await?;
+        let lsns = LsnSequence::load(&store).await?;
         let txn = tree.begin(store.guard());
         txn.init().await?;
+        if options.warm_up_on_open > 0 {
+            txn.warm_up(options.warm_up_on_open).await?;
+        }
         Ok(Self {
             tree,
             store: Arc::new(store),
+            trees: Arc::default(),
+            lsns: Arc::new(lsns),
         })
     }
 
+    /// Opens the named tree in the table, creating it if it doesn't exist.
+    ///
+    /// A named tree is a logically independent table with its own root, but
+    /// it shares the page store, and so the write buffers, files, and space
+    /// reclamation, with all other trees of the table. It is opened with the
+    /// same options as this table. Savepoints, snapshots, and the safe LSN are
+    /// kept per tree, while operations on the page store, such as flushes and
+    /// metadata, are shared.
+    ///
+    /// The root ids of named trees are recorded in the metadata of the table,
+    /// so a tree created here can be opened again after restarts.
+    pub async fn open_tree(&self, name: &str) -> Result<Self> {
+        let mut trees = self.trees.lock().await;
+        if let Some(tree) = trees.get(name) {
+            return Ok(self.with_tree(tree.clone()));
+        }
+
+        let metadata = self.store.get_metadata(TREES_METADATA_KEY).await;
+        let mut roots = decode_tree_roots(metadata.as_deref().unwrap_or_default())?;
+        let root_id = match roots.get(name) {
+            Some(&root_id) => root_id,
+            None => {
+                let root_id = self.begin().create_root().await?;
+                // The root must be persisted before it is named, otherwise the
+                // name may refer to a lost page after a crash.
+                self.store.flush(&FlushOptions::default()).await;
+                roots.insert(name.to_owned(), root_id);
+                let metadata = encode_tree_roots(&roots);
+                self.store
+                    .set_metadata(TREES_METADATA_KEY, &metadata)
+                    .await?;
+                root_id
+            }
+        };
+        let tree = Arc::new(Tree::with_root(self.tree.options().clone(), root_id));
+        trees.insert(name.to_owned(), tree.clone());
+        Ok(self.with_tree(tree))
+    }
+
+    /// Returns a reference to the tree that shares the page store with this
+    /// table.
+    fn with_tree(&self, tree: Arc<Tree>) -> Self {
+        Self {
+            tree,
+            store: self.store.clone(),
+            trees: self.trees.clone(),
+            lsns: self.lsns.clone(),
+        }
+    }
+
     /// Closes the table if this is the only reference to it.
     ///
     /// If this is not the only reference, returns [`Result::Err`] with this
@@ -44,12 +126,37 @@ impl<E: Env> Table<E> {
             Err(store) => Err(Self {
                 tree: self.tree,
                 store,
+                trees: self.trees,
+                lsns: self.lsns,
+            }),
+        }
+    }
+
+    /// Closes the table and removes all its files if this is the only
+    /// reference to it.
+    ///
+    /// The page files and the manifest are removed after the background jobs
+    /// are stopped, and the folder of the table is removed if nothing else is
+    /// left in it. Since no other reference exists, no reader can still be
+    /// using the pages being removed.
+    ///
+    /// If this is not the only reference, returns [`Result::Err`] with this
+    /// reference. If the table is opened in read-only mode, the table is
+    /// closed without removing anything and [`Error::ReadOnly`] is returned.
+    pub async fn destroy(self) -> Result<Result<()>, Self> {
+        match Arc::try_unwrap(self.store) {
+            Ok(store) => Ok(store.destroy().await.map_err(Error::from)),
+            Err(store) => Err(Self {
+                tree: self.tree,
+                store,
+                trees: self.trees,
+                lsns: self.lsns,
             }),
         }
     }
 
     /// Begins a tree transaction.
-    fn begin(&self) -> TreeTxn<'_, E> {
+    pub(super) fn begin(&self) -> TreeTxn<'_, E> {
         self.tree.begin(self.store.guard())
     }
 
@@ -58,15 +165,128 @@ impl<E: Env> Table<E> {
         Guard::new(self)
     }
 
+    /// Returns an iterator over all live key-value entries in the table.
+    ///
+    /// The entries are yielded in key order with the latest visible values.
+    /// This is intended for exports and verifications, see [`Guard::pages`]
+    /// for scans without copying the entries.
+    pub fn dump(&self) -> Dump<'_, E> {
+        Dump::new(self)
+    }
+
+    /// Returns an iterator over the live key-value entries in the range
+    /// `[start, end)`, as of `lsn`.
+    ///
+    /// The entries are yielded in key order. The iterator starts from the
+    /// page that contains `start` and stops at the first key at or after
+    /// `end`.
+    pub fn scan(&self, start: &[u8], end: &[u8], lsn: u64) -> Scan<'_, E> {
+        let iter = TreeIter::new(Scan::<E>::read_options(lsn)).with_start(start);
+        let mut scan = Scan::new(self, iter, Some(end.to_vec()));
+        scan.done = start >= end;
+        scan
+    }
+
+    /// Returns an iterator over the live key-value entries whose keys start
+    /// with `prefix`, as of `lsn`.
+    ///
+    /// If `prefix` is extracted by [`TableOptions::prefix_extractor`], pages
+    /// that are known to have no such keys are skipped without being read.
+    ///
+    /// [`TableOptions::prefix_extractor`]: crate::TableOptions::prefix_extractor
+    pub fn scan_prefix(&self, prefix: &[u8], lsn: u64) -> Scan<'_, E> {
+        let iter = TreeIter::new(Scan::<E>::read_options(lsn)).with_prefix(prefix);
+        Scan::new(self, iter, prefix_end(prefix))
+    }
+
+    /// Returns an iterator over the live key-value entries within the bounds
+    /// of `options`, as of [`ReadOptions::max_lsn`].
+    ///
+    /// The entries are yielded in the direction of the options, up to the
+    /// limit of the options. Pages outside of the bounds are not read, and
+    /// the scan stops reading pages once the limit is reached.
+    pub fn scan_with_options(&self, options: ReadOptions) -> Scan<'_, E> {
+        let empty = matches!(
+            (&options.lower_bound, &options.upper_bound),
+            (Some(start), Some(end)) if start >= end
+        );
+        let end = options.upper_bound.clone();
+        let mut scan = Scan::new(self, TreeIter::new(options), end);
+        scan.done |= empty;
+        scan
+    }
+
     /// Gets the value corresponding to the key.
     pub async fn get(&self, key: &[u8], lsn: u64) -> Result<Option<Vec<u8>>> {
         let key = Key::new(key, lsn);
         let txn = self.begin();
         let value = txn.get(key).await?;
-        Ok(value.map(|v| v.to_vec()))
+        Ok(value.map(|v| v.into_owned()))
+    }
+
+    /// Gets the value corresponding to the key without copying it.
+    ///
+    /// The returned value borrows the page it is read from, and the page is
+    /// pinned until the value is dropped. This saves copying large values, but
+    /// the value should be dropped soon, since the pinned pages can't be
+    /// evicted or reclaimed.
+    pub async fn get_pinned(&self, key: &[u8], lsn: u64) -> Result<Option<PinnedValue<'_, E>>> {
+        let key = Key::new(key, lsn);
+        let txn = self.begin();
+        let value = txn.get(key).await?.map(|value| match value {
+            // Safety: the value is borrowed from the pages pinned by the
+            // transaction rather than the transaction itself, so it stays valid
+            // until the transaction is dropped, which happens after the value.
+            Cow::Borrowed(v) => Cow::Borrowed(unsafe { &*(v as *const [u8]) }),
+            Cow::Owned(v) => Cow::Owned(v),
+        });
+        Ok(value.map(|value| PinnedValue { value, _txn: txn }))
+    }
+
+    /// Gets the values corresponding to the keys at the LSN.
+    ///
+    /// The keys in the same page are looked up together, and the pages are
+    /// loaded concurrently, so the latency of reading many pages from disk is
+    /// overlapped. The values are returned in the order of the keys.
+    pub async fn multi_get(&self, keys: &[&[u8]], lsn: u64) -> Result<Vec<Option<Vec<u8>>>> {
+        let txn = self.begin();
+        let values = txn.multi_get(keys, lsn).await?;
+        Ok(values
+            .into_iter()
+            .map(|v| v.map(|v| v.into_owned()))
+            .collect())
+    }
+
+    /// Returns true if the table contains a value for the key.
+    ///
+    /// This is equivalent to `get(key, lsn)?.is_some()`, but it doesn't copy
+    /// the value or resolve merge operands.
+    pub async fn contains(&self, key: &[u8], lsn: u64) -> Result<bool> {
+        let key = Key::new(key, lsn);
+        let txn = self.begin();
+        Ok(txn.contains(key).await?)
+    }
+
+    /// Gets the value corresponding to the key without retrying on conflicts.
+    ///
+    /// Returns [`Error::Again`] if the read conflicts with concurrent
+    /// modifications, in which case the caller can retry it later.
+    ///
+    /// [`Error::Again`]: crate::Error::Again
+    pub async fn try_get(&self, key: &[u8], lsn: u64) -> Result<Option<Vec<u8>>> {
+        let key = Key::new(key, lsn);
+        let txn = self.begin();
+        let value = txn.try_get(key).await?;
+        Ok(value.map(|v| v.into_owned()))
     }
 
     /// Puts a key-value entry to the table.
+    ///
+    /// Returns [`Error::InvalidArgument`] if the key doesn't match the size of
+    /// [`DataPageLayout::FixedKey`].
+    ///
+    /// [`DataPageLayout::FixedKey`]: crate::DataPageLayout::FixedKey
+    /// [`Error::InvalidArgument`]: crate::Error::InvalidArgument
     pub async fn put(&self, key: &[u8], lsn: u64, value: &[u8]) -> Result<()> {
         let key = Key::new(key, lsn);
         let value = Value::Put(value);
@@ -75,6 +295,56 @@ impl<E: Env> Table<E> {
         Ok(())
     }
 
+    /// Puts a key-value entry that expires after the TTL to the table.
+    ///
+    /// The expiration time is stored with the value. Once it is passed, reads
+    /// and scans at any LSN see the entry as deleted, and the entry is purged
+    /// when its page is consolidated. Merge operands applied to the value
+    /// produce values that don't expire.
+    pub async fn put_with_ttl(
+        &self,
+        key: &[u8],
+        lsn: u64,
+        value: &[u8],
+        ttl: Duration,
+    ) -> Result<()> {
+        let ttl = u64::try_from(ttl.as_millis()).unwrap_or(u64::MAX);
+        let key = Key::new(key, lsn);
+        let value = Value::Expiring(value, now_millis().saturating_add(ttl));
+        let txn = self.begin();
+        txn.write(key, value).await?;
+        Ok(())
+    }
+
+    /// Puts a key-value entry to the table at the next LSN of the table.
+    ///
+    /// The LSNs are assigned from a sequence shared by all trees of the table,
+    /// which keeps increasing across restarts. Returns the assigned LSN, which
+    /// can be used to read the entry or to replicate it in order. LSNs passed
+    /// to other writes must not be mixed with the assigned ones.
+    pub async fn put_auto(&self, key: &[u8], value: &[u8]) -> Result<u64> {
+        let lsn = self.lsns.next(&self.store).await?;
+        self.put(key, lsn.lsn, value).await?;
+        Ok(lsn.lsn)
+    }
+
+    /// Merges an operand into the value corresponding to the key.
+    ///
+    /// The operand is resolved with [`TableOptions::merge_operator`] when the
+    /// value is read or the page is consolidated.
+    ///
+    /// Returns [`Error::InvalidArgument`] if no merge operator is specified.
+    ///
+    /// [`TableOptions::merge_operator`]: crate::TableOptions::merge_operator
+    /// [`Error::InvalidArgument`]: crate::Error::InvalidArgument
+    pub async fn merge(&self, key: &[u8], lsn: u64, operand: &[u8]) -> Result<()> {
+        let key = Key::new(key, lsn);
+        let value = Value::Merge(operand);
+        let txn = self.begin();
+        txn.write(key, value).await?;
+        Ok(())
+    }
+
     /// Deletes the entry corresponding to the key from the table.
     pub async fn delete(&self, key: &[u8], lsn: u64) -> Result<()> {
         let key = Key::new(key, lsn);
@@ -84,6 +354,278 @@ impl<E: Env> Table<E> {
         Ok(())
     }
 
+    /// Deletes the entry corresponding to the key from the table at the next
+    /// LSN of the table.
+    ///
+    /// Returns the assigned LSN, see [`Table::put_auto`].
+    pub async fn delete_auto(&self, key: &[u8]) -> Result<u64> {
+        let lsn = self.lsns.next(&self.store).await?;
+        self.delete(key, lsn.lsn).await?;
+        Ok(lsn.lsn)
+    }
+
+    /// Returns the latest LSN assigned by [`Table::put_auto`] and
+    /// [`Table::delete_auto`] such that the writes at it and all former LSNs
+    /// are completed.
+    ///
+    /// After restarts, this starts from an LSN above all LSNs assigned before.
+    pub fn latest_lsn(&self) -> u64 {
+        self.lsns.latest()
+    }
+
+    /// Deletes the entries in `[start, end)` from the table at the LSN.
+    ///
+    /// Unlike scanning the range and deleting the keys one by one, no key is
+    /// read out of the table. Each page in the range gets one range delete,
+    /// regardless of how many keys it has, which deletes every key written to
+    /// the range at or below the LSN.
+    pub async fn delete_range(&self, start: &[u8], end: &[u8], lsn: u64) -> Result<()> {
+        let txn = self.begin();
+        Ok(txn.delete_range(start, end, lsn).await?)
+    }
+
+    /// Writes a batch of entries at the LSN.
+    ///
+    /// Each entry puts the value if it is some, or deletes the key otherwise.
+    /// The entries of keys in the same page are written with one delta, while
+    /// entries in different pages are written one page at a time.
+    ///
+    /// If a key appears more than once, the last entry of it in the batch
+    /// wins. Returns [`Error::DuplicateKey`] instead if
+    /// [`TableOptions::reject_duplicate_keys`] is set.
+    ///
+    /// [`TableOptions::reject_duplicate_keys`]: crate::TableOptions::reject_duplicate_keys
+    /// [`Error::DuplicateKey`]: crate::Error::DuplicateKey
+    pub async fn write_batch(&self, batch: &[(&[u8], Option<&[u8]>)], lsn: u64) -> Result<()> {
+        let batch: Vec<_> = batch
+            .iter()
+            .map(|(key, value)| (*key, value.map_or(Value::Delete, Value::Put)))
+            .collect();
+        let txn = self.begin();
+        txn.write_batch(&batch, lsn).await?;
+        Ok(())
+    }
+
+    /// Writes a [`WriteBatch`] at the LSN as a whole.
+    ///
+    /// The entries of the batch are reserved from one write buffer at once
+    /// and the buffer is not flushed until all of them are written, so they
+    /// are persisted all together or not at all. Reads below the LSN never
+    /// see the batch, but reads at or above it may see a part of the batch
+    /// before this returns.
+    ///
+    /// Duplicate keys are handled as in [`Table::write_batch`]. Returns
+    /// [`Error::TooLargeSize`] if the batch doesn't fit in a write buffer.
+    ///
+    /// [`Error::TooLargeSize`]: crate::Error::TooLargeSize
+    pub async fn write(&self, batch: &WriteBatch, lsn: u64) -> Result<()> {
+        let entries: Vec<_> = batch
+            .entries
+            .iter()
+            .map(|(key, value)| {
+                let value = value.as_deref().map_or(Value::Delete, Value::Put);
+                (key.as_slice(), value)
+            })
+            .collect();
+        let txn = self.begin();
+        txn.write_batch_atomic(&entries, lsn).await?;
+        Ok(())
+    }
+
+    /// Writes `new` to the key at the LSN if its current value is `expected`.
+    ///
+    /// The value is compared with the one visible at the LSN, where [`None`]
+    /// stands for no value, and `new` deletes the key if it is [`None`]. The
+    /// comparison and the write are done atomically, so no other write to the
+    /// key can come in between. This is useful for counters and metadata
+    /// updated by several writers.
+    ///
+    /// Returns false without writing anything if the value doesn't match.
+    pub async fn compare_and_swap(
+        &self,
+        key: &[u8],
+        lsn: u64,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<bool> {
+        let key = Key::new(key, lsn);
+        let new = new.map_or(Value::Delete, Value::Put);
+        let txn = self.begin();
+        Ok(txn.compare_and_swap(key, expected, new).await?)
+    }
+
+    /// Gets the newest value of the key and takes an intent to update it.
+    ///
+    /// The intent records the LSN of the newest write to the key, so that
+    /// [`Table::commit_update`] can tell whether the key has been written
+    /// since. This is a building block for read-modify-write loops: read the
+    /// value, compute the new one, and retry from the read if the commit
+    /// fails.
+    pub async fn get_for_update(&self, key: &[u8]) -> Result<(Option<Vec<u8>>, UpdateIntent)> {
+        let txn = self.begin();
+        let (value, version) = txn.get_for_update(key).await?;
+        let intent = UpdateIntent {
+            tree: self.tree.clone(),
+            key: key.to_vec(),
+            version,
+        };
+        Ok((value.map(|v| v.into_owned()), intent))
+    }
+
+    /// Writes `new` to the key of the intent at the LSN if the key hasn't been
+    /// written since the intent was taken.
+    ///
+    /// `new` deletes the key if it is [`None`]. The check and the write are
+    /// done atomically. Writes are told apart by their LSNs, so the LSN must
+    /// be greater than any former LSN of the key, or this returns
+    /// [`Error::InvalidArgument`]. A tombstone dropped by consolidation counts
+    /// as a write, so this may fail spuriously after the safe LSN advances.
+    ///
+    /// Returns false without writing anything if the key has been written
+    /// since the intent was taken.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the intent is not taken from this table.
+    pub async fn commit_update(
+        &self,
+        intent: &UpdateIntent,
+        lsn: u64,
+        new: Option<&[u8]>,
+    ) -> Result<bool> {
+        assert!(
+            Arc::ptr_eq(&self.tree, &intent.tree),
+            "the intent is taken from another table"
+        );
+        let key = Key::new(&intent.key, lsn);
+        let new = new.map_or(Value::Delete, Value::Put);
+        let txn = self.begin();
+        Ok(txn.update_if_unchanged(key, intent.version, new).await?)
+    }
+
+    /// Moves the value of `from` to `to` at the LSN.
+    ///
+    /// If both keys belong to the same page, the value is written to `to` and
+    /// `from` is deleted with one delta, so that no reader sees the keys in
+    /// between. Otherwise, the two deltas are installed in one transaction
+    /// and persisted together, like the pages of [`Table::write`], and
+    /// readers at the LSN may see `from` deleted before `to` is written until
+    /// this returns.
+    ///
+    /// The value is only moved if `from` is not written since it is read.
+    ///
+    /// Returns false if `from` has no value at the LSN.
+    pub async fn rename(&self, from: &[u8], to: &[u8], lsn: u64) -> Result<bool> {
+        let txn = self.begin();
+        Ok(txn.rename(from, to, lsn).await?)
+    }
+
+    /// Deletes all entries visible at the LSN that match the predicate.
+    ///
+    /// The table is scanned one page at a time, and the predicate is called
+    /// with the key and value of each entry. The entries matched in the same
+    /// page are deleted with tombstones at the LSN in one batch, so that the
+    /// page grows by one delta at most.
+    ///
+    /// The `progress` is called with the number of deleted entries after each
+    /// page. The operation stops early if it returns false, with the entries
+    /// deleted so far kept deleted.
+    ///
+    /// Returns the number of deleted entries.
+    pub async fn delete_where<P, F>(
+        &self,
+        lsn: u64,
+        mut predicate: P,
+        mut progress: F,
+    ) -> Result<usize>
+    where
+        P: FnMut(&[u8], &[u8]) -> bool,
+        F: FnMut(usize) -> bool,
+    {
+        let options = ReadOptions {
+            max_lsn: lsn,
+            ..Default::default()
+        };
+        let mut iter = TreeIter::new(options);
+        let mut num_deleted = 0;
+        loop {
+            // Pins pages with a new transaction for each page, so that the
+            // scanned pages are released in time.
+            let txn = self.begin();
+            let keys: Vec<Vec<u8>> = match iter.next_page(&txn).await? {
+                Some(page) => page
+                    .filter(|(k, v)| predicate(k, v))
+                    .map(|(k, _)| k.to_vec())
+                    .collect(),
+                None => break,
+            };
+            if !keys.is_empty() {
+                txn.delete_batch(&keys, lsn).await?;
+                num_deleted += keys.len();
+            }
+            if !progress(num_deleted) {
+                break;
+            }
+        }
+        Ok(num_deleted)
+    }
+
+    /// Loads sorted key-value entries into an empty table in parallel.
+    ///
+    /// The entries are split into `num_shards` key ranges of balanced sizes.
+    /// Each shard is packed into leaf pages by a background task of the
+    /// environment, like a [`BulkLoader`] does, and the index over the leaf
+    /// pages of all shards is built under one root once they are done.
+    ///
+    /// Returns [`Error::InvalidArgument`] if the table is not empty or the keys
+    /// are not strictly increasing, and [`Error::Again`] if the table is
+    /// written by others during the load.
+    pub async fn load_sharded(
+        &self,
+        entries: Vec<(Vec<u8>, Vec<u8>)>,
+        lsn: u64,
+        num_shards: usize,
+    ) -> Result<()> {
+        if entries.windows(2).any(|w| w[0].0 >= w[1].0) {
+            return Err(Error::InvalidArgument);
+        }
+
+        let mut load = self.begin().begin_bulk_load(lsn).await?;
+        let handles = split_shards(entries, num_shards)
+            .into_iter()
+            .map(|shard| {
+                let table = self.clone();
+                let mut shard_load = load.fork();
+                self.store.env().spawn_background(async move {
+                    for (key, value) in &shard {
+                        let txn = table.begin();
+                        shard_load.add(&txn, key, value).await?;
+                    }
+                    Ok::<_, Error>(shard_load)
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            let shard_load = handle.await?;
+            load.append(&self.begin(), shard_load).await?;
+        }
+        load.finish(&self.begin()).await?;
+        Ok(())
+    }
+
+    /// Returns a [`BulkLoader`] to fill the table with entries at the LSN.
+    ///
+    /// This is intended for initial loads, which are much faster than
+    /// writing the entries one by one. The table must be empty, otherwise
+    /// [`Error::InvalidArgument`] is returned.
+    ///
+    /// [`Error::InvalidArgument`]: crate::Error::InvalidArgument
+    pub async fn bulk_loader(&self, lsn: u64) -> Result<BulkLoader<'_, E>> {
+        let txn = self.begin();
+        let load = txn.begin_bulk_load(lsn).await?;
+        Ok(BulkLoader::new(self, load))
+    }
+
     /// Returns the statistics of the table.
     pub fn stats(&self) -> TableStats {
         TableStats {
@@ -92,6 +634,137 @@ impl<E: Env> Table<E> {
         }
     }
 
+    /// Returns the page store of the table.
+    #[cfg(test)]
+    pub(crate) fn store(&self) -> &PageStore<E> {
+        &self.store
+    }
+
+    /// Returns the id of the index page above the leaf page that contains the
+    /// key.
+    ///
+    /// The id can be used to scan the subtree with [`Table::scan_subtree`].
+    /// Returns [`None`] if the table has only one page.
+    pub async fn index_page_id(&self, key: &[u8]) -> Result<Option<PageId>> {
+        let txn = self.begin();
+        Ok(txn.index_page_id(key).await?)
+    }
+
+    /// Returns all live entries in the subtree rooted at the page, in key
+    /// order.
+    ///
+    /// This is intended for range-partitioned access patterns, which know the
+    /// subtree to scan and don't need to descend from the root. The page id
+    /// should be obtained from [`Table::index_page_id`] recently, since pages
+    /// can be restructured at any time.
+    ///
+    /// Returns [`Error::InvalidArgument`] if the page doesn't exist.
+    ///
+    /// [`Error::InvalidArgument`]: crate::Error::InvalidArgument
+    pub async fn scan_subtree(&self, page_id: PageId, lsn: u64) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let txn = self.begin();
+        Ok(txn.scan_subtree(page_id, lsn).await?)
+    }
+
+    /// Returns the keys whose newest versions are written at or after `lsn`,
+    /// in key order.
+    ///
+    /// This is intended for change-feed consumers that index the table
+    /// incrementally. The scan reads at the largest LSN written since the
+    /// table is opened when it starts, so the LSNs of writes should be
+    /// increasing, and later writes are not included. Writes that haven't
+    /// returned when the scan starts are not included either.
+    ///
+    /// If `ghost` is true, keys whose newest versions are deletions are
+    /// returned with [`None`] values, otherwise they are skipped. Deletions
+    /// below the safe LSN may have been dropped already.
+    pub async fn scan_since(
+        &self,
+        lsn: u64,
+        ghost: bool,
+    ) -> Result<Vec<(Vec<u8>, Option<Vec<u8>>)>> {
+        let options = ReadOptions {
+            max_lsn: self.tree.last_lsn(),
+            ..Default::default()
+        };
+        let mut iter = TreeIter::new(options);
+        let mut entries = Vec::new();
+        loop {
+            let txn = self.begin();
+            let Some(mut page) = iter.next_page(&txn).await? else {
+                break;
+            };
+            while let Some((key, key_lsn, value)) = page.next_version() {
+                if key_lsn >= lsn && (ghost || value.is_some()) {
+                    entries.push((key.to_vec(), value.map(|v| v.to_vec())));
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Estimates the number of entries and bytes in the range `[start, end)`.
+    ///
+    /// The estimate is computed from the sizes of the pages overlapping with
+    /// the range without reading the entries, which is cheap enough for query
+    /// planning. It is not exact, but it never decreases as the range grows.
+    pub async fn estimate_range(&self, start: &[u8], end: &[u8]) -> Result<(u64, u64)> {
+        let txn = self.begin();
+        Ok(txn.estimate_range(start, end).await?)
+    }
+
+    /// Returns the approximate size in bytes that the range `[start, end)`
+    /// takes in the page store.
+    ///
+    /// This sums up the stored sizes of all pages of the leaf pages that
+    /// overlap with the range, as recorded in the metadata of the files, so
+    /// it only reads the index pages. Unlike [`Table::estimate_range`], the
+    /// boundary pages are counted as a whole, and the sizes are after
+    /// compression for pages in files. This is useful to split a table into
+    /// shards of similar sizes.
+    pub async fn approximate_size(&self, start: &[u8], end: &[u8]) -> Result<u64> {
+        let txn = self.begin();
+        Ok(txn.approximate_size(start, end).await?)
+    }
+
+    /// Returns the estimated number of live keys in the table.
+    ///
+    /// The keys of each leaf page are counted when the page is consolidated,
+    /// and the counts are carried over when pages are split or removed. Writes
+    /// that are not consolidated yet are not counted, so the estimate lags
+    /// behind, and it starts from zero when the table is opened. This is also
+    /// reported in [`TableStats`].
+    pub fn estimated_num_keys(&self) -> u64 {
+        self.tree.estimated_num_keys()
+    }
+
+    /// Returns the number of pages of each kind in the table.
+    ///
+    /// This walks through all pages reachable from the root, so it is only
+    /// intended for diagnostics.
+    pub async fn page_kind_counts(&self) -> Result<HashMap<PageKind, usize>> {
+        let txn = self.begin();
+        Ok(txn.page_kind_counts().await?)
+    }
+
+    /// Rebuilds the index of the table from its leaf pages.
+    ///
+    /// This is a last resort to recover a table whose index pages are lost or
+    /// corrupted while the leaf pages survive. It must not run concurrently
+    /// with other operations on the table.
+    ///
+    /// Returns the number of indexed leaf pages, or [`Error::InvalidArgument`]
+    /// if the table has named trees, whose leaf pages can't be told apart.
+    ///
+    /// [`Error::InvalidArgument`]: crate::Error::InvalidArgument
+    pub async fn rebuild_index(&self) -> Result<usize> {
+        if self.store.get_metadata(TREES_METADATA_KEY).await.is_some() {
+            return Err(Error::InvalidArgument);
+        }
+        let txn = self.begin();
+        Ok(txn.rebuild_index().await?)
+    }
+
     /// Returns the minimal LSN that the table can safely read with.
     ///
     /// The table guarantees that entries visible to the returned LSN are
@@ -109,21 +782,357 @@ impl<E: Env> Table<E> {
         self.tree.set_safe_lsn(lsn);
     }
 
+    /// Creates a named savepoint that reads can be served at later, or
+    /// replaces the existing one with the same name.
+    ///
+    /// The savepoint reads at the largest LSN written since the table is
+    /// opened, so the LSNs of writes should be increasing. A write is only
+    /// taken into account once it returns, like in [`Table::snapshot`].
+    /// Entries visible to the savepoint are retained until it is released
+    /// with [`Table::release_savepoint`], even if the safe LSN advances beyond
+    /// it, and so is the current version of the page store.
+    /// Savepoints are not persisted, so they are lost once the table is
+    /// closed.
+    ///
+    /// Returns the LSN of the savepoint.
+    pub fn create_savepoint(&self, name: &str) -> u64 {
+        self.tree.create_savepoint(name, self.store.pin_version())
+    }
+
+    /// Releases the savepoint so that the entries visible to it can be
+    /// dropped.
+    ///
+    /// Returns false if the savepoint doesn't exist.
+    pub fn release_savepoint(&self, name: &str) -> bool {
+        self.tree.release_savepoint(name)
+    }
+
+    /// Gets the value corresponding to the key as of the savepoint.
+    ///
+    /// Returns [`Error::InvalidArgument`] if the savepoint doesn't exist.
+    ///
+    /// [`Error::InvalidArgument`]: crate::Error::InvalidArgument
+    pub async fn get_at_savepoint(&self, name: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let lsn = self.savepoint_lsn(name)?;
+        self.get(key, lsn).await
+    }
+
+    /// Pins the pages covering the range `[start, end)` in the page cache, so
+    /// that they are not evicted for a known hot set.
+    ///
+    /// The pinned pages still count against
+    /// [`PageStoreOptions::cache_capacity`], but the cache evicts other pages
+    /// instead. Only the pages of the range as of now are pinned, since pages
+    /// written later replace them, so pin the range again after it changes a
+    /// lot.
+    ///
+    /// [`PageStoreOptions::cache_capacity`]: crate::PageStoreOptions::cache_capacity
+    pub async fn pin_range(&self, start: &[u8], end: &[u8]) -> Result<()> {
+        let txn = self.begin();
+        Ok(txn.pin_range(start, end).await?)
+    }
+
+    /// Unpins a range pinned by [`Table::pin_range`] with the same bounds.
+    ///
+    /// Returns false if there is no such range.
+    pub fn unpin_range(&self, start: &[u8], end: &[u8]) -> bool {
+        self.tree.unpin_range(start, end)
+    }
+
+    /// Takes a snapshot of the table that reads can be served at later.
+    ///
+    /// The snapshot reads at the largest LSN written since the table is
+    /// opened, so the LSNs of writes should be increasing. A write is only
+    /// taken into account once it returns, so the snapshot never sees a part
+    /// of a write. Entries visible to the snapshot are retained until it is
+    /// dropped, even if the safe LSN advances beyond it, and so is the current
+    /// version of the page store.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::new(self.tree.clone(), self.store.pin_version())
+    }
+
+    /// Gets the value corresponding to the key as of the snapshot.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the snapshot is not taken from this table.
+    pub async fn get_at(&self, snapshot: &Snapshot, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.check_snapshot(snapshot);
+        self.get(key, snapshot.lsn).await
+    }
+
+    /// Returns an iterator over the live key-value entries in the range
+    /// `[start, end)` as of the snapshot.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the snapshot is not taken from this table.
+    pub fn scan_at(&self, snapshot: &Snapshot, start: &[u8], end: &[u8]) -> Scan<'_, E> {
+        self.check_snapshot(snapshot);
+        self.scan(start, end, snapshot.lsn)
+    }
+
+    fn check_snapshot(&self, snapshot: &Snapshot) {
+        assert!(
+            Arc::ptr_eq(&self.tree, &snapshot.tree),
+            "the snapshot is taken from another table"
+        );
+    }
+
+    fn savepoint_lsn(&self, name: &str) -> Result<u64> {
+        self.tree.savepoint_lsn(name).ok_or(Error::InvalidArgument)
+    }
+
+    /// Sets a piece of user metadata, such as a schema version.
+    ///
+    /// The metadata is stored in the manifest, separately from the entries of
+    /// the table, and is durable once this returns. The total size of all
+    /// keys and values is limited to 64 KiB, beyond which
+    /// [`Error::TooLargeSize`] is returned.
+    ///
+    /// The key `photondb.trees` is reserved for [`Table::open_tree`], and the
+    /// key `photondb.lsn` is reserved for [`Table::put_auto`].
+    /// [`Error::InvalidArgument`] is returned for them.
+    ///
+    /// [`Error::InvalidArgument`]: crate::Error::InvalidArgument
+    /// [`Error::TooLargeSize`]: crate::Error::TooLargeSize
+    pub async fn set_metadata(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        if key == TREES_METADATA_KEY || key == LSN_METADATA_KEY {
+            return Err(Error::InvalidArgument);
+        }
+        Ok(self.store.set_metadata(key, value).await?)
+    }
+
+    /// Gets the value of the user metadata set by [`Table::set_metadata`].
+    pub async fn get_metadata(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.store.get_metadata(key).await
+    }
+
+    /// Pauses background maintenance until [`Table::resume_maintenance`] is
+    /// called.
+    ///
+    /// This stops consolidating pages, flushing write buffers, and reclaiming
+    /// space, so that they don't interfere with latency-sensitive workloads.
+    /// The work in progress is finished before the maintenance is paused.
+    /// Writes still work while paused, but pages are consolidated once their
+    /// chains are full, and write buffers are flushed once all of them are
+    /// used up.
+    pub fn pause_maintenance(&self) {
+        self.tree.pause_maintenance();
+        self.store.pause_maintenance();
+    }
+
+    /// Resumes background maintenance paused by
+    /// [`Table::pause_maintenance`].
+    pub fn resume_maintenance(&self) {
+        self.tree.resume_maintenance();
+        self.store.resume_maintenance();
+    }
+
     /// Flush all write buffer data.
     pub async fn flush(&self, opts: &FlushOptions) {
         self.store.flush(opts).await;
     }
 
+    /// Makes all writes that returned before this durable.
+    ///
+    /// Writes are durable once their write buffers are flushed, since page
+    /// files are synced when they are built, before they are recorded in the
+    /// manifest. So this seals the active write buffer and waits for it and
+    /// all sealed ones to be flushed. This can be used to establish
+    /// durability points, such as before acknowledging client requests.
+    pub async fn sync(&self) {
+        self.store.flush(&FlushOptions::default()).await;
+    }
+
     /// Wait all pending reclaiming to finish.
     pub async fn wait_for_reclaiming(&self) {
         self.store.wait_for_reclaiming().await;
     }
+
+    /// Returns the files that space reclaiming would compact now and the
+    /// space it would reclaim, without compacting any file.
+    ///
+    /// The plan is computed from the in-memory statistics of the files, so it
+    /// does no I/O. The background job may pick different files if more pages
+    /// are written or deleted before it runs.
+    pub async fn plan_gc(&self) -> GcPlan {
+        self.store.plan_reclaiming().await
+    }
+
+    /// Returns the ids of the obsolete files that are safe to remove.
+    ///
+    /// Files are only listed once no pinned guard can read them anymore.
+    /// This is always empty unless
+    /// [`PageStoreOptions::keep_obsolete_files`] is set.
+    ///
+    /// [`PageStoreOptions::keep_obsolete_files`]: crate::PageStoreOptions::keep_obsolete_files
+    pub fn obsolete_files(&self) -> Vec<u32> {
+        self.store.obsolete_files()
+    }
+
+    /// Removes the files listed by [`Table::obsolete_files`].
+    ///
+    /// Returns the number of removed files.
+    pub async fn delete_obsolete_files(&self) -> usize {
+        self.store.delete_obsolete_files().await
+    }
+}
+
+/// Splits `items` into at most `num_shards` consecutive shards, whose sizes
+/// differ by at most one.
+fn split_shards<T>(mut items: Vec<T>, num_shards: usize) -> Vec<Vec<T>> {
+    let num_shards = num_shards.clamp(1, items.len().max(1));
+    let (base, extra) = (items.len() / num_shards, items.len() % num_shards);
+    let mut shards = Vec::with_capacity(num_shards);
+    for i in (0..num_shards).rev() {
+        let size = base + usize::from(i < extra);
+        shards.push(items.split_off(items.len() - size));
+    }
+    shards.reverse();
+    shards
+}
+
+/// A value returned by [`Table::get_pinned`].
+///
+/// The pages the value is read from are pinned until it is dropped.
+pub struct PinnedValue<'a, E: Env> {
+    // The value is dropped before the transaction that pins its pages.
+    value: Cow<'static, [u8]>,
+    _txn: TreeTxn<'a, E>,
+}
+
+impl<E: Env> Deref for PinnedValue<'_, E> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.value
+    }
+}
+
+impl<E: Env> AsRef<[u8]> for PinnedValue<'_, E> {
+    fn as_ref(&self) -> &[u8] {
+        &self.value
+    }
+}
+
+impl<E: Env> std::fmt::Debug for PinnedValue<'_, E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("PinnedValue").field(&&*self.value).finish()
+    }
+}
+
+/// A snapshot of a table taken by [`Table::snapshot`].
+///
+/// Entries visible to the snapshot are retained until it is dropped.
+pub struct Snapshot {
+    tree: Arc<Tree>,
+    lsn: u64,
+    _version: VersionPin,
+}
+
+impl Snapshot {
+    fn new(tree: Arc<Tree>, version: VersionPin) -> Self {
+        let lsn = tree.acquire_snapshot();
+        Self {
+            tree,
+            lsn,
+            _version: version,
+        }
+    }
+
+    /// Returns the LSN that the snapshot reads at.
+    pub fn lsn(&self) -> u64 {
+        self.lsn
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        self.tree.release_snapshot(self.lsn);
+    }
+}
+
+impl std::fmt::Debug for Snapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Snapshot").field("lsn", &self.lsn).finish()
+    }
+}
+
+/// An intent to update a key taken by [`Table::get_for_update`].
+pub struct UpdateIntent {
+    tree: Arc<Tree>,
+    key: Vec<u8>,
+    version: Option<u64>,
+}
+
+impl UpdateIntent {
+    /// Returns the key to update.
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Returns the LSN of the newest write to the key when the intent was
+    /// taken, or `None` if the key had never been written.
+    pub fn version(&self) -> Option<u64> {
+        self.version
+    }
+}
+
+impl std::fmt::Debug for UpdateIntent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UpdateIntent")
+            .field("key", &self.key)
+            .field("version", &self.version)
+            .finish()
+    }
+}
+
+/// A batch of puts and deletes to write with [`Table::write`].
+#[derive(Clone, Debug, Default)]
+pub struct WriteBatch {
+    entries: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+}
+
+impl WriteBatch {
+    /// Creates an empty batch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Puts the value of the key in the batch.
+    pub fn put(&mut self, key: &[u8], value: &[u8]) {
+        self.entries.push((key.to_vec(), Some(value.to_vec())));
+    }
+
+    /// Deletes the key in the batch.
+    pub fn delete(&mut self, key: &[u8]) {
+        self.entries.push((key.to_vec(), None));
+    }
+
+    /// Returns the number of entries in the batch.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the batch has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Removes all entries from the batch.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
 }
 
 /// A handle that holds some resources of a table for user operations.
 pub struct Guard<'a, E: Env> {
     table: &'a Table<E>,
     txn: TreeTxn<'a, E>,
+    // The page addresses returned since the table is pinned, which are the only
+    // ones that can be read safely.
+    addrs: std::sync::Mutex<HashSet<PageAddr>>,
 }
 
 impl<'a, E: Env> Guard<'a, E> {
@@ -131,44 +1140,447 @@ impl<'a, E: Env> Guard<'a, E> {
         Self {
             table,
             txn: table.begin(),
+            addrs: std::sync::Mutex::default(),
         }
     }
 
     /// Re-pins the table so that the current pinned resources can be released.
+    ///
+    /// Page addresses returned before are no longer readable.
     pub fn repin(&mut self) {
         self.txn = self.table.begin();
+        self.addrs.get_mut().expect("Poisoned").clear();
     }
 
     /// Gets the value corresponding to the key.
     //
     /// On success, if the value is found, returns [`Option::Some`] with the
     /// value; if the value is not found, returns [`Option::None`].
-    pub async fn get(&self, key: &[u8], lsn: u64) -> Result<Option<&[u8]>> {
+    ///
+    /// The value is borrowed from the table unless it is resolved from some
+    /// merge operands.
+    pub async fn get(&self, key: &[u8], lsn: u64) -> Result<Option<Cow<'_, [u8]>>> {
         let key = Key::new(key, lsn);
         Ok(self.txn.get(key).await?)
     }
 
+    /// Gets the value corresponding to the key without retrying on conflicts.
+    ///
+    /// See [`Table::try_get`] for more details.
+    pub async fn try_get(&self, key: &[u8], lsn: u64) -> Result<Option<Cow<'_, [u8]>>> {
+        let key = Key::new(key, lsn);
+        Ok(self.txn.try_get(key).await?)
+    }
+
+    /// Returns the address of the page that contains the key.
+    ///
+    /// The address can be used to read the page with [`Guard::read_page`]
+    /// later without looking up the key again.
+    pub async fn page_addr(&self, key: &[u8]) -> Result<PageAddr> {
+        let addr = self.txn.page_addr(key).await?;
+        self.addrs.lock().expect("Poisoned").insert(addr);
+        Ok(addr)
+    }
+
+    /// Reads the raw content of the page at the address.
+    ///
+    /// The page is returned as it is stored, which may have been replaced by
+    /// newer pages in the table, but it stays readable until the guard is
+    /// dropped or re-pinned.
+    ///
+    /// Returns [`Error::InvalidArgument`] if the address is not obtained from
+    /// [`Guard::page_addr`] with this guard since it is pinned.
+    pub async fn read_page(&self, addr: PageAddr) -> Result<&[u8]> {
+        if !self.addrs.lock().expect("Poisoned").contains(&addr) {
+            return Err(Error::InvalidArgument);
+        }
+        let page = self.txn.read_page(addr).await?;
+        Ok(page.data())
+    }
+
     /// Returns an iterator over pages in the table.
     pub fn pages(&self) -> Pages<'_, 'a, E> {
-        Pages::new(&self.txn)
+        Pages::new(&self.txn, ReadOptions::default())
+    }
+
+    /// Returns an iterator over pages in the table with the given options.
+    ///
+    /// The iterator borrows the guard exclusively, so that it can release the
+    /// pages it has read once they exceed [`ReadOptions::memory_budget`].
+    pub fn pages_with_options(&mut self, options: ReadOptions) -> Pages<'_, 'a, E> {
+        Pages::new(&self.txn, options)
+    }
+
+    /// Returns an iterator over pages in the table as of the savepoint.
+    ///
+    /// Returns [`Error::InvalidArgument`] if the savepoint doesn't exist.
+    ///
+    /// [`Error::InvalidArgument`]: crate::Error::InvalidArgument
+    pub fn scan_at_savepoint(&self, name: &str) -> Result<Pages<'_, 'a, E>> {
+        let options = ReadOptions {
+            max_lsn: self.table.savepoint_lsn(name)?,
+            ..Default::default()
+        };
+        Ok(Pages::new(&self.txn, options))
     }
 }
 
 /// An iterator over pages in a table.
+///
+/// Once the pages read by the iterator exceed [`ReadOptions::memory_budget`],
+/// they are released before the iterator moves to the next page.
 pub struct Pages<'a, 't: 'a, E: Env> {
-    iter: TreeIter<'a, 't, E>,
+    txn: &'a TreeTxn<'t, E>,
+    iter: TreeIter,
+    memory_budget: usize,
+    // The position in the pages read by the guard when the iterator starts.
+    mark: PinMark,
 }
 
 impl<'a, 't: 'a, E: Env> Pages<'a, 't, E> {
-    fn new(txn: &'a TreeTxn<'t, E>) -> Self {
+    // Note that the memory budget must be unlimited unless the guard is
+    // borrowed exclusively, since the pages read since the mark are released.
+    fn new(txn: &'a TreeTxn<'t, E>, options: ReadOptions) -> Self {
         Self {
-            iter: TreeIter::new(txn, ReadOptions::default()),
+            txn,
+            memory_budget: options.memory_budget,
+            mark: txn.pin_mark(),
+            iter: TreeIter::new(options),
         }
     }
 
     /// Returns the next page in the table.
+    ///
+    /// Returns [`Error::MemoryLimit`] if the page alone exceeds the memory
+    /// budget.
     pub async fn next(&mut self) -> Result<Option<PageIter<'_>>> {
-        Ok(self.iter.next_page().await?)
+        if self.memory_budget == usize::MAX {
+            return Ok(self.iter.next_page(self.txn).await?);
+        }
+        if self.pinned_bytes() > self.memory_budget {
+            // Safety: the former pages are not borrowed by the iterator anymore,
+            // and no one else reads with the guard since it is borrowed
+            // exclusively.
+            unsafe { self.txn.unpin_since(self.mark) };
+        }
+        let released = self.pinned_bytes() == 0;
+        let page = self.iter.next_page(self.txn).await?;
+        if released && self.pinned_bytes() > self.memory_budget {
+            return Err(Error::MemoryLimit);
+        }
+        Ok(page)
+    }
+
+    /// Returns the size of pages read by the iterator and not released yet.
+    pub fn pinned_bytes(&self) -> usize {
+        self.txn.pinned_bytes() - self.mark.pinned_bytes()
+    }
+}
+
+/// An iterator over all key-value entries in a table.
+///
+/// The entries are copied from one page at a time, so the pages are not
+/// pinned between calls.
+pub struct Dump<'a, E: Env> {
+    table: &'a Table<E>,
+    iter: TreeIter,
+    entries: VecDeque<(Vec<u8>, Vec<u8>)>,
+}
+
+impl<'a, E: Env> Dump<'a, E> {
+    fn new(table: &'a Table<E>) -> Self {
+        Self {
+            table,
+            iter: TreeIter::new(ReadOptions::default()),
+            entries: VecDeque::new(),
+        }
+    }
+
+    /// Returns the next entry in the table.
+    pub async fn next(&mut self) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        loop {
+            if let Some(entry) = self.entries.pop_front() {
+                return Ok(Some(entry));
+            }
+            let txn = self.table.begin();
+            match self.iter.next_page(&txn).await? {
+                Some(page) => self
+                    .entries
+                    .extend(page.map(|(k, v)| (k.to_vec(), v.to_vec()))),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// An iterator over the key-value entries in a range of a table.
+///
+/// The entries are copied from one page at a time, so the pages are not
+/// pinned between calls.
+pub struct Scan<'a, E: Env> {
+    table: &'a Table<E>,
+    iter: TreeIter,
+    // The inclusive start of the range, or `None` if the range is unbounded.
+    start: Option<Vec<u8>>,
+    // The exclusive end of the range, or `None` if the range is unbounded.
+    end: Option<Vec<u8>>,
+    // The number of entries the scan can still yield.
+    limit: usize,
+    backward: bool,
+    entries: VecDeque<(Vec<u8>, Vec<u8>)>,
+    done: bool,
+    keys_only: bool,
+}
+
+impl<'a, E: Env> Scan<'a, E> {
+    fn new(table: &'a Table<E>, iter: TreeIter, end: Option<Vec<u8>>) -> Self {
+        let options = iter.options();
+        let start = options.lower_bound.clone();
+        let limit = options.limit;
+        let backward = options.direction == Direction::Backward;
+        Self {
+            table,
+            iter,
+            start,
+            end,
+            limit,
+            backward,
+            entries: VecDeque::new(),
+            done: limit == 0,
+            keys_only: false,
+        }
+    }
+
+    /// Makes the iterator yield only the keys, with empty values.
+    ///
+    /// The values are never copied out of the pages, which saves allocations
+    /// for scans that only check which keys exist.
+    pub fn keys_only(mut self) -> Self {
+        self.keys_only = true;
+        self
+    }
+
+    fn read_options(lsn: u64) -> ReadOptions {
+        ReadOptions {
+            max_lsn: lsn,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the next entry in the range.
+    pub async fn next(&mut self) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        loop {
+            if let Some(entry) = self.entries.pop_front() {
+                return Ok(Some(entry));
+            }
+            if self.done {
+                return Ok(None);
+            }
+            let txn = self.table.begin();
+            let Some(page) = self.iter.next_page(&txn).await? else {
+                self.done = true;
+                continue;
+            };
+            let mut entries = Vec::new();
+            for (k, v) in page {
+                if self.end.as_deref().map_or(false, |end| k >= end) {
+                    // Former pages of a backward scan may still be in the range.
+                    self.done = !self.backward;
+                    break;
+                }
+                if self.start.as_deref().map_or(false, |start| k < start) {
+                    continue;
+                }
+                let v = if self.keys_only {
+                    Vec::new()
+                } else {
+                    v.to_vec()
+                };
+                entries.push((k.to_vec(), v));
+                if !self.backward && entries.len() == self.limit {
+                    break;
+                }
+            }
+            if self.backward {
+                entries.reverse();
+                entries.truncate(self.limit);
+            }
+            self.limit -= entries.len();
+            if self.limit == 0 {
+                self.done = true;
+            }
+            self.entries.extend(entries);
+        }
+    }
+
+    /// Converts this into a [`Stream`] of the entries in the range.
+    ///
+    /// The next page is only read when the stream is polled after all entries
+    /// of the previous page are consumed, so a slow consumer holds at most one
+    /// page of entries in memory. The stream ends after an error.
+    pub fn into_stream(self) -> impl Stream<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a {
+        stream::unfold(Some(self), |scan| async move {
+            let mut scan = scan?;
+            match scan.next().await {
+                Ok(Some(entry)) => Some((Ok(entry), Some(scan))),
+                Ok(None) => None,
+                Err(err) => Some((Err(err), None)),
+            }
+        })
+    }
+}
+
+/// Returns the smallest key after all keys with the prefix, or `None` if there
+/// is no such key.
+fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return Some(end);
+        }
+    }
+    None
+}
+
+/// Encodes the root ids of named trees as a sequence of the name length, the
+/// name, and the root id.
+fn encode_tree_roots(roots: &HashMap<String, u64>) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for (name, root_id) in roots {
+        buf.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        buf.extend_from_slice(name.as_bytes());
+        buf.extend_from_slice(&root_id.to_le_bytes());
+    }
+    buf
+}
+
+fn decode_tree_roots(mut buf: &[u8]) -> Result<HashMap<String, u64>> {
+    fn take<'a>(buf: &mut &'a [u8], n: usize) -> Result<&'a [u8]> {
+        if buf.len() < n {
+            return Err(Error::Corrupted);
+        }
+        let (head, tail) = buf.split_at(n);
+        *buf = tail;
+        Ok(head)
+    }
+
+    let mut roots = HashMap::new();
+    while !buf.is_empty() {
+        let len = u32::from_le_bytes(take(&mut buf, 4)?.try_into().unwrap());
+        let name = take(&mut buf, len as usize)?;
+        let name = String::from_utf8(name.to_vec()).map_err(|_| Error::Corrupted)?;
+        let root_id = u64::from_le_bytes(take(&mut buf, 8)?.try_into().unwrap());
+        roots.insert(name, root_id);
+    }
+    Ok(roots)
+}
+
+/// A sequence of LSNs that keeps increasing across restarts.
+///
+/// Persisting every assigned LSN would cost a manifest write per write, so
+/// LSNs are reserved in the metadata in batches instead. After restarts, the
+/// sequence continues from the largest reserved LSN, which skips the unused
+/// LSNs of the last batch.
+#[derive(Debug)]
+struct LsnSequence {
+    // The LSN at or below which all assigned LSNs are completed.
+    latest: AtomicU64,
+    state: std::sync::Mutex<LsnState>,
+    // Serializes the reservations.
+    reserve_lock: Mutex<()>,
+}
+
+struct LsnState {
+    // The last assigned LSN.
+    assigned: u64,
+    // The last LSN reserved in the metadata.
+    reserved: u64,
+    // The assigned LSNs that are not completed yet.
+    pending: BTreeSet<u64>,
+}
+
+impl LsnSequence {
+    async fn load<E: Env>(store: &PageStore<E>) -> Result<Self> {
+        let reserved = match store.get_metadata(LSN_METADATA_KEY).await {
+            Some(buf) => u64::from_le_bytes(buf.try_into().map_err(|_| Error::Corrupted)?),
+            None => 0,
+        };
+        let state = LsnState {
+            assigned: reserved,
+            reserved,
+            pending: BTreeSet::new(),
+        };
+        Ok(Self {
+            latest: AtomicU64::new(reserved),
+            state: std::sync::Mutex::new(state),
+            reserve_lock: Mutex::new(()),
+        })
+    }
+
+    fn latest(&self) -> u64 {
+        self.latest.load(Ordering::Acquire)
+    }
+
+    /// Assigns the next LSN, which is reserved before it is assigned.
+    ///
+    /// The LSN is completed when the returned value is dropped. Nothing is
+    /// assigned if the reservation fails.
+    async fn next<E: Env>(&self, store: &PageStore<E>) -> Result<AssignedLsn<'_>> {
+        loop {
+            if let Some(lsn) = self.try_assign() {
+                return Ok(AssignedLsn { lsns: self, lsn });
+            }
+            let _lock = self.reserve_lock.lock().await;
+            let reserved = {
+                let state = self.state.lock().expect("Poisoned");
+                if state.assigned < state.reserved {
+                    // Another reservation has been made.
+                    continue;
+                }
+                state.reserved + LSN_RESERVE_SIZE
+            };
+            store
+                .set_metadata(LSN_METADATA_KEY, &reserved.to_le_bytes())
+                .await?;
+            self.state.lock().expect("Poisoned").reserved = reserved;
+        }
+    }
+
+    fn try_assign(&self) -> Option<u64> {
+        let mut state = self.state.lock().expect("Poisoned");
+        if state.assigned >= state.reserved {
+            return None;
+        }
+        state.assigned += 1;
+        let lsn = state.assigned;
+        state.pending.insert(lsn);
+        Some(lsn)
+    }
+
+    // Completes the LSN, and advances the latest LSN up to the first one that
+    // is not completed yet.
+    fn complete(&self, lsn: u64) {
+        let mut state = self.state.lock().expect("Poisoned");
+        state.pending.remove(&lsn);
+        let latest = match state.pending.iter().next() {
+            Some(first) => first - 1,
+            None => state.assigned,
+        };
+        self.latest.store(latest, Ordering::Release);
+    }
+}
+
+/// An LSN assigned by [`LsnSequence::next`], which is completed on drop,
+/// whether the write at it succeeds or not.
+struct AssignedLsn<'a> {
+    lsns: &'a LsnSequence,
+    lsn: u64,
+}
+
+impl Drop for AssignedLsn<'_> {
+    fn drop(&mut self) {
+        self.lsns.complete(self.lsn);
     }
 }
 
@@ -0,0 +1,224 @@
+use std::marker::PhantomData;
+
+use super::{Scan, Table};
+use crate::{env::Env, Error, Result};
+
+/// A type that can be encoded into bytes for a [`TypedTable`].
+///
+/// The encodings of keys must preserve their order, so that the entries of a
+/// table are sorted by the keys as they are compared in Rust, and scans over a
+/// range of keys are meaningful.
+pub trait Encodable {
+    /// Appends the encoding of this to `buf`.
+    fn encode_to(&self, buf: &mut Vec<u8>);
+
+    /// Returns the encoding of this.
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.encode_to(&mut buf);
+        buf
+    }
+}
+
+/// A type that can be decoded from the bytes of its [`Encodable`] encoding.
+pub trait Decodable: Sized {
+    /// Decodes a value from the bytes.
+    ///
+    /// Returns [`Error::Corrupted`] if the bytes are not a valid encoding.
+    fn decode(buf: &[u8]) -> Result<Self>;
+}
+
+// Unsigned integers are encoded in big-endian, which sorts in the same order.
+macro_rules! impl_unsigned {
+    ($($t:ty),*) => {$(
+        impl Encodable for $t {
+            fn encode_to(&self, buf: &mut Vec<u8>) {
+                buf.extend_from_slice(&self.to_be_bytes());
+            }
+        }
+
+        impl Decodable for $t {
+            fn decode(buf: &[u8]) -> Result<Self> {
+                let bytes = buf.try_into().map_err(|_| Error::Corrupted)?;
+                Ok(<$t>::from_be_bytes(bytes))
+            }
+        }
+    )*};
+}
+
+// Signed integers are encoded in big-endian with the sign bit flipped, so that
+// negative values sort before positive ones.
+macro_rules! impl_signed {
+    ($($t:ty => $u:ty),*) => {$(
+        impl Encodable for $t {
+            fn encode_to(&self, buf: &mut Vec<u8>) {
+                let v = (*self as $u) ^ (1 << (<$u>::BITS - 1));
+                buf.extend_from_slice(&v.to_be_bytes());
+            }
+        }
+
+        impl Decodable for $t {
+            fn decode(buf: &[u8]) -> Result<Self> {
+                let v = <$u>::decode(buf)?;
+                Ok((v ^ (1 << (<$u>::BITS - 1))) as $t)
+            }
+        }
+    )*};
+}
+
+impl_unsigned!(u8, u16, u32, u64, u128);
+impl_signed!(i8 => u8, i16 => u16, i32 => u32, i64 => u64, i128 => u128);
+
+impl Encodable for [u8] {
+    fn encode_to(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self);
+    }
+}
+
+impl Encodable for Vec<u8> {
+    fn encode_to(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self);
+    }
+}
+
+impl Decodable for Vec<u8> {
+    fn decode(buf: &[u8]) -> Result<Self> {
+        Ok(buf.to_vec())
+    }
+}
+
+// UTF-8 bytes sort in the same order as the code points.
+impl Encodable for str {
+    fn encode_to(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl Encodable for String {
+    fn encode_to(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl Decodable for String {
+    fn decode(buf: &[u8]) -> Result<Self> {
+        String::from_utf8(buf.to_vec()).map_err(|_| Error::Corrupted)
+    }
+}
+
+/// A table with keys and values of the types.
+///
+/// Keys and values are encoded with [`Encodable`] when they are written, and
+/// decoded with [`Decodable`] when they are read. The typed table is a thin
+/// wrapper around the [`Table`], which can still be used to access the raw
+/// entries.
+pub struct TypedTable<K, V, E: Env> {
+    table: Table<E>,
+    _marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<K, V, E: Env> TypedTable<K, V, E>
+where
+    K: Encodable + Decodable,
+    V: Encodable + Decodable,
+{
+    /// Creates a typed table over the table.
+    pub fn new(table: Table<E>) -> Self {
+        Self {
+            table,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the underlying table.
+    pub fn table(&self) -> &Table<E> {
+        &self.table
+    }
+
+    /// Gets the value corresponding to the key.
+    pub async fn get(&self, key: &K, lsn: u64) -> Result<Option<V>> {
+        let value = self.table.get(&key.encode(), lsn).await?;
+        value.map(|v| V::decode(&v)).transpose()
+    }
+
+    /// Puts a key-value entry to the table.
+    pub async fn put(&self, key: &K, lsn: u64, value: &V) -> Result<()> {
+        self.table.put(&key.encode(), lsn, &value.encode()).await
+    }
+
+    /// Deletes the entry corresponding to the key from the table.
+    pub async fn delete(&self, key: &K, lsn: u64) -> Result<()> {
+        self.table.delete(&key.encode(), lsn).await
+    }
+
+    /// Returns an iterator over the live entries in the range `[start, end)`,
+    /// as of `lsn`.
+    pub fn scan(&self, start: &K, end: &K, lsn: u64) -> TypedScan<'_, K, V, E> {
+        let scan = self.table.scan(&start.encode(), &end.encode(), lsn);
+        TypedScan {
+            scan,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<K, V, E: Env> Clone for TypedTable<K, V, E> {
+    fn clone(&self) -> Self {
+        Self {
+            table: self.table.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// An iterator over the decoded entries in a range of a [`TypedTable`].
+pub struct TypedScan<'a, K, V, E: Env> {
+    scan: Scan<'a, E>,
+    _marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<'a, K, V, E: Env> TypedScan<'a, K, V, E>
+where
+    K: Decodable,
+    V: Decodable,
+{
+    /// Returns the next entry in the range.
+    pub async fn next(&mut self) -> Result<Option<(K, V)>> {
+        match self.scan.next().await? {
+            Some((k, v)) => Ok(Some((K::decode(&k)?, V::decode(&v)?))),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_order_preserved<T>(values: &[T])
+    where
+        T: Encodable + Decodable + Ord + std::fmt::Debug,
+    {
+        for w in values.windows(2) {
+            assert!(w[0] < w[1]);
+            assert!(w[0].encode() < w[1].encode());
+        }
+        for v in values {
+            assert_eq!(&T::decode(&v.encode()).unwrap(), v);
+        }
+    }
+
+    #[test]
+    fn encoding_order() {
+        assert_order_preserved(&[0u8, 1, 127, 128, u8::MAX]);
+        assert_order_preserved(&[0u64, 1, 256, u32::MAX as u64, u64::MAX]);
+        assert_order_preserved(&[i8::MIN, -1, 0, 1, i8::MAX]);
+        assert_order_preserved(&[i64::MIN, -256, -1, 0, 1, 256, i64::MAX]);
+        assert_order_preserved(&[i128::MIN, -1, 0, i128::MAX]);
+        assert_order_preserved(&[String::new(), "a".into(), "ab".into(), "b".into()]);
+        assert_order_preserved(&[vec![], vec![0u8], vec![0, 0], vec![1]]);
+
+        assert!(matches!(u64::decode(&[0; 7]), Err(Error::Corrupted)));
+        assert!(matches!(String::decode(&[0xff]), Err(Error::Corrupted)));
+    }
+}
@@ -10,16 +10,22 @@
 //! [`Std`]: crate::env::Std
 
 use std::{
+    borrow::Cow,
     future::Future,
     ops::Deref,
     path::Path,
     pin::Pin,
     task::{Context, Poll},
+    time::Duration,
 };
 
 use futures::task::noop_waker_ref;
 
-use crate::{env::Std, raw, PageIter, Result, TableOptions};
+use crate::{
+    env::Std,
+    raw::{self, Decodable, Encodable},
+    FlushOptions, PageIter, ReadOptions, Result, Snapshot, TableOptions, UpdateIntent, WriteBatch,
+};
 
 /// A reference to a latch-free, log-structured table that stores sorted
 /// key-value entries.
@@ -45,11 +51,82 @@ impl Table {
         poll(self.0.close()).map_err(Self)
     }
 
+    /// Closes the table and removes all its files if this is the only
+    /// reference to it.
+    ///
+    /// This is a synchronous version of [`raw::Table::destroy`].
+    pub fn destroy(self) -> Result<Result<()>, Self> {
+        poll(self.0.destroy()).map_err(Self)
+    }
+
+    /// Opens the named tree in the table, creating it if it doesn't exist.
+    ///
+    /// This is a synchronous version of [`raw::Table::open_tree`].
+    pub fn open_tree(&self, name: &str) -> Result<Self> {
+        let table = poll(self.0.open_tree(name))?;
+        Ok(Self(table))
+    }
+
+    /// Returns a [`TypedTable`] over this table.
+    pub fn typed<K, V>(&self) -> TypedTable<K, V>
+    where
+        K: Encodable + Decodable,
+        V: Encodable + Decodable,
+    {
+        TypedTable(raw::TypedTable::new(self.0.clone()))
+    }
+
     /// Returns a [`Guard`] that pins the table for user operations.
     pub fn pin(&self) -> Guard<'_> {
         Guard(self.0.pin())
     }
 
+    /// Returns an iterator over all live key-value entries in the table.
+    ///
+    /// This is a synchronous version of [`raw::Table::dump`].
+    pub fn dump(&self) -> Dump<'_> {
+        Dump(self.0.dump())
+    }
+
+    /// Returns an iterator over the live key-value entries in the range
+    /// `[start, end)`.
+    ///
+    /// This is a synchronous version of [`raw::Table::scan`].
+    pub fn scan(&self, start: &[u8], end: &[u8], lsn: u64) -> Scan<'_> {
+        Scan(self.0.scan(start, end, lsn))
+    }
+
+    /// Gets the value corresponding to the key as of the snapshot.
+    ///
+    /// This is a synchronous version of [`raw::Table::get_at`].
+    pub fn get_at(&self, snapshot: &Snapshot, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        poll(self.0.get_at(snapshot, key))
+    }
+
+    /// Returns an iterator over the live key-value entries in the range
+    /// `[start, end)` as of the snapshot.
+    ///
+    /// This is a synchronous version of [`raw::Table::scan_at`].
+    pub fn scan_at(&self, snapshot: &Snapshot, start: &[u8], end: &[u8]) -> Scan<'_> {
+        Scan(self.0.scan_at(snapshot, start, end))
+    }
+
+    /// Returns an iterator over the live key-value entries whose keys start
+    /// with `prefix`.
+    ///
+    /// This is a synchronous version of [`raw::Table::scan_prefix`].
+    pub fn scan_prefix(&self, prefix: &[u8], lsn: u64) -> Scan<'_> {
+        Scan(self.0.scan_prefix(prefix, lsn))
+    }
+
+    /// Returns an iterator over the live key-value entries within the bounds
+    /// of `options`.
+    ///
+    /// This is a synchronous version of [`raw::Table::scan_with_options`].
+    pub fn scan_with_options(&self, options: ReadOptions) -> Scan<'_> {
+        Scan(self.0.scan_with_options(options))
+    }
+
     /// Gets the value corresponding to the key.
     ///
     /// This is a synchronous version of [`raw::Table::get`].
@@ -57,6 +134,34 @@ impl Table {
         poll(self.0.get(key, lsn))
     }
 
+    /// Gets the value corresponding to the key without copying it.
+    ///
+    /// This is a synchronous version of [`raw::Table::get_pinned`].
+    pub fn get_pinned(&self, key: &[u8], lsn: u64) -> Result<Option<raw::PinnedValue<'_, Std>>> {
+        poll(self.0.get_pinned(key, lsn))
+    }
+
+    /// Gets the values corresponding to the keys at the LSN.
+    ///
+    /// This is a synchronous version of [`raw::Table::multi_get`].
+    pub fn multi_get(&self, keys: &[&[u8]], lsn: u64) -> Result<Vec<Option<Vec<u8>>>> {
+        poll(self.0.multi_get(keys, lsn))
+    }
+
+    /// Returns true if the table contains a value for the key.
+    ///
+    /// This is a synchronous version of [`raw::Table::contains`].
+    pub fn contains(&self, key: &[u8], lsn: u64) -> Result<bool> {
+        poll(self.0.contains(key, lsn))
+    }
+
+    /// Gets the value corresponding to the key without retrying on conflicts.
+    ///
+    /// This is a synchronous version of [`raw::Table::try_get`].
+    pub fn try_get(&self, key: &[u8], lsn: u64) -> Result<Option<Vec<u8>>> {
+        poll(self.0.try_get(key, lsn))
+    }
+
     /// Puts a key-value entry to the table.
     ///
     /// This is a synchronous version of [`raw::Table::put`].
@@ -64,12 +169,196 @@ impl Table {
         poll(self.0.put(key, lsn, value))
     }
 
+    /// Puts a key-value entry that expires after the TTL to the table.
+    ///
+    /// This is a synchronous version of [`raw::Table::put_with_ttl`].
+    pub fn put_with_ttl(&self, key: &[u8], lsn: u64, value: &[u8], ttl: Duration) -> Result<()> {
+        poll(self.0.put_with_ttl(key, lsn, value, ttl))
+    }
+
+    /// Puts a key-value entry to the table at the next LSN of the table.
+    ///
+    /// This is a synchronous version of [`raw::Table::put_auto`].
+    pub fn put_auto(&self, key: &[u8], value: &[u8]) -> Result<u64> {
+        poll(self.0.put_auto(key, value))
+    }
+
+    /// Merges an operand into the value corresponding to the key.
+    ///
+    /// This is a synchronous version of [`raw::Table::merge`].
+    pub fn merge(&self, key: &[u8], lsn: u64, operand: &[u8]) -> Result<()> {
+        poll(self.0.merge(key, lsn, operand))
+    }
+
     /// Deletes the entry corresponding to the key from the table.
     ///
     /// This is a synchronous version of [`raw::Table::delete`].
     pub fn delete(&self, key: &[u8], lsn: u64) -> Result<()> {
         poll(self.0.delete(key, lsn))
     }
+
+    /// Deletes the entry corresponding to the key from the table at the next
+    /// LSN of the table.
+    ///
+    /// This is a synchronous version of [`raw::Table::delete_auto`].
+    pub fn delete_auto(&self, key: &[u8]) -> Result<u64> {
+        poll(self.0.delete_auto(key))
+    }
+
+    /// Deletes the entries in `[start, end)` from the table at the LSN.
+    ///
+    /// This is a synchronous version of [`raw::Table::delete_range`].
+    pub fn delete_range(&self, start: &[u8], end: &[u8], lsn: u64) -> Result<()> {
+        poll(self.0.delete_range(start, end, lsn))
+    }
+
+    /// Writes a batch of entries at the LSN.
+    ///
+    /// This is a synchronous version of [`raw::Table::write_batch`].
+    pub fn write_batch(&self, batch: &[(&[u8], Option<&[u8]>)], lsn: u64) -> Result<()> {
+        poll(self.0.write_batch(batch, lsn))
+    }
+
+    /// Writes a [`WriteBatch`] at the LSN as a whole.
+    ///
+    /// This is a synchronous version of [`raw::Table::write`].
+    pub fn write(&self, batch: &WriteBatch, lsn: u64) -> Result<()> {
+        poll(self.0.write(batch, lsn))
+    }
+
+    /// Writes `new` to the key at the LSN if its current value is `expected`.
+    ///
+    /// This is a synchronous version of [`raw::Table::compare_and_swap`].
+    pub fn compare_and_swap(
+        &self,
+        key: &[u8],
+        lsn: u64,
+        expected: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<bool> {
+        poll(self.0.compare_and_swap(key, lsn, expected, new))
+    }
+
+    /// Gets the newest value of the key and takes an intent to update it.
+    ///
+    /// This is a synchronous version of [`raw::Table::get_for_update`].
+    pub fn get_for_update(&self, key: &[u8]) -> Result<(Option<Vec<u8>>, UpdateIntent)> {
+        poll(self.0.get_for_update(key))
+    }
+
+    /// Writes `new` to the key of the intent at the LSN if the key hasn't been
+    /// written since the intent was taken.
+    ///
+    /// This is a synchronous version of [`raw::Table::commit_update`].
+    pub fn commit_update(
+        &self,
+        intent: &UpdateIntent,
+        lsn: u64,
+        new: Option<&[u8]>,
+    ) -> Result<bool> {
+        poll(self.0.commit_update(intent, lsn, new))
+    }
+
+    /// Moves the value of `from` to `to` at the LSN.
+    ///
+    /// This is a synchronous version of [`raw::Table::rename`].
+    pub fn rename(&self, from: &[u8], to: &[u8], lsn: u64) -> Result<bool> {
+        poll(self.0.rename(from, to, lsn))
+    }
+
+    /// Deletes all entries visible at the LSN that match the predicate.
+    ///
+    /// This is a synchronous version of [`raw::Table::delete_where`].
+    pub fn delete_where<P, F>(&self, lsn: u64, predicate: P, progress: F) -> Result<usize>
+    where
+        P: FnMut(&[u8], &[u8]) -> bool,
+        F: FnMut(usize) -> bool,
+    {
+        poll(self.0.delete_where(lsn, predicate, progress))
+    }
+
+    /// Returns the keys whose newest versions are written at or after `lsn`.
+    ///
+    /// This is a synchronous version of [`raw::Table::scan_since`].
+    pub fn scan_since(&self, lsn: u64, ghost: bool) -> Result<Vec<(Vec<u8>, Option<Vec<u8>>)>> {
+        poll(self.0.scan_since(lsn, ghost))
+    }
+
+    /// Estimates the number of entries and bytes in the range `[start, end)`.
+    ///
+    /// This is a synchronous version of [`raw::Table::estimate_range`].
+    pub fn estimate_range(&self, start: &[u8], end: &[u8]) -> Result<(u64, u64)> {
+        poll(self.0.estimate_range(start, end))
+    }
+
+    /// Returns the approximate size in bytes that the range `[start, end)`
+    /// takes in the page store.
+    ///
+    /// This is a synchronous version of [`raw::Table::approximate_size`].
+    pub fn approximate_size(&self, start: &[u8], end: &[u8]) -> Result<u64> {
+        poll(self.0.approximate_size(start, end))
+    }
+
+    /// Gets the value corresponding to the key as of the savepoint.
+    ///
+    /// This is a synchronous version of [`raw::Table::get_at_savepoint`].
+    pub fn get_at_savepoint(&self, name: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        poll(self.0.get_at_savepoint(name, key))
+    }
+
+    /// Pins the pages covering the range `[start, end)` in the page cache.
+    ///
+    /// This is a synchronous version of [`raw::Table::pin_range`].
+    pub fn pin_range(&self, start: &[u8], end: &[u8]) -> Result<()> {
+        poll(self.0.pin_range(start, end))
+    }
+
+    /// Sets a piece of user metadata.
+    ///
+    /// This is a synchronous version of [`raw::Table::set_metadata`].
+    pub fn set_metadata(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        poll(self.0.set_metadata(key, value))
+    }
+
+    /// Gets the value of the user metadata.
+    ///
+    /// This is a synchronous version of [`raw::Table::get_metadata`].
+    pub fn get_metadata(&self, key: &[u8]) -> Option<Vec<u8>> {
+        poll(self.0.get_metadata(key))
+    }
+
+    /// Loads sorted key-value entries into an empty table in parallel.
+    ///
+    /// This is a synchronous version of [`raw::Table::load_sharded`].
+    pub fn load_sharded(
+        &self,
+        entries: Vec<(Vec<u8>, Vec<u8>)>,
+        lsn: u64,
+        num_shards: usize,
+    ) -> Result<()> {
+        poll(self.0.load_sharded(entries, lsn, num_shards))
+    }
+
+    /// Flush all write buffer data.
+    ///
+    /// This is a synchronous version of [`raw::Table::flush`].
+    pub fn flush(&self, opts: &FlushOptions) {
+        poll(self.0.flush(opts))
+    }
+
+    /// Makes all writes that returned before this durable.
+    ///
+    /// This is a synchronous version of [`raw::Table::sync`].
+    pub fn sync(&self) {
+        poll(self.0.sync())
+    }
+
+    /// Returns a [`BulkLoader`] to fill the table with entries at the LSN.
+    ///
+    /// This is a synchronous version of [`raw::Table::bulk_loader`].
+    pub fn bulk_loader(&self, lsn: u64) -> Result<BulkLoader<'_>> {
+        poll(self.0.bulk_loader(lsn)).map(BulkLoader)
+    }
 }
 
 impl Deref for Table {
@@ -87,14 +376,31 @@ impl<'a> Guard<'a> {
     /// Gets the value corresponding to the key.
     ///
     /// This is a synchronous version of [`raw::Guard::get`].
-    pub fn get(&self, key: &[u8], lsn: u64) -> Result<Option<&[u8]>> {
+    pub fn get(&self, key: &[u8], lsn: u64) -> Result<Option<Cow<'_, [u8]>>> {
         poll(self.0.get(key, lsn))
     }
 
+    /// Gets the value corresponding to the key without retrying on conflicts.
+    ///
+    /// This is a synchronous version of [`raw::Guard::try_get`].
+    pub fn try_get(&self, key: &[u8], lsn: u64) -> Result<Option<Cow<'_, [u8]>>> {
+        poll(self.0.try_get(key, lsn))
+    }
+
     /// Returns an iterator over pages in the table.
     pub fn pages(&self) -> Pages<'_, 'a> {
         Pages(self.0.pages())
     }
+
+    /// Returns an iterator over pages in the table with the given options.
+    pub fn pages_with_options(&mut self, options: ReadOptions) -> Pages<'_, 'a> {
+        Pages(self.0.pages_with_options(options))
+    }
+
+    /// Returns an iterator over pages in the table as of the savepoint.
+    pub fn scan_at_savepoint(&self, name: &str) -> Result<Pages<'_, 'a>> {
+        self.0.scan_at_savepoint(name).map(Pages)
+    }
 }
 
 impl<'a> Deref for Guard<'a> {
@@ -127,6 +433,110 @@ impl<'a, 't> Deref for Pages<'a, 't> {
     }
 }
 
+/// An iterator over all key-value entries in a table.
+pub struct Dump<'a>(raw::Dump<'a, Std>);
+
+impl<'a> Iterator for Dump<'a> {
+    type Item = Result<(Vec<u8>, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        poll(self.0.next()).transpose()
+    }
+}
+
+/// An iterator over the key-value entries in a range of a table.
+pub struct Scan<'a>(raw::Scan<'a, Std>);
+
+impl<'a> Scan<'a> {
+    /// Makes the iterator yield only the keys, with empty values.
+    ///
+    /// This is a synchronous version of [`raw::Scan::keys_only`].
+    pub fn keys_only(self) -> Self {
+        Self(self.0.keys_only())
+    }
+}
+
+impl<'a> Iterator for Scan<'a> {
+    type Item = Result<(Vec<u8>, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        poll(self.0.next()).transpose()
+    }
+}
+
+/// A loader that fills an empty table with entries in key order.
+pub struct BulkLoader<'a>(raw::BulkLoader<'a, Std>);
+
+impl<'a> BulkLoader<'a> {
+    /// Adds an entry to the table.
+    ///
+    /// This is a synchronous version of [`raw::BulkLoader::add`].
+    pub fn add(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        poll(self.0.add(key, value))
+    }
+
+    /// Builds the index of the added entries and makes them visible.
+    ///
+    /// This is a synchronous version of [`raw::BulkLoader::finish`].
+    pub fn finish(self) -> Result<usize> {
+        poll(self.0.finish())
+    }
+}
+
+/// A table with keys and values of the types.
+///
+/// This is the same as [`raw::TypedTable`] with the [`Std`] environment.
+pub struct TypedTable<K, V>(raw::TypedTable<K, V, Std>);
+
+impl<K, V> TypedTable<K, V>
+where
+    K: Encodable + Decodable,
+    V: Encodable + Decodable,
+{
+    /// Gets the value corresponding to the key.
+    ///
+    /// This is a synchronous version of [`raw::TypedTable::get`].
+    pub fn get(&self, key: &K, lsn: u64) -> Result<Option<V>> {
+        poll(self.0.get(key, lsn))
+    }
+
+    /// Puts a key-value entry to the table.
+    ///
+    /// This is a synchronous version of [`raw::TypedTable::put`].
+    pub fn put(&self, key: &K, lsn: u64, value: &V) -> Result<()> {
+        poll(self.0.put(key, lsn, value))
+    }
+
+    /// Deletes the entry corresponding to the key from the table.
+    ///
+    /// This is a synchronous version of [`raw::TypedTable::delete`].
+    pub fn delete(&self, key: &K, lsn: u64) -> Result<()> {
+        poll(self.0.delete(key, lsn))
+    }
+
+    /// Returns an iterator over the live entries in the range `[start, end)`.
+    ///
+    /// This is a synchronous version of [`raw::TypedTable::scan`].
+    pub fn scan(&self, start: &K, end: &K, lsn: u64) -> TypedScan<'_, K, V> {
+        TypedScan(self.0.scan(start, end, lsn))
+    }
+}
+
+/// An iterator over the decoded entries in a range of a typed table.
+pub struct TypedScan<'a, K, V>(raw::TypedScan<'a, K, V, Std>);
+
+impl<'a, K, V> Iterator for TypedScan<'a, K, V>
+where
+    K: Decodable,
+    V: Decodable,
+{
+    type Item = Result<(K, V)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        poll(self.0.next()).transpose()
+    }
+}
+
 fn poll<F: Future>(mut future: F) -> F::Output {
     let cx = &mut Context::from_waker(noop_waker_ref());
     loop {
@@ -0,0 +1,298 @@
+use super::*;
+
+/// Builds the pages of an empty tree from entries in key order.
+///
+/// Entries are packed into leaf pages as they are added, and the index pages
+/// are built on top of the leaf pages once all entries are added. The pages
+/// are written to the page store as they are, without delta chains or
+/// consolidations, and the tree only sees them when the root is replaced in
+/// [`BulkLoad::finish`].
+///
+/// Disjoint key ranges can be loaded concurrently by the forks of a load,
+/// whose leaf pages are then appended to it in key order.
+///
+/// The pages are released if the load fails to finish. If the load is dropped
+/// before it finishes, its pages are released by the next load of the tree.
+pub(crate) struct BulkLoad {
+    lsn: u64,
+    // The address of the empty root, which must not change during the load.
+    root_addr: u64,
+    // The entries of the leaf page being built.
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+    entries_size: usize,
+    // The first keys and ids of the leaf pages built so far.
+    leaves: Vec<(Vec<u8>, u64)>,
+    // The number of entries of each leaf page.
+    leaf_counts: Vec<u64>,
+    last_key: Option<Vec<u8>>,
+    num_entries: usize,
+    // The ids and addresses of the pages inserted by the load, which are not
+    // reachable from the tree until the load finishes.
+    pages: Vec<(u64, u64)>,
+    abandoned_pages: Arc<Mutex<Vec<(u64, u64)>>>,
+}
+
+impl BulkLoad {
+    fn new(lsn: u64, root_addr: u64, abandoned_pages: Arc<Mutex<Vec<(u64, u64)>>>) -> Self {
+        BulkLoad {
+            lsn,
+            root_addr,
+            entries: Vec::new(),
+            entries_size: 0,
+            leaves: Vec::new(),
+            leaf_counts: Vec::new(),
+            last_key: None,
+            num_entries: 0,
+            pages: Vec::new(),
+            abandoned_pages,
+        }
+    }
+
+    /// Returns an empty load into the same tree, which builds its pages
+    /// independently of this one until it is appended with
+    /// [`BulkLoad::append`].
+    pub(crate) fn fork(&self) -> BulkLoad {
+        BulkLoad::new(self.lsn, self.root_addr, self.abandoned_pages.clone())
+    }
+
+    /// Adds an entry, which must be larger than all entries added before.
+    pub(crate) async fn add<E: Env>(
+        &mut self,
+        txn: &TreeTxn<'_, E>,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<()> {
+        txn.check_write(key, Value::Put(value))?;
+        if self.last_key.as_deref().map_or(false, |last| last >= key) {
+            return Err(Error::InvalidArgument);
+        }
+        self.last_key = Some(key.to_vec());
+        self.entries_size += key.len() + value.len();
+        self.entries.push((key.to_vec(), value.to_vec()));
+        self.num_entries += 1;
+        // Only half of the page is filled with keys and values, which leaves
+        // room for the encoding overhead and for writes without splits.
+        if self.entries_size >= txn.tree.options.page_size / 2 {
+            self.build_leaf(txn).await?;
+        }
+        Ok(())
+    }
+
+    async fn build_leaf<E: Env>(&mut self, txn: &TreeTxn<'_, E>) -> Result<()> {
+        let items: Vec<_> = self
+            .entries
+            .iter()
+            .map(|(k, v)| (Key::new(k, self.lsn), Value::Put(v)))
+            .collect();
+        let builder = SortedPageBuilder::new(PageTier::Leaf, PageKind::Data)
+            .with_key_size(txn.tree.data_key_size(PageTier::Leaf))
+            .with_slice(&items);
+        let (id, addr) = txn.insert_bulk_page(builder).await?;
+        self.pages.push((id, addr));
+        self.leaves.push((self.entries[0].0.clone(), id));
+        self.leaf_counts.push(items.len() as u64);
+        self.entries.clear();
+        self.entries_size = 0;
+        Ok(())
+    }
+
+    // Builds a leaf page of the pending entries, if any.
+    async fn flush<E: Env>(&mut self, txn: &TreeTxn<'_, E>) -> Result<()> {
+        if !self.entries.is_empty() {
+            self.build_leaf(txn).await?;
+        }
+        Ok(())
+    }
+
+    /// Appends the leaf pages of a fork, whose entries must be larger than the
+    /// entries of this load.
+    ///
+    /// Returns [`Error::InvalidArgument`] if the entries overlap.
+    pub(crate) async fn append<E: Env>(
+        &mut self,
+        txn: &TreeTxn<'_, E>,
+        mut other: BulkLoad,
+    ) -> Result<()> {
+        self.flush(txn).await?;
+        other.flush(txn).await?;
+        let Some((first, _)) = other.leaves.first() else {
+            return Ok(());
+        };
+        if self.last_key.as_ref().map_or(false, |last| last >= first) {
+            return Err(Error::InvalidArgument);
+        }
+        self.pages.append(&mut other.pages);
+        self.leaves.append(&mut other.leaves);
+        self.leaf_counts.append(&mut other.leaf_counts);
+        self.last_key = other.last_key.take();
+        self.num_entries += other.num_entries;
+        Ok(())
+    }
+
+    /// Builds the index of the leaf pages and installs it as the root.
+    ///
+    /// Returns the number of loaded entries. The pages of the load are
+    /// released if it fails.
+    pub(crate) async fn finish<E: Env>(mut self, txn: &TreeTxn<'_, E>) -> Result<usize> {
+        match self.install(txn).await {
+            Ok(num_entries) => {
+                self.pages.clear();
+                Ok(num_entries)
+            }
+            Err(err) => {
+                // The pages that can't be released now are left to the next
+                // load when this one is dropped.
+                let _ = txn.release_bulk_pages(&mut self.pages).await;
+                Err(err)
+            }
+        }
+    }
+
+    async fn install<E: Env>(&mut self, txn: &TreeTxn<'_, E>) -> Result<usize> {
+        self.flush(txn).await?;
+        if self.leaves.is_empty() {
+            return Ok(0);
+        }
+
+        // Inner pages are split at half of the page size, so they are filled
+        // to a quarter of it.
+        let max_size = txn.tree.options.page_size / 4;
+        let mut children = self.leaves.clone();
+        // The leftmost page covers all keys before the others.
+        children[0].0.clear();
+        loop {
+            let total_size: usize = children.iter().map(|(k, _)| k.len() + 16).sum();
+            if children.len() == 1 || total_size <= max_size {
+                break;
+            }
+            let mut parents = Vec::new();
+            let mut start = 0;
+            let mut size = 0;
+            for (i, (key, _)) in children.iter().enumerate() {
+                size += key.len() + 16;
+                // Each page takes at least two children, so that every level
+                // is smaller than the one below even if the keys are large.
+                if (size >= max_size && i > start) || i + 1 == children.len() {
+                    let (id, addr) = txn.insert_bulk_index(&children[start..=i]).await?;
+                    self.pages.push((id, addr));
+                    parents.push((children[start].0.clone(), id));
+                    start = i + 1;
+                    size = 0;
+                }
+            }
+            children = parents;
+        }
+
+        let entries: Vec<_> = children
+            .iter()
+            .map(|(start, id)| (start.as_slice(), Index::new(*id, 0)))
+            .collect();
+        let builder = SortedPageBuilder::new(PageTier::Inner, PageKind::Data).with_slice(&entries);
+        let mut page_txn = txn.guard.begin().await;
+        let (new_addr, mut new_page) = page_txn.alloc_page(builder.size()).await?;
+        builder.build(&mut new_page);
+        // Writes to the tree during the load fail the replacement.
+        let root_id = txn.tree.root_id;
+        page_txn
+            .replace_page(root_id, self.root_addr, new_addr, &[self.root_addr])
+            .await
+            .map_err(|_| Error::Again)?;
+        txn.tree.key_counts.remove(root_id);
+        for ((_, id), count) in self.leaves.iter().zip(self.leaf_counts.iter()) {
+            txn.tree.key_counts.set(*id, *count);
+        }
+        txn.tree.publish_lsn(self.lsn);
+        if let Some(cache) = &txn.tree.negative_cache {
+            cache.invalidate_all();
+        }
+        Ok(self.num_entries)
+    }
+}
+
+impl Drop for BulkLoad {
+    fn drop(&mut self) {
+        if !self.pages.is_empty() {
+            let mut abandoned = self.abandoned_pages.lock().expect("Poisoned");
+            abandoned.append(&mut self.pages);
+        }
+    }
+}
+
+impl<'a, E: Env> TreeTxn<'a, E> {
+    /// Starts to load entries at the LSN into the tree, which must be empty.
+    ///
+    /// The pages of the loads dropped before are released first.
+    pub(crate) async fn begin_bulk_load(&self, lsn: u64) -> Result<BulkLoad> {
+        let mut abandoned = {
+            let mut pages = self.tree.abandoned_bulk_pages.lock().expect("Poisoned");
+            std::mem::take(&mut *pages)
+        };
+        if let Err(err) = self.release_bulk_pages(&mut abandoned).await {
+            let mut pages = self.tree.abandoned_bulk_pages.lock().expect("Poisoned");
+            pages.append(&mut abandoned);
+            return Err(err);
+        }
+
+        let root = self.page_view(self.tree.root_id, None).await?;
+        if !root.page.tier().is_leaf() || root.page.chain_next() != 0 {
+            return Err(Error::InvalidArgument);
+        }
+        if self.iter_page::<Key, Value>(&root).await?.next().is_some() {
+            return Err(Error::InvalidArgument);
+        }
+        Ok(BulkLoad::new(
+            lsn,
+            root.addr,
+            self.tree.abandoned_bulk_pages.clone(),
+        ))
+    }
+
+    // Releases the pages of a bulk load, which are not reachable from the
+    // tree. The pages are removed as they are released, so the remaining ones
+    // can be released again if it fails.
+    async fn release_bulk_pages(&self, pages: &mut Vec<(u64, u64)>) -> Result<()> {
+        // The addresses are deallocated in chunks to bound the record size.
+        const CHUNK_SIZE: usize = 512;
+        while !pages.is_empty() {
+            let at = pages.len().saturating_sub(CHUNK_SIZE);
+            let addrs: Vec<u64> = pages[at..].iter().map(|(_, addr)| *addr).collect();
+            loop {
+                let txn = self.guard.begin().await;
+                match txn.dealloc_pages(&addrs).await {
+                    Ok(()) => break,
+                    Err(Error::Again) => continue,
+                    Err(err) => return Err(err),
+                }
+            }
+            for (id, _) in pages.drain(at..) {
+                self.guard.dealloc_page_id(id);
+            }
+        }
+        Ok(())
+    }
+
+    // Inserts a page built for a bulk load, and returns its id and address.
+    async fn insert_bulk_page<I, K, V>(&self, builder: SortedPageBuilder<I>) -> Result<(u64, u64)>
+    where
+        I: RewindableIterator<Item = (K, V)>,
+        K: SortedPageKey,
+        V: SortedPageValue,
+    {
+        let mut txn = self.guard.begin().await;
+        let (new_addr, mut new_page) = txn.alloc_page(builder.size()).await?;
+        builder.build(&mut new_page);
+        let id = txn.insert_page(new_addr).await?;
+        txn.commit();
+        Ok((id, new_addr))
+    }
+
+    // Inserts an inner page over the children for a bulk load.
+    async fn insert_bulk_index(&self, children: &[(Vec<u8>, u64)]) -> Result<(u64, u64)> {
+        let entries: Vec<_> = children
+            .iter()
+            .map(|(start, id)| (start.as_slice(), Index::new(*id, 0)))
+            .collect();
+        let builder = SortedPageBuilder::new(PageTier::Inner, PageKind::Data).with_slice(&entries);
+        self.insert_bulk_page(builder).await
+    }
+}
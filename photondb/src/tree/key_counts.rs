@@ -0,0 +1,64 @@
+use std::{collections::HashMap, sync::Mutex};
+
+/// The number of live keys of leaf pages.
+///
+/// A page is counted when its whole chain is consolidated, and its count is
+/// carried over when it is split or removed. Writes since the last
+/// consolidation of a page are not counted, so the total is an estimate.
+#[derive(Default)]
+pub(super) struct KeyCounts {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    counts: HashMap<u64, u64>,
+    total: u64,
+}
+
+impl KeyCounts {
+    /// Sets the number of live keys of the page.
+    pub(super) fn set(&self, id: u64, count: u64) {
+        let mut inner = self.inner.lock().expect("Poisoned");
+        let old = inner.counts.insert(id, count).unwrap_or(0);
+        inner.total = inner.total - old + count;
+    }
+
+    /// Forgets the page, which is removed or no longer a leaf page.
+    pub(super) fn remove(&self, id: u64) {
+        let mut inner = self.inner.lock().expect("Poisoned");
+        let old = inner.counts.remove(&id).unwrap_or(0);
+        inner.total -= old;
+    }
+
+    /// Returns the number of live keys of the page, if it is counted.
+    pub(super) fn get(&self, id: u64) -> Option<u64> {
+        let inner = self.inner.lock().expect("Poisoned");
+        inner.counts.get(&id).copied()
+    }
+
+    /// Returns the number of live keys of all counted pages.
+    pub(super) fn total(&self) -> u64 {
+        self.inner.lock().expect("Poisoned").total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_counts() {
+        let counts = KeyCounts::default();
+        counts.set(1, 10);
+        counts.set(2, 20);
+        assert_eq!(counts.total(), 30);
+        counts.set(1, 5);
+        assert_eq!(counts.total(), 25);
+        assert_eq!(counts.get(1), Some(5));
+        assert_eq!(counts.get(3), None);
+        counts.remove(2);
+        counts.remove(3);
+        assert_eq!(counts.total(), 5);
+    }
+}
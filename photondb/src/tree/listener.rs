@@ -0,0 +1,68 @@
+use std::fmt;
+
+use crate::page_store::PageId;
+
+/// A listener to observe structural changes of a table.
+///
+/// The callbacks are invoked synchronously by the tasks that change the
+/// table, so they should return quickly.
+pub trait EventListener: Send + Sync + fmt::Debug {
+    /// Called after a page is split into two halves.
+    fn on_split_page(&self, _: &SplitPageInfo) {}
+
+    /// Called after the delta chain of a page is consolidated.
+    fn on_consolidate_page(&self, _: &ConsolidatePageInfo) {}
+
+    /// Called after an empty page is removed and its range is merged into
+    /// the left sibling.
+    fn on_remove_page(&self, _: &RemovePageInfo) {}
+}
+
+/// Information about a page split.
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub struct SplitPageInfo {
+    /// The id of the split page.
+    pub id: PageId,
+    /// The id of the page holding the left half.
+    ///
+    /// This is the split page itself unless the root is split, whose halves
+    /// are both moved to new pages.
+    pub left_id: PageId,
+    /// The id of the page holding the right half.
+    pub right_id: PageId,
+    /// The size of the page before the split.
+    pub size: usize,
+    /// The size of the page holding the right half.
+    pub right_size: usize,
+    /// The length of the page chain before the split.
+    pub chain_len: usize,
+}
+
+/// Information about a page consolidation.
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub struct ConsolidatePageInfo {
+    /// The id of the consolidated page.
+    pub id: PageId,
+    /// The total size of the data pages consolidated.
+    pub size: usize,
+    /// The size of the consolidated page.
+    pub new_size: usize,
+    /// The length of the page chain before the consolidation.
+    pub chain_len: usize,
+    /// The length of the page chain after the consolidation.
+    ///
+    /// This is larger than one if only part of the chain is consolidated.
+    pub new_chain_len: usize,
+}
+
+/// Information about a page removal.
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub struct RemovePageInfo {
+    /// The id of the removed page.
+    pub id: PageId,
+    /// The id of the left sibling that takes over the range of the page.
+    pub left_id: PageId,
+}
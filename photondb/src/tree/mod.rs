@@ -1,11 +1,17 @@
 use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
     fmt,
-    sync::atomic::{AtomicU64, Ordering},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
 };
 
+use futures::future::try_join_all;
 use log::trace;
 
-use crate::{env::Env, page::*, page_store::*};
+use crate::{env::Env, page::*, page_store::*, util::rate_limiter::RateLimiter};
 
 mod page;
 pub use page::PageIter;
@@ -16,35 +22,264 @@ use stats::AtomicStats;
 pub use stats::TreeStats;
 
 mod options;
-pub use options::{Options, ReadOptions, WriteOptions};
+pub use options::{
+    DataPageLayout, Direction, MergeOperator, Options, PrefixExtractor, ReadOptions, WriteOptions,
+};
+
+mod listener;
+pub use listener::{ConsolidatePageInfo, EventListener, RemovePageInfo, SplitPageInfo};
+
+mod negative_cache;
+use negative_cache::NegativeCache;
+
+mod prefix_filter;
+use prefix_filter::PrefixFilters;
+
+mod key_counts;
+use key_counts::KeyCounts;
+
+mod bulk_load;
+pub(crate) use bulk_load::BulkLoad;
 
 pub(crate) struct Tree {
     options: Options,
+    // The page id of the root, which never changes.
+    root_id: u64,
     stats: AtomicStats,
     safe_lsn: AtomicU64,
+    maintenance_paused: AtomicBool,
+    // The largest LSN written to the tree since it is opened, which is only
+    // advanced once the write is installed, see `Tree::publish_lsn`.
+    last_lsn: AtomicU64,
+    // The LSNs of the savepoints and the versions they pin by name.
+    savepoints: Mutex<HashMap<String, (u64, VersionPin)>>,
+    // The number of snapshots by LSN, which is locked after `savepoints`.
+    snapshots: Mutex<BTreeMap<u64, usize>>,
+    // The smallest LSN of the savepoints and snapshots, or `u64::MAX` if there
+    // is none.
+    min_savepoint_lsn: AtomicU64,
+    // The keys known to be absent, if enabled.
+    negative_cache: Option<NegativeCache>,
+    // The prefix filters of leaf pages, if enabled.
+    prefix_filters: Option<PrefixFilters>,
+    // The limit of consolidations triggered by writes, if enabled.
+    consolidate_limiter: Option<RateLimiter>,
+    // The ranges pinned by `TreeTxn::pin_range`.
+    pinned_ranges: Mutex<Vec<PinnedRange>>,
+    // The number of live keys of consolidated leaf pages.
+    key_counts: KeyCounts,
+    // The pages of the bulk loads dropped before they are finished, which are
+    // released by the next bulk load.
+    abandoned_bulk_pages: Arc<Mutex<Vec<(u64, u64)>>>,
+    // The number of delta installations to fail before trying to update the
+    // page, see `Tree::force_conflicts`.
+    #[cfg(test)]
+    forced_conflicts: std::sync::atomic::AtomicUsize,
 }
 
 impl Tree {
     pub(crate) fn new(options: Options) -> Self {
+        Self::with_root(options, ROOT_ID)
+    }
+
+    /// Creates a tree whose root is the page of the id.
+    ///
+    /// Trees with different roots share nothing but the page store.
+    pub(crate) fn with_root(options: Options, root_id: u64) -> Self {
+        let negative_cache = (options.negative_cache_size > 0)
+            .then(|| NegativeCache::new(options.negative_cache_size));
+        let prefix_filters = options.prefix_extractor.map(PrefixFilters::new);
+        let consolidate_limiter =
+            (options.consolidate_rate > 0).then(|| RateLimiter::new(options.consolidate_rate));
         Self {
             options,
+            root_id,
             stats: AtomicStats::default(),
             safe_lsn: AtomicU64::new(0),
+            maintenance_paused: AtomicBool::new(false),
+            last_lsn: AtomicU64::new(0),
+            savepoints: Mutex::default(),
+            snapshots: Mutex::default(),
+            min_savepoint_lsn: AtomicU64::new(u64::MAX),
+            negative_cache,
+            prefix_filters,
+            consolidate_limiter,
+            pinned_ranges: Mutex::default(),
+            key_counts: KeyCounts::default(),
+            abandoned_bulk_pages: Arc::default(),
+            #[cfg(test)]
+            forced_conflicts: Default::default(),
         }
     }
 
+    /// Makes the next `n` delta installations fail as if the page was updated
+    /// concurrently, so that tests can exercise the retry paths.
+    #[cfg(test)]
+    fn force_conflicts(&self, n: usize) {
+        self.forced_conflicts.store(n, Ordering::Release);
+    }
+
+    /// Consumes one forced conflict if any.
+    #[cfg(test)]
+    fn take_forced_conflict(&self) -> bool {
+        self.forced_conflicts
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1))
+            .is_ok()
+    }
+
+    #[cfg(not(test))]
+    #[inline(always)]
+    fn take_forced_conflict(&self) -> bool {
+        false
+    }
+
+    pub(crate) fn options(&self) -> &Options {
+        &self.options
+    }
+
     pub(crate) fn begin<E: Env>(&self, guard: Guard<E>) -> TreeTxn<E> {
         TreeTxn::new(self, guard)
     }
 
     pub(crate) fn stats(&self) -> TreeStats {
-        self.stats.snapshot()
+        let mut stats = self.stats.snapshot();
+        stats.estimated_num_keys = self.estimated_num_keys();
+        stats
+    }
+
+    /// Returns the estimated number of live keys in the tree.
+    ///
+    /// Leaf pages are counted when they are consolidated, so writes since then
+    /// are not taken into account.
+    pub(crate) fn estimated_num_keys(&self) -> u64 {
+        self.key_counts.total()
     }
 
     pub(crate) fn safe_lsn(&self) -> u64 {
         self.safe_lsn.load(Ordering::Acquire)
     }
 
+    /// Returns the size of keys to lay out data pages of the given tier with.
+    fn data_key_size(&self, tier: PageTier) -> Option<usize> {
+        match self.options.data_page_layout {
+            DataPageLayout::FixedKey(size) if tier.is_leaf() => Some(size),
+            _ => None,
+        }
+    }
+
+    /// Pauses consolidations until [`Tree::resume_maintenance`] is called.
+    ///
+    /// Pages are still consolidated once their chains are full, so writes are
+    /// never blocked.
+    pub(crate) fn pause_maintenance(&self) {
+        self.maintenance_paused.store(true, Ordering::Release);
+    }
+
+    /// Resumes consolidations paused by [`Tree::pause_maintenance`].
+    pub(crate) fn resume_maintenance(&self) {
+        self.maintenance_paused.store(false, Ordering::Release);
+    }
+
+    /// Creates a savepoint with the largest LSN whose write is installed so
+    /// far, or replaces the existing one with the same name.
+    ///
+    /// Entries visible to the savepoint are retained until it is released, even
+    /// if the safe LSN advances beyond it, and so is the version. Returns the
+    /// LSN of the savepoint.
+    pub(crate) fn create_savepoint(&self, name: &str, version: VersionPin) -> u64 {
+        let mut savepoints = self.savepoints.lock().expect("Poisoned");
+        let lsn = self.last_lsn.load(Ordering::Acquire);
+        savepoints.insert(name.to_owned(), (lsn, version));
+        self.update_min_savepoint_lsn(&savepoints);
+        lsn
+    }
+
+    /// Returns the largest LSN written to the tree since it is opened.
+    pub(crate) fn last_lsn(&self) -> u64 {
+        self.last_lsn.load(Ordering::Acquire)
+    }
+
+    // Advances the last LSN once the write at the LSN is installed, so that
+    // savepoints and snapshots taken with it see the whole write.
+    fn publish_lsn(&self, lsn: u64) {
+        self.last_lsn.fetch_max(lsn, Ordering::AcqRel);
+    }
+
+    /// Returns the LSN of the savepoint.
+    pub(crate) fn savepoint_lsn(&self, name: &str) -> Option<u64> {
+        let savepoints = self.savepoints.lock().expect("Poisoned");
+        savepoints.get(name).map(|(lsn, _)| *lsn)
+    }
+
+    /// Releases the savepoint, returns false if it doesn't exist.
+    pub(crate) fn release_savepoint(&self, name: &str) -> bool {
+        let mut savepoints = self.savepoints.lock().expect("Poisoned");
+        let released = savepoints.remove(name).is_some();
+        self.update_min_savepoint_lsn(&savepoints);
+        released
+    }
+
+    /// Takes a snapshot with the largest LSN written so far.
+    ///
+    /// Like savepoints, entries visible to the snapshot are retained until it
+    /// is released with [`Self::release_snapshot`]. Returns the LSN of the
+    /// snapshot.
+    pub(crate) fn acquire_snapshot(&self) -> u64 {
+        let savepoints = self.savepoints.lock().expect("Poisoned");
+        let lsn = self.last_lsn.load(Ordering::Acquire);
+        {
+            let mut snapshots = self.snapshots.lock().expect("Poisoned");
+            *snapshots.entry(lsn).or_default() += 1;
+        }
+        self.update_min_savepoint_lsn(&savepoints);
+        lsn
+    }
+
+    /// Releases a snapshot taken by [`Self::acquire_snapshot`].
+    pub(crate) fn release_snapshot(&self, lsn: u64) {
+        let savepoints = self.savepoints.lock().expect("Poisoned");
+        {
+            let mut snapshots = self.snapshots.lock().expect("Poisoned");
+            let count = snapshots.get_mut(&lsn).expect("no such snapshot");
+            *count -= 1;
+            if *count == 0 {
+                snapshots.remove(&lsn);
+            }
+        }
+        self.update_min_savepoint_lsn(&savepoints);
+    }
+
+    /// Unpins the range pinned by [`TreeTxn::pin_range`] with the same bounds.
+    ///
+    /// Returns false if there is no such range.
+    pub(crate) fn unpin_range(&self, start: &[u8], end: &[u8]) -> bool {
+        let mut ranges = self.pinned_ranges.lock().expect("Poisoned");
+        let Some(index) = ranges.iter().position(|r| r.start == start && r.end == end) else {
+            return false;
+        };
+        // The pages become evictable once their cache entries are dropped.
+        ranges.swap_remove(index);
+        true
+    }
+
+    fn update_min_savepoint_lsn(&self, savepoints: &HashMap<String, (u64, VersionPin)>) {
+        let snapshots = self.snapshots.lock().expect("Poisoned");
+        let lsn = savepoints
+            .values()
+            .map(|(lsn, _)| lsn)
+            .chain(snapshots.keys())
+            .cloned()
+            .min()
+            .unwrap_or(u64::MAX);
+        self.min_savepoint_lsn.store(lsn, Ordering::Release);
+    }
+
+    /// Returns the LSN that entries must be retained for.
+    fn retained_lsn(&self) -> u64 {
+        let min_savepoint_lsn = self.min_savepoint_lsn.load(Ordering::Acquire);
+        self.safe_lsn().min(min_savepoint_lsn)
+    }
+
     pub(crate) fn set_safe_lsn(&self, lsn: u64) {
         loop {
             let safe_lsn = self.safe_lsn.load(Ordering::Acquire);
@@ -72,6 +307,14 @@ impl fmt::Debug for Tree {
     }
 }
 
+// A range of keys whose pages are pinned in the page cache.
+struct PinnedRange {
+    start: Vec<u8>,
+    end: Vec<u8>,
+    // The cache entries that keep the pages from being evicted.
+    _pages: Vec<CacheEntryGuard>,
+}
+
 pub(crate) struct TreeTxn<'a, E: Env> {
     tree: &'a Tree,
     guard: Guard<E>,
@@ -82,33 +325,183 @@ impl<'a, E: Env> TreeTxn<'a, E> {
         Self { tree, guard }
     }
 
+    /// Returns the size of pages read by this transaction.
+    pub(crate) fn pinned_bytes(&self) -> usize {
+        self.guard.pinned_bytes()
+    }
+
+    /// Returns the current position in the pages read by this transaction.
+    pub(crate) fn pin_mark(&self) -> PinMark {
+        self.guard.pin_mark()
+    }
+
+    /// Releases the pages read since the mark.
+    ///
+    /// # Safety
+    ///
+    /// See [`Guard::unpin_since`].
+    pub(crate) unsafe fn unpin_since(&self, mark: PinMark) {
+        self.guard.unpin_since(mark);
+    }
+
     /// Initializes the tree if it is not initialized yet.
     pub(crate) async fn init(&self) -> Result<()> {
-        let addr = self.guard.page_addr(ROOT_ID);
+        let addr = self.guard.page_addr(self.tree.root_id);
         if addr != 0 {
             return Ok(());
         }
+        let root_id = self.create_root().await?;
+        assert_eq!(root_id, self.tree.root_id);
+        Ok(())
+    }
 
-        // Insert an empty data page as the root.
+    /// Inserts an empty data page as the root of a new tree.
+    ///
+    /// Returns the page id of the root.
+    pub(crate) async fn create_root(&self) -> Result<u64> {
         let iter: ItemIter<(Key, Value)> = None.into();
         let builder = SortedPageBuilder::new(PageTier::Leaf, PageKind::Data).with_iter(iter);
         let mut txn = self.guard.begin().await;
         let (new_addr, mut new_page) = txn.alloc_page(builder.size()).await?;
         builder.build(&mut new_page);
-        let root_id = txn.insert_page(new_addr);
-        assert_eq!(root_id, ROOT_ID);
+        let root_id = txn.insert_page(new_addr).await?;
         txn.commit();
-
-        Ok(())
+        Ok(root_id)
     }
 
     /// Gets the value corresponding to the key.
-    pub(crate) async fn get(&self, key: Key<'_>) -> Result<Option<&[u8]>> {
-        let (view, _) = self.find_leaf(key.raw).await?;
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(key_size = key.raw.len()))
+    )]
+    pub(crate) async fn get(&self, key: Key<'_>) -> Result<Option<Cow<'_, [u8]>>> {
+        let Some(cache) = &self.tree.negative_cache else {
+            let view = self.find_leaf_to_read(key.raw).await?;
+            return self.get_from_leaf(key, view).await;
+        };
+        if cache.contains(key.raw) {
+            self.tree.stats.negative_cache_hits.inc();
+            return Ok(None);
+        }
+        let version = cache.version(key.raw);
+        let view = self.find_leaf_to_read(key.raw).await?;
+        let value = self.get_from_leaf(key, view.clone()).await?;
+        if value.is_none() && self.is_key_absent(key.raw, &view).await? {
+            cache.insert(key.raw, version);
+        }
+        Ok(value)
+    }
+
+    /// Gets the value corresponding to the key without retrying on conflicts.
+    ///
+    /// Returns [`Error::Again`] if the operation conflicts with concurrent
+    /// modifications to the tree.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(key_size = key.raw.len()))
+    )]
+    pub(crate) async fn try_get(&self, key: Key<'_>) -> Result<Option<Cow<'_, [u8]>>> {
+        let (view, _) = match self.try_find_leaf(Target::At(key.raw), true).await {
+            Ok(v) => {
+                self.tree.stats.success.read.inc();
+                v
+            }
+            Err(e) => {
+                if let Error::Again = e {
+                    self.tree.stats.conflict.read.inc();
+                }
+                return Err(e);
+            }
+        };
+        self.get_from_leaf(key, view).await
+    }
+
+    /// Returns true if the tree contains a live value for the key.
+    ///
+    /// This is cheaper than [`Self::get`] since it never resolves merge
+    /// operands or copies the value.
+    pub(crate) async fn contains(&self, key: Key<'_>) -> Result<bool> {
+        let view = self.find_leaf_to_read(key.raw).await?;
+        self.find_key(&key, &view).await
+    }
+
+    /// Gets the values corresponding to the keys at the LSN.
+    ///
+    /// The keys are grouped by the leaf pages that contain them, and the pages
+    /// are read concurrently, one future per page. The values are returned in
+    /// the order of the keys.
+    pub(crate) async fn multi_get(
+        &self,
+        keys: &[&[u8]],
+        lsn: u64,
+    ) -> Result<Vec<Option<Cow<'_, [u8]>>>> {
+        let mut order: Vec<usize> = (0..keys.len()).collect();
+        order.sort_by_key(|&i| keys[i]);
+
+        // Locate the leaf page of each group. Inner pages are mostly cached, so
+        // only the leaf pages are worth reading concurrently.
+        let mut groups = Vec::new();
+        let mut rest = order.as_slice();
+        while let Some(&first) = rest.first() {
+            let view = self.find_leaf_to_read(keys[first]).await?;
+            let num_keys = match view.range.unwrap().end {
+                Some(end) => rest.partition_point(|&i| keys[i] < end),
+                None => rest.len(),
+            };
+            groups.push((view, &rest[..num_keys]));
+            rest = &rest[num_keys..];
+        }
+
+        let reads = groups.into_iter().map(|(view, group)| async move {
+            let mut values = Vec::with_capacity(group.len());
+            for &i in group {
+                let key = Key::new(keys[i], lsn);
+                values.push((i, self.get_from_leaf(key, view.clone()).await?));
+            }
+            Ok::<_, Error>(values)
+        });
+        let mut values: Vec<_> = keys.iter().map(|_| None).collect();
+        for (i, value) in try_join_all(reads).await?.into_iter().flatten() {
+            values[i] = value;
+        }
+        Ok(values)
+    }
+
+    /// Returns the address of the leaf page that contains the key.
+    pub(crate) async fn page_addr(&self, key: &[u8]) -> Result<PageAddr> {
+        let (view, _) = self.find_leaf(key).await?;
+        Ok(PageAddr::from_raw(view.addr))
+    }
+
+    /// Reads the page at the address.
+    ///
+    /// The address must be obtained with this transaction, so that the page is
+    /// retained until the transaction is dropped.
+    pub(crate) async fn read_page(&self, addr: PageAddr) -> Result<PageRef<'_>> {
+        let (page, _) = self
+            .guard
+            .read_page(addr.into(), CacheOption::default())
+            .await?;
+        Ok(page)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(page_id = view.id, page_addr = view.addr)
+        )
+    )]
+    async fn get_from_leaf<'g>(
+        &'g self,
+        key: Key<'_>,
+        view: PageView<'g>,
+    ) -> Result<Option<Cow<'g, [u8]>>> {
         let value = self.find_value(&key, &view).await?;
 
         let key_size = key.len() as u64;
-        let value_size = value.map(|v| v.len()).unwrap_or_default() as u64;
+        let value_size = value.as_ref().map(|v| v.len()).unwrap_or_default() as u64;
         self.tree
             .stats
             .success
@@ -120,10 +513,12 @@ impl<'a, E: Env> TreeTxn<'a, E> {
 
     /// Writes the key-value pair to the tree.
     pub(crate) async fn write(&self, key: Key<'_>, value: Value<'_>) -> Result<()> {
+        self.check_write(key.raw, value)?;
         let bytes = key.len() + value.len();
         loop {
             match self.try_write(key, value).await {
                 Ok(_) => {
+                    self.tree.publish_lsn(key.lsn);
                     self.tree.stats.success.write.inc();
                     self.tree.stats.success.write_bytes.add(bytes as u64);
                     return Ok(());
@@ -137,18 +532,670 @@ impl<'a, E: Env> TreeTxn<'a, E> {
         }
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key_size = key.raw.len(), value_size = value.len())
+        )
+    )]
     async fn try_write(&self, key: Key<'_>, value: Value<'_>) -> Result<()> {
-        let (mut view, _) = self.find_leaf(key.raw).await?;
+        let (view, parent) = self.find_leaf(key.raw).await?;
+
+        // Build a delta page with the given key-value pair.
+        let delta = (key, value);
+        let builder = SortedPageBuilder::new(PageTier::Leaf, PageKind::Data).with_item(delta);
+        self.try_install_delta(view, parent, builder).await?;
+        if let Some(cache) = &self.tree.negative_cache {
+            cache.invalidate(key.raw);
+        }
+        Ok(())
+    }
+
+    // Returns an error if the entry can't be written with the options.
+    fn check_write(&self, key: &[u8], value: Value<'_>) -> Result<()> {
+        if let Value::Merge(_) = value {
+            if self.tree.options.merge_operator.is_none() {
+                return Err(Error::InvalidArgument);
+            }
+        }
+        if let DataPageLayout::FixedKey(size) = self.tree.options.data_page_layout {
+            if key.len() != size {
+                return Err(Error::InvalidArgument);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the entries at the LSN.
+    ///
+    /// The entries of keys in the same leaf page are installed as one delta
+    /// page. If a key appears more than once, the last entry of it in the
+    /// batch wins, unless duplicate keys are rejected by the options.
+    pub(crate) async fn write_batch(&self, batch: &[(&[u8], Value<'_>)], lsn: u64) -> Result<()> {
+        let entries = self.sort_batch(batch, lsn)?;
+        self.write_sorted_batch(&entries).await?;
+        self.tree.publish_lsn(lsn);
+        Ok(())
+    }
+
+    // Writes the sorted entries one leaf page at a time.
+    async fn write_sorted_batch(&self, entries: &[(Key<'_>, Value<'_>)]) -> Result<()> {
+        let mut rest = entries;
+        while !rest.is_empty() {
+            match self.try_write_batch(rest).await {
+                Ok(n) => {
+                    let bytes: usize = rest[..n].iter().map(|(k, v)| k.len() + v.len()).sum();
+                    self.tree.stats.success.write.add(n as u64);
+                    self.tree.stats.success.write_bytes.add(bytes as u64);
+                    rest = &rest[n..];
+                }
+                Err(Error::Again) => {
+                    self.tree.stats.conflict.write.inc();
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    // Checks the entries of a batch, and sorts them by key with the duplicate
+    // keys removed.
+    fn sort_batch<'b>(
+        &self,
+        batch: &[(&'b [u8], Value<'b>)],
+        lsn: u64,
+    ) -> Result<Vec<(Key<'b>, Value<'b>)>> {
+        for (key, value) in batch {
+            self.check_write(key, *value)?;
+        }
+        // The sort is stable, so the entries of the same key keep their order
+        // in the batch.
+        let mut entries: Vec<_> = batch
+            .iter()
+            .map(|(key, value)| (Key::new(key, lsn), *value))
+            .collect();
+        entries.sort_by(|a, b| a.0.raw.cmp(b.0.raw));
+        let mut deduped: Vec<(Key<'_>, Value<'_>)> = Vec::with_capacity(entries.len());
+        for entry in entries {
+            match deduped.last_mut() {
+                Some(last) if last.0.raw == entry.0.raw => {
+                    if self.tree.options.reject_duplicate_keys {
+                        return Err(Error::DuplicateKey);
+                    }
+                    *last = entry;
+                }
+                _ => deduped.push(entry),
+            }
+        }
+        Ok(deduped)
+    }
+
+    /// Writes the entries at the LSN as a whole.
+    ///
+    /// Unlike [`Self::write_batch`], the delta pages of all leaf pages are
+    /// allocated from one write buffer at once, and the buffer is held until
+    /// all of them are installed. So the buffer is never flushed with only a
+    /// part of the batch, and the entries are persisted all together or not at
+    /// all. Reads below the LSN never see the batch, while reads at or above
+    /// it may see a part of the batch before this returns.
+    ///
+    /// If a leaf page is restructured after a part of the batch is installed,
+    /// the rest of the batch is written one page at a time instead.
+    pub(crate) async fn write_batch_atomic(
+        &self,
+        batch: &[(&[u8], Value<'_>)],
+        lsn: u64,
+    ) -> Result<()> {
+        let entries = self.sort_batch(batch, lsn)?;
+        if entries.is_empty() {
+            return Ok(());
+        }
+        loop {
+            match self.try_write_batch_atomic(&entries).await {
+                Ok(()) => break,
+                Err(Error::Again) => {
+                    self.tree.stats.conflict.write.inc();
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        self.tree.publish_lsn(lsn);
+        let bytes: usize = entries.iter().map(|(k, v)| k.len() + v.len()).sum();
+        self.tree.stats.success.write.add(entries.len() as u64);
+        self.tree.stats.success.write_bytes.add(bytes as u64);
+        if let Some(cache) = &self.tree.negative_cache {
+            for (key, _) in &entries {
+                cache.invalidate(key.raw);
+            }
+        }
+        Ok(())
+    }
+
+    async fn try_write_batch_atomic(&self, entries: &[(Key<'_>, Value<'_>)]) -> Result<()> {
+        // Locate the leaf pages of the entries before allocating anything.
+        let mut groups = Vec::new();
+        let mut rest = entries;
+        while !rest.is_empty() {
+            let (view, _) = self.find_leaf(rest[0].0.raw).await?;
+            if self.is_chain_full(&view.page) {
+                self.consolidate_page(view).await?;
+                return Err(Error::Again);
+            }
+            let num_entries = match view.range.unwrap().end {
+                Some(end) => rest.partition_point(|(key, _)| key.raw < end),
+                None => rest.len(),
+            };
+            groups.push((view, &rest[..num_entries]));
+            rest = &rest[num_entries..];
+        }
+
+        let builders: Vec<_> = groups
+            .iter()
+            .map(|(_, entries)| {
+                SortedPageBuilder::new(PageTier::Leaf, PageKind::Data).with_slice(entries)
+            })
+            .collect();
+        let sizes: Vec<_> = builders.iter().map(|builder| builder.size()).collect();
+        let mut txn = self.guard.begin().await;
+        let pages = txn.alloc_pages(&sizes).await?;
+
+        let mut installed = 0;
+        for ((mut view, group), (builder, (new_addr, mut new_page))) in
+            groups.into_iter().zip(builders.into_iter().zip(pages))
+        {
+            builder.build(&mut new_page);
+            loop {
+                new_page.set_epoch(view.page.epoch());
+                new_page.set_chain_len(view.page.chain_len().saturating_add(1));
+                new_page.set_chain_next(view.addr);
+                let addr = match txn.try_update_page(view.id, view.addr, new_addr) {
+                    Ok(()) => break,
+                    Err(addr) => addr,
+                };
+                // The page has been updated by other transactions. We can keep
+                // retrying as long as the page epoch remains the same, see
+                // `try_install_delta` for details. The chain limit is ignored
+                // once a part of the batch is installed.
+                let page = match addr {
+                    Some(addr) if view.id != self.tree.root_id => {
+                        Some((addr, self.guard.read_page_info(addr)?))
+                    }
+                    _ => None,
+                };
+                match page {
+                    Some((addr, page))
+                        if page.epoch() == view.page.epoch()
+                            && (installed > 0 || !self.is_chain_full(&page)) =>
+                    {
+                        view.addr = addr;
+                        view.page = page;
+                    }
+                    // Nothing is installed yet, so the batch can be retried as a whole.
+                    _ if installed == 0 => return Err(Error::Again),
+                    _ => {
+                        // The page has been restructured. The installed pages are
+                        // detached from the transaction, so dropping it only discards
+                        // the rest, which are written again one page at a time. The
+                        // buffer must be released first, since the writes may wait
+                        // for it to be flushed.
+                        drop(txn);
+                        return self.write_sorted_batch(&entries[installed..]).await;
+                    }
+                }
+            }
+            installed += group.len();
+        }
+        txn.commit();
+        Ok(())
+    }
+
+    /// Writes the leading entries that belong to the same leaf page as the
+    /// first one. Returns the number of written entries.
+    async fn try_write_batch(&self, entries: &[(Key<'_>, Value<'_>)]) -> Result<usize> {
+        let (view, parent) = self.find_leaf(entries[0].0.raw).await?;
+        let num_entries = match view.range.unwrap().end {
+            Some(end) => entries.partition_point(|(key, _)| key.raw < end),
+            None => entries.len(),
+        };
+
+        let builder = SortedPageBuilder::new(PageTier::Leaf, PageKind::Data)
+            .with_slice(&entries[..num_entries]);
+        self.try_install_delta(view, parent, builder).await?;
+        if let Some(cache) = &self.tree.negative_cache {
+            for (key, _) in &entries[..num_entries] {
+                cache.invalidate(key.raw);
+            }
+        }
+        Ok(num_entries)
+    }
+
+    /// Writes `new` to the key at the LSN if the value of the key at the LSN is
+    /// `expected`, where [`None`] stands for no value.
+    ///
+    /// The delta is only installed if the leaf page is not updated since the
+    /// value is read, so no other write to the page can come in between.
+    /// Returns false if the value doesn't match.
+    pub(crate) async fn compare_and_swap(
+        &self,
+        key: Key<'_>,
+        expected: Option<&[u8]>,
+        new: Value<'_>,
+    ) -> Result<bool> {
+        self.check_write(key.raw, new)?;
+        loop {
+            match self.try_compare_and_swap(key, expected, new).await {
+                Ok(swapped) => {
+                    if swapped {
+                        self.tree.publish_lsn(key.lsn);
+                        self.tree.stats.success.write.inc();
+                        let bytes = key.len() + new.len();
+                        self.tree.stats.success.write_bytes.add(bytes as u64);
+                    }
+                    return Ok(swapped);
+                }
+                Err(Error::Again) => {
+                    self.tree.stats.conflict.write.inc();
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn try_compare_and_swap(
+        &self,
+        key: Key<'_>,
+        expected: Option<&[u8]>,
+        new: Value<'_>,
+    ) -> Result<bool> {
+        let (view, parent) = self.find_leaf(key.raw).await?;
+        if self.find_value(&key, &view).await?.as_deref() != expected {
+            return Ok(false);
+        }
 
+        let builder = SortedPageBuilder::new(PageTier::Leaf, PageKind::Data).with_item((key, new));
+        self.try_install_delta_impl(view, parent, builder, false)
+            .await?;
+        if let Some(cache) = &self.tree.negative_cache {
+            cache.invalidate(key.raw);
+        }
+        Ok(true)
+    }
+
+    /// Gets the newest value of the key, together with the LSN of the newest
+    /// entry of the key, or `None` if the key has no entry.
+    pub(crate) async fn get_for_update(
+        &self,
+        key: &[u8],
+    ) -> Result<(Option<Cow<'_, [u8]>>, Option<u64>)> {
+        let view = self.find_leaf_to_read(key).await?;
+        let value = self.find_value(&Key::new(key, u64::MAX), &view).await?;
+        let version = self.find_version(key, &view).await?;
+        Ok((value, version))
+    }
+
+    /// Writes the key if the LSN of its newest entry is still `version`.
+    ///
+    /// Returns false without writing anything if the key has been written
+    /// since. Returns [`Error::InvalidArgument`] if the LSN of the write is not
+    /// greater than `version`, since later writes couldn't be told apart.
+    pub(crate) async fn update_if_unchanged(
+        &self,
+        key: Key<'_>,
+        version: Option<u64>,
+        new: Value<'_>,
+    ) -> Result<bool> {
+        if version.map_or(false, |version| key.lsn <= version) {
+            return Err(Error::InvalidArgument);
+        }
+        self.check_write(key.raw, new)?;
+        loop {
+            match self.try_update_if_unchanged(key, version, new).await {
+                Ok(updated) => {
+                    if updated {
+                        self.tree.publish_lsn(key.lsn);
+                        self.tree.stats.success.write.inc();
+                        let bytes = key.len() + new.len();
+                        self.tree.stats.success.write_bytes.add(bytes as u64);
+                    }
+                    return Ok(updated);
+                }
+                Err(Error::Again) => {
+                    self.tree.stats.conflict.write.inc();
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn try_update_if_unchanged(
+        &self,
+        key: Key<'_>,
+        version: Option<u64>,
+        new: Value<'_>,
+    ) -> Result<bool> {
+        let (view, parent) = self.find_leaf(key.raw).await?;
+        if self.find_version(key.raw, &view).await? != version {
+            return Ok(false);
+        }
+
+        let builder = SortedPageBuilder::new(PageTier::Leaf, PageKind::Data).with_item((key, new));
+        self.try_install_delta_impl(view, parent, builder, false)
+            .await?;
+        if let Some(cache) = &self.tree.negative_cache {
+            cache.invalidate(key.raw);
+        }
+        Ok(true)
+    }
+
+    /// Moves the value of `from` to `to` at the LSN.
+    ///
+    /// Returns false if `from` has no value at the LSN.
+    pub(crate) async fn rename(&self, from: &[u8], to: &[u8], lsn: u64) -> Result<bool> {
+        if let DataPageLayout::FixedKey(size) = self.tree.options.data_page_layout {
+            if from.len() != size || to.len() != size {
+                return Err(Error::InvalidArgument);
+            }
+        }
+        loop {
+            match self.try_rename(from, to, lsn).await {
+                Ok(renamed) => {
+                    if renamed {
+                        self.tree.publish_lsn(lsn);
+                        self.tree.stats.success.write.inc();
+                    }
+                    return Ok(renamed);
+                }
+                Err(Error::Again) => {
+                    self.tree.stats.conflict.write.inc();
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn try_rename(&self, from: &[u8], to: &[u8], lsn: u64) -> Result<bool> {
+        let (view, parent) = self.find_leaf(from).await?;
+        let value = match self.find_value(&Key::new(from, lsn), &view).await? {
+            Some(value) => value.into_owned(),
+            None => return Ok(false),
+        };
+        if from == to {
+            return Ok(true);
+        }
+
+        let range = view.range.unwrap();
+        if to < range.start || range.end.map_or(false, |end| to >= end) {
+            self.try_rename_across(view, from, to, &value, lsn).await?;
+            return Ok(true);
+        }
+
+        // Build a delta page with both keys, so that they are updated at once.
+        let put = (Key::new(to, lsn), Value::Put(&value));
+        let delete = (Key::new(from, lsn), Value::Delete);
+        let deltas = if to < from {
+            [put, delete]
+        } else {
+            [delete, put]
+        };
+        let builder = SortedPageBuilder::new(PageTier::Leaf, PageKind::Data).with_slice(&deltas);
+        // The page must not be updated since the value is read.
+        self.try_install_delta_impl(view, parent, builder, false)
+            .await?;
+        if let Some(cache) = &self.tree.negative_cache {
+            cache.invalidate(from);
+            cache.invalidate(to);
+        }
+        Ok(true)
+    }
+
+    // Moves the value to a key in another leaf page.
+    //
+    // Both deltas are allocated from one write buffer and installed in one
+    // transaction, so they are persisted together, like the pages of
+    // `write_batch_atomic`. The delete is installed first, and only if the page
+    // of `from` is not updated since the value is read. If the page of `to` is
+    // restructured after that, the put is written again on its own.
+    async fn try_rename_across(
+        &self,
+        from_view: PageView<'_>,
+        from: &[u8],
+        to: &[u8],
+        value: &[u8],
+        lsn: u64,
+    ) -> Result<()> {
+        let (mut to_view, _) = self.find_leaf(to).await?;
+        for view in [&from_view, &to_view] {
+            if self.is_chain_full(&view.page) {
+                self.consolidate_page(view.clone()).await?;
+                return Err(Error::Again);
+            }
+        }
+        if self.tree.take_forced_conflict() {
+            return Err(Error::Again);
+        }
+
+        let delete = [(Key::new(from, lsn), Value::Delete)];
+        let put = [(Key::new(to, lsn), Value::Put(value))];
+        let delete_builder =
+            SortedPageBuilder::new(PageTier::Leaf, PageKind::Data).with_slice(&delete);
+        let put_builder = SortedPageBuilder::new(PageTier::Leaf, PageKind::Data).with_slice(&put);
+        let mut txn = self.guard.begin().await;
+        let sizes = [delete_builder.size(), put_builder.size()];
+        let mut pages = txn.alloc_pages(&sizes).await?.into_iter();
+        let (delete_addr, mut delete_page) = pages.next().unwrap();
+        let (put_addr, mut put_page) = pages.next().unwrap();
+
+        delete_builder.build(&mut delete_page);
+        delete_page.set_epoch(from_view.page.epoch());
+        delete_page.set_chain_len(from_view.page.chain_len().saturating_add(1));
+        delete_page.set_chain_next(from_view.addr);
+        if txn
+            .try_update_page(from_view.id, from_view.addr, delete_addr)
+            .is_err()
+        {
+            // Nothing is installed yet, so the rename can be retried as a whole.
+            return Err(Error::Again);
+        }
+
+        put_builder.build(&mut put_page);
+        loop {
+            put_page.set_epoch(to_view.page.epoch());
+            put_page.set_chain_len(to_view.page.chain_len().saturating_add(1));
+            put_page.set_chain_next(to_view.addr);
+            let addr = match txn.try_update_page(to_view.id, to_view.addr, put_addr) {
+                Ok(()) => break,
+                Err(addr) => addr,
+            };
+            // Keep retrying as long as the page epoch remains the same, see
+            // `try_install_delta_impl` for details. The chain limit is ignored
+            // since the delete is installed.
+            let page = match addr {
+                Some(addr) if to_view.id != self.tree.root_id => {
+                    Some((addr, self.guard.read_page_info(addr)?))
+                }
+                _ => None,
+            };
+            match page {
+                Some((addr, page)) if page.epoch() == to_view.page.epoch() => {
+                    to_view.addr = addr;
+                    to_view.page = page;
+                }
+                _ => {
+                    // The buffer must be released first, since the write may
+                    // wait for it to be flushed.
+                    drop(txn);
+                    let put = [(Key::new(to, lsn), Value::Put(value))];
+                    loop {
+                        match self.try_write_batch(&put).await {
+                            Ok(_) => return Ok(()),
+                            Err(Error::Again) => continue,
+                            Err(e) => return Err(e),
+                        }
+                    }
+                }
+            }
+        }
+        txn.commit();
+        if let Some(cache) = &self.tree.negative_cache {
+            cache.invalidate(from);
+            cache.invalidate(to);
+        }
+        Ok(())
+    }
+
+    /// Deletes the keys with tombstones at the LSN.
+    ///
+    /// The keys must be strictly increasing. The tombstones of keys in the
+    /// same leaf page are installed as one delta page.
+    pub(crate) async fn delete_batch(&self, keys: &[Vec<u8>], lsn: u64) -> Result<()> {
+        let mut rest = keys;
+        while !rest.is_empty() {
+            match self.try_delete_batch(rest, lsn).await {
+                Ok(n) => {
+                    self.tree.stats.success.write.add(n as u64);
+                    rest = &rest[n..];
+                }
+                Err(Error::Again) => {
+                    self.tree.stats.conflict.write.inc();
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        self.tree.publish_lsn(lsn);
+        Ok(())
+    }
+
+    /// Deletes the leading keys that belong to the same leaf page as the first
+    /// one. Returns the number of deleted keys.
+    async fn try_delete_batch(&self, keys: &[Vec<u8>], lsn: u64) -> Result<usize> {
+        let (view, parent) = self.find_leaf(&keys[0]).await?;
+        let num_keys = match view.range.unwrap().end {
+            Some(end) => keys.partition_point(|key| key.as_slice() < end),
+            None => keys.len(),
+        };
+
+        let deltas: Vec<_> = keys[..num_keys]
+            .iter()
+            .map(|key| (Key::new(key, lsn), Value::Delete))
+            .collect();
+        let builder = SortedPageBuilder::new(PageTier::Leaf, PageKind::Data).with_slice(&deltas);
+        self.try_install_delta(view, parent, builder).await?;
+        if let Some(cache) = &self.tree.negative_cache {
+            for key in &keys[..num_keys] {
+                cache.invalidate(key);
+            }
+        }
+        Ok(num_keys)
+    }
+
+    /// Deletes the keys in `[start, end)` at the LSN.
+    ///
+    /// A range delete delta is installed on each leaf page that overlaps with
+    /// the range, which deletes all versions of the keys in the page at or
+    /// below the LSN, no matter how many keys there are. The deletes are
+    /// applied to reads and merged into the data by consolidations.
+    pub(crate) async fn delete_range(&self, start: &[u8], end: &[u8], lsn: u64) -> Result<()> {
+        let mut next = Some(start.to_vec());
+        while let Some(start) = next.take().filter(|start| start.as_slice() < end) {
+            match self.try_delete_range(&start, end, lsn).await {
+                Ok(page_end) => {
+                    self.tree.stats.success.write.inc();
+                    next = page_end;
+                }
+                Err(Error::Again) => {
+                    self.tree.stats.conflict.write.inc();
+                    next = Some(start);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        self.tree.publish_lsn(lsn);
+        Ok(())
+    }
+
+    /// Deletes the keys in `[start, end)` of the leaf page that contains
+    /// `start`. Returns the end of the page.
+    async fn try_delete_range(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        lsn: u64,
+    ) -> Result<Option<Vec<u8>>> {
+        let (view, parent) = self.find_leaf(start).await?;
+        let page_end = view.range.unwrap().end.map(|end| end.to_vec());
+        // Clip the range to the page, so that the delta only covers its keys.
+        let delta_end = match &page_end {
+            Some(page_end) if page_end.as_slice() < end => page_end.as_slice(),
+            _ => end,
+        };
+        let delta = (Key::new(start, lsn), Value::Put(delta_end));
+        let builder =
+            SortedPageBuilder::new(PageTier::Leaf, PageKind::DeleteRange).with_item(delta);
+        self.try_install_delta(view, parent, builder).await?;
+        // Keys without any entries are still absent, so the negative cache
+        // doesn't need to be invalidated.
+        Ok(page_end)
+    }
+
+    /// Prepends a delta page built by the builder to the leaf page.
+    async fn try_install_delta<I, K, V>(
+        &self,
+        view: PageView<'_>,
+        parent: Option<PageView<'_>>,
+        builder: SortedPageBuilder<I>,
+    ) -> Result<()>
+    where
+        I: RewindableIterator<Item = (K, V)>,
+        K: SortedPageKey,
+        V: SortedPageValue,
+    {
+        self.try_install_delta_impl(view, parent, builder, true)
+            .await
+    }
+
+    /// Prepends a delta page built by the builder to the leaf page.
+    ///
+    /// If `rebase` is false, the delta is only installed if the page is still
+    /// at the address of the view, otherwise it is also installed if the page
+    /// is updated by others but not restructured.
+    async fn try_install_delta_impl<I, K, V>(
+        &self,
+        mut view: PageView<'_>,
+        parent: Option<PageView<'_>>,
+        builder: SortedPageBuilder<I>,
+        rebase: bool,
+    ) -> Result<()>
+    where
+        I: RewindableIterator<Item = (K, V)>,
+        K: SortedPageKey,
+        V: SortedPageValue,
+    {
         // Try to split the page before every write to avoid starving the split
         // operation due to contentions.
         if self.should_split_page(&view.page) && self.split_page(view.clone()).await.is_ok() {
             return Err(Error::Again);
         }
 
-        // Build a delta page with the given key-value pair.
-        let delta = (key, value);
-        let builder = SortedPageBuilder::new(PageTier::Leaf, PageKind::Data).with_item(delta);
+        // The page must be consolidated before the write if its chain is too
+        // long, which happens when previous consolidations fail under
+        // contentions or are skipped by the rate limit.
+        if self.is_chain_full(&view.page) {
+            self.consolidate_page(view).await?;
+            return Err(Error::Again);
+        }
+
+        if self.tree.take_forced_conflict() {
+            return Err(Error::Again);
+        }
+
         let mut txn = self.guard.begin().await;
         let (new_addr, mut new_page) = txn.alloc_page(builder.size()).await?;
         builder.build(&mut new_page);
@@ -170,9 +1217,9 @@ impl<'a, E: Env> TreeTxn<'a, E> {
                     // We can keep retrying as long as the page epoch remains
                     // the same. However, this doesn't work for the root
                     // because we split the root without updating its epoch.
-                    if view.id != ROOT_ID {
+                    if rebase && view.id != self.tree.root_id {
                         let page = self.guard.read_page_info(addr)?;
-                        if page.epoch() == view.page.epoch() {
+                        if page.epoch() == view.page.epoch() && !self.is_chain_full(&page) {
                             txn = _txn;
                             view.addr = addr;
                             view.page = page;
@@ -184,10 +1231,391 @@ impl<'a, E: Env> TreeTxn<'a, E> {
             }
         }
 
-        // Try to consolidate the page if it is too long.
-        if self.should_consolidate_page(&view.page) {
-            let _ = self.consolidate_and_restructure_page(view).await;
+        // Try to consolidate the page if it is too long. The write is done, so
+        // the consolidation can be skipped under the rate limit. The chain is
+        // still bounded by the check above.
+        if self.should_consolidate_page(&view.page) && self.acquire_consolidation() {
+            let _ = self.consolidate_and_restructure_page(view, parent).await;
+        }
+        Ok(())
+    }
+
+    /// Returns the id of the inner page above the leaf page that may contain
+    /// the key.
+    ///
+    /// Returns [`None`] if the root is a leaf page.
+    pub(crate) async fn index_page_id(&self, key: &[u8]) -> Result<Option<PageId>> {
+        let (_, parent) = self.find_leaf(key).await?;
+        Ok(parent.map(|parent| PageId::new(parent.id)))
+    }
+
+    /// Collects the visible entries of all leaf pages in the subtree rooted at
+    /// the page, in key order.
+    ///
+    /// Pages split from the subtree but not reconciled to it yet are included.
+    pub(crate) async fn scan_subtree(
+        &self,
+        id: PageId,
+        read_lsn: u64,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let id = u64::from(id);
+        if self.guard.try_page_addr(id).unwrap_or(0) == 0 {
+            return Err(Error::InvalidArgument);
+        }
+        let to_owned = |(k, v): (&[u8], &[u8])| (k.to_vec(), v.to_vec());
+        let mut entries = Vec::new();
+        let mut pending = vec![id];
+        while let Some(id) = pending.pop() {
+            let view = self.page_view(id, None).await?;
+            // The right pages of pending splits are visited after this page. The
+            // smaller the split key, the earlier the right page.
+            let mut splits = Vec::new();
+            self.walk_page(
+                view.addr,
+                |_, page, _| {
+                    if page.kind().is_split() {
+                        splits.push(split_delta_from_page(page));
+                    }
+                    false
+                },
+                CacheOption::default(),
+            )
+            .await?;
+            splits.sort_by(|a, b| b.0.cmp(a.0));
+            pending.extend(splits.into_iter().map(|(_, index)| index.id));
+
+            if view.page.tier().is_inner() {
+                let iter = self.iter_page(&view).await?;
+                let children: Vec<u64> = MergingInnerPageIter::new(iter)
+                    .map(|(_, index)| index.id)
+                    .collect();
+                pending.extend(children.into_iter().rev());
+                continue;
+            }
+            let iter = self.iter_page(&view).await?;
+            if let Some(op) = self.tree.options.merge_operator {
+                let merged: Vec<MergedEntry> = resolve_merges(iter, op, true)
+                    .iter()
+                    .map(|(k, v)| MergedEntry::new(*k, v))
+                    .collect();
+                entries.extend(PageIter::with_merged(&merged, read_lsn).map(to_owned));
+            } else {
+                entries.extend(PageIter::new(iter, read_lsn).map(to_owned));
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Consolidates pages with long chains, up to `budget` pages.
+    ///
+    /// Pages are visited from the root in breadth-first order. Returns the
+    /// number of pages that have been consolidated.
+    pub(crate) async fn warm_up(&self, budget: usize) -> Result<usize> {
+        let mut count = 0;
+        let mut pending = VecDeque::from([self.tree.root_id]);
+        while let Some(id) = pending.pop_front() {
+            if count >= budget {
+                break;
+            }
+            let view = self.page_view(id, None).await?;
+            if view.page.tier().is_inner() {
+                let iter = self.iter_page(&view).await?;
+                pending.extend(MergingInnerPageIter::new(iter).map(|(_, index)| index.id));
+            }
+            // Pending removals are completed by the next traversal instead.
+            if self.should_consolidate_page(&view.page) && !view.page.kind().is_remove() {
+                count += 1;
+                let _ = self.consolidate_and_restructure_page(view, None).await;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Rebuilds the index of the tree from its leaf pages.
+    ///
+    /// This is a last resort to recover a tree whose separators are corrupted
+    /// or out of date. The leaf pages are collected by walking from the root
+    /// through the inner pages and the pending splits, so pages that are not
+    /// reachable from the root, such as the ones left by failed operations,
+    /// are never indexed. The leaf pages are ordered by their smallest keys,
+    /// and the root is replaced with an inner page that indexes all of them,
+    /// which is split by later writes as usual. Leaf pages without entries are
+    /// skipped, and the former inner pages are left unreachable.
+    ///
+    /// This must not run concurrently with other operations on the tree.
+    /// Returns the number of indexed leaf pages.
+    pub(crate) async fn rebuild_index(&self) -> Result<usize> {
+        let mut children = Vec::new();
+        let mut visited = HashSet::new();
+        let mut pending = vec![self.tree.root_id];
+        while let Some(id) = pending.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+            let view = self.page_view(id, None).await?;
+            if view.page.kind().is_remove() {
+                continue;
+            }
+            self.walk_page(
+                view.addr,
+                |_, page, _| {
+                    // The right page of a pending split may not be in the parent yet.
+                    if page.kind().is_split() {
+                        let (_, index) = split_delta_from_page(page);
+                        pending.push(index.id);
+                    }
+                    false
+                },
+                CacheOption::default(),
+            )
+            .await?;
+            if view.page.tier().is_inner() {
+                let iter = self.iter_page(&view).await?;
+                pending.extend(MergingInnerPageIter::new(iter).map(|(_, index)| index.id));
+            } else if id != self.tree.root_id {
+                let mut iter = self.iter_page::<Key, Value>(&view).await?;
+                if let Some((key, _)) = iter.next() {
+                    children.push((key.raw.to_vec(), Index::new(id, view.page.epoch())));
+                }
+            }
+        }
+        children.sort_by(|a, b| a.0.cmp(&b.0));
+        let Some((first, _)) = children.first_mut() else {
+            return Ok(0);
+        };
+        // The leftmost child covers all keys before the others.
+        first.clear();
+
+        let root_addr = self.guard.page_addr(self.tree.root_id);
+        let mut root_addrs = Vec::new();
+        self.walk_page(
+            root_addr,
+            |addr, _, _| {
+                root_addrs.push(addr);
+                false
+            },
+            CacheOption::default(),
+        )
+        .await?;
+
+        let entries: Vec<_> = children
+            .iter()
+            .map(|(start, index)| (start.as_slice(), *index))
+            .collect();
+        let builder = SortedPageBuilder::new(PageTier::Inner, PageKind::Data).with_slice(&entries);
+        let mut txn = self.guard.begin().await;
+        let (new_addr, mut new_page) = txn.alloc_page(builder.size()).await?;
+        builder.build(&mut new_page);
+        txn.replace_page(self.tree.root_id, root_addr, new_addr, &root_addrs)
+            .await?;
+        Ok(entries.len())
+    }
+
+    /// Counts the pages of each kind on the page chains reachable from the
+    /// root.
+    ///
+    /// Split pages that stay around for long indicate that the splits are not
+    /// reconciled or consolidated in time.
+    pub(crate) async fn page_kind_counts(&self) -> Result<HashMap<PageKind, usize>> {
+        let mut counts = HashMap::new();
+        let mut visited = HashSet::new();
+        let mut pending = vec![self.tree.root_id];
+        while let Some(id) = pending.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+            let view = self.page_view(id, None).await?;
+            self.walk_page(
+                view.addr,
+                |_, page, _| {
+                    *counts.entry(page.kind()).or_default() += 1;
+                    // The right page of a pending split may not be in the parent yet.
+                    if page.kind().is_split() {
+                        let (_, index) = split_delta_from_page(page);
+                        pending.push(index.id);
+                    }
+                    false
+                },
+                CacheOption::default(),
+            )
+            .await?;
+            if view.page.tier().is_inner() {
+                let iter = self.iter_page(&view).await?;
+                pending.extend(MergingInnerPageIter::new(iter).map(|(_, index)| index.id));
+            }
+        }
+        Ok(counts)
+    }
+
+    /// Estimates the number of keys and bytes in the range `[start, end)`.
+    ///
+    /// The leaf pages that overlap with the range are located by the
+    /// separators of their parents. The keys of a leaf page come from its key
+    /// count and its bytes from the metadata of its chain, so only the leaf
+    /// pages that are not counted yet or have pending splits are read. The
+    /// parts of the boundary pages covered by the range are interpolated by
+    /// the keys, and the estimate never decreases as the range grows.
+    pub(crate) async fn estimate_range(&self, start: &[u8], end: &[u8]) -> Result<(u64, u64)> {
+        let mut keys = 0.0;
+        let mut bytes = 0.0;
+        let mut cursor = start.to_vec();
+        while cursor.as_slice() < end {
+            let (_, parent) = self.find_leaf(&cursor).await?;
+            let Some(parent) = parent else {
+                // The root is a leaf page.
+                let (leaf_keys, leaf_bytes) =
+                    self.estimate_leaves(start, end, &cursor, end).await?;
+                keys += leaf_keys;
+                bytes += leaf_bytes;
+                break;
+            };
+            let range = parent.range.unwrap();
+            let iter = self.iter_page(&parent).await?;
+            let children: Vec<_> = MergingInnerPageIter::new(iter).collect();
+            for (i, &(child_start, index)) in children.iter().enumerate() {
+                // The parent may still hold the children moved out by a pending split.
+                if child_start >= end || range.end.map_or(false, |e| child_start >= e) {
+                    break;
+                }
+                let child_end = match (children.get(i + 1).map(|(next, _)| *next), range.end) {
+                    (Some(next), Some(e)) => Some(next.min(e)),
+                    (next, e) => next.or(e),
+                };
+                if child_end.map_or(false, |e| e <= cursor.as_slice()) {
+                    continue;
+                }
+                let child_range = Range {
+                    start: child_start,
+                    end: child_end,
+                };
+                let covered = key_fraction(child_range, end) - key_fraction(child_range, start);
+                if let Some((leaf_keys, leaf_bytes)) = self.counted_leaf(index.id)? {
+                    keys += leaf_keys as f64 * covered;
+                    bytes += leaf_bytes as f64 * covered;
+                    continue;
+                }
+                let from = child_start.max(cursor.as_slice());
+                let until = child_end.map_or(end, |e| e.min(end));
+                let (leaf_keys, leaf_bytes) = self.estimate_leaves(start, end, from, until).await?;
+                keys += leaf_keys;
+                bytes += leaf_bytes;
+            }
+            match range.end {
+                Some(next) => cursor = next.to_vec(),
+                None => break,
+            }
+        }
+        Ok((keys.round() as u64, bytes.round() as u64))
+    }
+
+    // Returns the key count of the leaf page and the stored size of its chain,
+    // or None if the page is not counted or has a pending split.
+    fn counted_leaf(&self, id: u64) -> Result<Option<(u64, u64)>> {
+        let Some(keys) = self.tree.key_counts.get(id) else {
+            return Ok(None);
+        };
+        let mut bytes = 0;
+        let mut addr = self.guard.page_addr(id);
+        while addr != 0 {
+            let info = self.guard.read_page_info(addr)?;
+            // The count only covers the left half of a pending split.
+            if info.kind().is_split() {
+                return Ok(None);
+            }
+            bytes += self.guard.page_stored_size(addr)? as u64;
+            addr = info.chain_next();
+        }
+        Ok(Some((keys, bytes)))
+    }
+
+    // Estimates the part of the range `[start, end)` in the leaf pages from
+    // `from` to `until` by reading the pages. All versions of the keys in the
+    // pages are counted.
+    async fn estimate_leaves(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        from: &[u8],
+        until: &[u8],
+    ) -> Result<(f64, f64)> {
+        let mut keys = 0.0;
+        let mut bytes = 0.0;
+        let mut cursor = from.to_vec();
+        while cursor.as_slice() < until {
+            let (view, _) = self.find_leaf(&cursor).await?;
+            let range = view.range.unwrap();
+            let mut page_keys = 0;
+            let mut page_bytes = 0;
+            self.walk_page(
+                view.addr,
+                |_, page, _| {
+                    if page.kind().is_data() {
+                        page_keys += ValuePageRef::new(page).len();
+                    }
+                    page_bytes += page.size();
+                    false
+                },
+                CacheOption::default(),
+            )
+            .await?;
+            let covered = key_fraction(range, end) - key_fraction(range, start);
+            keys += page_keys as f64 * covered;
+            bytes += page_bytes as f64 * covered;
+            match range.end {
+                Some(next) => cursor = next.to_vec(),
+                None => break,
+            }
+        }
+        Ok((keys, bytes))
+    }
+
+    /// Returns the stored size of the leaf pages that overlap with the range
+    /// `[start, end)`.
+    ///
+    /// The leaf pages are located through the index, and the sizes of their
+    /// chains are summed up from the page metadata, so no leaf page is read.
+    pub(crate) async fn approximate_size(&self, start: &[u8], end: &[u8]) -> Result<u64> {
+        let mut size = 0;
+        let mut cursor = start.to_vec();
+        while cursor.as_slice() < end {
+            let (view, _) = self.find_leaf(&cursor).await?;
+            let mut addr = view.addr;
+            while addr != 0 {
+                size += self.guard.page_stored_size(addr)? as u64;
+                addr = self.guard.read_page_info(addr)?.chain_next();
+            }
+            match view.range.unwrap().end {
+                Some(next) => cursor = next.to_vec(),
+                None => break,
+            }
+        }
+        Ok(size)
+    }
+
+    /// Pins the pages of the nodes covering the range `[start, end)` in the
+    /// page cache, until the range is unpinned by [`Tree::unpin_range`].
+    ///
+    /// The pinned pages are charged to the cache, but they are never evicted.
+    /// Only the pages read from files now are pinned, so pages written to the
+    /// nodes later are not.
+    pub(crate) async fn pin_range(self, start: &[u8], end: &[u8]) -> Result<()> {
+        let mut cursor = start.to_vec();
+        while cursor.as_slice() < end {
+            let (view, _) = self.find_leaf(&cursor).await?;
+            self.walk_page(view.addr, |_, _, _| false, CacheOption::default())
+                .await?;
+            match view.range.unwrap().end {
+                Some(next) => cursor = next.to_vec(),
+                None => break,
+            }
         }
+        let range = PinnedRange {
+            start: start.to_vec(),
+            end: end.to_vec(),
+            _pages: self.guard.into_cache_entries(),
+        };
+        let mut ranges = self.tree.pinned_ranges.lock().expect("Poisoned");
+        ranges.push(range);
         Ok(())
     }
 
@@ -208,7 +1636,7 @@ impl<'a, E: Env> TreeTxn<'a, E> {
     /// Returns the leaf page and its parent.
     async fn find_leaf(&self, key: &[u8]) -> Result<(PageView<'_>, Option<PageView<'_>>)> {
         loop {
-            match self.try_find_leaf(key).await {
+            match self.try_find_leaf(Target::At(key), false).await {
                 Ok((view, parent)) => {
                     self.tree.stats.success.read.inc();
                     return Ok((view, parent));
@@ -222,13 +1650,60 @@ impl<'a, E: Env> TreeTxn<'a, E> {
         }
     }
 
-    async fn try_find_leaf(&self, key: &[u8]) -> Result<(PageView<'_>, Option<PageView<'_>>)> {
+    /// Finds the leaf page that may contain the key to read it.
+    ///
+    /// Unlike [`Self::find_leaf`], this follows pending splits to the page that
+    /// contains the key, so that reads don't wait for the splits to be
+    /// reconciled. The returned page can only be read, since its parent may not
+    /// index it yet.
+    async fn find_leaf_to_read(&self, key: &[u8]) -> Result<PageView<'_>> {
+        loop {
+            match self.try_find_leaf(Target::At(key), true).await {
+                Ok((view, _)) => {
+                    self.tree.stats.success.read.inc();
+                    return Ok(view);
+                }
+                Err(Error::Again) => {
+                    self.tree.stats.conflict.read.inc();
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Finds the leaf page that contains the keys right before `key`, or the
+    /// last leaf page if `key` is `None`, to read it.
+    ///
+    /// Like [`Self::find_leaf_to_read`], this follows pending splits, so the
+    /// returned page can only be read.
+    async fn find_leaf_before(&self, key: Option<&[u8]>) -> Result<PageView<'_>> {
+        loop {
+            match self.try_find_leaf(Target::Before(key), true).await {
+                Ok((view, _)) => {
+                    self.tree.stats.success.read.inc();
+                    return Ok(view);
+                }
+                Err(Error::Again) => {
+                    self.tree.stats.conflict.read.inc();
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn try_find_leaf(
+        &self,
+        target: Target<'_>,
+        follow_splits: bool,
+    ) -> Result<(PageView<'_>, Option<PageView<'_>>)> {
         // The index, range, and parent of the current page, starting from the root.
-        let mut index = ROOT_INDEX;
+        let mut index = Index::new(self.tree.root_id, 0);
         let mut range = ROOT_RANGE;
         let mut parent = None;
         loop {
-            let view = self.page_view(index.id, Some(range)).await?;
+            let mut view = self.page_view(index.id, Some(range)).await?;
             // If the page epoch has changed, the page may not contain the data we expect
             // anymore. Try to reconcile pending conflicts and restart the operation.
             //
@@ -248,8 +1723,34 @@ impl<'a, E: Env> TreeTxn<'a, E> {
             // page, we should check if the logical page's key range is what we
             // expect (between step 3 and 4). We use epoch to track the key range of a
             // logical page.
-            if view.page.epoch() != index.epoch {
-                let _ = self.reconcile_page(view, parent).await;
+            //
+            // A removed page must be reconciled even if its epoch matches, which
+            // indicates that the removal is aborted.
+            //
+            // Readers don't modify the page, so they can follow a pending split
+            // instead. A split delta is only installed on a base page, so it is the
+            // only change if the epoch is bumped once.
+            let is_pending_split =
+                view.page.kind().is_split() && view.page.epoch() == index.epoch + 1;
+            if follow_splits && is_pending_split {
+                let (page, _) = self
+                    .guard
+                    .read_page(view.addr, CacheOption::default())
+                    .await?;
+                let (split_key, split_index) = split_delta_from_page(page);
+                if target.is_after(split_key) {
+                    // The key has moved to the right page.
+                    index = split_index;
+                    range.start = split_key;
+                    continue;
+                }
+                range.end = Some(split_key);
+                view.range = Some(range);
+            } else if view.page.epoch() != index.epoch || view.page.kind().is_remove() {
+                // Retrying doesn't help if the conflicts can never be reconciled.
+                if let Err(Error::ReadOnly) = self.reconcile_page(view, parent).await {
+                    return Err(Error::ReadOnly);
+                }
                 return Err(Error::Again);
             }
             if view.page.tier().is_leaf() {
@@ -257,7 +1758,7 @@ impl<'a, E: Env> TreeTxn<'a, E> {
             }
             // Find the child page that may contain the key.
             let (child_index, child_range) = self
-                .find_child(key, &view)
+                .find_child(target, &view)
                 .await?
                 .expect("child page must exist");
             index = child_index;
@@ -294,8 +1795,10 @@ impl<'a, E: Env> TreeTxn<'a, E> {
         K: SortedPageKey,
         V: SortedPageValue,
     {
-        let mut builder = MergingIterBuilder::with_capacity(view.page.chain_len() as usize);
+        let mut builder = MergingIterBuilder::with_capacity(view.page.chain_len() as usize)
+            .with_tournament_threshold(self.tree.options.merge_tournament_threshold);
         let mut range_limit = None;
+        let mut range_deletes = Vec::new();
         self.walk_page(
             view.addr,
             |_, page, _| {
@@ -303,6 +1806,9 @@ impl<'a, E: Env> TreeTxn<'a, E> {
                     PageKind::Data => {
                         builder.add(SortedPageIter::from(page));
                     }
+                    PageKind::DeleteRange => {
+                        range_deletes.push(range_delete_from_page(page));
+                    }
                     PageKind::Split => {
                         // The split key we first encountered must be the smallest.
                         #[cfg(debug_assertions)]
@@ -315,26 +1821,166 @@ impl<'a, E: Env> TreeTxn<'a, E> {
                             range_limit = Some(split_key);
                         }
                     }
+                    // A removed page has no entries.
+                    PageKind::Remove => {}
                 }
                 false
             },
             CacheOption::default(),
         )
         .await?;
-        Ok(MergingPageIter::new(builder.build(), range_limit))
+        Ok(MergingPageIter::new(builder.build(), range_limit).with_range_deletes(range_deletes))
     }
 
     /// Finds the value corresponding to the key from the page.
+    ///
+    /// Merge operands of the key are resolved with the merge operator.
+    /// Returns true if the page has no entries of the key at any LSN.
+    async fn is_key_absent(&self, raw: &[u8], view: &PageView<'_>) -> Result<bool> {
+        let key = Key::new(raw, u64::MAX);
+        let mut found = false;
+        self.walk_page(
+            view.addr,
+            |_, page, _| {
+                if page.kind().is_data() {
+                    let page = ValuePageRef::from(page);
+                    let index = match page.rank(&key) {
+                        Ok(i) => i,
+                        Err(i) => i,
+                    };
+                    found = page.get(index).map_or(false, |(k, _)| k.raw == raw);
+                }
+                found
+            },
+            CacheOption::default(),
+        )
+        .await?;
+        Ok(!found)
+    }
+
     async fn find_value<'g>(
         &'g self,
         key: &Key<'_>,
         view: &PageView<'g>,
-    ) -> Result<Option<&'g [u8]>> {
+    ) -> Result<Option<Cow<'g, [u8]>>> {
+        let now = now_millis();
         let mut value = None;
+        // Merge operands from the newest to the oldest.
+        let mut operands = Vec::new();
+        // The LSN of the newest visible range delete of the key.
+        let mut deleted_lsn = None;
+        self.walk_page(
+            view.addr,
+            |_, page, _| {
+                debug_assert!(page.tier().is_leaf());
+                if page.kind().is_delete_range() {
+                    let rd = range_delete_from_page(page);
+                    if rd.contains(key.raw) && rd.lsn <= key.lsn {
+                        deleted_lsn = deleted_lsn.max(Some(rd.lsn));
+                    }
+                }
+                // We only care about data pages here.
+                if page.kind().is_data() {
+                    let page = ValuePageRef::from(page);
+                    let mut index = match page.rank(key) {
+                        Ok(i) => i,
+                        Err(i) => i,
+                    };
+                    while let Some((k, v)) = page.get(index) {
+                        if k.raw != key.raw {
+                            break;
+                        }
+                        debug_assert!(k.lsn <= key.lsn);
+                        if deleted_lsn.map_or(false, |lsn| k.lsn <= lsn) {
+                            return true;
+                        }
+                        match v.expire(now) {
+                            Value::Put(v) | Value::Expiring(v, _) => {
+                                value = Some(v);
+                                return true;
+                            }
+                            Value::Delete => return true,
+                            Value::Merge(v) => {
+                                operands.push(v);
+                                index += 1;
+                            }
+                        }
+                    }
+                }
+                false
+            },
+            CacheOption::default(),
+        )
+        .await?;
+        if operands.is_empty() {
+            return Ok(value.map(Cow::Borrowed));
+        }
+        let op = self
+            .tree
+            .options
+            .merge_operator
+            .expect("merge operator must be specified");
+        let value = merge_value(op, key.raw, value, &operands);
+        Ok(Some(Cow::Owned(value)))
+    }
+
+    /// Returns the LSN of the newest entry of the key in the page, or `None`
+    /// if the key has no entry. Range deletes of the key count as entries.
+    async fn find_version(&self, key: &[u8], view: &PageView<'_>) -> Result<Option<u64>> {
+        let target = Key::new(key, u64::MAX);
+        let mut version = None;
+        // The LSN of the newest range delete of the key, which is a version too
+        // if the key has any entry below it.
+        let mut deleted_lsn = None;
+        self.walk_page(
+            view.addr,
+            |_, page, _| {
+                debug_assert!(page.tier().is_leaf());
+                if page.kind().is_delete_range() {
+                    let rd = range_delete_from_page(page);
+                    if rd.contains(key) {
+                        deleted_lsn = deleted_lsn.max(Some(rd.lsn));
+                    }
+                }
+                // We only care about data pages here.
+                if page.kind().is_data() {
+                    let page = ValuePageRef::from(page);
+                    let index = match page.rank(&target) {
+                        Ok(i) => i,
+                        Err(i) => i,
+                    };
+                    if let Some((k, _)) = page.get(index) {
+                        if k.raw == key {
+                            version = Some(k.lsn);
+                            return true;
+                        }
+                    }
+                }
+                false
+            },
+            CacheOption::default(),
+        )
+        .await?;
+        Ok(version.map(|lsn| lsn.max(deleted_lsn.unwrap_or(0))))
+    }
+
+    /// Returns true if the newest entry of the key in the page is not a
+    /// tombstone.
+    async fn find_key<'g>(&'g self, key: &Key<'_>, view: &PageView<'g>) -> Result<bool> {
+        let now = now_millis();
+        let mut found = false;
+        // The LSN of the newest visible range delete of the key.
+        let mut deleted_lsn = None;
         self.walk_page(
             view.addr,
             |_, page, _| {
                 debug_assert!(page.tier().is_leaf());
+                if page.kind().is_delete_range() {
+                    let rd = range_delete_from_page(page);
+                    if rd.contains(key.raw) && rd.lsn <= key.lsn {
+                        deleted_lsn = deleted_lsn.max(Some(rd.lsn));
+                    }
+                }
                 // We only care about data pages here.
                 if page.kind().is_data() {
                     let page = ValuePageRef::from(page);
@@ -345,9 +1991,10 @@ impl<'a, E: Env> TreeTxn<'a, E> {
                     if let Some((k, v)) = page.get(index) {
                         if k.raw == key.raw {
                             debug_assert!(k.lsn <= key.lsn);
-                            if let Value::Put(v) = v {
-                                value = Some(v);
-                            }
+                            // Merge operands are resolved to a value even without
+                            // a base value.
+                            found = deleted_lsn.map_or(true, |lsn| k.lsn > lsn)
+                                && !matches!(v.expire(now), Value::Delete);
                             return true;
                         }
                     }
@@ -357,7 +2004,7 @@ impl<'a, E: Env> TreeTxn<'a, E> {
             CacheOption::default(),
         )
         .await?;
-        Ok(value)
+        Ok(found)
     }
 
     /// Finds the child page that may contain the key from the page.
@@ -365,7 +2012,7 @@ impl<'a, E: Env> TreeTxn<'a, E> {
     /// Returns the index and range of the child page.
     async fn find_child<'g>(
         &'g self,
-        key: &[u8],
+        target: Target<'_>,
         view: &PageView<'g>,
     ) -> Result<Option<(Index, Range<'g>)>> {
         let mut child = None;
@@ -377,14 +2024,32 @@ impl<'a, E: Env> TreeTxn<'a, E> {
                 if page.kind().is_data() {
                     let page = IndexPageRef::from(page);
                     // Find the two items that enclose the key.
-                    let (left, right) = match page.rank(&key) {
-                        // The `i` item is equal to the key, so the range is [i, i + 1).
-                        Ok(i) => (page.get(i), i.checked_add(1).and_then(|i| page.get(i))),
-                        // The `i` item is greater than the key, so the range is [i - 1, i).
-                        Err(i) => (i.checked_sub(1).and_then(|i| page.get(i)), page.get(i)),
+                    let (mut left, right) = match target {
+                        Target::At(key) => match page.rank(&key) {
+                            // The `i` item is equal to the key, so the range is [i, i + 1).
+                            Ok(i) => (Some(i), i.checked_add(1)),
+                            // The `i` item is greater than the key, so the range is [i - 1, i).
+                            Err(i) => (i.checked_sub(1), Some(i)),
+                        },
+                        // The `i` item is not less than the key, so the keys right before it
+                        // are in the range [i - 1, i).
+                        Target::Before(Some(key)) => match page.rank(&key) {
+                            Ok(i) | Err(i) => (i.checked_sub(1), Some(i)),
+                        },
+                        // The last item starts the last range.
+                        Target::Before(None) => (page.len().checked_sub(1), None),
                     };
+                    // A removed page is merged into its left sibling, which comes right
+                    // before it in the same page.
+                    if let Some(i) = left {
+                        if matches!(page.get(i), Some((_, index)) if index == REMOVED_INDEX) {
+                            left = i.checked_sub(1);
+                        }
+                    }
+                    let left = left.and_then(|i| page.get(i));
+                    let right = right.and_then(|i| page.get(i));
                     if let Some((start, index)) = left {
-                        if index != NULL_INDEX {
+                        if index != NULL_INDEX && index != REMOVED_INDEX {
                             let range = Range {
                                 start,
                                 end: right.map(|(end, _)| end),
@@ -419,27 +2084,37 @@ impl<'a, E: Env> TreeTxn<'a, E> {
         K: SortedPageKey,
         V: SortedPageValue,
     {
-        if view.id == ROOT_ID {
+        if view.id == self.tree.root_id {
             return self.split_root_impl::<K, V>(view).await;
         }
 
-        let (page, _) = self
+        let (base, _) = self
             .guard
             .read_page(view.addr, CacheOption::default())
             .await?;
-        let page = SortedPageRef::<K, V>::from(page);
+        let page = SortedPageRef::<K, V>::from(base);
         let Some((split_key, _, right_iter)) = page.into_split_iter() else {
             return Ok(());
         };
 
         let mut txn = self.guard.begin().await;
         // Build and insert the right page.
-        let right_id = {
-            let builder =
-                SortedPageBuilder::new(view.page.tier(), PageKind::Data).with_iter(right_iter);
-            let (new_addr, mut new_page) = txn.alloc_page(builder.size()).await?;
+        let (right_id, right_size) = {
+            let builder = SortedPageBuilder::new(view.page.tier(), PageKind::Data)
+                .with_key_size(self.tree.data_key_size(view.page.tier()))
+                .with_iter(right_iter);
+            let right_size = builder.size();
+            let (new_addr, mut new_page) = txn.alloc_page(right_size).await?;
             builder.build(&mut new_page);
-            txn.insert_page(new_addr)
+            (txn.insert_page(new_addr).await?, right_size)
+        };
+        let info = SplitPageInfo {
+            id: PageId::new(view.id),
+            left_id: PageId::new(view.id),
+            right_id: PageId::new(right_id),
+            size: view.page.size(),
+            right_size,
+            chain_len: view.page.chain_len() as usize,
         };
         // Build a delta page with the right index.
         let delta = (split_key.as_raw(), Index::new(right_id, 0));
@@ -462,24 +2137,38 @@ impl<'a, E: Env> TreeTxn<'a, E> {
                 self.tree.stats.conflict.split_page.inc();
                 Error::Again
             })?;
+        if view.page.tier().is_leaf() {
+            self.count_split_keys(base, split_key.as_raw(), view.id, right_id);
+        }
+        if let Some(listener) = &self.tree.options.event_listener {
+            listener.on_split_page(&info);
+        }
 
         Ok(())
     }
 
+    // Splits the root into two new pages.
+    //
+    // The root id is fixed, so the root can't keep the left half like other
+    // pages do. Instead, both halves are moved to new pages, and the root is
+    // replaced with an inner page that indexes them. This is the only way the
+    // tree grows in height: the root of an empty tree is a leaf page, and its
+    // first split turns it into an inner page with two leaf children. The root
+    // covers the whole key space, so its epoch stays zero across splits.
     async fn split_root_impl<K, V>(&self, view: PageView<'_>) -> Result<()>
     where
         K: SortedPageKey,
         V: SortedPageValue,
     {
-        assert_eq!(view.id, ROOT_ID);
+        assert_eq!(view.id, self.tree.root_id);
         assert_eq!(view.page.epoch(), 0);
         assert_eq!(view.page.chain_len(), 1);
 
-        let (page, _) = self
+        let (base, _) = self
             .guard
             .read_page(view.addr, CacheOption::default())
             .await?;
-        let page = SortedPageRef::<K, V>::from(page);
+        let page = SortedPageRef::<K, V>::from(base);
         let Some((split_key, left_iter, right_iter)) = page.into_split_iter() else {
             return Ok(());
         };
@@ -487,19 +2176,30 @@ impl<'a, E: Env> TreeTxn<'a, E> {
         let mut txn = self.guard.begin().await;
         // Build and insert the left page.
         let left_id = {
-            let builder =
-                SortedPageBuilder::new(view.page.tier(), PageKind::Data).with_iter(left_iter);
+            let builder = SortedPageBuilder::new(view.page.tier(), PageKind::Data)
+                .with_key_size(self.tree.data_key_size(view.page.tier()))
+                .with_iter(left_iter);
             let (new_addr, mut new_page) = txn.alloc_page(builder.size()).await?;
             builder.build(&mut new_page);
-            txn.insert_page(new_addr)
+            txn.insert_page(new_addr).await?
         };
         // Build and insert the right page.
-        let right_id = {
-            let builder =
-                SortedPageBuilder::new(view.page.tier(), PageKind::Data).with_iter(right_iter);
-            let (new_addr, mut new_page) = txn.alloc_page(builder.size()).await?;
+        let (right_id, right_size) = {
+            let builder = SortedPageBuilder::new(view.page.tier(), PageKind::Data)
+                .with_key_size(self.tree.data_key_size(view.page.tier()))
+                .with_iter(right_iter);
+            let right_size = builder.size();
+            let (new_addr, mut new_page) = txn.alloc_page(right_size).await?;
             builder.build(&mut new_page);
-            txn.insert_page(new_addr)
+            (txn.insert_page(new_addr).await?, right_size)
+        };
+        let info = SplitPageInfo {
+            id: PageId::new(view.id),
+            left_id: PageId::new(left_id),
+            right_id: PageId::new(right_id),
+            size: view.page.size(),
+            right_size,
+            chain_len: view.page.chain_len() as usize,
         };
         // Build a delta page with the right index.
         let delta = [
@@ -519,13 +2219,36 @@ impl<'a, E: Env> TreeTxn<'a, E> {
             .map_err(|_| {
                 self.tree.stats.conflict.split_page.inc();
                 Error::Again
-            })
+            })?;
+        if view.page.tier().is_leaf() {
+            // The root is an inner page now.
+            self.tree.key_counts.remove(view.id);
+            self.count_split_keys(base, split_key.as_raw(), left_id, right_id);
+        }
+        if let Some(listener) = &self.tree.options.event_listener {
+            listener.on_split_page(&info);
+        }
+        Ok(())
+    }
+
+    // Counts the live keys of the halves of a split leaf page.
+    fn count_split_keys(&self, page: PageRef<'_>, split_key: &[u8], left_id: u64, right_id: u64) {
+        let (mut left, mut right) = (0, 0);
+        for key in live_keys(page, now_millis()) {
+            if key < split_key {
+                left += 1;
+            } else {
+                right += 1;
+            }
+        }
+        self.tree.key_counts.set(left_id, left);
+        self.tree.key_counts.set(right_id, right);
     }
 
     /// Reconciles any conflicts on the page.
     async fn reconcile_page(&self, view: PageView<'_>, parent: Option<PageView<'_>>) -> Result<()> {
         let result = match view.page.kind() {
-            PageKind::Data => Ok(()),
+            PageKind::Data | PageKind::DeleteRange => Ok(()),
             PageKind::Split => {
                 if let Some(parent) = parent {
                     self.reconcile_split_page(view, parent).await
@@ -533,6 +2256,13 @@ impl<'a, E: Env> TreeTxn<'a, E> {
                     Err(Error::InvalidArgument)
                 }
             }
+            PageKind::Remove => {
+                if let Some(parent) = parent {
+                    self.reconcile_remove_page(view, parent).await
+                } else {
+                    Err(Error::InvalidArgument)
+                }
+            }
         };
         match result {
             Ok(_) => {
@@ -595,49 +2325,318 @@ impl<'a, E: Env> TreeTxn<'a, E> {
 
         // Try to consolidate the parent page if it is too long.
         if self.should_consolidate_page(&parent.page) {
-            let _ = self.consolidate_and_restructure_page(parent).await;
+            let _ = self.consolidate_and_restructure_page(parent, None).await;
+        }
+        Ok(())
+    }
+
+    /// Removes an empty leaf page by merging its range into its left sibling.
+    ///
+    /// The removal is recorded in a delta page first, which stops further
+    /// modifications to the page, and then reconciled to the parent.
+    async fn remove_page(&self, view: PageView<'_>, parent: PageView<'_>) -> Result<()> {
+        let Some(range) = view.range else {
+            return Err(Error::InvalidArgument);
+        };
+        // The leftmost child of the parent can not be removed.
+        let left = self.find_left_sibling(range.start, &parent).await?;
+        if left.is_none() {
+            return Ok(());
+        }
+
+        let builder = SortedPageBuilder::new(PageTier::Leaf, PageKind::Remove)
+            .with_iter(ItemIter::<(Key, Value)>::from(None));
+        let mut txn = self.guard.begin().await;
+        let (new_addr, mut new_page) = txn.alloc_page(builder.size()).await?;
+        builder.build(&mut new_page);
+        // The page epoch must be updated to indicate the change of the page range.
+        new_page.set_epoch(view.page.epoch() + 1);
+        new_page.set_chain_len(view.page.chain_len().saturating_add(1));
+        new_page.set_chain_next(view.addr);
+        txn.update_page(view.id, view.addr, new_addr).map_err(|_| {
+            self.tree.stats.conflict.remove_page.inc();
+            Error::Again
+        })?;
+        trace!("remove page {:?}", view);
+        self.tree.key_counts.remove(view.id);
+
+        let view = PageView {
+            addr: new_addr,
+            page: new_page.info(),
+            ..view
+        };
+        self.reconcile_remove_page(view, parent).await
+    }
+
+    // Reconciles a pending removal on the page.
+    async fn reconcile_remove_page(&self, view: PageView<'_>, parent: PageView<'_>) -> Result<()> {
+        let Some(range) = view.range else {
+            return Err(Error::InvalidArgument);
+        };
+        let (index, _) = self
+            .find_child(Target::At(range.start), &parent)
+            .await?
+            .expect("child page must exist");
+        // The parent has been updated, restart to find the new one.
+        if index.id != view.id {
+            return Err(Error::Again);
+        }
+        // The removal has been aborted but the page is not revived yet.
+        if index.epoch == view.page.epoch() {
+            return self.revive_page(view).await;
+        }
+
+        // Build a delta page that merges the range into the left sibling, or aborts
+        // the removal with the new epoch if the page has no left sibling anymore,
+        // which happens if the parent has been split since the removal.
+        let left = self.find_left_sibling(range.start, &parent).await?;
+        let mut delta = match left {
+            Some(left) => vec![left, (range.start, REMOVED_INDEX)],
+            None => vec![(range.start, Index::new(view.id, view.page.epoch()))],
+        };
+        if let Some(range_end) = range.end {
+            // This is a placeholder to indicate the range end of the left page.
+            delta.push((range_end, NULL_INDEX));
         }
+        let builder = SortedPageBuilder::new(PageTier::Inner, PageKind::Data).with_slice(&delta);
+        let mut txn = self.guard.begin().await;
+        let (new_addr, mut new_page) = txn.alloc_page(builder.size()).await?;
+        builder.build(&mut new_page);
+        // Update the parent page with the delta.
+        new_page.set_epoch(parent.page.epoch());
+        new_page.set_chain_len(parent.page.chain_len().saturating_add(1));
+        new_page.set_chain_next(parent.addr);
+        if left.is_none() {
+            return txn
+                .update_page(parent.id, parent.addr, new_addr)
+                .map(|_| trace!("abort removing page {:?} with delta {:?}", view, delta))
+                .map_err(|_| Error::Again);
+        }
+
+        // Deallocate the removed page along with the update of the parent.
+        let mut page_addrs = Vec::new();
+        self.walk_page(
+            view.addr,
+            |addr, _, _| {
+                page_addrs.push(addr);
+                false
+            },
+            CacheOption::default(),
+        )
+        .await?;
+        txn.replace_page(parent.id, parent.addr, new_addr, &page_addrs)
+            .await
+            .map(|_| {
+                trace!("reconcile remove page {:?} with delta {:?}", view, delta);
+                self.tree.stats.success.remove_page.inc();
+            })
+            .map_err(|_| {
+                self.tree.stats.conflict.remove_page.inc();
+                Error::Again
+            })?;
+        if let (Some(listener), Some((_, left))) = (&self.tree.options.event_listener, left) {
+            let info = RemovePageInfo {
+                id: PageId::new(view.id),
+                left_id: PageId::new(left.id),
+            };
+            listener.on_remove_page(&info);
+        }
+        self.guard.dealloc_page_id(view.id);
         Ok(())
     }
 
+    // Revives a page whose removal has been aborted.
+    async fn revive_page(&self, view: PageView<'_>) -> Result<()> {
+        debug_assert!(view.page.kind().is_remove());
+        // The removed page is always empty, so we just build a new one with the
+        // current epoch.
+        let builder = SortedPageBuilder::new(PageTier::Leaf, PageKind::Data)
+            .with_iter(ItemIter::<(Key, Value)>::from(None));
+        let mut txn = self.guard.begin().await;
+        let (new_addr, mut new_page) = txn.alloc_page(builder.size()).await?;
+        builder.build(&mut new_page);
+        new_page.set_epoch(view.page.epoch());
+        new_page.set_chain_len(1);
+        new_page.set_chain_next(0);
+        txn.replace_page(
+            view.id,
+            view.addr,
+            new_addr,
+            &[view.addr, view.page.chain_next()],
+        )
+        .await
+        .map(|_| trace!("revive page {:?}", view))
+    }
+
+    /// Finds the left sibling of the child page that starts at the key.
+    ///
+    /// Returns [`None`] if the child is the leftmost one of the parent.
+    async fn find_left_sibling<'g>(
+        &'g self,
+        key: &[u8],
+        parent: &PageView<'g>,
+    ) -> Result<Option<(&'g [u8], Index)>> {
+        let mut sibling = None;
+        self.walk_page(
+            parent.addr,
+            |_, page, _| {
+                if page.kind().is_data() {
+                    let page = IndexPageRef::from(page);
+                    let mut i = match page.rank(&key) {
+                        Ok(i) | Err(i) => i,
+                    };
+                    while let Some(prev) = i.checked_sub(1) {
+                        i = prev;
+                        match page.get(i) {
+                            // Skip removed pages, which are merged into their left
+                            // siblings.
+                            Some((_, index)) if index == REMOVED_INDEX => continue,
+                            Some((start, index)) if index != NULL_INDEX => {
+                                sibling = Some((start, index));
+                                return true;
+                            }
+                            _ => break,
+                        }
+                    }
+                }
+                false
+            },
+            CacheOption::default(),
+        )
+        .await?;
+        Ok(sibling)
+    }
+
     /// Consolidates delta pages on the page chain.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(page_id = view.id, chain_len = view.page.chain_len())
+        )
+    )]
     async fn consolidate_page<'g>(&'g self, view: PageView<'g>) -> Result<PageView<'g>> {
         match view.page.tier() {
             PageTier::Leaf => {
-                let safe_lsn = self.tree.safe_lsn();
-                self.consolidate_page_impl(view, |iter| MergingLeafPageIter::new(iter, safe_lsn))
-                    .await
+                let safe_lsn = self.tree.retained_lsn();
+                if let Some(op) = self.tree.options.merge_operator {
+                    return self.consolidate_merged_leaf_page(view, op, safe_lsn).await;
+                }
+                self.consolidate_page_impl(view, |iter, complete| {
+                    MergingLeafPageIter::new(iter, safe_lsn, complete)
+                })
+                .await
             }
             PageTier::Inner => {
-                self.consolidate_page_impl(view, MergingInnerPageIter::new)
+                self.consolidate_page_impl(view, |iter, _| MergingInnerPageIter::new(iter))
                     .await
             }
         }
     }
 
-    async fn consolidate_page_impl<'g, F, I, K, V>(
+    async fn consolidate_page_impl<'g, F, I, K, V>(
+        &'g self,
+        view: PageView<'g>,
+        f: F,
+    ) -> Result<PageView<'g>>
+    where
+        F: Fn(MergingPageIter<'g, K, V>, bool) -> I,
+        I: RewindableIterator<Item = (K, V)>,
+        K: SortedPageKey,
+        V: SortedPageValue,
+    {
+        // Collect information for this consolidation.
+        let info = self.collect_consolidation_info(&view).await?;
+        // The whole chain is consolidated if there are no pages left.
+        let complete = info.last_page.chain_next() == 0;
+        let iter = f(info.iter, complete);
+        self.install_consolidated_page(
+            view,
+            iter,
+            &info.last_page,
+            &info.page_addrs,
+            info.page_size,
+        )
+        .await
+    }
+
+    /// Consolidates a leaf page with merge operands resolved.
+    async fn consolidate_merged_leaf_page<'g>(
+        &'g self,
+        view: PageView<'g>,
+        op: MergeOperator,
+        safe_lsn: u64,
+    ) -> Result<PageView<'g>> {
+        let info = self.collect_consolidation_info(&view).await?;
+        // Merge operands must be resolved before applying the safe LSN, which may
+        // drop their bases. Operands without bases in this consolidation are
+        // kept as they are unless the whole chain is consolidated.
+        let complete = info.last_page.chain_next() == 0;
+        let entries = resolve_merges(info.iter, op, complete);
+        let iter = MergingLeafPageIter::new(MergedPageIter::new(&entries), safe_lsn, complete);
+        self.install_consolidated_page(
+            view,
+            iter,
+            &info.last_page,
+            &info.page_addrs,
+            info.page_size,
+        )
+        .await
+    }
+
+    /// Replaces the consolidated pages with a new page built from the
+    /// iterator.
+    async fn install_consolidated_page<'g, I, K, V>(
         &'g self,
         mut view: PageView<'g>,
-        f: F,
+        mut iter: I,
+        last_page: &PageInfo,
+        page_addrs: &[u64],
+        page_size: usize,
     ) -> Result<PageView<'g>>
     where
-        F: Fn(MergingPageIter<'g, K, V>) -> I,
         I: RewindableIterator<Item = (K, V)>,
         K: SortedPageKey,
         V: SortedPageValue,
     {
-        // Collect information for this consolidation.
-        let info = self.collect_consolidation_info(&view).await?;
-        let iter = f(info.iter);
-        let builder = SortedPageBuilder::new(view.page.tier(), PageKind::Data).with_iter(iter);
+        // Filters are only built for whole chains, which contain all keys of the
+        // pages.
+        let prefix_filter = self
+            .tree
+            .prefix_filters
+            .as_ref()
+            .filter(|_| view.page.tier().is_leaf() && last_page.chain_next() == 0)
+            .map(|filters| {
+                let mut builder = filters.builder();
+                for (k, _) in &mut iter {
+                    builder.add(k.as_raw());
+                }
+                iter.rewind();
+                builder.finish()
+            });
+        let builder = SortedPageBuilder::new(view.page.tier(), PageKind::Data)
+            .with_key_size(self.tree.data_key_size(view.page.tier()))
+            .with_iter(iter);
         let mut txn = self.guard.begin().await;
         let (new_addr, mut new_page) = txn.alloc_page(builder.size()).await?;
         builder.build(&mut new_page);
         new_page.set_epoch(view.page.epoch());
-        new_page.set_chain_len(info.last_page.chain_len());
-        new_page.set_chain_next(info.last_page.chain_next());
+        new_page.set_chain_len(last_page.chain_len());
+        new_page.set_chain_next(last_page.chain_next());
+        // Keys are only counted for whole chains, like the filters.
+        let num_keys = (view.page.tier().is_leaf() && last_page.chain_next() == 0)
+            .then(|| live_keys(PageRef::from(*new_page), now_millis()).count() as u64);
+        let info = ConsolidatePageInfo {
+            id: PageId::new(view.id),
+            size: page_size,
+            new_size: new_page.size(),
+            chain_len: view.page.chain_len() as usize,
+            new_chain_len: last_page.chain_len() as usize,
+        };
         // Update the page and deallocate the consolidated delta pages.
-        txn.replace_page(view.id, view.addr, new_addr, &info.page_addrs)
+        let view = txn
+            .replace_page(view.id, view.addr, new_addr, page_addrs)
             .await
             .map(|_| {
                 trace!("consolidate page {:?}", view);
@@ -649,7 +2648,17 @@ impl<'a, E: Env> TreeTxn<'a, E> {
             .map_err(|_| {
                 self.tree.stats.conflict.consolidate_page.inc();
                 Error::Again
-            })
+            })?;
+        if let (Some(filters), Some(filter)) = (&self.tree.prefix_filters, prefix_filter) {
+            filters.insert(view.id, view.addr, filter);
+        }
+        if let Some(num_keys) = num_keys {
+            self.tree.key_counts.set(view.id, num_keys);
+        }
+        if let Some(listener) = &self.tree.options.event_listener {
+            listener.on_consolidate_page(&info);
+        }
+        Ok(view)
     }
 
     /// Collects some information to consolidate a page.
@@ -662,11 +2671,13 @@ impl<'a, E: Env> TreeTxn<'a, E> {
         V: SortedPageValue,
     {
         let chain_len = view.page.chain_len() as usize;
-        let mut builder = MergingIterBuilder::with_capacity(chain_len);
+        let mut builder = MergingIterBuilder::with_capacity(chain_len)
+            .with_tournament_threshold(self.tree.options.merge_tournament_threshold);
         let mut page_size = 0;
         let mut last_page = view.page.clone();
         let mut page_addrs = Vec::with_capacity(chain_len);
         let mut range_limit = None;
+        let mut range_deletes = Vec::new();
         let opt = CacheOption::default().set_refill_cold_when_not_full(true);
         self.walk_page(
             view.addr,
@@ -676,10 +2687,15 @@ impl<'a, E: Env> TreeTxn<'a, E> {
                         // Inner pages can not do partial consolidations because of the
                         // placeholders. This is fine since inner pages
                         // doesn't consolidate as often as leaf pages.
+                        //
+                        // Range deletes can only be merged into the data if all pages
+                        // are consolidated, since older versions of the deleted keys
+                        // may exist in any of them.
                         if page.tier().is_leaf()
                             && builder.len() >= 2
                             && page_size < page.size() / 2
                             && range_limit.is_none()
+                            && range_deletes.is_empty()
                             && !self.should_consolidate_page(&page.info())
                         {
                             return true;
@@ -696,6 +2712,11 @@ impl<'a, E: Env> TreeTxn<'a, E> {
                             range_limit = Some(split_key);
                         }
                     }
+                    PageKind::DeleteRange => {
+                        range_deletes.push(range_delete_from_page(page));
+                        page_size += page.size();
+                    }
+                    PageKind::Remove => unreachable!("removed pages are never consolidated"),
                 }
                 last_page = page.info();
                 page_addrs.push(addr);
@@ -704,20 +2725,33 @@ impl<'a, E: Env> TreeTxn<'a, E> {
             opt,
         )
         .await?;
-        let iter = MergingPageIter::new(builder.build(), range_limit);
+        let iter =
+            MergingPageIter::new(builder.build(), range_limit).with_range_deletes(range_deletes);
         Ok(ConsolidationInfo {
             iter,
             last_page,
             page_addrs,
+            page_size,
         })
     }
 
     /// Consolidates and restructures a page.
-    async fn consolidate_and_restructure_page<'g>(&'g self, mut view: PageView<'g>) -> Result<()> {
+    ///
+    /// Empty leaf pages are removed if the parent is given.
+    async fn consolidate_and_restructure_page<'g>(
+        &'g self,
+        mut view: PageView<'g>,
+        parent: Option<PageView<'g>>,
+    ) -> Result<()> {
         view = self.consolidate_page(view).await?;
         // Try to split the page if it is too large.
         if self.should_split_page(&view.page) {
             let _ = self.split_page(view).await;
+        } else if let Some(parent) = parent {
+            // Try to remove the page if it is empty.
+            if self.should_remove_page(&view).await? {
+                let _ = self.remove_page(view, parent).await;
+            }
         }
         Ok(())
     }
@@ -732,8 +2766,27 @@ impl<'a, E: Env> TreeTxn<'a, E> {
         page.size() > max_size && page.chain_next() == 0
     }
 
+    // Returns true if the page is an empty leaf page that should be removed.
+    async fn should_remove_page(&self, view: &PageView<'_>) -> Result<bool> {
+        if view.id == self.tree.root_id
+            || !view.page.tier().is_leaf()
+            || !view.page.kind().is_data()
+            || view.page.chain_next() != 0
+        {
+            return Ok(false);
+        }
+        let (page, _) = self
+            .guard
+            .read_page(view.addr, CacheOption::default())
+            .await?;
+        Ok(ValuePageRef::from(page).len() == 0)
+    }
+
     // Returns true if the page should be consolidated.
     fn should_consolidate_page(&self, page: &PageInfo) -> bool {
+        if self.tree.maintenance_paused.load(Ordering::Acquire) {
+            return false;
+        }
         let mut max_chain_len = self.tree.options.page_chain_length;
         if page.tier().is_inner() {
             // Adjust the chain length for inner pages.
@@ -741,69 +2794,267 @@ impl<'a, E: Env> TreeTxn<'a, E> {
         }
         page.chain_len() as usize > max_chain_len.max(1)
     }
+
+    // Returns true if a consolidation triggered by writes is allowed by the
+    // rate limit.
+    fn acquire_consolidation(&self) -> bool {
+        let Some(limiter) = &self.tree.consolidate_limiter else {
+            return true;
+        };
+        if limiter.try_acquire() {
+            return true;
+        }
+        self.tree.stats.throttled_consolidations.inc();
+        false
+    }
+
+    // Returns true if no more deltas can be chained to the page.
+    fn is_chain_full(&self, page: &PageInfo) -> bool {
+        let options = &self.tree.options;
+        // A consolidated page may still chain some short delta pages, so the
+        // limit must leave some room for them.
+        let max_chain_len = options
+            .max_chain_length
+            .clamp(options.page_chain_length.max(1) + 2, u8::MAX as usize);
+        page.chain_len() as usize >= max_chain_len
+    }
 }
 
 /// An iterator over leaf pages in a tree.
-pub(crate) struct TreeIter<'a, 't: 'a, E: Env> {
-    txn: &'a TreeTxn<'t, E>,
+///
+/// The iterator walks the children of a parent page, reading the parent again
+/// for each child so that no page is borrowed between two calls. Pages that are
+/// consolidated during the iteration are still readable under the guard, but a
+/// page whose epoch has changed may not cover the expected range anymore. In
+/// that case, the iterator re-seeks from the start of the stale page, which is
+/// also the end of the last yielded one, so that no key is skipped or yielded
+/// twice. The same applies if the parent page has changed, since the ids of
+/// removed children may be reused.
+///
+/// The iterator doesn't borrow any page between two calls, so the caller can
+/// switch to a new transaction to release the pages pinned by the former ones.
+///
+/// If [`ReadOptions::direction`] is backward, the iterator yields pages from
+/// the last one, finding each page right before the start of the former one
+/// from the root. The entries of each page are still yielded in key order.
+pub(crate) struct TreeIter {
     options: ReadOptions,
-    inner_iter: Option<MergingInnerPageIter<'a>>,
-    inner_next: Option<&'a [u8]>,
+    // The id and address of the parent page that the children come from.
+    inner_parent: Option<(u64, u64)>,
+    // The start of the next child to visit in the parent page, if any.
+    inner_child: Option<Vec<u8>>,
+    // The key to resume from once the children are exhausted.
+    inner_next: Option<Vec<u8>>,
+    // Entries of the current page with merge operands resolved.
+    merged: Vec<MergedEntry>,
+    // The prefix of the keys to iterate, if any.
+    prefix: Option<Vec<u8>>,
+    // The exclusive end of the next page to yield backward, or `None` if
+    // unbounded.
+    prev_end: Option<Vec<u8>>,
+    // Whether the backward iteration has reached the first page to yield.
+    prev_done: bool,
 }
 
-impl<'a, 't: 'a, E: Env> TreeIter<'a, 't, E> {
-    pub(crate) fn new(txn: &'a TreeTxn<'t, E>, options: ReadOptions) -> Self {
+impl TreeIter {
+    pub(crate) fn new(options: ReadOptions) -> Self {
+        let inner_next = options.lower_bound.clone().unwrap_or_default();
+        let prev_end = options.upper_bound.clone();
         Self {
-            txn,
             options,
-            inner_iter: None,
-            inner_next: Some(&[]),
+            inner_parent: None,
+            inner_child: None,
+            inner_next: Some(inner_next),
+            merged: Vec::new(),
+            prefix: None,
+            prev_done: prev_end.as_deref() == Some(&[]),
+            prev_end,
         }
     }
 
-    async fn seek(&mut self, target: &[u8]) -> Result<PageIter<'_>> {
-        let (view, parent) = self.txn.find_leaf(target).await?;
-        let iter = self.txn.iter_page(&view).await?;
-        let mut leaf_iter = PageIter::new(iter, self.options.max_lsn);
-        leaf_iter.seek(target);
-        if let Some(parent) = parent {
-            let iter = self.txn.iter_page(&parent).await?;
-            let mut iter = MergingInnerPageIter::new(iter);
-            if iter.seek(target) {
-                iter.next();
+    /// Returns the options of the iterator.
+    pub(crate) fn options(&self) -> &ReadOptions {
+        &self.options
+    }
+
+    /// Starts the iterator from the page that contains `start`, skipping the
+    /// keys before it.
+    pub(crate) fn with_start(mut self, start: &[u8]) -> Self {
+        self.inner_next = Some(start.to_vec());
+        self
+    }
+
+    /// Limits the iterator to the pages that may contain keys with `prefix`.
+    ///
+    /// The iterator starts from the page that contains `prefix`, skips pages
+    /// that are known to have no such keys by the prefix filters, and stops
+    /// at the first page after the keys. Note that the yielded pages may
+    /// still contain other keys.
+    pub(crate) fn with_prefix(self, prefix: &[u8]) -> Self {
+        let mut iter = self.with_start(prefix);
+        iter.prefix = Some(prefix.to_vec());
+        iter
+    }
+
+    // Returns true if the keys from `start` are all after the prefix or the
+    // upper bound.
+    fn after_end(&self, start: &[u8]) -> bool {
+        let after_prefix = self
+            .prefix
+            .as_deref()
+            .map_or(false, |prefix| start > prefix && !start.starts_with(prefix));
+        let after_upper_bound = self
+            .options
+            .upper_bound
+            .as_deref()
+            .map_or(false, |end| start >= end);
+        after_prefix || after_upper_bound
+    }
+
+    fn leaf_iter<'g>(
+        &'g mut self,
+        iter: MergingPageIter<'g, Key<'g>, Value<'g>>,
+        merge_operator: Option<MergeOperator>,
+    ) -> PageIter<'g> {
+        match merge_operator {
+            Some(op) => {
+                self.merged = resolve_merges(iter, op, true)
+                    .iter()
+                    .map(|(k, v)| MergedEntry::new(*k, v))
+                    .collect();
+                PageIter::with_merged(&self.merged, self.options.max_lsn)
             }
-            self.inner_iter = Some(iter);
-            self.inner_next = parent.range.unwrap().end;
+            None => PageIter::new(iter, self.options.max_lsn),
+        }
+    }
+
+    async fn seek<'g, E: Env>(
+        &'g mut self,
+        txn: &'g TreeTxn<'_, E>,
+        target: &[u8],
+    ) -> Result<PageIter<'g>> {
+        let (view, parent) = txn.find_leaf(target).await?;
+        let iter = txn.iter_page(&view).await?;
+        self.inner_parent = parent.as_ref().map(|parent| (parent.id, parent.addr));
+        if let Some(parent) = parent {
+            // The next child starts at the end of the leaf page. Note that the leaf
+            // page may cover the ranges of removed pages.
+            self.inner_child = view.range.unwrap().end.map(|end| end.to_vec());
+            self.inner_next = parent.range.unwrap().end.map(|end| end.to_vec());
         } else {
-            self.inner_iter = None;
+            self.inner_child = None;
             self.inner_next = None;
         }
+        let mut leaf_iter = self.leaf_iter(iter, txn.tree.options.merge_operator);
+        leaf_iter.seek(target);
         Ok(leaf_iter)
     }
 
-    pub(crate) async fn next_page(&mut self) -> Result<Option<PageIter<'_>>> {
+    /// Returns the next page in the tree, in the direction of the iterator.
+    ///
+    /// Different transactions of the same tree can be used for different calls.
+    pub(crate) async fn next_page<'g, E: Env>(
+        &'g mut self,
+        txn: &'g TreeTxn<'_, E>,
+    ) -> Result<Option<PageIter<'g>>> {
+        match self.options.direction {
+            Direction::Forward => self.forward_page(txn).await,
+            Direction::Backward => self.backward_page(txn).await,
+        }
+    }
+
+    async fn forward_page<'g, E: Env>(
+        &'g mut self,
+        txn: &'g TreeTxn<'_, E>,
+    ) -> Result<Option<PageIter<'g>>> {
         let mut inner_next = self.inner_next.take();
-        if let Some(inner_iter) = self.inner_iter.as_mut() {
-            if let Some((start, index)) = inner_iter.next() {
-                let view = self.txn.page_view(index.id, None).await?;
+        while let Some(start) = self.inner_child.take() {
+            if self.after_end(&start) {
+                return Ok(None);
+            }
+            let index = match self.inner_parent {
+                Some((id, addr)) if txn.guard.page_addr(id) == addr => {
+                    let view = txn.page_view(id, None).await?;
+                    let iter = txn.iter_page(&view).await?;
+                    let mut iter = MergingInnerPageIter::new(iter);
+                    iter.seek(&start);
+                    let index = iter.next().map(|(_, index)| index);
+                    self.inner_child = iter.next().map(|(start, _)| start.to_vec());
+                    index
+                }
+                _ => None,
+            };
+            if let Some(index) = index {
+                if !self.may_contain_prefix(txn, index.id) {
+                    continue;
+                }
+                let view = txn.page_view(index.id, None).await?;
                 if view.page.epoch() == index.epoch {
-                    let iter = self.txn.iter_page(&view).await?;
+                    let iter = txn.iter_page(&view).await?;
                     self.inner_next = inner_next;
-                    return Ok(Some(PageIter::new(iter, self.options.max_lsn)));
-                } else {
-                    // The page epoch has changed, we need to restart from this.
-                    inner_next = Some(start);
+                    return Ok(Some(self.leaf_iter(iter, txn.tree.options.merge_operator)));
                 }
             }
+            // The page epoch or the parent has changed, we need to restart from this.
+            // All keys before `start` have been yielded by previous pages.
+            self.inner_child = None;
+            inner_next = Some(start);
+            break;
         }
-        if let Some(next) = inner_next {
-            let iter = self.seek(next).await?;
+        if let Some(next) = inner_next.filter(|next| !self.after_end(next)) {
+            let iter = self.seek(txn, &next).await?;
             Ok(Some(iter))
         } else {
-            self.inner_iter = None;
             Ok(None)
         }
     }
+
+    async fn backward_page<'g, E: Env>(
+        &'g mut self,
+        txn: &'g TreeTxn<'_, E>,
+    ) -> Result<Option<PageIter<'g>>> {
+        if self.prev_done {
+            return Ok(None);
+        }
+        let view = txn.find_leaf_before(self.prev_end.as_deref()).await?;
+        let start = view.range.unwrap().start;
+        // Stop after the page that contains the lower bound or the first key.
+        let lower_bound = self.options.lower_bound.as_deref().unwrap_or_default();
+        self.prev_done = start <= lower_bound;
+        self.prev_end = Some(start.to_vec());
+        let iter = txn.iter_page(&view).await?;
+        Ok(Some(self.leaf_iter(iter, txn.tree.options.merge_operator)))
+    }
+
+    // Returns false if the page is known to have no keys with the prefix.
+    fn may_contain_prefix<E: Env>(&self, txn: &TreeTxn<'_, E>, id: u64) -> bool {
+        match (&self.prefix, &txn.tree.prefix_filters) {
+            (Some(prefix), Some(filters)) => {
+                filters.may_contain(id, txn.guard.page_addr(id), prefix)
+            }
+            _ => true,
+        }
+    }
+}
+
+// The position of the keys to find the leaf page for.
+#[derive(Clone, Copy)]
+enum Target<'a> {
+    // The key itself.
+    At(&'a [u8]),
+    // The keys right before the key, or the last keys if `None`.
+    Before(Option<&'a [u8]>),
+}
+
+impl Target<'_> {
+    // Returns true if the target keys are at or after `key`.
+    fn is_after(&self, key: &[u8]) -> bool {
+        match *self {
+            Target::At(target) => target >= key,
+            Target::Before(Some(target)) => target > key,
+            Target::Before(None) => true,
+        }
+    }
 }
 
 struct ConsolidationInfo<'a, K, V>
@@ -814,6 +3065,20 @@ where
     iter: MergingPageIter<'a, K, V>,
     last_page: PageInfo,
     page_addrs: Vec<u64>,
+    // The total size of the data pages to consolidate.
+    page_size: usize,
+}
+
+// Returns the raw keys whose newest versions in the leaf page are live.
+fn live_keys(page: PageRef<'_>, now: u64) -> impl Iterator<Item = &[u8]> {
+    let mut last = None;
+    SortedPageIter::<Key, Value>::from(page).filter_map(move |(k, v)| {
+        if last == Some(k.raw) {
+            return None;
+        }
+        last = Some(k.raw);
+        (!matches!(v.expire(now), Value::Delete)).then_some(k.raw)
+    })
 }
 
 fn split_delta_from_page(page: PageRef<'_>) -> (&[u8], Index) {
@@ -822,3 +3087,682 @@ fn split_delta_from_page(page: PageRef<'_>) -> (&[u8], Index) {
         .get(0)
         .expect("split page delta must exist")
 }
+
+/// Returns the range delete of a delta page, which is encoded as the start
+/// and LSN in the key and the end in the value.
+fn range_delete_from_page(page: PageRef<'_>) -> RangeDelete<'_> {
+    debug_assert!(page.kind().is_delete_range());
+    let (key, value) = ValuePageRef::from(page)
+        .get(0)
+        .expect("range delete delta must exist");
+    let Value::Put(end) = value else {
+        unreachable!("range delete delta must have an end");
+    };
+    RangeDelete {
+        start: key.raw,
+        end,
+        lsn: key.lsn,
+    }
+}
+
+/// Returns the position of the key in the range, from 0 to 1.
+///
+/// The keys in the range are mapped to numbers by the 8 bytes after the common
+/// prefix of the range bounds, which preserves the order of the keys.
+fn key_fraction(range: Range<'_>, key: &[u8]) -> f64 {
+    if key <= range.start {
+        return 0.0;
+    }
+    if range.end.map_or(false, |end| key >= end) {
+        return 1.0;
+    }
+    let prefix = range.end.map_or(0, |end| {
+        range
+            .start
+            .iter()
+            .zip(end)
+            .take_while(|(a, b)| a == b)
+            .count()
+    });
+    let number = |key: &[u8]| {
+        let mut buf = [0u8; 8];
+        let tail = key.get(prefix..).unwrap_or_default();
+        let len = tail.len().min(buf.len());
+        buf[..len].copy_from_slice(&tail[..len]);
+        u64::from_be_bytes(buf) as f64
+    };
+    let lo = number(range.start);
+    let hi = range.end.map_or(u64::MAX as f64 + 1.0, number);
+    if hi <= lo {
+        return 0.5;
+    }
+    ((number(key) - lo) / (hi - lo)).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tempfile::{tempdir, TempDir};
+
+    use super::*;
+    use crate::{env::Photon, page_store::PageStore, PageStoreOptions};
+
+    // Opens a tree with `options` on a new store in a temporary directory, and
+    // initializes the root. The directory is removed once it is dropped.
+    async fn open_tree(options: Options) -> (TempDir, Tree, PageStore<Photon>) {
+        let path = tempdir().unwrap();
+        let tree = Tree::new(options.clone());
+        let store = PageStore::open(Photon, &path, options.page_store)
+            .await
+            .unwrap();
+        tree.begin(store.guard()).init().await.unwrap();
+        (path, tree, store)
+    }
+
+    // The options of a tree with small pages, which splits after a few writes.
+    fn small_page_options() -> Options {
+        Options {
+            page_size: 256,
+            ..Default::default()
+        }
+    }
+
+    // Writes the key `i` at `lsn`, with the key itself as the value.
+    async fn must_put(tree: &Tree, store: &PageStore<Photon>, i: u64, lsn: u64) {
+        let txn = tree.begin(store.guard());
+        let value = i.to_be_bytes();
+        txn.write(Key::new(&value, lsn), Value::Put(&value))
+            .await
+            .unwrap();
+    }
+
+    #[photonio::test]
+    async fn write_with_max_chain_length() {
+        let options = Options {
+            page_chain_length: 4,
+            max_chain_length: 6,
+            page_store: PageStoreOptions {
+                write_buffer_capacity: 1 << 20,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let max_chain_len = options.max_chain_length;
+        let (_path, tree, store) = open_tree(options).await;
+        let (tree, store) = (Arc::new(tree), Arc::new(store));
+
+        // Update the same key concurrently, so that some consolidations fail.
+        const N: u64 = 1 << 10;
+        let mut tasks = Vec::new();
+        for t in 0..4 {
+            let tree = tree.clone();
+            let store = store.clone();
+            let handle = photonio::task::spawn(async move {
+                for i in 0..N {
+                    let lsn = i * 4 + t;
+                    let txn = tree.begin(store.guard());
+                    let key = Key::new(b"key", lsn);
+                    let value = lsn.to_be_bytes();
+                    txn.write(key, Value::Put(&value)).await.unwrap();
+                    let (view, _) = txn.find_leaf(key.raw).await.unwrap();
+                    assert!(view.page.chain_len() as usize <= max_chain_len);
+                }
+            });
+            tasks.push(handle);
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        if let Ok(store) = Arc::try_unwrap(store) {
+            store.close().await;
+        }
+    }
+
+    #[photonio::test]
+    async fn scan_subtree() {
+        let (_path, tree, store) = open_tree(small_page_options()).await;
+
+        const N: u64 = 4096;
+        for i in 0..N {
+            must_put(&tree, &store, i, 1).await;
+        }
+
+        // Find an inner page below the root.
+        let txn = tree.begin(store.guard());
+        let target = (N / 2).to_be_bytes();
+        let (_, parent) = txn.find_leaf(&target).await.unwrap();
+        let parent = parent.unwrap();
+        assert_ne!(parent.id, ROOT_ID);
+        let range = parent.range.unwrap();
+        let in_range = |key: &[u8]| key >= range.start && range.end.map_or(true, |end| key < end);
+
+        let entries = txn
+            .scan_subtree(PageId::new(parent.id), u64::MAX)
+            .await
+            .unwrap();
+        let expect: Vec<_> = (0..N)
+            .map(|i| i.to_be_bytes())
+            .filter(|key| in_range(key.as_slice()))
+            .map(|key| (key.to_vec(), key.to_vec()))
+            .collect();
+        assert!(!expect.is_empty() && expect.len() < N as usize);
+        assert_eq!(entries, expect);
+
+        assert!(matches!(
+            txn.scan_subtree(PageId::new(u64::MAX), u64::MAX).await,
+            Err(Error::InvalidArgument)
+        ));
+        drop(txn);
+
+        store.close().await;
+    }
+
+    #[photonio::test]
+    async fn iter_with_consolidation() {
+        let (_path, tree, store) = open_tree(small_page_options()).await;
+
+        const N: u64 = 1024;
+        for lsn in [1, 2] {
+            // Rewrite half of the keys to leave deltas on the pages.
+            for i in (0..N).step_by(lsn as usize) {
+                must_put(&tree, &store, i, lsn).await;
+            }
+        }
+
+        let mut iter = TreeIter::new(ReadOptions::default());
+        let mut next = 0u64;
+        loop {
+            let txn = tree.begin(store.guard());
+            let Some(page) = iter.next_page(&txn).await.unwrap() else {
+                break;
+            };
+            for (k, v) in page {
+                assert_eq!(k, next.to_be_bytes());
+                assert_eq!(v, next.to_be_bytes());
+                next += 1;
+            }
+            // Consolidate the page being scanned and the next one.
+            for key in [next - 1, next] {
+                let (view, _) = txn.find_leaf(&key.to_be_bytes()).await.unwrap();
+                txn.consolidate_page(view).await.unwrap();
+            }
+        }
+        assert_eq!(next, N);
+
+        store.close().await;
+    }
+
+    #[photonio::test]
+    async fn estimate_range() {
+        let (_path, tree, store) = open_tree(small_page_options()).await;
+
+        const N: u64 = 4096;
+        for i in 0..N {
+            must_put(&tree, &store, i, 1).await;
+        }
+
+        let txn = tree.begin(store.guard());
+        // Count the keys of every leaf page.
+        consolidate_leaves(&txn).await;
+        let start = (N / 4).to_be_bytes();
+        let mut last = (0, 0);
+        for width in [N / 16, N / 8, N / 4, N / 2] {
+            let end = (N / 4 + width).to_be_bytes();
+            let (keys, bytes) = txn.estimate_range(&start, &end).await.unwrap();
+            assert!(keys >= last.0 && bytes >= last.1);
+            // The boundary pages are interpolated, so the estimate is not exact.
+            let (lower, upper) = (width * 3 / 4, width * 5 / 4);
+            assert!(keys >= lower && keys <= upper, "{keys} vs {width}");
+            last = (keys, bytes);
+        }
+        let (keys, _) = txn.estimate_range(&start, &start).await.unwrap();
+        assert_eq!(keys, 0);
+        drop(txn);
+
+        store.close().await;
+    }
+
+    #[photonio::test]
+    async fn approximate_size() {
+        let (_path, tree, store) = open_tree(small_page_options()).await;
+
+        const N: u64 = 4096;
+        for i in 0..N {
+            must_put(&tree, &store, i, 1).await;
+        }
+
+        // Sizes are taken from both write buffers and files.
+        for flush in [false, true] {
+            if flush {
+                store.flush(&FlushOptions::default()).await;
+            }
+            let txn = tree.begin(store.guard());
+            let start = (N / 4).to_be_bytes();
+            let mut last = 0;
+            for width in [N / 16, N / 8, N / 4, N / 2] {
+                let end = (N / 4 + width).to_be_bytes();
+                let size = txn.approximate_size(&start, &end).await.unwrap();
+                // Flushed pages are compressed, so the size is not bounded by the
+                // entries.
+                assert!(size > last, "{size} vs {last}");
+                last = size;
+            }
+            assert_eq!(txn.approximate_size(&start, &start).await.unwrap(), 0);
+        }
+
+        store.close().await;
+    }
+
+    #[photonio::test]
+    async fn remove_empty_page() {
+        let options = Options {
+            page_size: 256,
+            page_chain_length: 4,
+            ..Default::default()
+        };
+        let (_path, tree, store) = open_tree(options).await;
+
+        const N: u64 = 256;
+        for i in 0..N {
+            must_put(&tree, &store, i, 1).await;
+        }
+
+        // Find a leaf page that is not the leftmost one.
+        let target = (N / 2).to_be_bytes();
+        let (start, end) = {
+            let txn = tree.begin(store.guard());
+            let (view, parent) = txn.find_leaf(&target).await.unwrap();
+            assert!(parent.is_some());
+            let range = view.range.unwrap();
+            assert!(!range.start.is_empty());
+            (range.start.to_vec(), range.end.map(|end| end.to_vec()))
+        };
+        let in_range = |key: &[u8]| {
+            key >= start.as_slice() && end.as_ref().map_or(true, |end| key < end.as_slice())
+        };
+
+        // Delete all keys in the page and consolidate it until it is removed.
+        for i in 0..N {
+            let value = i.to_be_bytes();
+            if in_range(&value) {
+                let txn = tree.begin(store.guard());
+                txn.write(Key::new(&value, 2), Value::Delete).await.unwrap();
+            }
+        }
+        tree.set_safe_lsn(3);
+        loop {
+            let txn = tree.begin(store.guard());
+            let (view, parent) = txn.find_leaf(&target).await.unwrap();
+            if view.range.unwrap().start != start.as_slice() {
+                break;
+            }
+            let _ = txn.consolidate_and_restructure_page(view, parent).await;
+        }
+        assert!(tree.stats().success.remove_page >= 1);
+
+        for i in 0..N {
+            let txn = tree.begin(store.guard());
+            let value = i.to_be_bytes();
+            let got = txn.get(Key::new(&value, 3)).await.unwrap();
+            if in_range(&value) {
+                assert_eq!(got, None);
+            } else {
+                assert_eq!(got.as_deref(), Some(value.as_slice()));
+            }
+        }
+
+        store.close().await;
+    }
+
+    #[photonio::test]
+    async fn rebuild_index() {
+        let (_path, tree, store) = open_tree(small_page_options()).await;
+
+        const N: u64 = 1024;
+        for i in 0..N {
+            must_put(&tree, &store, i, 1).await;
+        }
+        store.flush(&FlushOptions::default()).await;
+
+        // Corrupt the index by replacing the root with an inner page that
+        // indexes the leaf pages with wrong separators.
+        {
+            let txn = tree.begin(store.guard());
+            let mut leaves = Vec::new();
+            let mut key = Some(Vec::new());
+            while let Some(start) = key.take() {
+                let (view, parent) = txn.find_leaf(&start).await.unwrap();
+                assert!(parent.is_some() && view.id != ROOT_ID);
+                leaves.push(Index::new(view.id, view.page.epoch()));
+                key = view.range.unwrap().end.map(|end| end.to_vec());
+            }
+            let separators: Vec<_> = (0..leaves.len() as u64).map(u64::to_be_bytes).collect();
+            let mut entries: Vec<_> = separators
+                .iter()
+                .zip(leaves)
+                .map(|(separator, index)| (separator.as_slice(), index))
+                .collect();
+            entries[0].0 = &[];
+            let root_addr = txn.guard.page_addr(ROOT_ID);
+            let builder =
+                SortedPageBuilder::new(PageTier::Inner, PageKind::Data).with_slice(&entries);
+            let mut page_txn = txn.guard.begin().await;
+            let (new_addr, mut new_page) = page_txn.alloc_page(builder.size()).await.unwrap();
+            builder.build(&mut new_page);
+            page_txn
+                .replace_page(ROOT_ID, root_addr, new_addr, &[])
+                .await
+                .unwrap();
+        }
+        let txn = tree.begin(store.guard());
+        let value = (N / 2).to_be_bytes();
+        assert_eq!(txn.get(Key::new(&value, 1)).await.unwrap(), None);
+        drop(txn);
+
+        let num_leaves = tree.begin(store.guard()).rebuild_index().await.unwrap();
+        assert!(num_leaves > 1);
+        for i in 0..N * 2 {
+            let txn = tree.begin(store.guard());
+            let value = i.to_be_bytes();
+            if i >= N {
+                txn.write(Key::new(&value, 2), Value::Put(&value))
+                    .await
+                    .unwrap();
+            }
+            let got = txn.get(Key::new(&value, 2)).await.unwrap();
+            assert_eq!(got.as_deref(), Some(value.as_slice()));
+        }
+        for i in 0..N * 2 {
+            let txn = tree.begin(store.guard());
+            let value = i.to_be_bytes();
+            let got = txn.get(Key::new(&value, 2)).await.unwrap();
+            assert_eq!(got.as_deref(), Some(value.as_slice()));
+        }
+
+        store.close().await;
+    }
+
+    #[photonio::test]
+    async fn bulk_load_release_pages() {
+        let (_path, tree, store) = open_tree(small_page_options()).await;
+        let pending_frees = || store.stats().epoch.pending_page_id_frees;
+        let abandoned_pages = || tree.abandoned_bulk_pages.lock().unwrap().len();
+
+        const N: u64 = 256;
+        let txn = tree.begin(store.guard());
+        let mut load = txn.begin_bulk_load(1).await.unwrap();
+        for i in 0..N {
+            let key = i.to_be_bytes();
+            load.add(&txn, &key, &key).await.unwrap();
+        }
+
+        // The pages of a dropped load are released by the next load.
+        drop(load);
+        let num_pages = abandoned_pages();
+        assert!(num_pages > 1);
+        let frees = pending_frees();
+        let mut load = txn.begin_bulk_load(1).await.unwrap();
+        assert_eq!(abandoned_pages(), 0);
+        assert_eq!(pending_frees(), frees + num_pages as u64);
+
+        // The pages of a failed load, including its index pages, are released
+        // once it fails.
+        for i in 0..N {
+            let key = i.to_be_bytes();
+            load.add(&txn, &key, &key).await.unwrap();
+        }
+        must_put(&tree, &store, N, 2).await;
+        let frees = pending_frees();
+        assert!(matches!(load.finish(&txn).await, Err(Error::Again)));
+        assert!(pending_frees() > frees + num_pages as u64);
+        assert_eq!(abandoned_pages(), 0);
+        drop(txn);
+
+        store.close().await;
+    }
+
+    #[photonio::test]
+    async fn read_pending_split() {
+        let (_path, tree, store) = open_tree(small_page_options()).await;
+
+        const N: u64 = 1 << 8;
+        for i in 0..N {
+            must_put(&tree, &store, i, 1).await;
+        }
+
+        // Split a leaf page without reconciling its parent.
+        let txn = tree.begin(store.guard());
+        let key = (N / 2).to_be_bytes();
+        let (mut view, parent) = txn.find_leaf(&key).await.unwrap();
+        assert!(parent.is_some());
+        while view.page.chain_next() != 0 {
+            view = txn.consolidate_page(view).await.unwrap();
+        }
+        let start = tree.stats();
+        txn.split_page(view).await.unwrap();
+        assert_eq!(tree.stats().sub(&start).success.split_page, 1);
+        drop(txn);
+
+        // Reads on both halves of the page don't conflict with the split.
+        let start = tree.stats();
+        for i in 0..N {
+            let txn = tree.begin(store.guard());
+            let value = i.to_be_bytes();
+            let got = txn.try_get(Key::new(&value, 1)).await.unwrap();
+            assert_eq!(got.as_deref(), Some(value.as_slice()));
+        }
+        let stats = tree.stats().sub(&start);
+        assert_eq!(stats.conflict.read, 0);
+        assert_eq!(stats.success.reconcile_page, 0);
+
+        // Writes still reconcile the split.
+        let txn = tree.begin(store.guard());
+        txn.write(Key::new(&key, 2), Value::Put(&key))
+            .await
+            .unwrap();
+        assert!(tree.stats().sub(&start).success.reconcile_page > 0);
+
+        store.close().await;
+    }
+
+    #[photonio::test]
+    async fn split_root() {
+        let (_path, tree, store) = open_tree(small_page_options()).await;
+
+        // The root of an empty tree is a leaf page.
+        let txn = tree.begin(store.guard());
+        let (view, parent) = txn.find_leaf(&[]).await.unwrap();
+        assert_eq!(view.id, ROOT_ID);
+        assert!(view.page.tier().is_leaf());
+        assert!(parent.is_none());
+        drop(txn);
+
+        let mut n = 0u64;
+        while tree.stats().success.split_page == 0 {
+            must_put(&tree, &store, n, 1).await;
+            n += 1;
+        }
+
+        // The first split turns the root into an inner page above two leaves.
+        let txn = tree.begin(store.guard());
+        let root = txn.page_view(ROOT_ID, None).await.unwrap();
+        assert!(root.page.tier().is_inner());
+        assert_eq!(root.page.epoch(), 0);
+        let mut leaves = HashSet::new();
+        for i in 0..n {
+            let value = i.to_be_bytes();
+            let (view, parent) = txn.find_leaf(&value).await.unwrap();
+            assert_ne!(view.id, ROOT_ID);
+            assert!(view.page.tier().is_leaf());
+            assert_eq!(parent.unwrap().id, ROOT_ID);
+            leaves.insert(view.id);
+            let got = txn.get(Key::new(&value, 1)).await.unwrap();
+            assert_eq!(got.as_deref(), Some(value.as_slice()));
+        }
+        assert_eq!(leaves.len(), 2);
+        drop(txn);
+
+        store.close().await;
+    }
+
+    #[photonio::test]
+    async fn estimated_num_keys() {
+        let (_path, tree, store) = open_tree(small_page_options()).await;
+
+        const N: u64 = 1024;
+        for i in 0..N {
+            must_put(&tree, &store, i, 1).await;
+        }
+        for i in (0..N).step_by(2) {
+            let txn = tree.begin(store.guard());
+            let value = i.to_be_bytes();
+            txn.write(Key::new(&value, 2), Value::Delete).await.unwrap();
+        }
+        assert!(tree.estimated_num_keys() <= N);
+
+        // The counts are exact once all leaf pages are consolidated.
+        let txn = tree.begin(store.guard());
+        let mut cursor = Vec::new();
+        loop {
+            let (mut view, _) = txn.find_leaf(&cursor).await.unwrap();
+            while view.page.chain_next() != 0 {
+                view = txn.consolidate_page(view).await.unwrap();
+            }
+            match view.range.unwrap().end {
+                Some(next) => cursor = next.to_vec(),
+                None => break,
+            }
+        }
+        drop(txn);
+        assert_eq!(tree.estimated_num_keys(), N / 2);
+        assert_eq!(tree.stats().estimated_num_keys, N / 2);
+
+        store.close().await;
+    }
+
+    // Consolidates every leaf page into one data page, and returns the entries
+    // of the pages.
+    async fn consolidate_leaves(txn: &TreeTxn<'_, Photon>) -> Vec<(u64, u64, bool)> {
+        let mut entries = Vec::new();
+        let mut cursor = Vec::new();
+        loop {
+            let (view, _) = txn.find_leaf(&cursor).await.unwrap();
+            let mut view = txn.consolidate_page(view).await.unwrap();
+            while view.page.chain_next() != 0 {
+                view = txn.consolidate_page(view).await.unwrap();
+            }
+            assert!(view.page.kind().is_data());
+            let (page, _) = txn
+                .guard
+                .read_page(view.addr, CacheOption::default())
+                .await
+                .unwrap();
+            for (k, v) in SortedPageIter::<Key, Value>::from(page) {
+                let raw = u64::from_be_bytes(k.raw.try_into().unwrap());
+                entries.push((raw, k.lsn, v == Value::Delete));
+            }
+            match view.range.unwrap().end {
+                Some(next) => cursor = next.to_vec(),
+                None => break,
+            }
+        }
+        entries
+    }
+
+    #[photonio::test]
+    async fn consolidate_range_delete() {
+        let (_path, tree, store) = open_tree(small_page_options()).await;
+
+        const N: u64 = 1024;
+        for i in 0..N {
+            must_put(&tree, &store, i, 1).await;
+        }
+        let txn = tree.begin(store.guard());
+        let (start, end) = (10u64, N - 10);
+        txn.delete_range(&start.to_be_bytes(), &end.to_be_bytes(), 2)
+            .await
+            .unwrap();
+
+        // The deleted keys are kept for reads below the range delete.
+        tree.set_safe_lsn(1);
+        let entries = consolidate_leaves(&txn).await;
+        assert_eq!(entries.len() as u64, N + (end - start));
+        for (raw, lsn, deleted) in entries {
+            assert_eq!(deleted, lsn == 2);
+            assert!(!deleted || (start..end).contains(&raw));
+        }
+        let key = Key::new(&start.to_be_bytes(), 1);
+        assert!(txn.get(key).await.unwrap().is_some());
+
+        // The deleted keys are dropped once no read can see them.
+        tree.set_safe_lsn(2);
+        let entries = consolidate_leaves(&txn).await;
+        let expect: Vec<_> = (0..start).chain(end..N).map(|i| (i, 1, false)).collect();
+        assert_eq!(entries, expect);
+        let key = Key::new(&start.to_be_bytes(), 2);
+        assert!(txn.get(key).await.unwrap().is_none());
+        drop(txn);
+
+        store.close().await;
+    }
+
+    #[photonio::test]
+    async fn consolidate_rate() {
+        let options = Options {
+            page_chain_length: 4,
+            max_chain_length: 8,
+            consolidate_rate: 1,
+            ..Default::default()
+        };
+        let (_path, tree, store) = open_tree(options.clone()).await;
+
+        // The first consolidation takes the only token, and the following
+        // ones are left to the chain limit.
+        let start = tree.stats();
+        let mut max_chain_len = 0;
+        for i in 0..32u64 {
+            let txn = tree.begin(store.guard());
+            let key = i.to_be_bytes();
+            txn.write(Key::new(&key, i), Value::Put(&key))
+                .await
+                .unwrap();
+            let (view, _) = txn.find_leaf(&key).await.unwrap();
+            max_chain_len = max_chain_len.max(view.page.chain_len() as usize);
+        }
+        assert!(max_chain_len > options.page_chain_length + 1);
+        assert!(max_chain_len <= options.max_chain_length);
+
+        let stats = tree.stats().sub(&start);
+        assert!(stats.throttled_consolidations > 0);
+        assert!(stats.success.consolidate_page > 1);
+
+        store.close().await;
+    }
+
+    #[photonio::test]
+    async fn forced_write_conflicts() {
+        let (_path, tree, store) = open_tree(Options::default()).await;
+
+        for n in [1, 2] {
+            let start = tree.stats();
+            tree.force_conflicts(n);
+            let txn = tree.begin(store.guard());
+            let value = n.to_be_bytes();
+            txn.write(Key::new(&value, 1), Value::Put(&value))
+                .await
+                .unwrap();
+            let got = txn.get(Key::new(&value, 1)).await.unwrap();
+            assert_eq!(got.as_deref(), Some(value.as_slice()));
+            drop(txn);
+
+            let stats = tree.stats().sub(&start);
+            assert_eq!(stats.conflict.write, n as u64);
+            assert_eq!(stats.success.write, 1);
+            assert!(!tree.take_forced_conflict());
+        }
+
+        store.close().await;
+    }
+}
@@ -0,0 +1,107 @@
+use std::{
+    hash::{Hash, Hasher},
+    sync::Mutex,
+};
+
+use rustc_hash::FxHasher;
+
+/// A small cache of keys that are known to be absent from the tree.
+///
+/// Each key is hashed to a slot that remembers the last absent key. A key is
+/// only recorded if it has no entries at any LSN, so the miss holds for reads
+/// at any LSN until the key is written again.
+pub(super) struct NegativeCache {
+    slots: Box<[Mutex<Slot>]>,
+}
+
+#[derive(Default)]
+struct Slot {
+    // Bumped every time the slot is invalidated, so that a miss observed
+    // before a concurrent write is not recorded after the write.
+    version: u64,
+    key: Option<Vec<u8>>,
+}
+
+impl NegativeCache {
+    pub(super) fn new(num_slots: usize) -> Self {
+        assert!(num_slots > 0);
+        let slots = (0..num_slots).map(|_| Mutex::default()).collect();
+        Self { slots }
+    }
+
+    /// Returns true if the key is known to be absent.
+    pub(super) fn contains(&self, key: &[u8]) -> bool {
+        let slot = self.slot(key).lock().expect("Poisoned");
+        slot.key.as_deref() == Some(key)
+    }
+
+    /// Returns the version of the slot of the key.
+    ///
+    /// The version must be obtained before the key is looked up, and passed
+    /// to [`Self::insert`] if the key is absent.
+    pub(super) fn version(&self, key: &[u8]) -> u64 {
+        self.slot(key).lock().expect("Poisoned").version
+    }
+
+    /// Records that the key is absent, unless the key is written since the
+    /// slot is at the version.
+    pub(super) fn insert(&self, key: &[u8], version: u64) {
+        let mut slot = self.slot(key).lock().expect("Poisoned");
+        if slot.version == version {
+            slot.key = Some(key.to_vec());
+        }
+    }
+
+    /// Invalidates the record of the key, which must be called after the key
+    /// is written.
+    pub(super) fn invalidate(&self, key: &[u8]) {
+        let mut slot = self.slot(key).lock().expect("Poisoned");
+        slot.version += 1;
+        slot.key = None;
+    }
+
+    /// Invalidates the records of all keys, which must be called after keys
+    /// are written without being invalidated one by one.
+    pub(super) fn invalidate_all(&self) {
+        for slot in self.slots.iter() {
+            let mut slot = slot.lock().expect("Poisoned");
+            slot.version += 1;
+            slot.key = None;
+        }
+    }
+
+    fn slot(&self, key: &[u8]) -> &Mutex<Slot> {
+        let mut hasher = FxHasher::default();
+        key.hash(&mut hasher);
+        &self.slots[hasher.finish() as usize % self.slots.len()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negative_cache() {
+        let cache = NegativeCache::new(4);
+        assert!(!cache.contains(b"a"));
+        let version = cache.version(b"a");
+        cache.insert(b"a", version);
+        assert!(cache.contains(b"a"));
+        assert!(!cache.contains(b"b"));
+
+        cache.invalidate(b"a");
+        assert!(!cache.contains(b"a"));
+
+        // A miss observed before the invalidation is not recorded.
+        let version = cache.version(b"a");
+        cache.invalidate(b"a");
+        cache.insert(b"a", version);
+        assert!(!cache.contains(b"a"));
+
+        let version = cache.version(b"b");
+        cache.insert(b"b", version);
+        cache.invalidate_all();
+        assert!(!cache.contains(b"b"));
+    }
+}
@@ -1,6 +1,51 @@
+use std::sync::Arc;
+
+use super::EventListener;
 use crate::PageStoreOptions;
 
+/// A function that merges an operand into the existing value of a key.
+///
+/// The function is called with the key, the existing value if any, and the
+/// operand, and returns the merged value.
+pub type MergeOperator = fn(&[u8], Option<&[u8]>, &[u8]) -> Vec<u8>;
+
+/// A function that extracts the prefix of a key.
+///
+/// The function returns [`None`] for keys without a prefix. Keys with the
+/// same prefix must be adjacent in key order, which holds if the prefix is a
+/// leading part of the key.
+pub type PrefixExtractor = fn(&[u8]) -> Option<&[u8]>;
+
+/// The layout of keys in data pages.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum DataPageLayout {
+    /// Keys of any size, each framed with its length.
+    #[default]
+    Variable,
+    /// Keys of the given size, stored without length framing.
+    ///
+    /// Keys are located by offset arithmetic instead of per-item offsets,
+    /// which saves the space of the offsets in leaf pages. Writes with keys of
+    /// other sizes are rejected.
+    FixedKey(usize),
+}
+
+/// The direction in which iterators yield keys.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Direction {
+    /// From smaller keys to larger keys.
+    #[default]
+    Forward,
+    /// From larger keys to smaller keys.
+    Backward,
+}
+
 /// Options to configure a table.
+///
+/// Tables make no random decisions. Splits, consolidations, and cache
+/// evictions only depend on the options and the operations, so tables opened
+/// with the same options make the same decisions for the same sequence of
+/// operations.
 #[non_exhaustive]
 #[derive(Clone, Debug)]
 pub struct Options {
@@ -17,6 +62,105 @@ pub struct Options {
     /// Default: 4
     pub page_chain_length: usize,
 
+    /// The maximum number of delta pages chained per leaf page.
+    ///
+    /// Writes to a page whose chain reaches this length are blocked until the
+    /// page is consolidated, which bounds the cost of reads even if
+    /// consolidations fall behind writes. The length is at least
+    /// `page_chain_length + 2` and at most 255.
+    ///
+    /// Default: 32
+    pub max_chain_length: usize,
+
+    /// The operator to resolve merge operands.
+    ///
+    /// Merges are rejected if no operator is specified. The operator must not
+    /// be changed once some operands are written, since unresolved operands are
+    /// persisted with the pages.
+    ///
+    /// Default: None
+    pub merge_operator: Option<MergeOperator>,
+
+    /// The maximum number of pages to consolidate when the table is opened.
+    ///
+    /// Pages recovered from the store keep the delta chains they had when the
+    /// table was closed, which slows down the first reads on them. If this is
+    /// not zero, pages whose chains are longer than `page_chain_length` are
+    /// consolidated before the table is returned, which trades startup time
+    /// for steady-state latency. The budget bounds the number of consolidated
+    /// pages rather than the elapsed time, so that the warm-up is
+    /// deterministic.
+    ///
+    /// Default: 0
+    pub warm_up_on_open: usize,
+
+    /// The layout of keys in leaf data pages.
+    ///
+    /// The layout can be changed between opens. Pages keep the layout they
+    /// were built with until they are consolidated or split.
+    ///
+    /// Default: [`DataPageLayout::Variable`]
+    pub data_page_layout: DataPageLayout,
+
+    /// The number of pages in a chain above which the entries of the chain
+    /// are merged with a tournament tree instead of a binary heap.
+    ///
+    /// A tournament tree takes fewer comparisons to merge very long chains,
+    /// while a binary heap is cheaper to set up for short ones. The merged
+    /// entries are the same in either way.
+    ///
+    /// Default: 64
+    pub merge_tournament_threshold: usize,
+
+    /// The number of slots in the cache of absent keys.
+    ///
+    /// If this is not zero, reads of keys that have no entries are recorded
+    /// in a cache, so that repeated reads of them return without walking the
+    /// tree until the keys are written. Each slot holds one key, and keys
+    /// hashed to the same slot replace each other.
+    ///
+    /// Default: 0
+    pub negative_cache_size: usize,
+
+    /// The maximum number of consolidations per second triggered by writes.
+    ///
+    /// Consolidations triggered once chains exceed `page_chain_length` are
+    /// skipped beyond this rate, so that bursts of writes don't spend their
+    /// time on maintenance. Writes to a page whose chain reaches
+    /// `max_chain_length` still consolidate it regardless of the rate. Note
+    /// that a rate makes consolidations depend on timing as well.
+    ///
+    /// If this is zero, consolidations are not limited.
+    ///
+    /// Default: 0
+    pub consolidate_rate: u32,
+
+    /// Whether to reject batches that write the same key more than once.
+    ///
+    /// If this is false, the last entry of a key in a batch wins and the
+    /// others are dropped. Otherwise, such batches fail with
+    /// [`Error::DuplicateKey`](crate::Error::DuplicateKey) and nothing is
+    /// written.
+    ///
+    /// Default: false
+    pub reject_duplicate_keys: bool,
+
+    /// The extractor of key prefixes for prefix filters.
+    ///
+    /// If this is specified, a bloom filter of key prefixes is built for each
+    /// leaf page whose whole chain is consolidated, so that prefix scans of
+    /// prefixes extracted by it skip pages without reading them from the page
+    /// store. The filters are kept in memory and rebuilt as pages are
+    /// consolidated again, so the extractor can be changed between opens.
+    ///
+    /// Default: None
+    pub prefix_extractor: Option<PrefixExtractor>,
+
+    /// The listener to observe page splits, consolidations, and removals.
+    ///
+    /// Default: None
+    pub event_listener: Option<Arc<dyn EventListener>>,
+
     /// Options for the underlying page store.
     pub page_store: PageStoreOptions,
 }
@@ -26,6 +170,16 @@ impl Default for Options {
         Self {
             page_size: 8 << 10,
             page_chain_length: 4,
+            max_chain_length: 32,
+            merge_operator: None,
+            warm_up_on_open: 0,
+            data_page_layout: DataPageLayout::Variable,
+            merge_tournament_threshold: 64,
+            negative_cache_size: 0,
+            consolidate_rate: 0,
+            reject_duplicate_keys: false,
+            prefix_extractor: None,
+            event_listener: None,
             page_store: PageStoreOptions::default(),
         }
     }
@@ -46,6 +200,43 @@ pub struct ReadOptions {
     ///
     /// Default: true
     pub fill_cache: bool,
+
+    /// The approximate size of pages a scan can pin at once.
+    ///
+    /// Once the pinned pages exceed this size, the scan releases them before
+    /// it moves to the next page. The scan fails with
+    /// [`Error::MemoryLimit`](crate::Error::MemoryLimit) if a single page
+    /// exceeds this size.
+    ///
+    /// Default: `usize::MAX`
+    pub memory_budget: usize,
+
+    /// The inclusive lower bound of the keys to iterate.
+    ///
+    /// Iterators don't read pages that only contain keys before the bound.
+    ///
+    /// Default: `None`
+    pub lower_bound: Option<Vec<u8>>,
+
+    /// The exclusive upper bound of the keys to iterate.
+    ///
+    /// Iterators don't read pages that only contain keys at or after the
+    /// bound.
+    ///
+    /// Default: `None`
+    pub upper_bound: Option<Vec<u8>>,
+
+    /// The maximum number of entries a scan yields.
+    ///
+    /// The scan stops reading pages once the limit is reached.
+    ///
+    /// Default: `usize::MAX`
+    pub limit: usize,
+
+    /// The direction in which iterators yield keys.
+    ///
+    /// Default: [`Direction::Forward`]
+    pub direction: Direction,
 }
 
 impl Default for ReadOptions {
@@ -53,6 +244,11 @@ impl Default for ReadOptions {
         Self {
             max_lsn: u64::MAX,
             fill_cache: true,
+            memory_budget: usize::MAX,
+            lower_bound: None,
+            upper_bound: None,
+            limit: usize::MAX,
+            direction: Direction::Forward,
         }
     }
 }
@@ -1,10 +1,13 @@
+use super::MergeOperator;
 use crate::{page::*, page_store::*};
 
-/// The root id is fixed to the minimal id in the page store.
+/// The root id of the default tree is fixed to the minimal id in the page
+/// store.
 pub(super) const ROOT_ID: u64 = MIN_ID;
 pub(super) const ROOT_RANGE: Range = Range::full();
-pub(super) const ROOT_INDEX: Index = Index::new(MIN_ID, 0);
 pub(super) const NULL_INDEX: Index = Index::new(NAN_ID, 0);
+/// The index of a removed page, whose range is merged into its left sibling.
+pub(super) const REMOVED_INDEX: Index = Index::new(NAN_ID, 1);
 
 /// Related information of a page.
 #[derive(Clone, Debug)]
@@ -17,31 +20,47 @@ pub(super) struct PageView<'a> {
 
 /// An iterator over user entries in a page.
 pub struct PageIter<'a> {
-    iter: MergingPageIter<'a, Key<'a>, Value<'a>>,
+    iter: LeafPageIter<'a>,
     read_lsn: u64,
+    now: u64,
     last_raw: Option<&'a [u8]>,
 }
 
 impl<'a> PageIter<'a> {
     pub(super) fn new(iter: MergingPageIter<'a, Key<'a>, Value<'a>>, read_lsn: u64) -> Self {
         Self {
-            iter,
+            iter: LeafPageIter::Page(iter),
+            read_lsn,
+            now: now_millis(),
+            last_raw: None,
+        }
+    }
+
+    /// Creates an iterator over entries with merge operands resolved.
+    pub(super) fn with_merged(entries: &'a [MergedEntry], read_lsn: u64) -> Self {
+        Self {
+            iter: LeafPageIter::Merged(MergedEntryIter::new(entries)),
             read_lsn,
+            now: now_millis(),
             last_raw: None,
         }
     }
 
     /// Positions the iterator at the first item that is at or after `target`.
     pub fn seek(&mut self, target: &[u8]) {
-        self.iter.seek(&Key::new(target, self.read_lsn));
+        let target = Key::new(target, self.read_lsn);
+        match &mut self.iter {
+            LeafPageIter::Page(iter) => iter.seek(&target),
+            LeafPageIter::Merged(iter) => iter.seek(&target),
+        };
         self.last_raw = None;
     }
 }
 
-impl<'a> Iterator for PageIter<'a> {
-    type Item = (&'a [u8], &'a [u8]);
-
-    fn next(&mut self) -> Option<Self::Item> {
+impl<'a> PageIter<'a> {
+    /// Returns the key, LSN, and value of the next newest visible version,
+    /// with [`None`] values for deleted keys.
+    pub(crate) fn next_version(&mut self) -> Option<(&'a [u8], u64, Option<&'a [u8]>)> {
         for (k, v) in &mut self.iter {
             if k.lsn > self.read_lsn {
                 continue;
@@ -52,14 +71,256 @@ impl<'a> Iterator for PageIter<'a> {
                 }
             }
             self.last_raw = Some(k.raw);
-            if let Value::Put(value) = v {
-                return Some((k.raw, value));
+            match v.expire(self.now) {
+                Value::Put(value) | Value::Expiring(value, _) => {
+                    return Some((k.raw, k.lsn, Some(value)))
+                }
+                Value::Delete => return Some((k.raw, k.lsn, None)),
+                Value::Merge(_) => {}
+            }
+        }
+        None
+    }
+}
+
+impl<'a> Iterator for PageIter<'a> {
+    type Item = (&'a [u8], &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((key, _, value)) = self.next_version() {
+            if let Some(value) = value {
+                return Some((key, value));
             }
         }
         None
     }
 }
 
+enum LeafPageIter<'a> {
+    Page(MergingPageIter<'a, Key<'a>, Value<'a>>),
+    Merged(MergedEntryIter<'a>),
+}
+
+impl<'a> Iterator for LeafPageIter<'a> {
+    type Item = (Key<'a>, Value<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Page(iter) => iter.next(),
+            Self::Merged(iter) => iter.next(),
+        }
+    }
+}
+
+/// A value with merge operands resolved.
+#[derive(Clone, Debug)]
+pub(super) enum MergedValue<'a> {
+    Value(Value<'a>),
+    Merged(Vec<u8>),
+}
+
+impl MergedValue<'_> {
+    pub(super) fn as_value(&self) -> Value<'_> {
+        match self {
+            Self::Value(v) => *v,
+            Self::Merged(v) => Value::Put(v),
+        }
+    }
+}
+
+/// An entry with merge operands resolved.
+///
+/// Unlike [`MergedValue`], the entry owns its data, so it can outlive the page
+/// it comes from.
+#[derive(Clone, Debug)]
+pub(super) struct MergedEntry {
+    raw: Vec<u8>,
+    lsn: u64,
+    value: Option<Vec<u8>>,
+}
+
+impl MergedEntry {
+    pub(super) fn new(key: Key<'_>, value: &MergedValue<'_>) -> Self {
+        let value = match value.as_value() {
+            Value::Put(value) | Value::Expiring(value, _) => Some(value.to_vec()),
+            Value::Delete => None,
+            Value::Merge(_) => unreachable!("merge operands must be resolved"),
+        };
+        Self {
+            raw: key.raw.to_vec(),
+            lsn: key.lsn,
+            value,
+        }
+    }
+
+    fn key(&self) -> Key<'_> {
+        Key::new(&self.raw, self.lsn)
+    }
+
+    fn value(&self) -> Value<'_> {
+        match &self.value {
+            Some(value) => Value::Put(value),
+            None => Value::Delete,
+        }
+    }
+}
+
+/// An iterator over owned entries with merge operands resolved.
+struct MergedEntryIter<'a> {
+    entries: &'a [MergedEntry],
+    next: usize,
+}
+
+impl<'a> MergedEntryIter<'a> {
+    fn new(entries: &'a [MergedEntry]) -> Self {
+        Self { entries, next: 0 }
+    }
+}
+
+impl<'a> Iterator for MergedEntryIter<'a> {
+    type Item = (Key<'a>, Value<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.entries.get(self.next)?;
+        self.next += 1;
+        Some((entry.key(), entry.value()))
+    }
+}
+
+impl<'a> SeekableIterator<Key<'_>> for MergedEntryIter<'a> {
+    fn seek(&mut self, target: &Key<'_>) -> bool {
+        self.next = self.entries.partition_point(|e| &e.key() < target);
+        matches!(self.entries.get(self.next), Some(e) if &e.key() == target)
+    }
+}
+
+/// Resolves merge operands in the entries.
+///
+/// The entries must be sorted and contain all versions of each key in the
+/// range. If `complete` is false, older versions of the keys may exist
+/// elsewhere, so operands without any base value in the entries are left
+/// unresolved.
+///
+/// Expired values are resolved as deletes, and operands merged into an
+/// expiring value don't expire.
+pub(super) fn resolve_merges<'a, I>(
+    iter: I,
+    op: MergeOperator,
+    complete: bool,
+) -> Vec<(Key<'a>, MergedValue<'a>)>
+where
+    I: Iterator<Item = (Key<'a>, Value<'a>)>,
+{
+    let now = now_millis();
+    let mut entries = Vec::new();
+    let mut versions = Vec::new();
+    let mut iter = iter.peekable();
+    while let Some((k, v)) = iter.next() {
+        versions.push((k, v.expire(now)));
+        if matches!(iter.peek(), Some((next, _)) if next.raw == k.raw) {
+            continue;
+        }
+        // Resolve versions of the same raw from the oldest to the newest. The base
+        // is `None` if it is unknown.
+        let start = entries.len();
+        let mut base: Option<Option<Vec<u8>>> = complete.then_some(None);
+        for (k, v) in versions.drain(..).rev() {
+            let value = match v {
+                Value::Put(value) | Value::Expiring(value, _) => {
+                    base = Some(Some(value.to_vec()));
+                    MergedValue::Value(v)
+                }
+                Value::Delete => {
+                    base = Some(None);
+                    MergedValue::Value(v)
+                }
+                Value::Merge(operand) => match base.as_mut() {
+                    Some(base) => {
+                        let merged = op(k.raw, base.as_deref(), operand);
+                        *base = Some(merged.clone());
+                        MergedValue::Merged(merged)
+                    }
+                    None => MergedValue::Value(v),
+                },
+            };
+            entries.push((k, value));
+        }
+        entries[start..].reverse();
+    }
+    entries
+}
+
+/// Applies merge operands to the base value.
+///
+/// The operands are ordered from the newest to the oldest.
+pub(super) fn merge_value(
+    op: MergeOperator,
+    key: &[u8],
+    base: Option<&[u8]>,
+    operands: &[&[u8]],
+) -> Vec<u8> {
+    let (oldest, rest) = operands.split_last().expect("operands must not be empty");
+    let mut value = op(key, base, oldest);
+    for operand in rest.iter().rev() {
+        value = op(key, Some(&value), operand);
+    }
+    value
+}
+
+/// An iterator over entries with merge operands resolved.
+pub(super) struct MergedPageIter<'a> {
+    entries: &'a [(Key<'a>, MergedValue<'a>)],
+    next: usize,
+}
+
+impl<'a> MergedPageIter<'a> {
+    pub(super) fn new(entries: &'a [(Key<'a>, MergedValue<'a>)]) -> Self {
+        Self { entries, next: 0 }
+    }
+}
+
+impl<'a> Iterator for MergedPageIter<'a> {
+    type Item = (Key<'a>, Value<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (k, v) = self.entries.get(self.next)?;
+        self.next += 1;
+        Some((*k, v.as_value()))
+    }
+}
+
+impl<'a> RewindableIterator for MergedPageIter<'a> {
+    fn rewind(&mut self) {
+        self.next = 0;
+    }
+}
+
+impl<'a> SeekableIterator<Key<'_>> for MergedPageIter<'a> {
+    fn seek(&mut self, target: &Key<'_>) -> bool {
+        self.next = self.entries.partition_point(|(k, _)| k < target);
+        matches!(self.entries.get(self.next), Some((k, _)) if k == target)
+    }
+}
+
+/// A range delete that deletes the versions of the keys in `[start, end)` at
+/// or below the LSN.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct RangeDelete<'a> {
+    pub(super) start: &'a [u8],
+    pub(super) end: &'a [u8],
+    pub(super) lsn: u64,
+}
+
+impl RangeDelete<'_> {
+    pub(super) fn contains(&self, raw: &[u8]) -> bool {
+        self.start <= raw && raw < self.end
+    }
+}
+
+/// An iterator over the entries of a page chain.
+///
+/// The range deletes of the chain are applied as tombstones, which are
+/// yielded right before the first version of each key they delete.
 pub(super) struct MergingPageIter<'a, K, V>
 where
     K: SortedPageKey,
@@ -67,6 +328,13 @@ where
 {
     iter: MergingIter<SortedPageIter<'a, K, V>>,
     range_limit: Option<&'a [u8]>,
+    range_deletes: Vec<RangeDelete<'a>>,
+    // The version deferred by a tombstone yielded before it.
+    pending: Option<(K, V)>,
+    // The last yielded key and the LSNs of the range deletes covering it that
+    // are not yielded yet, from the newest to the oldest.
+    last_key: Option<K>,
+    covering_lsns: Vec<u64>,
 }
 
 impl<'a, K, V> MergingPageIter<'a, K, V>
@@ -78,7 +346,39 @@ where
         iter: MergingIter<SortedPageIter<'a, K, V>>,
         range_limit: Option<&'a [u8]>,
     ) -> Self {
-        Self { iter, range_limit }
+        Self {
+            iter,
+            range_limit,
+            range_deletes: Vec::new(),
+            pending: None,
+            last_key: None,
+            covering_lsns: Vec::new(),
+        }
+    }
+
+    /// Applies the range deletes to the entries.
+    pub(super) fn with_range_deletes(mut self, range_deletes: Vec<RangeDelete<'a>>) -> Self {
+        self.range_deletes = range_deletes;
+        self
+    }
+
+    fn reset(&mut self) {
+        self.pending = None;
+        self.last_key = None;
+        self.covering_lsns.clear();
+    }
+
+    // Collects the LSNs of the range deletes covering the key up to `max_lsn`.
+    fn cover(&mut self, key: &K, max_lsn: u64) {
+        self.covering_lsns.clear();
+        self.covering_lsns.extend(
+            self.range_deletes
+                .iter()
+                .filter(|rd| rd.contains(key.as_raw()) && rd.lsn <= max_lsn)
+                .map(|rd| rd.lsn),
+        );
+        self.covering_lsns.sort_unstable_by(|a, b| b.cmp(a));
+        self.last_key = Some(key.clone());
     }
 }
 
@@ -90,15 +390,34 @@ where
     type Item = (K, V);
 
     fn next(&mut self) -> Option<Self::Item> {
-        let Some((k, v)) = self.iter.next() else {
-            return None;
+        let (k, v) = match self.pending.take() {
+            Some(item) => item,
+            None => self.iter.next()?,
         };
         if let Some(limit) = self.range_limit {
             if k.as_raw() >= limit {
                 return None;
             }
         }
-        Some((k, v))
+        let (Some(lsn), Some(tombstone)) = (k.lsn(), V::tombstone()) else {
+            return Some((k, v));
+        };
+        if self.range_deletes.is_empty() {
+            return Some((k, v));
+        }
+        if self.last_key.as_ref().map(|last| last.as_raw()) != Some(k.as_raw()) {
+            self.cover(&k, u64::MAX);
+        }
+        // Yield the newest tombstone that deletes this version first.
+        match self.covering_lsns.first() {
+            Some(&rd_lsn) if rd_lsn >= lsn => {
+                self.covering_lsns.remove(0);
+                let key = k.with_lsn(rd_lsn);
+                self.pending = Some((k, v));
+                Some((key, tombstone))
+            }
+            _ => Some((k, v)),
+        }
     }
 }
 
@@ -109,6 +428,7 @@ where
 {
     fn rewind(&mut self) {
         self.iter.rewind();
+        self.reset();
     }
 }
 
@@ -117,7 +437,19 @@ where
     V: SortedPageValue,
 {
     fn seek(&mut self, target: &Key<'_>) -> bool {
-        self.iter.seek(target)
+        self.reset();
+        let found = self.iter.seek(target);
+        // Tombstones of the target key above the target LSN are before the
+        // target, so they are skipped.
+        if !self.range_deletes.is_empty() {
+            if let Some((k, v)) = self.iter.next() {
+                if k.raw == target.raw {
+                    self.cover(&k, target.lsn);
+                }
+                self.pending = Some((k, v));
+            }
+        }
+        found
     }
 }
 
@@ -126,34 +458,51 @@ where
     V: SortedPageValue,
 {
     fn seek(&mut self, target: &[u8]) -> bool {
+        self.reset();
         self.iter.seek(target)
     }
 }
 
 /// An iterator that merges multiple leaf delta pages for consolidation.
-pub(super) struct MergingLeafPageIter<'a> {
-    iter: MergingPageIter<'a, Key<'a>, Value<'a>>,
+///
+/// If `complete` is false, older versions of the keys may exist elsewhere, so
+/// tombstones that are visible to the safe LSN are kept to cover them.
+///
+/// Expired values are handled as tombstones, so they are dropped like deletes.
+pub(super) struct MergingLeafPageIter<'a, I = MergingPageIter<'a, Key<'a>, Value<'a>>> {
+    iter: I,
     safe_lsn: u64,
+    complete: bool,
+    now: u64,
     last_raw: Option<&'a [u8]>,
     skip_same_raw: bool,
 }
 
-impl<'a> MergingLeafPageIter<'a> {
-    pub(super) fn new(iter: MergingPageIter<'a, Key<'a>, Value<'a>>, safe_lsn: u64) -> Self {
+impl<'a, I> MergingLeafPageIter<'a, I>
+where
+    I: Iterator<Item = (Key<'a>, Value<'a>)>,
+{
+    pub(super) fn new(iter: I, safe_lsn: u64, complete: bool) -> Self {
         Self {
             iter,
             safe_lsn,
+            complete,
+            now: now_millis(),
             last_raw: None,
             skip_same_raw: false,
         }
     }
 }
 
-impl<'a> Iterator for MergingLeafPageIter<'a> {
+impl<'a, I> Iterator for MergingLeafPageIter<'a, I>
+where
+    I: Iterator<Item = (Key<'a>, Value<'a>)>,
+{
     type Item = (Key<'a>, Value<'a>);
 
     fn next(&mut self) -> Option<Self::Item> {
         for (k, v) in &mut self.iter {
+            let v = v.expire(self.now);
             if let Some(last) = self.last_raw {
                 if k.raw == last {
                     // Skip versions of the same raw.
@@ -164,21 +513,31 @@ impl<'a> Iterator for MergingLeafPageIter<'a> {
                     if k.lsn > self.safe_lsn {
                         return Some((k, v));
                     }
-                    // This is the oldest version visible to the safe LSN.
-                    self.skip_same_raw = true;
+                    // This is the oldest version visible to the safe LSN, unless it is an
+                    // unresolved merge operand, which still needs the older versions.
                     match v {
-                        Value::Put(_) => return Some((k, v)),
-                        Value::Delete => continue,
+                        Value::Put(_) | Value::Expiring(..) => {
+                            self.skip_same_raw = true;
+                            return Some((k, v));
+                        }
+                        Value::Delete => {
+                            self.skip_same_raw = true;
+                            if self.complete {
+                                continue;
+                            }
+                            return Some((k, v));
+                        }
+                        Value::Merge(_) => return Some((k, v)),
                     }
                 }
             }
             // This is the latest version of this raw.
             self.last_raw = Some(k.raw);
-            self.skip_same_raw = k.lsn <= self.safe_lsn;
+            self.skip_same_raw = k.lsn <= self.safe_lsn && !matches!(v, Value::Merge(_));
             match v {
                 // If the latest version is a delete and all older versions are not visible to the
-                // safe LSN, we can skip all of them.
-                Value::Delete if k.lsn <= self.safe_lsn => {
+                // safe LSN, we can skip all of them, unless some older versions exist elsewhere.
+                Value::Delete if k.lsn <= self.safe_lsn && self.complete => {
                     continue;
                 }
                 _ => return Some((k, v)),
@@ -188,7 +547,10 @@ impl<'a> Iterator for MergingLeafPageIter<'a> {
     }
 }
 
-impl<'a> RewindableIterator for MergingLeafPageIter<'a> {
+impl<'a, I> RewindableIterator for MergingLeafPageIter<'a, I>
+where
+    I: RewindableIterator<Item = (Key<'a>, Value<'a>)>,
+{
     fn rewind(&mut self) {
         self.iter.rewind();
         self.last_raw = None;
@@ -196,8 +558,11 @@ impl<'a> RewindableIterator for MergingLeafPageIter<'a> {
     }
 }
 
-impl<'a> SeekableIterator<Key<'_>> for MergingLeafPageIter<'a> {
-    fn seek(&mut self, target: &Key<'_>) -> bool {
+impl<'a, 'k, I> SeekableIterator<Key<'k>> for MergingLeafPageIter<'a, I>
+where
+    I: SeekableIterator<Key<'k>, Item = (Key<'a>, Value<'a>)>,
+{
+    fn seek(&mut self, target: &Key<'k>) -> bool {
         self.last_raw = None;
         self.skip_same_raw = false;
         self.iter.seek(target)
@@ -235,6 +600,10 @@ impl<'a> Iterator for MergingInnerPageIter<'a> {
                 }
             }
             self.last_raw = Some(start);
+            // Skip removed pages, which also overwrite the former indexes
+            if index == REMOVED_INDEX {
+                continue;
+            }
             return Some((start, index));
         }
         None
@@ -326,6 +695,117 @@ mod tests {
         }
     }
 
+    #[test]
+    fn page_iter_with_range_deletes() {
+        let data = vec![
+            (Key::new(&[1], 4), Value::Put(&[4])),
+            (Key::new(&[1], 2), Value::Put(&[2])),
+            (Key::new(&[3], 1), Value::Put(&[1])),
+            (Key::new(&[5], 2), Value::Put(&[2])),
+        ];
+        let owned_page = OwnedSortedPage::from_slice(&data);
+        let range_deletes = vec![
+            RangeDelete {
+                start: &[0],
+                end: &[5],
+                lsn: 3,
+            },
+            RangeDelete {
+                start: &[3],
+                end: &[4],
+                lsn: 2,
+            },
+        ];
+        let build_iter = || {
+            build_merging_iter([owned_page.as_iter()], None)
+                .with_range_deletes(range_deletes.clone())
+        };
+
+        // Tombstones are yielded before the versions they delete.
+        let expect = vec![
+            (Key::new(&[1], 4), Value::Put(&[4])),
+            (Key::new(&[1], 3), Value::Delete),
+            (Key::new(&[1], 2), Value::Put(&[2])),
+            (Key::new(&[3], 3), Value::Delete),
+            (Key::new(&[3], 2), Value::Delete),
+            (Key::new(&[3], 1), Value::Put(&[1])),
+            (Key::new(&[5], 2), Value::Put(&[2])),
+        ];
+        assert_eq!(build_iter().collect::<Vec<_>>(), expect);
+        let mut iter = build_iter();
+        iter.seek(&Key::new(&[3], 2));
+        assert_eq!(iter.collect::<Vec<_>>(), expect[4..]);
+
+        let lsn_expect = [
+            (1, as_slice(&[([3], [1])])),
+            (2, as_slice(&[([1], [2]), ([5], [2])])),
+            (3, as_slice(&[([5], [2])])),
+            (4, as_slice(&[([1], [4]), ([5], [2])])),
+        ];
+        for (lsn, expect) in lsn_expect {
+            let iter = PageIter::new(build_iter(), lsn);
+            assert_eq!(iter.collect::<Vec<_>>(), expect);
+        }
+    }
+
+    fn add_operator(_: &[u8], base: Option<&[u8]>, operand: &[u8]) -> Vec<u8> {
+        vec![base.map_or(0, |v| v[0]) + operand[0]]
+    }
+
+    #[test]
+    fn resolve_merge_operands() {
+        let data = vec![
+            (Key::new(&[1], 4), Value::Merge(&[4])),
+            (Key::new(&[1], 3), Value::Merge(&[3])),
+            (Key::new(&[1], 2), Value::Put(&[2])),
+            (Key::new(&[1], 1), Value::Merge(&[1])),
+            (Key::new(&[3], 2), Value::Merge(&[2])),
+            (Key::new(&[3], 1), Value::Delete),
+            (Key::new(&[5], 1), Value::Merge(&[1])),
+        ];
+        let complete_expect = vec![
+            (Key::new(&[1], 4), Value::Put(&[9])),
+            (Key::new(&[1], 3), Value::Put(&[5])),
+            data[2],
+            (Key::new(&[1], 1), Value::Put(&[1])),
+            (Key::new(&[3], 2), Value::Put(&[2])),
+            data[5],
+            (Key::new(&[5], 1), Value::Put(&[1])),
+        ];
+        // Operands without bases are left unresolved.
+        let partial_expect = vec![
+            complete_expect[0],
+            complete_expect[1],
+            data[2],
+            data[3],
+            complete_expect[4],
+            data[5],
+            data[6],
+        ];
+        for (complete, expect) in [(true, complete_expect), (false, partial_expect)] {
+            let entries = resolve_merges(data.iter().cloned(), add_operator, complete);
+            let actual: Vec<_> = entries.iter().map(|(k, v)| (*k, v.as_value())).collect();
+            assert_eq!(actual, expect);
+
+            if complete {
+                let entries: Vec<_> = entries
+                    .iter()
+                    .map(|(k, v)| MergedEntry::new(*k, v))
+                    .collect();
+                let mut iter = PageIter::with_merged(&entries, 3);
+                assert_eq!(iter.next(), Some(([1].as_slice(), [5].as_slice())));
+                assert_eq!(iter.next(), Some(([3].as_slice(), [2].as_slice())));
+                iter.seek(&[5]);
+                assert_eq!(iter.next(), Some(([5].as_slice(), [1].as_slice())));
+            }
+        }
+        assert_eq!(merge_value(add_operator, &[], None, &[&[1]]), vec![1]);
+        assert_eq!(
+            merge_value(add_operator, &[], Some(&[1]), &[&[3], &[2]]),
+            vec![6]
+        );
+    }
+
     #[test]
     fn merging_page_iter() {
         let data = raw_slice(&[[1], [3], [5]]);
@@ -368,7 +848,7 @@ mod tests {
         ];
         for (lsn, expect) in lsn_expect {
             let merging_iter = build_merging_iter([owned_page.as_iter()], None);
-            let mut iter = MergingLeafPageIter::new(merging_iter, lsn);
+            let mut iter = MergingLeafPageIter::new(merging_iter, lsn, true);
             for (a, b) in (&mut iter).zip(expect) {
                 assert_eq!(a, b);
             }
@@ -376,7 +856,7 @@ mod tests {
 
         {
             let merging_iter = build_merging_iter([owned_page.as_iter()], None);
-            let mut iter = MergingLeafPageIter::new(merging_iter, 2);
+            let mut iter = MergingLeafPageIter::new(merging_iter, 2, true);
             iter.seek(&Key::new(&[], 2));
             assert_eq!(iter.next(), Some(data[0]));
             iter.seek(&Key::new(&[1], 2));
@@ -391,7 +871,7 @@ mod tests {
 
         {
             let merging_iter = build_merging_iter([owned_page.as_iter()], None);
-            let mut iter = MergingLeafPageIter::new(merging_iter, 2);
+            let mut iter = MergingLeafPageIter::new(merging_iter, 2, true);
             assert_eq!(iter.next(), Some(data[0]));
             assert_eq!(iter.next(), Some(data[1]));
 
@@ -399,6 +879,40 @@ mod tests {
             assert_eq!(iter.next(), Some(data[0]));
             assert_eq!(iter.next(), Some(data[1]));
         }
+
+        // Tombstones are kept if older versions may exist elsewhere.
+        let lsn_expect = [
+            (1, data.clone()),
+            (2, vec![data[0], data[1], data[3], data[4], data[5]]),
+            (3, vec![data[0], data[3], data[5]]),
+        ];
+        for (lsn, expect) in lsn_expect {
+            let merging_iter = build_merging_iter([owned_page.as_iter()], None);
+            let iter = MergingLeafPageIter::new(merging_iter, lsn, false);
+            assert_eq!(iter.collect::<Vec<_>>(), expect);
+        }
+    }
+
+    #[test]
+    fn merging_leaf_page_iter_with_expiring_values() {
+        let data = vec![
+            (Key::new(&[1], 2), Value::Expiring(&[2], 0)),
+            (Key::new(&[1], 1), Value::Put(&[1])),
+            (Key::new(&[3], 1), Value::Expiring(&[1], u64::MAX)),
+        ];
+        let owned_page = OwnedSortedPage::from_slice(&data);
+
+        // Expired values are dropped with older versions.
+        let merging_iter = build_merging_iter([owned_page.as_iter()], None);
+        let iter = MergingLeafPageIter::new(merging_iter, 2, true);
+        assert_eq!(iter.collect::<Vec<_>>(), vec![data[2]]);
+
+        // Expired values are kept as tombstones if older versions may exist
+        // elsewhere.
+        let merging_iter = build_merging_iter([owned_page.as_iter()], None);
+        let iter = MergingLeafPageIter::new(merging_iter, 2, false);
+        let expect = vec![(data[0].0, Value::Delete), data[2]];
+        assert_eq!(iter.collect::<Vec<_>>(), expect);
     }
 
     #[test]
@@ -455,5 +969,25 @@ mod tests {
             iter.rewind();
             assert_eq!(iter.next(), Some(([1].as_slice(), Index::new(1, 1))));
         }
+
+        {
+            // Page 3 is removed and merged into page 1.
+            let data3 = [
+                ([1].as_slice(), Index::new(1, 1)),
+                ([3].as_slice(), REMOVED_INDEX),
+                ([5].as_slice(), NULL_INDEX),
+            ];
+            let owned_page3 = OwnedSortedPage::from_slice(&data3);
+            let merging_iter = build_merging_iter(
+                [
+                    owned_page3.as_iter(),
+                    owned_page1.as_iter(),
+                    owned_page2.as_iter(),
+                ],
+                None,
+            );
+            let iter = MergingInnerPageIter::new(merging_iter);
+            assert_eq!(iter.collect::<Vec<_>>(), vec![data1[0], data2[1]]);
+        }
     }
 }
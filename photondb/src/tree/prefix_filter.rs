@@ -0,0 +1,156 @@
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    sync::Mutex,
+};
+
+use rustc_hash::FxHasher;
+
+use super::PrefixExtractor;
+
+/// The number of bits per prefix in a filter, which yields a false positive
+/// rate of about 1%.
+const BITS_PER_PREFIX: usize = 10;
+/// The number of bits probed per prefix.
+const NUM_PROBES: usize = 6;
+
+/// A bloom filter over the key prefixes of a page.
+pub(super) struct PrefixFilter {
+    bits: Box<[u64]>,
+}
+
+impl PrefixFilter {
+    /// Returns false if no key in the page has the prefix.
+    fn may_contain(&self, prefix: &[u8]) -> bool {
+        let num_bits = self.bits.len() * 64;
+        probes(hash(prefix), num_bits).all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+}
+
+/// Builds a [`PrefixFilter`] from the keys of a page in order.
+pub(super) struct PrefixFilterBuilder {
+    extractor: PrefixExtractor,
+    hashes: Vec<u64>,
+}
+
+impl PrefixFilterBuilder {
+    pub(super) fn add(&mut self, key: &[u8]) {
+        if let Some(prefix) = (self.extractor)(key) {
+            let h = hash(prefix);
+            // Keys with the same prefix are adjacent.
+            if self.hashes.last() != Some(&h) {
+                self.hashes.push(h);
+            }
+        }
+    }
+
+    pub(super) fn finish(self) -> PrefixFilter {
+        let num_bits = (self.hashes.len() * BITS_PER_PREFIX).max(64);
+        let mut bits = vec![0u64; (num_bits + 63) / 64].into_boxed_slice();
+        let num_bits = bits.len() * 64;
+        for h in self.hashes {
+            for bit in probes(h, num_bits) {
+                bits[bit / 64] |= 1 << (bit % 64);
+            }
+        }
+        PrefixFilter { bits }
+    }
+}
+
+fn hash(prefix: &[u8]) -> u64 {
+    let mut hasher = FxHasher::default();
+    prefix.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn probes(mut h: u64, num_bits: usize) -> impl Iterator<Item = usize> {
+    let delta = h.rotate_right(17);
+    (0..NUM_PROBES).map(move |_| {
+        let bit = (h % num_bits as u64) as usize;
+        h = h.wrapping_add(delta);
+        bit
+    })
+}
+
+/// The prefix filters of consolidated leaf pages.
+///
+/// A filter is only built if a whole chain is consolidated, and it only holds
+/// while the page stays at the address it is built for. Any update to the
+/// page moves it to another address, so stale filters are never used.
+pub(super) struct PrefixFilters {
+    extractor: PrefixExtractor,
+    filters: Mutex<HashMap<u64, (u64, PrefixFilter)>>,
+}
+
+impl PrefixFilters {
+    pub(super) fn new(extractor: PrefixExtractor) -> Self {
+        Self {
+            extractor,
+            filters: Mutex::default(),
+        }
+    }
+
+    pub(super) fn builder(&self) -> PrefixFilterBuilder {
+        PrefixFilterBuilder {
+            extractor: self.extractor,
+            hashes: Vec::new(),
+        }
+    }
+
+    /// Installs the filter of the page at the address.
+    pub(super) fn insert(&self, id: u64, addr: u64, filter: PrefixFilter) {
+        let mut filters = self.filters.lock().expect("Poisoned");
+        filters.insert(id, (addr, filter));
+    }
+
+    /// Returns false if the page at the address is known to have no keys with
+    /// the prefix.
+    ///
+    /// The filters can only tell if `prefix` is a whole prefix extracted from
+    /// keys, otherwise this always returns true.
+    pub(super) fn may_contain(&self, id: u64, addr: u64, prefix: &[u8]) -> bool {
+        if (self.extractor)(prefix) != Some(prefix) {
+            return true;
+        }
+        let filters = self.filters.lock().expect("Poisoned");
+        match filters.get(&id) {
+            Some((filter_addr, filter)) if *filter_addr == addr => filter.may_contain(prefix),
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract(key: &[u8]) -> Option<&[u8]> {
+        key.get(..2)
+    }
+
+    #[test]
+    fn prefix_filters() {
+        let filters = PrefixFilters::new(extract);
+        let mut builder = filters.builder();
+        for i in 0..100u8 {
+            builder.add(&[i, i, 1]);
+            builder.add(&[i, i, 2]);
+        }
+        // Keys without prefixes are skipped.
+        builder.add(&[255]);
+        filters.insert(1, 10, builder.finish());
+
+        for i in 0..100u8 {
+            assert!(filters.may_contain(1, 10, &[i, i]));
+        }
+        let false_positives = (100..200u8)
+            .filter(|&i| filters.may_contain(1, 10, &[i, i]))
+            .count();
+        assert!(false_positives < 10);
+
+        // Unknown pages, stale addresses, and partial prefixes are not filtered.
+        assert!(filters.may_contain(2, 10, &[200, 200]));
+        assert!(filters.may_contain(1, 11, &[200, 200]));
+        assert!(filters.may_contain(1, 10, &[200]));
+    }
+}
@@ -9,6 +9,12 @@ pub struct TreeStats {
     pub success: TxnStats,
     /// Statistics of conflicted transactions.
     pub conflict: TxnStats,
+    /// The number of reads answered by the negative cache.
+    pub negative_cache_hits: u64,
+    /// The number of consolidations skipped by the rate limit.
+    pub throttled_consolidations: u64,
+    /// The estimated number of live keys, see `Table::estimated_num_keys`.
+    pub estimated_num_keys: u64,
 }
 
 impl TreeStats {
@@ -17,6 +23,12 @@ impl TreeStats {
         Self {
             success: self.success.sub(&o.success),
             conflict: self.conflict.sub(&o.conflict),
+            negative_cache_hits: self.negative_cache_hits.wrapping_sub(o.negative_cache_hits),
+            throttled_consolidations: self
+                .throttled_consolidations
+                .wrapping_sub(o.throttled_consolidations),
+            // This is a gauge rather than a counter.
+            estimated_num_keys: self.estimated_num_keys,
         }
     }
 }
@@ -52,7 +64,18 @@ impl Display for TreeStats {
             self.conflict.split_page,
             self.conflict.reconcile_page,
             self.conflict.consolidate_page
-        )
+        )?;
+        writeln!(
+            f,
+            "TreeStats_negative_cache: hits: {}",
+            self.negative_cache_hits
+        )?;
+        writeln!(
+            f,
+            "TreeStats_consolidate_rate: throttled: {}",
+            self.throttled_consolidations
+        )?;
+        writeln!(f, "TreeStats_keys: estimated: {}", self.estimated_num_keys)
     }
 }
 
@@ -60,6 +83,8 @@ impl Display for TreeStats {
 pub(super) struct AtomicStats {
     pub(super) success: AtomicTxnStats,
     pub(super) conflict: AtomicTxnStats,
+    pub(super) negative_cache_hits: Counter,
+    pub(super) throttled_consolidations: Counter,
 }
 
 impl AtomicStats {
@@ -67,6 +92,9 @@ impl AtomicStats {
         TreeStats {
             success: self.success.snapshot(),
             conflict: self.conflict.snapshot(),
+            negative_cache_hits: self.negative_cache_hits.get(),
+            throttled_consolidations: self.throttled_consolidations.get(),
+            estimated_num_keys: 0,
         }
     }
 }
@@ -80,6 +108,7 @@ pub struct TxnStats {
     pub reconcile_page: u64,
     pub consolidate_page: u64,
     pub rewrite_page: u64,
+    pub remove_page: u64,
     pub read_bytes: u64,
     pub write_bytes: u64,
 }
@@ -94,6 +123,7 @@ pub(super) struct AtomicTxnStats {
     pub(super) reconcile_page: Counter,
     pub(super) consolidate_page: Counter,
     pub(super) rewrite_page: Counter,
+    pub(super) remove_page: Counter,
 }
 
 impl AtomicTxnStats {
@@ -107,6 +137,7 @@ impl AtomicTxnStats {
             reconcile_page: self.reconcile_page.get(),
             consolidate_page: self.consolidate_page.get(),
             rewrite_page: self.rewrite_page.get(),
+            remove_page: self.remove_page.get(),
         }
     }
 }
@@ -122,6 +153,7 @@ impl TxnStats {
             reconcile_page: self.reconcile_page.wrapping_sub(o.reconcile_page),
             consolidate_page: self.consolidate_page.wrapping_sub(o.consolidate_page),
             rewrite_page: self.rewrite_page.wrapping_sub(o.rewrite_page),
+            remove_page: self.remove_page.wrapping_sub(o.remove_page),
         }
     }
 }
@@ -3,4 +3,6 @@ pub(crate) mod bitmap;
 pub(crate) mod latch;
 pub(crate) mod linked_list;
 pub(crate) mod notify;
+pub(crate) mod pause;
+pub(crate) mod rate_limiter;
 pub(crate) mod shutdown;
@@ -0,0 +1,71 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use crate::util::notify::Notify;
+
+/// A switch to pause and resume background jobs.
+///
+/// The jobs check the switch between iterations, so the work in progress is
+/// always finished before they are paused.
+#[derive(Clone, Default)]
+pub(crate) struct Pause {
+    core: Arc<Core>,
+}
+
+#[derive(Default)]
+struct Core {
+    paused: AtomicBool,
+    notify: Notify,
+}
+
+impl Pause {
+    pub(crate) fn pause(&self) {
+        self.core.paused.store(true, Ordering::Release);
+    }
+
+    pub(crate) fn resume(&self) {
+        self.core.paused.store(false, Ordering::Release);
+        self.core.notify.notify_waiters();
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.core.paused.load(Ordering::Acquire)
+    }
+
+    /// Waits until the switch is resumed.
+    pub(crate) async fn wait_resumed(&self) {
+        loop {
+            // Register the waiter before checking the state, so that a resume in
+            // between is not missed.
+            let notified = self.core.notify.notified();
+            if !self.is_paused() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[photonio::test]
+    async fn pause_and_resume() {
+        let pause = Pause::default();
+        assert!(!pause.is_paused());
+        pause.wait_resumed().await;
+
+        pause.pause();
+        assert!(pause.is_paused());
+        let handle = photonio::task::spawn({
+            let pause = pause.clone();
+            async move { pause.wait_resumed().await }
+        });
+        pause.resume();
+        handle.await.unwrap();
+        assert!(!pause.is_paused());
+    }
+}
@@ -0,0 +1,77 @@
+use std::{sync::Mutex, time::Instant};
+
+/// A token bucket that limits the rate of some operations.
+///
+/// The bucket holds at most one second worth of tokens, so that operations
+/// skipped for a while can not burst beyond the rate afterwards.
+pub(crate) struct RateLimiter {
+    rate: u32,
+    state: Mutex<State>,
+}
+
+struct State {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    /// Creates a limiter that allows `rate` operations per second.
+    pub(crate) fn new(rate: u32) -> Self {
+        assert!(rate > 0);
+        let state = State {
+            tokens: rate as f64,
+            refilled_at: Instant::now(),
+        };
+        Self {
+            rate,
+            state: Mutex::new(state),
+        }
+    }
+
+    /// Takes a token if any is available.
+    ///
+    /// Returns false if the operation should be skipped.
+    pub(crate) fn try_acquire(&self) -> bool {
+        self.try_acquire_at(Instant::now())
+    }
+
+    fn try_acquire_at(&self, now: Instant) -> bool {
+        let mut state = self.state.lock().expect("Poisoned");
+        let elapsed = now.saturating_duration_since(state.refilled_at);
+        state.tokens =
+            (state.tokens + elapsed.as_secs_f64() * self.rate as f64).min(self.rate as f64);
+        state.refilled_at = now.max(state.refilled_at);
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn rate_limiter() {
+        let limiter = RateLimiter::new(2);
+        let now = Instant::now();
+        assert!(limiter.try_acquire_at(now));
+        assert!(limiter.try_acquire_at(now));
+        assert!(!limiter.try_acquire_at(now));
+
+        let now = now + Duration::from_millis(500);
+        assert!(limiter.try_acquire_at(now));
+        assert!(!limiter.try_acquire_at(now));
+
+        // Tokens don't accumulate beyond one second worth.
+        let now = now + Duration::from_secs(10);
+        assert!(limiter.try_acquire_at(now));
+        assert!(limiter.try_acquire_at(now));
+        assert!(!limiter.try_acquire_at(now));
+    }
+}